@@ -1,10 +1,19 @@
+use std::collections::BTreeMap;
+
 use indexmap::IndexMap;
 use polars::prelude::*;
 
-use crate::{config::Config, map_binary_names, order_by_binary, Coverage};
+use crate::{
+    bug_exploit_column,
+    config::{BlockOrder, Config, CoverageDelta, CoverageGranularity, DataSource, Diff, Story},
+    map_binary_names,
+    metadata::{self, Metadata},
+    order_by_binary, parse_filter_expr, polars_format_u64, style, Coverage,
+};
 
 /// Generate a Fuzzware-style coverage table showing min/max/median/total blocks reached by a fuzzer
-/// over all trials.
+/// over all trials, along with the fraction of the best-known blocks (see [best_known_blocks]) that
+/// were reached.
 pub fn coverage_table(config: &Config) -> anyhow::Result<LazyFrame> {
     // Call collect here to avoid crash caused by: https://github.com/pola-rs/polars/issues/5490
     let coverage = crate::load_raw_coverage(config)?.collect()?.lazy();
@@ -14,6 +23,10 @@ pub fn coverage_table(config: &Config) -> anyhow::Result<LazyFrame> {
         .group_by(["dataset", "fuzzer", "binary"])
         .agg([col("block").n_unique().alias("bb_total")]);
 
+    let best_known = best_known_blocks(coverage.clone())
+        .group_by(["binary"])
+        .agg([col("block").count().alias("bb_best_known")]);
+
     let total_blocks_per_trial = coverage
         .group_by(["dataset", "fuzzer", "binary", "trial"])
         .agg([col("block").count().alias("total_blocks")]);
@@ -27,6 +40,13 @@ pub fn coverage_table(config: &Config) -> anyhow::Result<LazyFrame> {
             max("total_blocks").alias("bb_max"),
         ])
         .join(total_unique_blocks, &join_key, &join_key, JoinType::Inner.into())
+        .join(best_known, [col("binary")], [col("binary")], JoinType::Left.into())
+        .with_columns([
+            (col("bb_avg") / col("bb_best_known").cast(DataType::Float64)).alias("avg_frac"),
+            (col("bb_total").cast(DataType::Float64) / col("bb_best_known").cast(DataType::Float64))
+                .alias("total_frac"),
+            bug_exploit_column(config),
+        ])
         .sort_by_exprs(
             &join_key,
             SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
@@ -35,6 +55,66 @@ pub fn coverage_table(config: &Config) -> anyhow::Result<LazyFrame> {
     Ok(summary)
 }
 
+/// Lists the functions reached by each fuzzer on each binary (when `coverage_granularity` is
+/// `function`), with the number of trials that reached the function and the median time (in hours)
+/// it was first reached.
+pub fn function_coverage(config: &Config) -> anyhow::Result<LazyFrame> {
+    let Some(functions) = crate::function_map(config)? else {
+        anyhow::bail!("function coverage requires `coverage_granularity: function`");
+    };
+    let names = functions
+        .group_by([col("binary"), col("function")])
+        .agg([col("function_name").first()]);
+
+    // `block` contains the entry address of the function when measuring function coverage.
+    let coverage = crate::load_raw_coverage(config)?.collect()?.lazy();
+    let trials = coverage
+        .clone()
+        .group_by(["fuzzer", "binary"])
+        .agg([col("trial").n_unique().alias("trials")]);
+
+    let join_key = [col("fuzzer"), col("binary")];
+    Ok(coverage
+        .group_by(["fuzzer", "binary", "block"])
+        .agg([
+            col("trial").n_unique().alias("reached"),
+            median("hours").alias("hours_median"),
+        ])
+        .join(trials, &join_key, &join_key, JoinType::Left.into())
+        .join(
+            names,
+            [col("binary"), col("block")],
+            [col("binary"), col("function")],
+            JoinType::Left.into(),
+        )
+        .select([
+            col("binary"),
+            col("function_name"),
+            polars_format_u64(col("block")).alias("function"),
+            col("fuzzer"),
+            col("reached"),
+            col("trials"),
+            col("hours_median"),
+        ])
+        .sort_by_exprs(
+            [order_by_binary(), col("function_name"), col("fuzzer")],
+            SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+        ))
+}
+
+/// Computes the union of blocks found by any fuzzer in any trial for each binary (i.e. the
+/// best-known reachable set). Unlike the total number of blocks in the block map, this excludes
+/// dead code so is a fairer denominator when comparing fuzzers.
+pub fn best_known_blocks(coverage: Coverage) -> LazyFrame {
+    coverage
+        .group_by(["binary", "block"])
+        .agg([col("fuzzer").n_unique().alias("found_by"), col("hours").min().alias("first_hit")])
+        .sort_by_exprs(
+            [order_by_binary(), col("block")],
+            SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+        )
+}
+
 pub fn load_preprocessed_coverage_table(config: &Config) -> anyhow::Result<LazyFrame> {
     let coverage = crate::load_block_hits(config)?;
 
@@ -67,7 +147,10 @@ pub fn median_coverage(config: &Config) -> anyhow::Result<DataFrame> {
 
     let with_reference = coverage
         .join(reference, &[col("binary")], &[col("binary")], JoinType::Left.into())
-        .with_columns([((col("bb_avg") / col("reference_avg")) * lit(100.0_f64)).alias("% ref")])
+        .with_columns([
+            ((col("bb_avg") / col("reference_avg")) * lit(100.0_f64)).alias("% ref"),
+            bug_exploit_column(config),
+        ])
         .sort_by_exprs(
             [order_by_binary(), col("dataset")],
             SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
@@ -87,7 +170,15 @@ pub fn median_coverage(config: &Config) -> anyhow::Result<DataFrame> {
                     .alias("val"),
             )
             .collect()?;
-        Ok(pivot::pivot_stable(&df, ["binary"], ["fuzzer"], Some(["val"]), false, None, None)?)
+        Ok(pivot::pivot_stable(
+            &df,
+            ["binary", "bug_exploit"],
+            ["fuzzer"],
+            Some(["val"]),
+            false,
+            None,
+            None,
+        )?)
     }
     else {
         let summary =
@@ -103,7 +194,7 @@ pub fn median_coverage(config: &Config) -> anyhow::Result<DataFrame> {
 
         let mut data = pivot::pivot_stable(
             &df,
-            ["binary"],
+            ["binary", "bug_exploit"],
             ["fuzzer"],
             Some(["bb_avg", "% ref"]),
             false,
@@ -141,6 +232,255 @@ fn sort_columns_by_element<'a>(cols: &[&'a str]) -> Vec<&'a str> {
     sorted_cols
 }
 
+/// Find trials where the final coverage deviates from the median of the other trials in the same
+/// group (dataset, fuzzer and binary) by more than `k` times the median absolute deviation (MAD).
+///
+/// These are typically caused by data-entry mistakes (e.g. a binary placed in the wrong directory)
+/// or broken runs, so the tags and source files of each trial are included to help track them down.
+///
+/// The MAD is zero when more than half of the trials in a group reach exactly the same coverage,
+/// which would flag every other trial. In that case the mean absolute deviation is used instead.
+pub fn outlier_trials(config: &Config, k: f64) -> anyhow::Result<LazyFrame> {
+    let mut coverage = crate::load_raw_coverage(config)?;
+
+    // Only MultiFuzz data includes tags.
+    if coverage.schema()?.get("tag").is_none() {
+        coverage = coverage.with_column(lit(NULL).cast(DataType::String).alias("tag"));
+    }
+
+    let group = ["dataset", "fuzzer", "variant", "binary"];
+    let diff = col("total_blocks").cast(DataType::Float64) - col("median_blocks");
+    let deviation = when(diff.clone().lt(lit(0.0))).then(lit(0.0) - diff.clone()).otherwise(diff);
+    let outliers = coverage
+        .group_by(["dataset", "fuzzer", "variant", "binary", "trial"])
+        .agg([
+            col("block").count().alias("total_blocks"),
+            col("tag").unique_stable().str().concat(";", true).alias("tags"),
+            col("source").unique_stable().str().concat(";", true).alias("sources"),
+        ])
+        .with_column(col("total_blocks").median().over(group).alias("median_blocks"))
+        .with_column(deviation.alias("deviation"))
+        .with_column(col("deviation").median().over(group).alias("mad"))
+        .with_column(col("deviation").mean().over(group).alias("mean_deviation"))
+        .with_column(
+            when(col("mad").eq(lit(0.0)))
+                .then(col("mean_deviation"))
+                .otherwise(col("mad"))
+                .alias("threshold"),
+        )
+        .filter(col("deviation").gt(lit(k) * col("threshold")))
+        .drop(["mean_deviation", "threshold"])
+        .sort_by_exprs(
+            [order_by_binary(), col("dataset"), col("trial")],
+            SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+        );
+
+    Ok(outliers)
+}
+
+/// Reports the effective runtime of each trial, flagging trials whose runtime differs from the
+/// median of the other trials of the same fuzzer and binary by more than `tolerance` (as a fraction
+/// of the median), e.g. because the host running the trial rebooted. Groups with fewer than
+/// `config.trials` trials are reported in the `missing_trials` column.
+///
+/// The runtime is the time of the last throughput sample of the trial when throughput data is
+/// available, otherwise the time of the last coverage event. The latter is only a lower bound,
+/// since coverage is not recorded after a fuzzer stops finding new blocks, so `runtime_anomaly` is
+/// null for these trials.
+pub fn trial_completeness(config: &Config, tolerance: f64) -> anyhow::Result<LazyFrame> {
+    let key = [col("fuzzer"), col("variant"), col("binary"), col("trial")];
+    let coverage = crate::trimmed_events(config)?.select([
+        col("fuzzer"),
+        col("variant"),
+        col("binary"),
+        col("trial").cast(DataType::UInt32),
+        col("last_event_hours"),
+        col("duration_hours"),
+    ]);
+
+    let mut throughput = vec![];
+    for (name, entry) in &config.throughput {
+        let Some(execs) = crate::bugs::read_total_execs_all(&entry.plot_data)? else {
+            continue;
+        };
+        throughput.push(
+            execs
+                .with_columns(crate::fuzzer_columns(config, name))
+                .group_by([
+                    col("fuzzer"),
+                    col("variant"),
+                    col("binary"),
+                    col("trial").cast(DataType::UInt32),
+                ])
+                .agg([crate::millis_to_hours(col("time")).max().alias("throughput_hours")]),
+        );
+    }
+    let runtimes = match throughput.is_empty() {
+        true => coverage.with_column(lit(NULL).cast(DataType::Float64).alias("throughput_hours")),
+        false => coverage
+            .join_builder()
+            .with(concat_lf_diagonal(throughput, UnionArgs::default())?)
+            .left_on(key.clone())
+            .right_on(key)
+            .how(JoinType::Outer)
+            .coalesce(JoinCoalesce::CoalesceColumns)
+            .finish(),
+    };
+
+    let group = ["fuzzer", "binary"];
+    let source = when(col("throughput_hours").is_not_null())
+        .then(lit("throughput"))
+        .otherwise(lit("coverage"));
+    let diff = col("runtime_hours") - col("median_runtime_hours");
+    let deviation = when(diff.clone().lt(lit(0.0))).then(lit(0.0) - diff.clone()).otherwise(diff);
+    let trials = col("trials").cast(DataType::Int64);
+    let expected = lit(config.trials as i64);
+    Ok(runtimes
+        .with_columns([
+            col("throughput_hours").fill_null(col("last_event_hours")).alias("runtime_hours"),
+            source.alias("runtime_source"),
+        ])
+        .with_columns([
+            col("runtime_hours").median().over(group).alias("median_runtime_hours"),
+            col("trial").n_unique().over(group).alias("trials"),
+        ])
+        .with_columns([
+            (deviation / col("median_runtime_hours")).alias("runtime_deviation"),
+            when(trials.clone().lt(expected.clone()))
+                .then(expected - trials)
+                .otherwise(lit(0i64))
+                .alias("missing_trials"),
+        ])
+        .with_column(
+            // Runtimes from coverage events are only a lower bound, so they cannot be compared.
+            when(col("runtime_source").eq(lit("throughput")))
+                .then(col("runtime_deviation").gt(lit(tolerance)))
+                .otherwise(lit(NULL).cast(DataType::Boolean))
+                .alias("runtime_anomaly"),
+        )
+        .sort_by_exprs(
+            [order_by_binary(), col("fuzzer"), col("trial")],
+            SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+        ))
+}
+
+/// Compares the trials found for each configured dataset on each binary against the expected
+/// number of trials (`config.trials`), reporting datasets without any data for a binary, missing
+/// trials and trials that stopped early (i.e. with a runtime more than `runtime_tolerance` below
+/// the duration of the dataset, see [trial_completeness]). Only trials with throughput data can be
+/// detected as stopping early, since the time of the last coverage event is only a lower bound of
+/// the runtime. Binaries are discovered from the data of all datasets, so a binary missing from
+/// every dataset cannot be detected.
+pub fn expected_trials(config: &Config) -> anyhow::Result<DataFrame> {
+    let strings = [
+        col("fuzzer").cast(DataType::String),
+        col("variant").cast(DataType::String),
+        col("binary").cast(DataType::String),
+    ];
+    let trials = crate::load_block_hits(config)?
+        .select([col("fuzzer"), col("variant"), col("binary"), col("trial").cast(DataType::UInt32)])
+        .with_columns(strings)
+        .unique(None, UniqueKeepStrategy::First);
+
+    let key = [col("fuzzer"), col("variant"), col("binary"), col("trial")];
+    let trials = match trial_completeness(config, config.runtime_tolerance) {
+        Ok(completeness) => {
+            let min_runtime = col("duration_hours") * lit(1.0 - config.runtime_tolerance);
+            let early = col("runtime_source")
+                .eq(lit("throughput"))
+                .and(col("runtime_hours").lt(min_runtime))
+                .fill_null(lit(false));
+            let incomplete = completeness
+                .filter(early)
+                .select([
+                    col("fuzzer").cast(DataType::String),
+                    col("variant").cast(DataType::String),
+                    col("binary").cast(DataType::String),
+                    col("trial"),
+                    lit(true).alias("incomplete"),
+                ])
+                .unique(None, UniqueKeepStrategy::First);
+            trials.join(incomplete, &key, &key, JoinType::Left.into())
+        }
+        Err(e) => {
+            eprintln!("WARNING: unable to check the runtime of trials: {e:#}");
+            trials.with_column(lit(NULL).cast(DataType::Boolean).alias("incomplete"))
+        }
+    };
+    let is_incomplete = col("incomplete").fill_null(lit(false));
+    let found = trials
+        .group_by([col("fuzzer"), col("variant"), col("binary")])
+        .agg([
+            col("trial").n_unique().cast(DataType::Int64).alias("found"),
+            is_incomplete.clone().sum().cast(DataType::Int64).alias("incomplete"),
+            col("trial")
+                .filter(is_incomplete)
+                .cast(DataType::String)
+                .str()
+                .concat(";", true)
+                .alias("incomplete_trials"),
+        ])
+        .collect()?;
+
+    // Every dataset with coverage is expected to have `config.trials` trials for every binary.
+    let binaries = found["binary"].str()?.unique()?;
+    let mut expected: [Vec<&str>; 3] = Default::default();
+    let mut seen = std::collections::HashSet::new();
+    for (_, name, entry) in config.datasets() {
+        if matches!(entry.source, DataSource::BugCsv { .. }) || !seen.insert(name) {
+            continue;
+        }
+        for binary in binaries.into_iter().flatten() {
+            let (fuzzer, variant) = (config.fuzzer_family(name).0, name.as_str());
+            for (column, value) in expected.iter_mut().zip([fuzzer, variant, binary]) {
+                column.push(value);
+            }
+        }
+    }
+    let [fuzzer, variant, binary] = expected;
+    let expected = df! { "fuzzer" => fuzzer, "variant" => variant, "binary" => binary }?;
+
+    let key = [col("fuzzer"), col("variant"), col("binary")];
+    let missing = col("expected") - col("found");
+    Ok(expected
+        .lazy()
+        .join(found.lazy(), &key, &key, JoinType::Left.into())
+        .with_columns([
+            lit(config.trials as i64).alias("expected"),
+            col("found").fill_null(lit(0i64)),
+            col("incomplete").fill_null(lit(0i64)),
+        ])
+        .with_column(
+            when(missing.clone().gt(lit(0i64))).then(missing).otherwise(lit(0i64)).alias("missing"),
+        )
+        .with_column(
+            when(col("found").eq(lit(0i64)))
+                .then(lit("no data"))
+                .when(col("missing").gt(lit(0i64)))
+                .then(lit("missing"))
+                .when(col("incomplete").gt(lit(0i64)))
+                .then(lit("incomplete"))
+                .otherwise(lit("ok"))
+                .alias("status"),
+        )
+        .select([
+            col("fuzzer"),
+            col("variant"),
+            col("binary"),
+            col("expected"),
+            col("found"),
+            col("missing"),
+            col("incomplete"),
+            col("incomplete_trials"),
+            col("status"),
+        ])
+        .sort_by_exprs(
+            [order_by_binary(), col("fuzzer"), col("variant")],
+            SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+        )
+        .collect()?)
+}
+
 pub type UniqueBlocks = LazyFrame;
 
 pub fn unique_blocks_per_fuzzer(config: &Config) -> anyhow::Result<UniqueBlocks> {
@@ -165,6 +505,76 @@ pub fn unique_blocks_per_fuzzer(config: &Config) -> anyhow::Result<UniqueBlocks>
     Ok(unique_blocks_per_fuzzer)
 }
 
+/// The maximum number of fuzzers compared by [block_overlap].
+pub const MAX_OVERLAP_FUZZERS: usize = 4;
+
+/// Gets the fuzzers compared by [block_overlap] (see [Config::overlap_fuzzers]), or `None` if
+/// `overlap_fuzzers` is not configured and there are fewer than two or more than
+/// [MAX_OVERLAP_FUZZERS] fuzzers.
+pub fn overlap_fuzzers(config: &Config) -> Option<Vec<String>> {
+    if !config.overlap_fuzzers.is_empty() {
+        return Some(config.overlap_fuzzers.clone());
+    }
+    let mut fuzzers: Vec<String> = vec![];
+    for (_, name, entry) in config.datasets() {
+        if matches!(entry.source, DataSource::BugCsv { .. } | DataSource::AflPlotData { .. }) {
+            continue;
+        }
+        let family = config.fuzzer_family(name).0;
+        if !fuzzers.iter().any(|x| x == family) {
+            fuzzers.push(family.to_owned());
+        }
+    }
+    (2..=MAX_OVERLAP_FUZZERS).contains(&fuzzers.len()).then_some(fuzzers)
+}
+
+/// Computes the intersection structure of the blocks reached by `fuzzers` (in any trial) on each
+/// binary, i.e. the number of blocks reached by exactly each combination of fuzzers. Each
+/// combination is identified by `mask` (with bit `i` set if `fuzzers[i]` reached the blocks), and
+/// is described by `fuzzers`, `degree` (the number of fuzzers in the combination) and a boolean
+/// column for each fuzzer. Combinations without any blocks are omitted.
+pub fn block_overlap(config: &Config, fuzzers: &[String]) -> anyhow::Result<LazyFrame> {
+    if !(2..=MAX_OVERLAP_FUZZERS).contains(&fuzzers.len()) {
+        anyhow::bail!(
+            "block overlap requires between 2 and {MAX_OVERLAP_FUZZERS} fuzzers (got: {})",
+            fuzzers.len()
+        );
+    }
+
+    let masks: Vec<u32> = (1..1 << fuzzers.len()).collect();
+    let members = |mask: u32| {
+        let names = fuzzers.iter().enumerate().filter(|(i, _)| mask & (1 << i) != 0);
+        names.map(|(_, name)| name.as_str()).collect::<Vec<_>>().join(" & ")
+    };
+    let mut columns = vec![
+        Series::new("mask", &masks),
+        Series::new("fuzzers", masks.iter().map(|x| members(*x)).collect::<Vec<_>>()),
+        Series::new("degree", masks.iter().map(|x| x.count_ones()).collect::<Vec<_>>()),
+    ];
+    for (i, fuzzer) in fuzzers.iter().enumerate() {
+        let member: Vec<bool> = masks.iter().map(|x| x & (1 << i) != 0).collect();
+        columns.push(Series::new(fuzzer, member));
+    }
+    let combinations = DataFrame::new(columns)?.lazy();
+
+    let bits: Vec<u32> = (0..fuzzers.len()).map(|i| 1 << i).collect();
+    let bits = df! { "fuzzer" => fuzzers, "bit" => bits }?.lazy();
+
+    Ok(crate::load_raw_coverage(config)?
+        .select([col("binary"), col("block"), col("fuzzer")])
+        .unique(None, UniqueKeepStrategy::First)
+        .join(bits, [col("fuzzer")], [col("fuzzer")], JoinType::Inner.into())
+        .group_by(["binary", "block"])
+        .agg([col("bit").sum().cast(DataType::UInt32).alias("mask")])
+        .group_by(["binary", "mask"])
+        .agg([len().cast(DataType::UInt32).alias("blocks")])
+        .join(combinations, [col("mask")], [col("mask")], JoinType::Inner.into())
+        .sort_by_exprs(
+            [order_by_binary(), col("degree"), col("mask")],
+            SortMultipleOptions::new().with_maintain_order(true),
+        ))
+}
+
 /// Represents a lazy frame generated by `block_diff`
 pub type BlockDiff = LazyFrame;
 
@@ -206,6 +616,398 @@ pub fn block_diff(config: &Config, fuzzer_a: &str, fuzzer_b: &str) -> anyhow::Re
     ))
 }
 
+/// Lists the blocks that only one of the fuzzers in `diff` reached (in any trial), along with the
+/// fuzzer that reached the block (`found_by`) and when (see [block_diff]). Blocks are symbolized
+/// using the block map of the binary if `coverage_metadata` is configured.
+pub fn unique_blocks(config: &Config, diff: &Diff) -> anyhow::Result<DataFrame> {
+    let (a, b) = (diff.fuzzer_a.as_str(), diff.fuzzer_b.as_str());
+    if a == b {
+        anyhow::bail!("diff requires two different fuzzers (got: {a})");
+    }
+
+    let mut blocks = block_diff(config, a, b)?
+        .filter(col(a).is_null().neq(col(b).is_null()))
+        .select([
+            col("binary"),
+            col("block"),
+            when(col(a).is_null()).then(lit(b)).otherwise(lit(a)).alias("found_by"),
+            col(a).fill_null(col(b)).alias("hours"),
+        ])
+        .sort_by_exprs(
+            [order_by_binary(), col("found_by"), col("hours"), col("block")],
+            SortMultipleOptions::new().with_maintain_order(true),
+        )
+        .collect()?;
+
+    add_block_names(config, &mut blocks)?;
+    Ok(blocks.lazy().with_column(polars_format_u64(col("block"))).collect()?)
+}
+
+/// Formats the blocks listed by [unique_blocks] as a Markdown report, with a table for each binary.
+pub fn unique_blocks_markdown(blocks: &DataFrame, diff: &Diff) -> anyhow::Result<String> {
+    let escape = |x: &str| x.replace('|', "\\|");
+    let binary = blocks.column("binary")?.str()?;
+    let found_by = blocks.column("found_by")?.str()?;
+    let block = blocks.column("block")?.str()?;
+    let name = blocks.column("name")?.str()?;
+    let hours = blocks.column("hours")?.cast(&DataType::Float64)?;
+    let hours = hours.f64()?;
+
+    let (a, b) = (&diff.fuzzer_a, &diff.fuzzer_b);
+    let mut out = format!("# Blocks reached by only one of {a} and {b}\n");
+    let mut current = None;
+    for i in 0..blocks.height() {
+        let row_binary = binary.get(i).unwrap_or_default();
+        if current != Some(row_binary) {
+            current = Some(row_binary);
+            let count = |fuzzer: &str| {
+                (i..blocks.height())
+                    .take_while(|j| binary.get(*j) == Some(row_binary))
+                    .filter(|j| found_by.get(*j) == Some(fuzzer))
+                    .count()
+            };
+            out.push_str(&format!("\n## {}\n\n", escape(row_binary)));
+            out.push_str(&format!("{a}: {} block(s), {b}: {} block(s)\n\n", count(a), count(b)));
+            out.push_str("| Found by | Block | Name | Hours |\n|---|---|---|---:|\n");
+        }
+        out.push_str(&format!(
+            "| {} | {} | {} | {:.2} |\n",
+            escape(found_by.get(i).unwrap_or_default()),
+            block.get(i).unwrap_or_default(),
+            escape(name.get(i).unwrap_or_default()),
+            hours.get(i).unwrap_or(f64::NAN),
+        ));
+    }
+    Ok(out)
+}
+
+/// Compares the blocks reached by each trial against the ground-truth reachable set of the binary
+/// (see [crate::load_ground_truth]). `precision` is the fraction of covered blocks that are in the
+/// ground truth and `recall` is the fraction of the ground truth that was covered.
+pub fn ground_truth_metrics(config: &Config) -> anyhow::Result<LazyFrame> {
+    let ground_truth = crate::load_ground_truth(config)?.cache();
+    let reachable = ground_truth.clone().group_by(["binary"]).agg([len().alias("reachable")]);
+
+    let binaries: Vec<&str> = config.ground_truth.keys().map(|x| x.as_str()).collect();
+    let covered = crate::load_raw_coverage(config)?
+        .filter(col("binary").is_in(lit(Series::new("binaries", binaries))))
+        .group_by(["dataset", "fuzzer", "binary", "trial", "block"])
+        .agg([]);
+
+    let join_key = [col("binary"), col("block")];
+    let metrics = covered
+        .join(
+            ground_truth.with_column(lit(true).alias("in_ground_truth")),
+            &join_key,
+            &join_key,
+            JoinType::Left.into(),
+        )
+        .group_by(["dataset", "fuzzer", "binary", "trial"])
+        .agg([
+            len().cast(DataType::UInt32).alias("covered"),
+            col("in_ground_truth")
+                .fill_null(lit(false))
+                .sum()
+                .cast(DataType::UInt32)
+                .alias("true_positives"),
+        ])
+        .join(reachable, [col("binary")], [col("binary")], JoinType::Inner.into())
+        .with_columns([
+            (col("true_positives").cast(DataType::Float64) / col("covered").cast(DataType::Float64))
+                .alias("precision"),
+            (col("true_positives").cast(DataType::Float64)
+                / col("reachable").cast(DataType::Float64))
+            .alias("recall"),
+        ])
+        .with_column(
+            (lit(2.0) * col("precision") * col("recall") / (col("precision") + col("recall")))
+                .fill_nan(lit(0.0))
+                .alias("f1"),
+        )
+        .sort_by_exprs(
+            [order_by_binary(), col("fuzzer"), col("trial")],
+            SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+        );
+
+    Ok(metrics)
+}
+
+/// Summarizes the per-trial metrics computed by [ground_truth_metrics] for each fuzzer and binary.
+pub fn ground_truth_summary(metrics: LazyFrame) -> LazyFrame {
+    metrics
+        .group_by_stable(["dataset", "fuzzer", "binary"])
+        .agg([
+            col("reachable").first(),
+            col("covered").median().alias("median_covered"),
+            col("precision").median().alias("median_precision"),
+            col("recall").min().alias("min_recall"),
+            col("recall").median().alias("median_recall"),
+            col("recall").max().alias("max_recall"),
+            col("f1").median().alias("median_f1"),
+        ])
+        .sort_by_exprs(
+            [order_by_binary(), col("dataset")],
+            SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+        )
+}
+
+/// Classifies the blocks reached by each fuzzer as valid or invalid according to the block maps
+/// used for `coverage_metadata` filtering, returning `None` if filtering is not configured. All
+/// blocks of binaries without a block map are discarded by the filter, so they are marked as
+/// invalid (with `has_block_map` set to `false`).
+pub fn coverage_filter_blocks(config: &Config) -> anyhow::Result<Option<LazyFrame>> {
+    let Some(metadata) = config.coverage_metadata.as_ref() else {
+        return Ok(None);
+    };
+    let metadata = Metadata::from_source(&config.path, metadata.clone())?;
+    let mapped: Vec<&str> = metadata.binary_mapping.keys().map(|x| x.as_str()).collect();
+
+    let join_key = [col("binary"), col("block")];
+    let blocks = crate::load_unfiltered_coverage(config)?
+        .group_by(["dataset", "fuzzer", "binary", "block"])
+        .agg([col("trial").n_unique().alias("trials")])
+        .join(
+            crate::valid_blocks(&metadata)?.with_column(lit(true).alias("valid")),
+            &join_key,
+            &join_key,
+            JoinType::Left.into(),
+        )
+        .with_columns([
+            col("valid").fill_null(lit(false)),
+            col("binary").is_in(lit(Series::new("mapped", mapped))).alias("has_block_map"),
+        ]);
+
+    Ok(Some(blocks))
+}
+
+/// Counts the number of raw blocks reached by each fuzzer that were discarded by
+/// `coverage_metadata` filtering (see [coverage_filter_blocks]).
+pub fn coverage_filter_summary(blocks: LazyFrame) -> LazyFrame {
+    blocks
+        .group_by_stable(["dataset", "fuzzer", "binary"])
+        .agg([
+            col("has_block_map").first(),
+            len().cast(DataType::UInt32).alias("raw_blocks"),
+            col("valid").not().sum().cast(DataType::UInt32).alias("invalid_blocks"),
+        ])
+        .with_column(
+            (col("invalid_blocks").cast(DataType::Float64)
+                / col("raw_blocks").cast(DataType::Float64))
+            .alias("invalid_fraction"),
+        )
+        .sort_by_exprs(
+            [order_by_binary(), col("dataset")],
+            SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+        )
+}
+
+/// Lists the `n` invalid blocks of each binary that were reached by the most trials (see
+/// [coverage_filter_blocks]). Binaries without a block map are excluded since all of their blocks
+/// are invalid.
+pub fn top_invalid_blocks(blocks: LazyFrame, n: usize) -> LazyFrame {
+    blocks
+        .filter(col("has_block_map").and(col("valid").not()))
+        .group_by(["binary", "block"])
+        .agg([
+            col("trials").sum().alias("trials"),
+            col("fuzzer").unique_stable().str().concat(";", true).alias("fuzzers"),
+        ])
+        .sort_by_exprs(
+            [col("trials"), col("block")],
+            SortMultipleOptions::new().with_order_descendings([true, false]),
+        )
+        .group_by_stable(["binary"])
+        .head(Some(n))
+        .sort_by_exprs(
+            [order_by_binary(), col("trials")],
+            SortMultipleOptions::new()
+                .with_order_descendings([false, true])
+                .with_nulls_last(true)
+                .with_maintain_order(true),
+        )
+        .with_column(polars_format_u64(col("block")))
+}
+
+/// The number of example addresses listed for each issue found by [check_metadata].
+const MAX_ISSUE_EXAMPLES: usize = 5;
+
+/// Validates the block maps of `coverage_metadata` against themselves and the (unfiltered) coverage
+/// data, returning `None` if `coverage_metadata` is not configured. Broken block maps silently
+/// distort every analysis that filters blocks, so each problem found (see [metadata::Issue]) is
+/// reported with the number of affected addresses (`count`) and a few `examples`.
+pub fn check_metadata(config: &Config) -> anyhow::Result<Option<DataFrame>> {
+    let Some(source) = config.coverage_metadata.as_ref() else {
+        return Ok(None);
+    };
+    let metadata = Metadata::from_source(&config.path, source.clone())?;
+
+    let mut issues: BTreeMap<(String, metadata::Issue), Vec<u64>> = BTreeMap::new();
+    for (binary, idx) in &metadata.binary_mapping {
+        for (issue, addr) in metadata.block_maps[*idx].check() {
+            issues.entry((binary.clone(), issue)).or_default().push(addr);
+        }
+    }
+
+    let hits = crate::load_unfiltered_coverage(config)?
+        .select([col("binary").cast(DataType::String), col("block")])
+        .unique(None, UniqueKeepStrategy::Any)
+        .collect()?;
+    for (binary, block) in hits["binary"].str()?.into_iter().zip(hits["block"].u64()?) {
+        let (Some(binary), Some(block)) = (binary, block) else { continue };
+        // Binaries without a block map are reported by `coverage-filter`.
+        let Some(block_map) = metadata.get_block_map_for(binary) else { continue };
+        if let Some(issue) = block_map.check_hit(block) {
+            issues.entry((binary.to_owned(), issue)).or_default().push(block);
+        }
+    }
+
+    let (mut binaries, mut names, mut counts, mut examples) = (vec![], vec![], vec![], vec![]);
+    for ((binary, issue), mut addrs) in issues {
+        addrs.sort_unstable();
+        addrs.dedup();
+        binaries.push(binary);
+        names.push(issue.name());
+        counts.push(addrs.len() as u32);
+        let addrs = addrs.iter().take(MAX_ISSUE_EXAMPLES).map(|x| format!("{x:#x}"));
+        examples.push(addrs.collect::<Vec<_>>().join(" "));
+    }
+    let df = df! {
+        "binary" => binaries,
+        "issue" => names,
+        "count" => counts,
+        "examples" => examples,
+    }?;
+    Ok(Some(
+        df.lazy()
+            .sort_by_exprs(
+                [order_by_binary(), col("issue")],
+                SortMultipleOptions::new().with_maintain_order(true),
+            )
+            .collect()?,
+    ))
+}
+
+/// Lists the blocks first reached by a single trial (selected by `story`) in each hour, sorted by
+/// the time they were reached. Blocks are symbolized using the block map of the binary if
+/// `coverage_metadata` is configured.
+pub fn new_blocks_by_hour(config: &Config, story: &Story) -> anyhow::Result<DataFrame> {
+    let mut blocks = crate::load_raw_coverage(config)?
+        .filter(
+            col("fuzzer")
+                .eq(lit(story.fuzzer.as_str()))
+                .and(col("binary").eq(lit(story.binary.as_str())))
+                .and(col("trial").eq(lit(story.trial))),
+        )
+        .group_by(["block"])
+        .agg([col("hours").min()])
+        .with_column(col("hours").floor().cast(DataType::UInt32).alias("hour"))
+        .sort_by_exprs(
+            [col("hours"), col("block")],
+            SortMultipleOptions::new().with_maintain_order(true),
+        )
+        .select([col("hour"), col("hours"), col("block")])
+        .collect()?;
+    if blocks.height() == 0 {
+        eprintln!(
+            "WARNING: no coverage found for {} on {} (trial={})",
+            story.fuzzer, story.binary, story.trial
+        );
+    }
+
+    let metadata = match config.coverage_metadata.as_ref() {
+        Some(source) => Some(Metadata::from_source(&config.path, source.clone())?),
+        None => None,
+    };
+    let block_map = metadata.as_ref().and_then(|x| x.get_block_map_for(&story.binary));
+
+    let (functions, names): (Vec<_>, Vec<_>) = blocks["block"]
+        .u64()?
+        .into_no_null_iter()
+        .map(|addr| match block_map {
+            Some(map) => (
+                map.get_containing_function(addr).map(|x| x.name.clone()),
+                map.get_block_name(addr),
+            ),
+            None => (None, format!("{addr:#x}")),
+        })
+        .unzip();
+    blocks.with_column(Series::new("function", functions))?;
+    blocks.with_column(Series::new("name", names))?;
+
+    Ok(blocks.lazy().with_column(polars_format_u64(col("block"))).collect()?)
+}
+
+/// Lists the blocks that each fuzzer first reached (across all trials) between `delta.from` and
+/// `delta.to`, along with the number of trials that reached each block by `delta.to`. Blocks are
+/// symbolized using the block map of the binary if `coverage_metadata` is configured.
+pub fn coverage_delta(config: &Config, delta: &CoverageDelta) -> anyhow::Result<DataFrame> {
+    let from = delta.from.as_secs_f64() / 3600.0;
+    let to = delta.to.as_secs_f64() / 3600.0;
+    if from >= to {
+        anyhow::bail!("coverage delta must end after it starts ({from}h >= {to}h)");
+    }
+
+    let mut blocks = crate::load_raw_coverage(config)?
+        .filter(col("hours").lt_eq(lit(to)))
+        .group_by(["fuzzer", "binary", "trial", "block"])
+        .agg([col("hours").min()])
+        .group_by(["fuzzer", "binary", "block"])
+        .agg([col("hours").min(), col("trial").n_unique().alias("trials")])
+        .filter(col("hours").gt(lit(from)))
+        .sort_by_exprs(
+            [col("fuzzer"), order_by_binary(), col("hours"), col("block")],
+            SortMultipleOptions::new().with_maintain_order(true),
+        )
+        .collect()?;
+
+    add_block_names(config, &mut blocks)?;
+    Ok(blocks.lazy().with_column(polars_format_u64(col("block"))).collect()?)
+}
+
+/// Adds the name of the `function` containing each block and a human readable `name` for the block
+/// (e.g. `main+0x10`) to `blocks`, using the block map of the binary if `coverage_metadata` is
+/// configured.
+fn add_block_names(config: &Config, blocks: &mut DataFrame) -> anyhow::Result<()> {
+    let metadata = match config.coverage_metadata.as_ref() {
+        Some(source) => Some(Metadata::from_source(&config.path, source.clone())?),
+        None => None,
+    };
+    let (functions, names): (Vec<_>, Vec<_>) = blocks["binary"]
+        .str()?
+        .into_no_null_iter()
+        .zip(blocks["block"].u64()?.into_no_null_iter())
+        .map(|(binary, addr)| {
+            match metadata.as_ref().and_then(|x| x.get_block_map_for(binary)) {
+                Some(map) => (
+                    map.get_containing_function(addr).map(|x| x.name.clone()),
+                    map.get_block_name(addr),
+                ),
+                None => (None, format!("{addr:#x}")),
+            }
+        })
+        .unzip();
+    blocks.with_column(Series::new("function", functions))?;
+    blocks.with_column(Series::new("name", names))?;
+    Ok(())
+}
+
+/// Counts the blocks gained by each fuzzer on each binary in the window used by
+/// [coverage_delta].
+pub fn coverage_delta_summary(delta: &DataFrame) -> LazyFrame {
+    delta
+        .clone()
+        .lazy()
+        .group_by(["fuzzer", "binary"])
+        .agg([
+            col("block").count().alias("blocks"),
+            col("trials").median().alias("trials_median"),
+        ])
+        .sort_by_exprs(
+            [col("fuzzer"), order_by_binary()],
+            SortMultipleOptions::new().with_maintain_order(true),
+        )
+}
+
 /// Represents a lazy frame generated by `blocks_hit_per_period`
 pub type BlockHits = LazyFrame;
 
@@ -307,6 +1109,181 @@ pub fn dynamic_fill_missing(
         .drop_nulls(None))
 }
 
+/// How coverage is interpolated between the points of a [BlockHits] frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Use the coverage of the last point at or before the query time (coverage only changes when
+    /// a new block is hit, so this matches the data exactly).
+    #[default]
+    Step,
+    /// Linearly interpolate between the points before and after the query time.
+    Linear,
+}
+
+/// Computes the number of blocks reached by each (fuzzer, binary, trial) in `block_hits` at each of
+/// the query `times` (in hours), returning a frame with `fuzzer`, `binary`, `trial`, `hours` and
+/// `blocks` (as a float) columns. Queries before the first point of a trial have zero coverage and
+/// queries after the last point use the final coverage of the trial.
+pub fn coverage_at(
+    block_hits: BlockHits,
+    times: &[f64],
+    interpolation: Interpolation,
+) -> anyhow::Result<LazyFrame> {
+    anyhow::ensure!(!times.is_empty(), "no query times");
+    let queries = df! {
+        "hours" => {
+            let mut times = times.to_vec();
+            times.sort_by(f64::total_cmp);
+            let mut i = Series::new("hours", times);
+            i.set_sorted_flag(polars::series::IsSorted::Ascending);
+            i
+        }
+    }?;
+
+    let block_hits = block_hits.select([
+        col("fuzzer"),
+        col("binary"),
+        col("trial"),
+        col("hours").cast(DataType::Float64),
+        col("blocks").cast(DataType::Float64),
+    ]);
+    let schema = block_hits.schema()?;
+    Ok(block_hits
+        .group_by_stable([col("fuzzer"), col("binary"), col("trial")])
+        .apply(
+            move |mut df| {
+                df.sort_in_place(["hours"], SortMultipleOptions::new().with_maintain_order(true))?;
+                // `join_asof` only keeps the time of the query, so keep a copy of the time of each
+                // point for interpolating.
+                let point_hours = df["hours"].clone().with_name("point_hours");
+                let points = df.select(["hours", "blocks"])?.hstack(&[point_hours])?;
+
+                let asof = |strategy| {
+                    queries.join_asof(&points, "hours", "hours", strategy, None, None)
+                };
+                let mut frame = asof(AsofStrategy::Backward)?;
+                let next = asof(AsofStrategy::Forward)?;
+                frame.hstack_mut(&[
+                    next["point_hours"].clone().with_name("next_hours"),
+                    next["blocks"].clone().with_name("next_blocks"),
+                ])?;
+
+                // Queries before the first point of the trial interpolate from zero coverage at
+                // time zero.
+                let prev_hours = col("point_hours").fill_null(lit(0.0));
+                let prev_blocks = col("blocks").fill_null(lit(0.0));
+                let blocks = match interpolation {
+                    Interpolation::Step => prev_blocks,
+                    Interpolation::Linear => {
+                        let fraction = (col("hours") - prev_hours.clone())
+                            / (col("next_hours") - prev_hours.clone());
+                        when(col("next_hours").is_null().or(col("next_hours").eq(prev_hours)))
+                            .then(prev_blocks.clone())
+                            .otherwise(
+                                prev_blocks.clone() + (col("next_blocks") - prev_blocks) * fraction,
+                            )
+                    }
+                };
+                let frame =
+                    frame.lazy().select([col("hours"), blocks.alias("blocks")]).collect()?;
+
+                let mut columns: Vec<_> = df
+                    .select(["fuzzer", "binary", "trial"])?
+                    .get_columns()
+                    .iter()
+                    .map(|x| x.new_from_index(0, frame.height()))
+                    .collect();
+                columns.extend(frame.get_columns().iter().cloned());
+                DataFrame::new(columns)
+            },
+            schema,
+        )
+        .sort(["fuzzer", "binary", "trial", "hours"], Default::default()))
+}
+
+/// Counts the number of new blocks discovered in each time bucket (of width `bucket_hours`) for
+/// each fuzzer and binary, revealing bursts of discovery that are hidden by median coverage curves.
+///
+/// `new_blocks` is the total over all trials and `new_blocks_mean` is the average per trial.
+/// Buckets where no blocks were discovered are omitted.
+pub fn discovery_histogram(coverage: Coverage, bucket_hours: f64) -> anyhow::Result<LazyFrame> {
+    anyhow::ensure!(bucket_hours > 0.0, "discovery bucket must be non-zero");
+    let group = [col("fuzzer"), col("binary")];
+    Ok(coverage
+        .with_columns([
+            ((col("hours") / lit(bucket_hours)).floor() * lit(bucket_hours)).alias("bucket"),
+            col("trial").n_unique().over(group.clone()).alias("trials"),
+        ])
+        .group_by_stable([col("fuzzer"), col("binary"), col("bucket")])
+        .agg([col("block").count().alias("new_blocks"), col("trials").first(), style().first()])
+        .with_column(
+            (col("new_blocks").cast(DataType::Float64) / col("trials").cast(DataType::Float64))
+                .alias("new_blocks_mean"),
+        )
+        .drop(["trials"])
+        .sort_by_exprs(
+            [col("binary"), col("fuzzer"), col("bucket")],
+            SortMultipleOptions::new().with_maintain_order(true),
+        ))
+}
+
+/// Finds the first time (`hours`) that each fuzzer reached each block of each binary over all of
+/// its trials, along with the `trial` that reached it first. Each block is assigned a `row`
+/// (shared by all fuzzers on the same binary) ordered according to `config.block_timeline_order`,
+/// so that periods where no new blocks were found show up as plateaus when plotted over time.
+pub fn block_timeline(config: &Config) -> anyhow::Result<LazyFrame> {
+    let first_hits = crate::load_raw_coverage(config)?
+        .select([col("fuzzer"), col("binary"), col("trial"), col("hours"), col("block")])
+        .sort(["hours"], SortMultipleOptions::default())
+        .unique_stable(
+            Some(vec!["fuzzer".into(), "binary".into(), "block".into()]),
+            UniqueKeepStrategy::First,
+        )
+        .cache();
+
+    let blocks = first_hits
+        .clone()
+        .select([col("binary"), col("block")])
+        .unique(None, UniqueKeepStrategy::First);
+    let blocks = match config.block_timeline_order {
+        BlockOrder::Address => blocks.sort(["binary", "block"], SortMultipleOptions::default()),
+        BlockOrder::Function => {
+            let Some(metadata) = config.coverage_metadata.as_ref() else {
+                anyhow::bail!("`block_timeline_order: function` requires `coverage_metadata`");
+            };
+            let metadata = Metadata::from_source(&config.path, metadata.clone())?;
+            let join_key = [col("binary"), col("block")];
+            blocks
+                .join(
+                    crate::block_functions(&metadata)?
+                        .select([col("binary"), col("block"), col("function")]),
+                    &join_key,
+                    &join_key,
+                    JoinType::Left.into(),
+                )
+                // Blocks outside of any known function are placed after all functions.
+                .sort_by_exprs(
+                    [col("binary"), col("function"), col("block")],
+                    SortMultipleOptions::new().with_nulls_last(true),
+                )
+                .drop(["function"])
+        }
+    };
+    let rows = blocks.with_column(
+        (col("block").cum_count(false).over([col("binary")]) - lit(1))
+            .cast(DataType::UInt32)
+            .alias("row"),
+    );
+
+    let join_key = [col("binary"), col("block")];
+    Ok(first_hits
+        .join(rows, &join_key, &join_key, JoinType::Inner.into())
+        .sort_by_exprs(
+            [order_by_binary(), col("fuzzer"), col("row")],
+            SortMultipleOptions::new().with_maintain_order(true),
+        ))
+}
+
 pub fn raw_blocks_hit(coverage: Coverage) -> BlockHits {
     coverage
         .group_by([col("fuzzer"), col("binary"), col("trial"), col("hours")])
@@ -318,6 +1295,679 @@ pub fn raw_blocks_hit(coverage: Coverage) -> BlockHits {
         .drop(["new_blocks"])
 }
 
+/// Approximates the edge coverage of each trial from the time each block was first reached, using
+/// the control flow graph edges in the block maps of `coverage_metadata`.
+///
+/// An edge is counted as covered once both of its blocks have been reached (at the later of the two
+/// times). Edges where both blocks were first reached by the same input (recorded in MultiFuzz
+/// coverage files) are likely to have been taken by that input, so are also counted separately in
+/// `edges_same_input`. Returns the cumulative `edges` and `edges_same_input` of each trial at each
+/// time an edge was first covered, along with the `blocks` covered by that time.
+pub fn edge_coverage(config: &Config) -> anyhow::Result<LazyFrame> {
+    let Some(metadata) = config.coverage_metadata.as_ref() else {
+        anyhow::bail!("edge coverage requires `coverage_metadata`");
+    };
+    let edges = crate::cfg_edges(&Metadata::from_source(&config.path, metadata.clone())?)?;
+
+    // Edges are between blocks, so coverage is always loaded at block granularity.
+    let config = &Config { coverage_granularity: CoverageGranularity::Block, ..config.clone() };
+    let keys = ["dataset", "fuzzer", "binary", "trial"];
+    let mut hits = crate::load_raw_coverage(config)?.collect()?;
+    // Only MultiFuzz coverage records the input that reached each block.
+    if hits.column("input").is_err() {
+        hits.with_column(Series::full_null("input", hits.height(), &DataType::UInt64))?;
+    }
+    let hits = hits
+        .lazy()
+        .with_column(col("input").cast(DataType::UInt64))
+        .select([cols(keys), col("block"), col("hours"), col("input")])
+        .cache();
+    let endpoint = |name: &str| {
+        hits.clone().select([
+            cols(keys),
+            col("block").alias(name),
+            col("hours").alias(&format!("{name}_hours")),
+            col("input").alias(&format!("{name}_input")),
+        ])
+    };
+
+    let with_keys = |x: &[&str]| keys.iter().chain(x).map(|x| col(x)).collect::<Vec<_>>();
+    let edge_key = [col("binary"), col("from")];
+    let edge_hits = endpoint("from")
+        .join(edges, &edge_key, &edge_key, JoinType::Inner.into())
+        .join(endpoint("to"), with_keys(&["to"]), with_keys(&["to"]), JoinType::Inner.into())
+        .select([
+            cols(keys),
+            // `max_horizontal` is ambiguous within polars itself, so take the later time manually.
+            when(col("from_hours").gt_eq(col("to_hours")))
+                .then(col("from_hours"))
+                .otherwise(col("to_hours"))
+                .alias("hours"),
+            col("from_input").eq(col("to_input")).fill_null(lit(false)).alias("same_input"),
+        ]);
+
+    // Combine the edge and block events so that the growth of both metrics can be compared.
+    let new_edges = edge_hits.group_by(with_keys(&["hours"])).agg([
+        col("same_input").count().alias("new_edges"),
+        col("same_input").sum().alias("new_edges_same_input"),
+    ]);
+    let new_blocks =
+        hits.group_by(with_keys(&["hours"])).agg([col("block").count().alias("new_blocks")]);
+    let by = [col("dataset"), col("fuzzer"), col("binary"), col("trial")];
+    let cumulative =
+        |name: &str| col(&format!("new_{name}")).cum_sum(false).over(&by).alias(name);
+    Ok(concat_lf_diagonal([new_edges, new_blocks], UnionArgs::default())?
+        .group_by(with_keys(&["hours"]))
+        .agg([
+            col("new_edges").sum().cast(DataType::UInt32),
+            col("new_edges_same_input").sum().cast(DataType::UInt32),
+            col("new_blocks").sum().cast(DataType::UInt32),
+        ])
+        .sort(["hours"], Default::default())
+        .select([
+            cols(keys),
+            col("hours"),
+            cumulative("blocks"),
+            cumulative("edges"),
+            cumulative("edges_same_input"),
+        ])
+        .sort_by_exprs(
+            [order_by_binary(), col("dataset"), col("trial"), col("hours")],
+            SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+        ))
+}
+
+/// Joins the number of crashes reported by each fuzzer over time with the number of unique bugs
+/// that were verified from those crashes.
+pub fn crashes_vs_bugs(config: &Config) -> anyhow::Result<LazyFrame> {
+    let res = config.time_resolution as i64;
+    let by = [col("binary"), col("trial")];
+    let join_key = [col("binary"), col("trial"), col("time")];
+
+    let mut data = vec![];
+    for (name, entry) in &config.crashes {
+        let Some(plot_data) = crate::bugs::read_plot_data_all(&entry.plot_data)? else {
+            continue;
+        };
+        let duration = entry.duration.as_millis() as i64;
+        let crashes =
+            fill_missing(plot_data.sort(["time"], Default::default()), duration, res, "time", &by)?;
+
+        let bugs = match crate::bugs::read_bugs_all(&entry.bugs)? {
+            Some(bugs) => cumulative_count_by_period(
+                bugs.sort(["time"], Default::default()),
+                duration,
+                res,
+                "time",
+                "bug_id",
+                &by,
+                "bugs",
+            )?,
+            None => crashes.clone().select([
+                col("binary"),
+                col("trial"),
+                col("time"),
+                lit(0_u32).alias("bugs"),
+            ]),
+        };
+
+        data.push(
+            crashes
+                .join(bugs, &join_key, &join_key, JoinType::Left.into())
+                .with_columns([
+                    col("bugs").fill_null(lit(0)).cast(DataType::UInt32),
+                    crate::millis_to_hours(col("time")),
+                ])
+                .with_columns(crate::fuzzer_columns(config, name))
+                .drop(["time"]),
+        );
+    }
+
+    Ok(concat_lf_diagonal(data, UnionArgs::default())?.filter(parse_filter_expr(&config.filter)))
+}
+
+/// Summarizes the final number of crashes and bugs for each group in `by`, where `false_positives`
+/// is the fraction of reported crashes that do not correspond to a distinct verified bug.
+pub fn crash_bug_summary(crashes_vs_bugs: LazyFrame, by: &[&str]) -> LazyFrame {
+    let trial_key = [col("fuzzer"), col("binary"), col("trial")];
+    crashes_vs_bugs
+        .filter(col("hours").eq(col("hours").max().over(&trial_key)))
+        .group_by_stable(by)
+        .agg([
+            col("trial").count().alias("trials"),
+            median("crashes").alias("crashes_median"),
+            median("bugs").alias("bugs_median"),
+            sum("crashes").alias("crashes_total"),
+            sum("bugs").alias("bugs_total"),
+        ])
+        .with_column(
+            when(col("crashes_total").gt(lit(0)))
+                .then(
+                    lit(1.0)
+                        - col("bugs_total").cast(DataType::Float64)
+                            / col("crashes_total").cast(DataType::Float64),
+                )
+                .otherwise(lit(NULL))
+                .alias("false_positives"),
+        )
+}
+
+/// Keeps the first time that each bug was found in each trial (see [crate::load_bugs]).
+fn first_bug_hits(bugs: LazyFrame) -> LazyFrame {
+    bugs.group_by([col("fuzzer"), col("binary"), col("trial"), col("bug_id")])
+        .agg([col("hours").min(), style().first()])
+}
+
+/// Summarizes the time taken to find each bug, where `trials_found` is the number of trials the bug
+/// was found in and the time statistics only consider those trials.
+pub fn time_to_bug(config: &Config) -> anyhow::Result<LazyFrame> {
+    let bugs = crate::load_bugs(config)?;
+    Ok(first_bug_hits(bugs)
+        .group_by([col("fuzzer"), col("binary"), col("bug_id")])
+        .agg([
+            col("trial").count().alias("trials_found"),
+            col("hours").min().alias("hours_min"),
+            col("hours").median().alias("hours_median"),
+            col("hours").max().alias("hours_max"),
+        ])
+        .with_column(
+            (col("trials_found").cast(DataType::Float64) / lit(config.trials as f64))
+                .alias("reliability"),
+        )
+        .sort_by_exprs(
+            [col("binary"), col("bug_id"), col("fuzzer")],
+            SortMultipleOptions::new().with_maintain_order(true),
+        ))
+}
+
+/// Computes the number of trials (`count`) that found each bug over time, for plotting bug survival
+/// curves alongside the coverage survival curves (see [block_survival]).
+pub fn bug_survival(config: &Config) -> anyhow::Result<LazyFrame> {
+    let bugs = crate::load_bugs(config)?;
+    Ok(first_bug_hits(bugs)
+        .sort(["hours"], Default::default())
+        .with_column(
+            col("hours").cum_count(false).over(["fuzzer", "binary", "bug_id"]).alias("count"),
+        )
+        .sort_by_exprs(
+            [col("binary"), col("bug_id"), col("fuzzer"), col("count")],
+            SortMultipleOptions::new().with_maintain_order(true),
+        ))
+}
+
+/// Tests whether the final coverage reached by each fuzzer differs significantly from the coverage
+/// reached by `config.reference` on the same binary, using a two-sided Mann-Whitney U test and the
+/// Vargha-Delaney A12 effect size (the probability that a trial of the fuzzer reaches more blocks
+/// than a trial of the reference, where 0.5 indicates no difference).
+pub fn significance_table(config: &Config) -> anyhow::Result<DataFrame> {
+    let samples = final_coverage_samples(config)?;
+
+    let (mut binary_col, mut fuzzer_col, mut trials, mut reference_trials) =
+        (vec![], vec![], vec![], vec![]);
+    let (mut u, mut p_value, mut a12) = (vec![], vec![], vec![]);
+    for ((binary, fuzzer), x) in &samples {
+        if *fuzzer == config.reference {
+            continue;
+        }
+        let Some(y) = samples.get(&(binary.clone(), config.reference.clone()))
+        else {
+            continue;
+        };
+        let test = mann_whitney_u(x, y);
+        binary_col.push(binary.as_str());
+        fuzzer_col.push(fuzzer.as_str());
+        trials.push(x.len() as u32);
+        reference_trials.push(y.len() as u32);
+        u.push(test.u);
+        p_value.push(test.p_value);
+        a12.push(test.a12);
+    }
+
+    let table = df! {
+        "binary" => binary_col,
+        "fuzzer" => fuzzer_col,
+        "trials" => trials,
+        "reference_trials" => reference_trials,
+        "u" => u,
+        "p_value" => p_value,
+        "a12" => a12,
+    }?;
+    Ok(table
+        .lazy()
+        .sort_by_exprs(
+            [order_by_binary(), col("fuzzer")],
+            SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+        )
+        .collect()?)
+}
+
+/// Loads the final coverage of each trial, grouped by binary and fuzzer.
+fn final_coverage_samples(
+    config: &Config,
+) -> anyhow::Result<IndexMap<(String, String), Vec<f64>>> {
+    let final_coverage = crate::load_block_hits(config)?
+        .group_by([col("fuzzer"), col("binary"), col("trial")])
+        .agg([col("blocks").max()])
+        .select([
+            col("fuzzer").cast(DataType::String),
+            col("binary").cast(DataType::String),
+            col("blocks").cast(DataType::Float64),
+        ])
+        .collect()?;
+
+    let mut samples: IndexMap<(String, String), Vec<f64>> = IndexMap::new();
+    let fuzzers = final_coverage.column("fuzzer")?.str()?;
+    let binaries = final_coverage.column("binary")?.str()?;
+    let blocks = final_coverage.column("blocks")?.f64()?;
+    for ((fuzzer, binary), blocks) in fuzzers.into_iter().zip(binaries).zip(blocks) {
+        let (Some(fuzzer), Some(binary), Some(blocks)) = (fuzzer, binary, blocks)
+        else {
+            continue;
+        };
+        samples.entry((binary.to_owned(), fuzzer.to_owned())).or_default().push(blocks);
+    }
+    Ok(samples)
+}
+
+/// Compares every pair of fuzzers on each binary (using the same test as [significance_table]),
+/// counting the number of binaries where `fuzzer` reaches significantly more coverage than
+/// `other` (`wins`), significantly less coverage (`losses`), and the number of binaries both were
+/// evaluated on (`binaries`). A difference is significant if the p-value is below
+/// `config.significance_level`. The `weighted_` columns sum the weights of these binaries instead
+/// (see [Config::binary_weights]).
+pub fn significance_summary(config: &Config) -> anyhow::Result<DataFrame> {
+    let samples = final_coverage_samples(config)?;
+
+    let mut fuzzers: Vec<&str> = samples.keys().map(|(_, fuzzer)| fuzzer.as_str()).collect();
+    let mut binaries: Vec<&str> = samples.keys().map(|(binary, _)| binary.as_str()).collect();
+    // Keep the order that fuzzers and binaries first appear in.
+    let mut seen = std::collections::HashSet::new();
+    fuzzers.retain(|x| seen.insert(*x));
+    seen.clear();
+    binaries.retain(|x| seen.insert(*x));
+
+    let (mut fuzzer_col, mut other_col) = (vec![], vec![]);
+    let (mut wins_col, mut losses_col, mut binaries_col) = (vec![], vec![], vec![]);
+    let (mut weighted_wins_col, mut weighted_losses_col, mut weight_col) = (vec![], vec![], vec![]);
+    for fuzzer in &fuzzers {
+        for other in fuzzers.iter().filter(|x| *x != fuzzer) {
+            let (mut wins, mut losses, mut n) = (0_u32, 0_u32, 0_u32);
+            let (mut weighted_wins, mut weighted_losses, mut weight) = (0.0, 0.0, 0.0);
+            for binary in &binaries {
+                let get = |name: &str| samples.get(&(binary.to_string(), name.to_owned()));
+                let (Some(x), Some(y)) = (get(fuzzer), get(other))
+                else {
+                    continue;
+                };
+                n += 1;
+                let binary_weight = config.binary_weight(binary);
+                weight += binary_weight;
+                let test = mann_whitney_u(x, y);
+                if test.p_value < config.significance_level {
+                    match test.a12 > 0.5 {
+                        true => {
+                            wins += 1;
+                            weighted_wins += binary_weight;
+                        }
+                        false => {
+                            losses += 1;
+                            weighted_losses += binary_weight;
+                        }
+                    }
+                }
+            }
+            fuzzer_col.push(*fuzzer);
+            other_col.push(*other);
+            wins_col.push(wins);
+            losses_col.push(losses);
+            binaries_col.push(n);
+            weighted_wins_col.push(weighted_wins);
+            weighted_losses_col.push(weighted_losses);
+            weight_col.push(weight);
+        }
+    }
+
+    Ok(df! {
+        "fuzzer" => fuzzer_col,
+        "other" => other_col,
+        "wins" => wins_col,
+        "losses" => losses_col,
+        "binaries" => binaries_col,
+        "weighted_wins" => weighted_wins_col,
+        "weighted_losses" => weighted_losses_col,
+        "weight" => weight_col,
+    }?)
+}
+
+/// Arranges a summary generated by [significance_summary] as a matrix, where each cell contains
+/// `wins/binaries` for the fuzzer of the row compared to the fuzzer of the column (i.e. the row
+/// significantly outperforms the column on `wins` of the `binaries` both were evaluated on).
+pub fn significance_matrix(summary: &DataFrame) -> anyhow::Result<DataFrame> {
+    let fuzzer = summary.column("fuzzer")?.str()?;
+    let other = summary.column("other")?.str()?;
+    let wins = summary.column("wins")?.u32()?;
+    let binaries = summary.column("binaries")?.u32()?;
+
+    let mut fuzzers: Vec<&str> = vec![];
+    let mut cells: std::collections::HashMap<(&str, &str), String> = Default::default();
+    for i in 0..summary.height() {
+        let (Some(fuzzer), Some(other)) = (fuzzer.get(i), other.get(i))
+        else {
+            continue;
+        };
+        if !fuzzers.contains(&fuzzer) {
+            fuzzers.push(fuzzer);
+        }
+        let cell = format!("{}/{}", wins.get(i).unwrap_or(0), binaries.get(i).unwrap_or(0));
+        cells.insert((fuzzer, other), cell);
+    }
+
+    let mut columns = vec![Series::new("fuzzer", &fuzzers)];
+    for column in &fuzzers {
+        let values: Vec<Option<&str>> =
+            fuzzers.iter().map(|row| cells.get(&(*row, *column)).map(|x| x.as_str())).collect();
+        columns.push(Series::new(column, values));
+    }
+    Ok(DataFrame::new(columns)?)
+}
+
+/// Formats a matrix generated by [significance_matrix] as a LaTeX table.
+pub fn significance_matrix_latex(matrix: &DataFrame) -> anyhow::Result<String> {
+    let escape = |x: &str| x.replace('_', "\\_").replace('&', "\\&").replace('%', "\\%");
+    let fuzzers = matrix.column("fuzzer")?.str()?;
+
+    let mut out = String::new();
+    let n = matrix.width() - 1;
+    out.push_str(&format!("\\begin{{tabular}}{{l{}}}\n\\toprule\n", "c".repeat(n)));
+    out.push_str("$>$");
+    for name in &matrix.get_column_names()[1..] {
+        out.push_str(&format!(" & {}", escape(name)));
+    }
+    out.push_str(" \\\\\n\\midrule\n");
+    for (i, row) in fuzzers.into_iter().enumerate() {
+        out.push_str(&escape(row.unwrap_or_default()));
+        for column in &matrix.get_columns()[1..] {
+            match column.str()?.get(i) {
+                Some(cell) => out.push_str(&format!(" & {cell}")),
+                None => out.push_str(" & --"),
+            }
+        }
+        out.push_str(" \\\\\n");
+    }
+    out.push_str("\\bottomrule\n\\end{tabular}\n");
+    Ok(out)
+}
+
+struct MannWhitneyU {
+    u: f64,
+    p_value: f64,
+    a12: f64,
+}
+
+/// Computes the Mann-Whitney U statistic of `x` compared to `y`, along with a two-sided p-value
+/// using the normal approximation (with tie and continuity corrections).
+fn mann_whitney_u(x: &[f64], y: &[f64]) -> MannWhitneyU {
+    let (n1, n2) = (x.len() as f64, y.len() as f64);
+    let mut values: Vec<(f64, bool)> =
+        x.iter().map(|v| (*v, true)).chain(y.iter().map(|v| (*v, false))).collect();
+    values.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // Tied values are assigned the average of the ranks they span.
+    let mut rank_sum = 0.0;
+    let mut ties = 0.0;
+    let mut start = 0;
+    while start < values.len() {
+        let end = start + values[start..].iter().take_while(|v| v.0 == values[start].0).count();
+        let rank = (start + end + 1) as f64 / 2.0;
+        rank_sum += rank * values[start..end].iter().filter(|v| v.1).count() as f64;
+        let t = (end - start) as f64;
+        ties += t * t * t - t;
+        start = end;
+    }
+
+    let u = rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let n = n1 + n2;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    let p_value = if variance > 0.0 {
+        let z = ((u - n1 * n2 / 2.0).abs() - 0.5).max(0.0) / variance.sqrt();
+        libm::erfc(z / std::f64::consts::SQRT_2)
+    }
+    else {
+        // All values are identical.
+        1.0
+    };
+
+    MannWhitneyU { u, p_value, a12: u / (n1 * n2) }
+}
+
+/// Joins the coverage reached by each trial with the number of executions performed by the fuzzer
+/// (see [Config::throughput]), computing the number of blocks reached per million executions over
+/// time. This normalizes for differences in execution speed between fuzzers (e.g. emulators).
+pub fn coverage_efficiency(config: &Config) -> anyhow::Result<LazyFrame> {
+    let res = config.time_resolution as i64;
+    let by = [col("binary"), col("trial")];
+
+    let mut data = vec![];
+    for (name, entry) in &config.throughput {
+        let Some(execs) = crate::bugs::read_total_execs_all(&entry.plot_data)? else {
+            continue;
+        };
+        let duration = entry.duration.as_millis() as i64;
+        let execs =
+            fill_missing(execs.sort(["time"], Default::default()), duration, res, "time", &by)?;
+        data.push(
+            execs
+                .with_column(crate::millis_to_hours(col("time")))
+                .with_columns(crate::fuzzer_columns(config, name)),
+        );
+    }
+    anyhow::ensure!(!data.is_empty(), "no throughput data found");
+    let execs = concat_lf_diagonal(data, UnionArgs::default())?;
+
+    // Coverage and throughput data may be recorded with different units, so join on the nearest
+    // second to avoid comparing floating point values.
+    let seconds =
+        || (col("hours") * lit(60.0 * 60.0)).round(0).cast(DataType::Int64).alias("seconds");
+    let join_key = [col("fuzzer"), col("binary"), col("trial"), col("seconds")];
+    Ok(crate::load_block_hits(config)?
+        .with_column(seconds())
+        .join(
+            execs.with_column(seconds()).select([
+                col("fuzzer"),
+                col("binary"),
+                col("trial"),
+                col("seconds"),
+                col("execs"),
+            ]),
+            &join_key,
+            &join_key,
+            JoinType::Inner.into(),
+        )
+        .drop(["seconds"])
+        .with_column(
+            when(col("execs").gt(lit(0)))
+                .then(
+                    col("blocks").cast(DataType::Float64)
+                        / (col("execs").cast(DataType::Float64) / lit(1_000_000.0)),
+                )
+                .otherwise(lit(NULL))
+                .alias("blocks_per_mexec"),
+        ))
+}
+
+/// Lists the weight of each binary in aggregate metrics (see [Config::binary_weights]) along with
+/// its share of the total weight, so that the weights used for aggregate results are recorded.
+/// Configured weights for binaries that are not part of the benchmark are reported as warnings.
+pub fn binary_weights(config: &Config) -> anyhow::Result<DataFrame> {
+    let binaries = crate::load_block_hits(config)?
+        .select([col("binary").cast(DataType::String)])
+        .unique_stable(None, UniqueKeepStrategy::Any)
+        .collect()?;
+    let binaries: Vec<&str> = binaries["binary"].str()?.into_no_null_iter().collect();
+    for binary in config.binary_weights.keys() {
+        if !binaries.contains(&binary.as_str()) {
+            eprintln!("WARNING: weight configured for unknown binary: {binary}");
+        }
+    }
+
+    let weights: Vec<f64> = binaries.iter().map(|x| config.binary_weight(x)).collect();
+    let total: f64 = weights.iter().sum();
+    let shares: Vec<f64> =
+        weights.iter().map(|x| if total > 0.0 { x / total } else { 0.0 }).collect();
+    let df = df! {
+        "binary" => &binaries,
+        "weight" => weights,
+        "share" => shares,
+    }?;
+    Ok(df
+        .lazy()
+        .sort_by_exprs([order_by_binary()], SortMultipleOptions::new().with_maintain_order(true))
+        .collect()?)
+}
+
+/// Summarizes the final coverage efficiency (see [coverage_efficiency]) of each fuzzer and binary.
+pub fn coverage_efficiency_summary(efficiency: LazyFrame) -> LazyFrame {
+    let trial_key = [col("fuzzer"), col("binary"), col("trial")];
+    efficiency
+        .filter(col("hours").eq(col("hours").max().over(&trial_key)))
+        .group_by_stable(["dataset", "fuzzer", "binary"])
+        .agg([
+            col("trial").count().alias("trials"),
+            col("blocks").median().alias("median_blocks"),
+            col("execs").median().alias("median_execs"),
+            col("blocks_per_mexec").min().alias("min_blocks_per_mexec"),
+            col("blocks_per_mexec").median().alias("median_blocks_per_mexec"),
+            col("blocks_per_mexec").max().alias("max_blocks_per_mexec"),
+        ])
+        .sort_by_exprs(
+            [order_by_binary(), col("dataset")],
+            SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+        )
+}
+
+/// Loads the execution speed (`execs_per_sec`) and total number of executions (`execs`) of each
+/// trial over time from AFL plot data: either from [Config::throughput] or from `AflPlotData`
+/// datasets (for fuzzers without a `throughput` entry). Comparing this with the coverage over time
+/// shows whether differences in coverage stem from raw speed or from the quality of the inputs.
+pub fn execs_over_time(config: &Config) -> anyhow::Result<LazyFrame> {
+    let res = config.time_resolution as i64;
+    let by = [col("binary"), col("trial")];
+
+    let mut sources: Vec<(&str, &str, std::time::Duration)> = config
+        .throughput
+        .iter()
+        .map(|(name, entry)| (name.as_str(), entry.plot_data.as_str(), entry.duration))
+        .collect();
+    for (_, name, entry) in config.datasets() {
+        if let DataSource::AflPlotData { glob, duration } = &entry.source {
+            if !config.throughput.contains_key(name) {
+                sources.push((name, glob, *duration));
+            }
+        }
+    }
+
+    let mut data = vec![];
+    for (name, glob, duration) in sources {
+        let Some(execs) = crate::bugs::read_total_execs_all(glob)? else {
+            continue;
+        };
+        let duration = duration.as_millis() as i64;
+        // Throughput entries are keyed by the name of the coverage dataset, so use its style.
+        let id = config.data.get_index_of(name).unwrap_or(config.data.len());
+        let style = config.data.get(name).and_then(|x| x.first()).map(|x| x.style.clone());
+        data.push(
+            fill_missing(execs.sort(["time"], Default::default()), duration, res, "time", &by)?
+                .with_column(crate::millis_to_hours(col("time")))
+                .drop(["time"])
+                .with_columns(crate::fuzzer_columns(config, name))
+                .with_column(lit(id as u32).alias("dataset"))
+                .with_columns(crate::style_columns(
+                    config.fuzzer_family(name).0,
+                    &style.unwrap_or_default(),
+                )),
+        );
+    }
+    anyhow::ensure!(!data.is_empty(), "no throughput data found");
+    let global_filter = parse_filter_expr(&config.filter);
+    Ok(concat_lf_diagonal(data, UnionArgs::default())?.filter(global_filter))
+}
+
+/// Summarizes [execs_over_time] over all trials of each fuzzer on each binary: the median, min and
+/// max execution speed at each point in time (like [summarize_coverage]).
+pub fn summarize_throughput(execs: LazyFrame) -> LazyFrame {
+    execs
+        .sort(["hours"], Default::default())
+        .group_by_stable(["hours", "binary", "fuzzer", "dataset"])
+        .agg([
+            median("execs_per_sec").alias("execs_per_sec_median"),
+            max("execs_per_sec").alias("execs_per_sec_max"),
+            min("execs_per_sec").alias("execs_per_sec_min"),
+            median("execs").alias("execs_median"),
+            style().first(),
+        ])
+        .sort_by_exprs(
+            [order_by_binary(), col("dataset")],
+            SortMultipleOptions::new().with_nulls_last(false).with_maintain_order(true),
+        )
+}
+
+/// Loads the execution speed (`execs_per_sec`) and corpus size (`corpus_count`) of each trial of
+/// the `AflPlotData` datasets over time, along with the number of edges found (`edges`), so that
+/// throughput and corpus growth can be compared with the coverage reached by the fuzzer.
+pub fn afl_stats(config: &Config) -> anyhow::Result<LazyFrame> {
+    let res = config.time_resolution as i64;
+    let by = [col("binary"), col("trial")];
+
+    let mut data = vec![];
+    for (id, name, entry) in config.datasets() {
+        let DataSource::AflPlotData { glob, duration } = &entry.source else {
+            continue;
+        };
+        let Some(stats) = crate::bugs::read_afl_stats_all(glob)? else {
+            continue;
+        };
+        let stats = stats.filter(parse_filter_expr(&entry.filter));
+        let stats =
+            crate::align_trials(config, stats, "time", &by).sort(["time"], Default::default());
+        let duration = duration.as_millis() as i64;
+        data.push(
+            fill_missing(stats, duration, res, "time", &by)?
+                .with_column(crate::millis_to_hours(col("time")))
+                .drop(["time"])
+                .with_columns(crate::fuzzer_columns(config, name))
+                .with_column(lit(id as u32).alias("dataset"))
+                .with_columns(crate::style_columns(config.fuzzer_family(name).0, &entry.style)),
+        );
+    }
+    anyhow::ensure!(!data.is_empty(), "no AFL plot data found");
+    let global_filter = parse_filter_expr(&config.filter);
+    Ok(concat_lf_diagonal(data, UnionArgs::default())?.filter(global_filter))
+}
+
+/// Summarizes [afl_stats] over all trials of each fuzzer on each binary: the median, min and max
+/// edges found, execution speed and corpus size at each point in time (like [summarize_coverage]).
+pub fn summarize_afl_stats(stats: LazyFrame) -> LazyFrame {
+    stats
+        .sort(["hours"], Default::default())
+        .group_by_stable(["hours", "binary", "fuzzer", "dataset"])
+        .agg([
+            median("edges").alias("edges_median"),
+            max("edges").alias("edges_max"),
+            min("edges").alias("edges_min"),
+            median("execs_per_sec").alias("execs_per_sec_median"),
+            max("execs_per_sec").alias("execs_per_sec_max"),
+            min("execs_per_sec").alias("execs_per_sec_min"),
+            median("corpus_count").alias("corpus_count_median"),
+            max("corpus_count").alias("corpus_count_max"),
+            min("corpus_count").alias("corpus_count_min"),
+            style().first(),
+        ])
+        .sort_by_exprs(
+            [order_by_binary(), col("dataset")],
+            SortMultipleOptions::new().with_nulls_last(false).with_maintain_order(true),
+        )
+}
+
 pub fn get_average_input_sizes(testcases: LazyFrame) -> LazyFrame {
     // If untrimed_len is zero, then the input was not trimmed, so correct the untrimed value here.
     let update_untrimmed = when(col("untrimed_len").eq(0))
@@ -370,6 +2020,7 @@ pub fn block_survival(
                 at_first_hit(col("blocks"), region.start).alias("start_blocks"),
                 at_first_hit(col("hours"), region.end).alias("end_time"),
                 at_first_hit(col("blocks"), region.end).alias("end_blocks"),
+                style().first(),
             ])
             .with_column((col("end_time") - col("start_time")).alias("duration"))
             .sort(["duration"], SortMultipleOptions::new().with_nulls_last(true))
@@ -395,6 +2046,7 @@ pub fn summarize_coverage(block_hits: BlockHits) -> LazyFrame {
             median("blocks").alias("blocks_median"),
             max("blocks").alias("blocks_max"),
             min("blocks").alias("blocks_min"),
+            style().first(),
         ])
         .sort_by_exprs(
             [order_by_binary(), col("dataset")],
@@ -402,6 +2054,90 @@ pub fn summarize_coverage(block_hits: BlockHits) -> LazyFrame {
         )
 }
 
+/// Summarizes the final coverage reached by each fuzzer on each binary over all trials: the median
+/// along with the min and max (a visual counterpart of [coverage_table]).
+pub fn coverage_endpoints(block_hits: BlockHits) -> LazyFrame {
+    block_hits
+        .group_by(["binary", "fuzzer", "trial"])
+        .agg([col("blocks").max().alias("total_blocks"), col("dataset").min(), style().first()])
+        .group_by(["binary", "fuzzer"])
+        .agg([
+            median("total_blocks").alias("blocks_median"),
+            max("total_blocks").alias("blocks_max"),
+            min("total_blocks").alias("blocks_min"),
+            col("trial").count().alias("trials"),
+            col("dataset").min(),
+            style().first(),
+        ])
+        .sort_by_exprs(
+            [order_by_binary(), col("dataset")],
+            SortMultipleOptions::new().with_nulls_last(false).with_maintain_order(true),
+        )
+}
+
+/// Normalizes the coverage of each binary to the fraction of its best-known block set (see
+/// [best_known_blocks]) then averages across all binaries for each fuzzer (weighted by
+/// [Config::binary_weights]), producing a single aggregate coverage curve with a 95% confidence
+/// band computed over trials. The total weight of the binaries is included as `weight`.
+///
+/// Binaries without raw coverage (e.g. Ember data) are normalized by the maximum observed blocks.
+pub fn normalized_coverage(config: &Config) -> anyhow::Result<LazyFrame> {
+    let block_hits = crate::load_block_hits(config)?.collect()?.lazy();
+
+    let raw = crate::load_raw_coverage(config)?.collect()?.lazy();
+    let best_known = best_known_blocks(raw)
+        .group_by(["binary"])
+        .agg([col("block").count().alias("bb_best_known")]);
+    let observed = block_hits
+        .clone()
+        .group_by(["binary"])
+        .agg([col("blocks").max().alias("bb_observed")]);
+
+    let per_trial = block_hits
+        .join(observed, [col("binary")], [col("binary")], JoinType::Inner.into())
+        .join(best_known, [col("binary")], [col("binary")], JoinType::Left.into())
+        .with_column(
+            (col("blocks").cast(DataType::Float64)
+                / col("bb_best_known")
+                    .fill_null(col("bb_observed"))
+                    .cast(DataType::Float64))
+            .alias("frac"),
+        )
+        .with_column(crate::binary_weight_column(config))
+        .group_by(["hours", "fuzzer", "dataset", "trial"])
+        .agg([
+            ((col("frac") * col("weight")).sum() / col("weight").sum()).alias("frac"),
+            col("binary").n_unique().alias("binaries"),
+            col("weight").sum(),
+            style().first(),
+        ]);
+
+    let ci = lit(1.96) * col("frac").std(1) / col("frac").count().cast(DataType::Float64).sqrt();
+    Ok(per_trial
+        .group_by(["hours", "fuzzer", "dataset"])
+        .agg([
+            col("frac").mean().alias("frac_mean"),
+            ci.alias("frac_ci"),
+            col("frac").count().alias("trials"),
+            col("binaries").max(),
+            col("weight").max(),
+            style().first(),
+        ])
+        .with_columns([
+            (col("frac_mean") - col("frac_ci").fill_null(0.0))
+                .clip(lit(0.0), lit(1.0))
+                .alias("frac_lower"),
+            (col("frac_mean") + col("frac_ci").fill_null(0.0))
+                .clip(lit(0.0), lit(1.0))
+                .alias("frac_upper"),
+        ])
+        .drop(["frac_ci"])
+        .sort_by_exprs(
+            [col("dataset"), col("hours")],
+            SortMultipleOptions::new().with_nulls_last(false).with_maintain_order(true),
+        ))
+}
+
 pub fn summarize_inspector(df: LazyFrame) -> LazyFrame {
     df.sort(["testcase"], Default::default())
         .group_by_stable(["testcase", "arch", "kind"])
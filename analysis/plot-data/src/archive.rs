@@ -0,0 +1,132 @@
+//! Support for reading data directly from the campaign archives created by `bench-harness archive`
+//! (zstd compressed tar files) without extracting them.
+//!
+//! Globs containing an archive (e.g. `archive/*.tar.zst/output/**/coverage.json`) are split into
+//! a glob matching the archives, and a pattern matched against the names of the archive members.
+
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::DataFile;
+
+pub const EXTENSION: &str = ".tar.zst";
+
+/// Member names are matched like paths on disk, i.e. wildcards do not match `/`.
+const MATCH_OPTIONS: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Splits `glob` into the part matching archives and the part matching members of the archives,
+/// returning `None` if the glob does not refer to an archive.
+pub fn split_glob(glob: &str) -> Option<(&str, &str)> {
+    let mut end = 0;
+    for component in glob.split('/') {
+        end += component.len();
+        if component.ends_with(EXTENSION) {
+            let members = glob[end..].trim_start_matches('/');
+            return Some((&glob[..end], if members.is_empty() { "**" } else { members }));
+        }
+        end += 1;
+    }
+    None
+}
+
+/// Reads all members matching `members` (and `filter`) from the archives matching `archives`.
+pub fn load_members(
+    archives: &str,
+    members: &str,
+    mut filter: impl FnMut(&Path) -> bool,
+) -> anyhow::Result<Vec<DataFile>> {
+    let pattern =
+        glob::Pattern::new(members).with_context(|| format!("Error parsing glob: {members}"))?;
+    let mut files = vec![];
+    for archive in
+        glob::glob(archives).with_context(|| format!("Error parsing glob: {archives}"))?
+    {
+        let archive = archive?;
+        let members = read_members(&archive, &pattern, &mut filter)
+            .with_context(|| format!("error reading archive: {}", archive.display()))?;
+        files.extend(members);
+    }
+    Ok(files)
+}
+
+fn read_members(
+    archive: &Path,
+    pattern: &glob::Pattern,
+    filter: &mut impl FnMut(&Path) -> bool,
+) -> anyhow::Result<Vec<DataFile>> {
+    let mut is_match =
+        |name: &str| pattern.matches_with(name, MATCH_OPTIONS) && filter(&archive.join(name));
+
+    // If the archive has an index we can skip archives without any matching members, and stop
+    // decompressing once all matching members have been read.
+    let mut remaining = None;
+    if let Some(index) = read_index(archive)? {
+        let count = index.iter().filter(|name| is_match(name)).count();
+        if count == 0 {
+            return Ok(vec![]);
+        }
+        remaining = Some(count);
+    }
+
+    let file = std::fs::File::open(archive)?;
+    let mut reader = tar::Archive::new(zstd::Decoder::new(file)?);
+    let mut files = vec![];
+    for entry in reader.entries()? {
+        if remaining == Some(0) {
+            break;
+        }
+
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        if !is_match(&name) {
+            continue;
+        }
+
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data).with_context(|| format!("failed to read: {name}"))?;
+        files.push(DataFile::archived(archive.join(&name), data));
+        remaining = remaining.map(|x: usize| x.saturating_sub(1));
+    }
+
+    // Keep the same order as files matched on disk.
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Reads the names of the files in `archive` from the manifest saved alongside the archive (if it
+/// exists).
+fn read_index(archive: &Path) -> anyhow::Result<Option<Vec<String>>> {
+    #[derive(serde::Deserialize)]
+    struct Manifest {
+        files: Vec<ManifestEntry>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct ManifestEntry {
+        path: String,
+    }
+
+    let mut path = archive.as_os_str().to_owned();
+    path.push(".manifest.json");
+    let path = PathBuf::from(path);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let bytes =
+        std::fs::read(&path).with_context(|| format!("failed to read: {}", path.display()))?;
+    let manifest: Manifest = serde_json::from_slice(&bytes)
+        .with_context(|| format!("failed to parse: {}", path.display()))?;
+    Ok(Some(manifest.files.into_iter().map(|x| x.path).collect()))
+}
@@ -0,0 +1,79 @@
+use polars::prelude::*;
+
+use crate::{data_loading, load_glob, multifuzz::add_metadata, DataFile};
+
+/// Read AFL plot data saved by the bench-harness `SaveTaggedAflPlotData` task, returning the
+/// number of crashes reported by the fuzzer over time.
+pub fn read_plot_data_all(glob: &str) -> anyhow::Result<Option<LazyFrame>> {
+    read_tagged_plot_data(glob, [col("saved_crashes").cast(DataType::UInt32).alias("crashes")])
+}
+
+/// Read AFL plot data saved by the bench-harness `SaveTaggedAflPlotData` task, returning the
+/// total number of executions performed by the fuzzer (and the execution speed) over time.
+pub fn read_total_execs_all(glob: &str) -> anyhow::Result<Option<LazyFrame>> {
+    read_tagged_plot_data(glob, [
+        col("total_execs").cast(DataType::UInt64).alias("execs"),
+        col("execs_per_sec").cast(DataType::Float64),
+    ])
+}
+
+/// Read AFL plot data saved by the bench-harness `SaveTaggedAflPlotData` task, returning the
+/// number of edges found (`edges`), the execution speed and the size of the corpus over time.
+pub fn read_afl_stats_all(glob: &str) -> anyhow::Result<Option<LazyFrame>> {
+    read_tagged_plot_data(glob, [
+        col("edges_found").cast(DataType::UInt32).alias("edges"),
+        col("execs_per_sec").cast(DataType::Float64),
+        col("corpus_count").cast(DataType::UInt32),
+    ])
+}
+
+/// Read the tagged AFL plot data files matching `glob`, selecting `columns` along with the time
+/// (in milliseconds) of each row.
+fn read_tagged_plot_data(
+    glob: &str,
+    columns: impl IntoIterator<Item = Expr>,
+) -> anyhow::Result<Option<LazyFrame>> {
+    let data = load_glob(glob, read_tagged_csv, |_| true)?;
+    if data.is_empty() {
+        eprintln!("WARNING: No tagged plot data files found for: {glob}");
+        return Ok(None);
+    }
+    let time = (col("relative_time").cast(DataType::Int64) * lit(1000)).alias("time");
+    let mut exprs = vec![col("tag"), time];
+    exprs.extend(columns);
+    let data = concat(data, UnionArgs::default())?.select(exprs);
+    Ok(Some(with_tag_columns(data)))
+}
+
+/// Read the verified bugs saved by the bench-harness bug verifiers (e.g. `InputPatternVerifier`).
+///
+/// Note: the verifiers add a dummy `none` bug to each trial to avoid dropping trials without any
+/// bugs, these are removed here.
+pub fn read_bugs_all(glob: &str) -> anyhow::Result<Option<LazyFrame>> {
+    let data = load_glob(glob, read_tagged_csv, |_| true)?;
+    if data.is_empty() {
+        eprintln!("WARNING: No verified bug files found for: {glob}");
+        return Ok(None);
+    }
+    let data = concat(data, UnionArgs::default())?
+        .select([
+            col("tag"),
+            col("bug_id").cast(DataType::String),
+            col("time").cast(DataType::Int64),
+        ])
+        .filter(col("bug_id").neq(lit("none")));
+    Ok(Some(with_tag_columns(data)))
+}
+
+fn read_tagged_csv(file: &DataFile) -> anyhow::Result<LazyFrame> {
+    file.scan_csv(CsvReadOptions::default().with_has_header(true))
+}
+
+/// Extract the binary and trial from the tag added by the bench-harness.
+fn with_tag_columns(lf: LazyFrame) -> LazyFrame {
+    lf.with_columns(
+        ["binary", "trial"].map(|key| data_loading::extract_tag_value(col("tag"), key).alias(key)),
+    )
+    .with_columns(add_metadata())
+    .drop(["tag"])
+}
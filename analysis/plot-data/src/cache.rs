@@ -0,0 +1,102 @@
+//! On-disk memoization of the data loaded from each dataset (see `cache_dir` in the config), so
+//! that re-running after changes that do not affect the loaded data (e.g. styles or plot layouts)
+//! skips parsing and aggregating the raw data again.
+//!
+//! Entries are keyed by the files matched by the dataset (path, size and modification time) and the
+//! config options used for loading it. Stale entries are never removed, however the cache directory
+//! can be deleted at any time.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::Context;
+use polars::prelude::*;
+
+use crate::Config;
+
+/// Incremented whenever the way that datasets are loaded changes, to invalidate existing entries.
+const VERSION: u32 = 2;
+
+/// The key of a cache entry.
+#[derive(Clone)]
+pub(crate) struct Key(DefaultHasher);
+
+impl Key {
+    /// Creates a key for the data of type `kind` (e.g. `block_hits`).
+    pub fn new(kind: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        (VERSION, kind).hash(&mut hasher);
+        Self(hasher)
+    }
+
+    /// Adds a config option to the key (using its debug representation).
+    pub fn param(mut self, value: &impl std::fmt::Debug) -> Self {
+        format!("{value:?}").hash(&mut self.0);
+        self
+    }
+
+    /// Adds the size and modification time of the file at `path` to the key.
+    pub fn file(mut self, path: &Path) -> Self {
+        path.hash(&mut self.0);
+        let modified = |meta: &std::fs::Metadata| {
+            meta.modified().ok().and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+        };
+        std::fs::metadata(path).ok().map(|meta| (meta.len(), modified(&meta))).hash(&mut self.0);
+        self
+    }
+
+    /// Adds all files matching `glob` (see [crate::load_glob]) to the key. For globs that refer to
+    /// files within campaign archives the archives themselves are added.
+    pub fn glob(mut self, glob: &str) -> Self {
+        glob.hash(&mut self.0);
+        let files = crate::archive::split_glob(glob).map_or(glob, |(archives, _)| archives);
+        for path in glob::glob(files).into_iter().flatten().flatten() {
+            self = self.file(&path);
+        }
+        self
+    }
+}
+
+/// Returns the data computed by `load`, reusing the entry for `key` from the cache directory of
+/// `config` if it exists. `load` returns `None` if there is no data to load (which is not cached).
+pub(crate) fn cached(
+    config: &Config,
+    key: Key,
+    load: impl FnOnce() -> anyhow::Result<Option<LazyFrame>>,
+) -> anyhow::Result<Option<LazyFrame>> {
+    let Some(dir) = config.cache_dir.as_ref() else {
+        return load();
+    };
+    let path = dir.join(format!("{:016x}.parquet", key.0.finish()));
+    if path.exists() {
+        match LazyFrame::scan_parquet(&path, ScanArgsParquet::default()) {
+            Ok(data) => return Ok(Some(data)),
+            Err(e) => eprintln!("WARNING: ignoring cache entry {}: {e}", path.display()),
+        }
+    }
+
+    let Some(data) = load()? else {
+        return Ok(None);
+    };
+    let mut df = data.collect()?;
+    if let Err(e) = write_entry(&path, &mut df) {
+        eprintln!("WARNING: failed to write cache entry {}: {e:#}", path.display());
+    }
+    Ok(Some(df.lazy()))
+}
+
+fn write_entry(path: &Path, df: &mut DataFrame) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Write to a temporary file first to avoid leaving behind partial entries if interrupted.
+    let tmp = path.with_extension("parquet.tmp");
+    ParquetWriter::new(&mut std::fs::File::create(&tmp)?)
+        .with_compression(ParquetCompression::Zstd(None))
+        .finish(df)?;
+    std::fs::rename(&tmp, path).with_context(|| format!("failed to rename {}", tmp.display()))
+}
@@ -57,11 +57,27 @@ where
     }
 }
 
+fn default_outlier_threshold() -> f64 {
+    3.0
+}
+
+fn default_significance_level() -> f64 {
+    0.05
+}
+
+fn default_runtime_tolerance() -> f64 {
+    0.1
+}
+
 fn one_day() -> Duration {
     Duration::from_secs(60 * 60 * 24)
 }
 
-#[derive(Clone, serde::Deserialize)]
+fn one_hour() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
 pub enum DataSource {
     EmberCsv {
         glob: String,
@@ -80,6 +96,45 @@ pub enum DataSource {
         #[serde(deserialize_with = "parse_duration", default = "one_day")]
         duration: Duration,
     },
+    /// Verified bugs (`tag,bug_id,time`) saved by the bench-harness bug verifiers (e.g.
+    /// `InputPatternVerifier`). These are ignored when loading coverage (see [crate::load_bugs]).
+    BugCsv {
+        glob: String,
+        #[serde(deserialize_with = "parse_duration", default = "one_day")]
+        duration: Duration,
+    },
+    /// AFL plot data saved by the bench-harness `SaveTaggedAflPlotData` task. AFL only reports the
+    /// number of edges found, so these datasets are ignored when loading block coverage. The edges
+    /// found, execution speed and corpus size are loaded by [crate::analysis::afl_stats].
+    AflPlotData {
+        glob: String,
+        #[serde(deserialize_with = "parse_duration", default = "one_day")]
+        duration: Duration,
+    },
+}
+
+impl DataSource {
+    /// The glob matching the files of the dataset.
+    pub fn glob(&self) -> &str {
+        match self {
+            Self::EmberCsv { glob, .. }
+            | Self::FuzzwareBlocksCsv { glob, .. }
+            | Self::MultiFuzzBench { glob, .. }
+            | Self::BugCsv { glob, .. }
+            | Self::AflPlotData { glob, .. } => glob,
+        }
+    }
+
+    /// The duration of each trial in the dataset.
+    pub fn duration(&self) -> Duration {
+        match self {
+            Self::EmberCsv { duration, .. }
+            | Self::FuzzwareBlocksCsv { duration, .. }
+            | Self::MultiFuzzBench { duration, .. }
+            | Self::BugCsv { duration, .. }
+            | Self::AflPlotData { duration, .. } => *duration,
+        }
+    }
 }
 
 #[derive(serde::Deserialize)]
@@ -98,7 +153,7 @@ impl<T> From<VecOrOne<T>> for Vec<T> {
     }
 }
 
-#[derive(Default, Clone, serde::Deserialize)]
+#[derive(Default, Clone, Debug, serde::Deserialize)]
 pub enum FilterExpr {
     Col(String),
     Str(String),
@@ -113,11 +168,131 @@ pub enum FilterExpr {
     True,
 }
 
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl LineStyle {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Solid => "solid",
+            Self::Dashed => "dashed",
+            Self::Dotted => "dotted",
+        }
+    }
+}
+
+/// How coverage events recorded after the end of a trial (e.g. by replays during teardown) are
+/// handled when loading raw coverage.
+#[derive(Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostCampaignEvents {
+    /// Keep all events.
+    #[default]
+    Keep,
+    /// Remove any events recorded after the `duration` of the dataset (see
+    /// [crate::trimmed_events] for the number of events removed from each trial).
+    Trim,
+}
+
+/// How trials that were loaded from more than one directory (e.g. because results were copied
+/// twice or a trial was re-run) are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateTrials {
+    /// Keep the data of the most recently modified directory (with a warning).
+    #[default]
+    Dedup,
+    /// Fail to load the dataset.
+    Error,
+}
+
+/// How the blocks of each binary are ordered in the block timeline (see
+/// [crate::analysis::block_timeline]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockOrder {
+    /// Order blocks by their address.
+    #[default]
+    Address,
+    /// Group blocks by the function containing them (requires `coverage_metadata`).
+    Function,
+}
+
+/// How the coverage of each binary is laid out in the coverage plot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverageLayout {
+    /// A single panel for each binary containing every fuzzer.
+    #[default]
+    Combined,
+    /// A separate panel for each fuzzer, with a row of panels for each binary.
+    PerFuzzer,
+}
+
+/// The unit that coverage is measured in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverageGranularity {
+    /// Count individual basic blocks.
+    #[default]
+    Block,
+    /// Count the functions containing each block reached (requires `coverage_metadata`). Analyses
+    /// that operate on blocks use the entry address of the function in the `block` column instead.
+    Function,
+}
+
+impl CoverageGranularity {
+    /// The name of the unit coverage is counted in (used for axis labels).
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Self::Block => "Blocks",
+            Self::Function => "Functions",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BugExploitMarker {
+    /// Shade the subplot of the binary in gray.
+    #[default]
+    Shade,
+    /// Draw diagonal hatching over the subplot of the binary.
+    Hatch,
+    /// Append an asterisk to the name of the binary.
+    Asterisk,
+}
+
+/// Presentation hints for a dataset. These are propagated to the plot crate as columns (see
+/// [crate::STYLE_COLUMNS]).
+#[derive(Clone, Default, serde::Deserialize)]
+pub struct Style {
+    /// Name to display in the legend (defaults to the name of the dataset).
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Index of the color in the plot palette.
+    #[serde(default)]
+    pub color: Option<u32>,
+    /// Index of the marker used for the series.
+    #[serde(default)]
+    pub marker: Option<u32>,
+    #[serde(default)]
+    pub line: LineStyle,
+}
+
 #[derive(Clone, serde::Deserialize)]
 pub struct Dataset {
     pub source: DataSource,
     #[serde(default)]
     pub filter: FilterExpr,
+    #[serde(default)]
+    pub style: Style,
 }
 
 #[derive(Clone, Default, serde::Deserialize)]
@@ -171,12 +346,108 @@ impl Default for PlotLayout {
     }
 }
 
+/// Crash and verified bug data generated by the bench-harness for a single fuzzer.
+#[derive(Clone, serde::Deserialize)]
+pub struct CrashDataset {
+    /// Glob for tagged AFL plot data files (containing the number of crashes over time).
+    pub plot_data: String,
+    /// Glob for CSV files generated by the bug verifier.
+    pub bugs: String,
+    #[serde(deserialize_with = "parse_duration", default = "one_day")]
+    pub duration: Duration,
+}
+
+/// Throughput data generated by the bench-harness for a single fuzzer.
+#[derive(Clone, serde::Deserialize)]
+pub struct ThroughputDataset {
+    /// Glob for tagged AFL plot data files (containing the total number of executions over time).
+    pub plot_data: String,
+    #[serde(deserialize_with = "parse_duration", default = "one_day")]
+    pub duration: Duration,
+}
+
 #[derive(Clone, serde::Deserialize)]
 pub struct Diff {
     pub fuzzer_a: String,
     pub fuzzer_b: String,
 }
 
+/// The time window used for listing the blocks that each fuzzer first reached late in a campaign
+/// (see [crate::analysis::coverage_delta]).
+#[derive(Clone, serde::Deserialize)]
+pub struct CoverageDelta {
+    #[serde(deserialize_with = "parse_duration")]
+    pub from: Duration,
+    #[serde(deserialize_with = "parse_duration")]
+    pub to: Duration,
+}
+
+/// Captions and provenance information added to generated figures, and the formats they are
+/// exported to.
+#[derive(Clone, serde::Deserialize)]
+pub struct FigureConfig {
+    /// A human readable caption for each figure (e.g. `coverage`, `aggregate`, `survival`).
+    #[serde(default)]
+    pub captions: HashMap<String, String>,
+    /// Draw the caption and provenance information as a visible footer below each figure (it is
+    /// always embedded in the SVG metadata).
+    #[serde(default)]
+    pub footer: bool,
+    /// Additional formats to export each figure to (the SVG file is always generated). Useful for
+    /// submission systems that struggle with large SVG files.
+    #[serde(default)]
+    pub export: Vec<FigureFormat>,
+    /// The resolution used for bitmap exports (SVG figures are laid out at 96 DPI).
+    #[serde(default = "default_dpi")]
+    pub dpi: f32,
+    /// Combine all figures generated by a run into a single PDF at this path, with one figure per
+    /// page below its caption.
+    #[serde(default)]
+    pub document: Option<PathBuf>,
+}
+
+impl Default for FigureConfig {
+    fn default() -> Self {
+        Self {
+            captions: HashMap::new(),
+            footer: false,
+            export: vec![],
+            dpi: default_dpi(),
+            document: None,
+        }
+    }
+}
+
+fn default_dpi() -> f32 {
+    300.0
+}
+
+/// A format figures can be exported to in addition to SVG.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+pub enum FigureFormat {
+    Png,
+    Pdf,
+}
+
+impl FigureFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Pdf => "pdf",
+        }
+    }
+}
+
+/// Selects the trial used for generating a table of the blocks first reached in each hour (see
+/// [crate::analysis::new_blocks_by_hour]).
+#[derive(Clone, serde::Deserialize)]
+pub struct Story {
+    pub fuzzer: String,
+    pub binary: String,
+    #[serde(default)]
+    pub trial: u32,
+}
+
 #[derive(Clone, serde::Deserialize)]
 pub struct Config {
     #[serde(default)]
@@ -185,9 +456,20 @@ pub struct Config {
     pub plot_layout: PlotLayout,
     #[serde(default)]
     pub survival_layout: PlotLayout,
+    /// Layout of the block discovery heatmaps (one cell per binary).
+    #[serde(default)]
+    pub heatmap_layout: PlotLayout,
+    /// Layout of the block overlap plots (one cell per binary).
+    #[serde(default)]
+    pub overlap_layout: PlotLayout,
+    /// Layout of the block timeline heatmaps (one cell per fuzzer on each binary).
+    #[serde(default)]
+    pub block_timeline_layout: PlotLayout,
     #[serde(default)]
     pub survival_hide_rect: bool,
     #[serde(default)]
+    pub figure: FigureConfig,
+    #[serde(default)]
     pub filter: FilterExpr,
     #[serde(default)]
     pub coverage_metadata: Option<MetadataSource>,
@@ -201,12 +483,96 @@ pub struct Config {
     pub survival_plot_max_hours: f32,
     #[serde(default)]
     pub diff: Option<Diff>,
+    #[serde(default)]
+    pub story: Option<Story>,
+    #[serde(default)]
+    pub coverage_delta: Option<CoverageDelta>,
     pub reference: String,
     #[serde(default)]
     pub legend_mapping: HashMap<String, usize>,
+    /// Labels of the fuzzers in the order they are shown in legends (and the rows of heatmaps).
+    /// Defaults to the order of the indices in `legend_mapping`. Fuzzers that are not listed are
+    /// shown after the listed fuzzers, in the order they are first plotted.
+    #[serde(default)]
+    pub legend_order: Vec<String>,
     /// List of binaries to mark as gray because they contain bug-exploits.
     #[serde(default)]
     pub bug_exploit: Vec<String>,
+    /// How binaries listed in `bug_exploit` are marked in plots.
+    #[serde(default)]
+    pub bug_exploit_marker: BugExploitMarker,
+    /// Number of median absolute deviations from the median coverage of a group before a trial is
+    /// flagged as an outlier.
+    #[serde(default = "default_outlier_threshold")]
+    pub outlier_threshold: f64,
+    /// The p-value below which differences in final coverage are considered significant (see
+    /// [crate::analysis::significance_summary]).
+    #[serde(default = "default_significance_level")]
+    pub significance_level: f64,
+    /// Fraction of the median runtime of a group that the runtime of a trial may differ by before
+    /// it is flagged (see [crate::analysis::trial_completeness]).
+    #[serde(default = "default_runtime_tolerance")]
+    pub runtime_tolerance: f64,
+    #[serde(default)]
+    pub crashes: IndexMap<String, CrashDataset>,
+    /// Execution counts for each fuzzer (keyed by the name of the coverage dataset), used for
+    /// computing the coverage reached per unit of work (see
+    /// [crate::analysis::coverage_efficiency]).
+    #[serde(default)]
+    pub throughput: IndexMap<String, ThroughputDataset>,
+    /// Files listing the blocks known to be reachable in each binary (one address per line), used
+    /// to compute the precision and recall of the coverage reached by each fuzzer.
+    #[serde(default)]
+    pub ground_truth: IndexMap<String, PathBuf>,
+    /// Shift each trial so that its first recorded coverage event occurs at t = 0 (factoring out
+    /// variable startup time).
+    #[serde(default)]
+    pub align_first_block: bool,
+    /// How coverage events recorded after the duration of a dataset are handled.
+    #[serde(default)]
+    pub post_campaign_events: PostCampaignEvents,
+    /// Whether coverage is measured in blocks or functions.
+    #[serde(default)]
+    pub coverage_granularity: CoverageGranularity,
+    /// Maps the names of datasets to the family of fuzzers they are a variant of (e.g.
+    /// `"MultiFuzz-seed1": "MultiFuzz"`). Variants are loaded with the name of their family in the
+    /// `fuzzer` column, keeping the name of the dataset in the `variant` column.
+    #[serde(default)]
+    pub fuzzer_families: IndexMap<String, String>,
+    /// Width of the time buckets used for the histogram of block discovery times (see
+    /// [crate::analysis::discovery_histogram]).
+    #[serde(deserialize_with = "parse_duration", default = "one_hour")]
+    pub discovery_bucket: Duration,
+    /// Directory that the data loaded from each dataset is cached in (e.g. `Some("output/cache")`).
+    /// Datasets are always reloaded if not set (the default). Delete the directory to force
+    /// datasets to be reloaded.
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+    /// The fuzzers compared when computing the overlap between the blocks reached by each fuzzer
+    /// (see [crate::analysis::block_overlap]). Defaults to all fuzzers if there are at most
+    /// [crate::analysis::MAX_OVERLAP_FUZZERS].
+    #[serde(default)]
+    pub overlap_fuzzers: Vec<String>,
+    /// How blocks are ordered in the block timeline (see [crate::analysis::block_timeline]).
+    #[serde(default)]
+    pub block_timeline_order: BlockOrder,
+    /// Weights of binaries in metrics aggregated across binaries (see
+    /// [crate::analysis::normalized_coverage] and [crate::analysis::significance_summary]), e.g.
+    /// based on code size or importance, so that aggregate results do not depend on the
+    /// composition of the benchmark suite. Binaries that are not listed have a weight of 1.
+    #[serde(default)]
+    pub binary_weights: HashMap<String, f64>,
+    /// How trials with data in more than one directory are handled when loading MultiFuzz
+    /// coverage.
+    #[serde(default)]
+    pub duplicate_trials: DuplicateTrials,
+    /// How the coverage plot is laid out.
+    #[serde(default)]
+    pub coverage_layout: CoverageLayout,
+    /// Draw the median coverage of `reference` as a band in the background of the panels of the
+    /// other fuzzers when using [CoverageLayout::PerFuzzer].
+    #[serde(default)]
+    pub reference_band: bool,
 }
 
 impl Config {
@@ -214,6 +580,12 @@ impl Config {
         let parse = || -> anyhow::Result<Self> { Ok(ron::de::from_bytes(&std::fs::read(path)?)?) };
         let mut data = parse().with_context(|| format!("error parsing: {}", path.display()))?;
         data.path = path.to_owned();
+        for (binary, weight) in &data.binary_weights {
+            anyhow::ensure!(
+                weight.is_finite() && *weight >= 0.0,
+                "invalid weight for {binary}: {weight} (must be non-negative)"
+            );
+        }
         Ok(data)
     }
 
@@ -224,6 +596,53 @@ impl Config {
             .flat_map(|(id, (name, sources))| sources.iter().map(move |x| (id, name, x)))
     }
 
+    /// The longest duration of any dataset in hours, used as the extent of the time axis of
+    /// coverage plots.
+    pub fn max_duration_hours(&self) -> f32 {
+        let max = self.datasets().map(|(_, _, x)| x.source.duration()).max().unwrap_or_default();
+        match max.is_zero() {
+            true => 24.0,
+            false => max.as_secs_f32() / (60.0 * 60.0),
+        }
+    }
+
+    /// Returns whether any of the datasets contain verified bugs.
+    pub fn has_bug_data(&self) -> bool {
+        self.datasets().any(|(_, _, x)| matches!(x.source, DataSource::BugCsv { .. }))
+    }
+
+    /// Returns whether any of the datasets contain AFL plot data.
+    pub fn has_afl_plot_data(&self) -> bool {
+        self.datasets().any(|(_, _, x)| matches!(x.source, DataSource::AflPlotData { .. }))
+    }
+
+    /// Gets the family of the dataset `name` (see [Config::fuzzer_families]) and the offset added
+    /// to the trial numbers of the dataset, so that the trials of each variant remain distinct
+    /// within the family.
+    pub fn fuzzer_family<'a>(&'a self, name: &'a str) -> (&'a str, u32) {
+        let Some(family) = self.fuzzer_families.get(name) else { return (name, 0) };
+        let index = self
+            .fuzzer_families
+            .iter()
+            .filter(|(_, x)| *x == family)
+            .position(|(variant, _)| variant == name)
+            .unwrap_or(0);
+        (family, index as u32 * self.trials)
+    }
+
+    /// Gets the position of each listed fuzzer label in legends (see [Config::legend_order]).
+    pub fn legend_ranks(&self) -> HashMap<String, usize> {
+        match self.legend_order.is_empty() {
+            true => self.legend_mapping.clone(),
+            false => self.legend_order.iter().enumerate().map(|(i, x)| (x.clone(), i)).collect(),
+        }
+    }
+
+    /// Gets the weight of `binary` in aggregate metrics (see [Config::binary_weights]).
+    pub fn binary_weight(&self, binary: &str) -> f64 {
+        self.binary_weights.get(binary).copied().unwrap_or(1.0)
+    }
+
     pub fn has_bug_exploit(&self, name: &str) -> bool {
         self.bug_exploit.iter().any(|x| x == name)
     }
@@ -0,0 +1,221 @@
+//! Exports the final coverage of each fuzzer on each binary in formats understood by existing
+//! coverage tooling (e.g. `genhtml` and Codecov), using the block maps of `coverage_metadata`.
+//!
+//! Binaries do not have source lines, so each block is reported as a "line" numbered by its index
+//! in the block map (ordered by address), grouped into the functions containing it. The hit count
+//! of a block is the number of trials that reached it.
+
+use std::{collections::HashMap, fmt::Write as _, path::Path};
+
+use anyhow::Context;
+use polars::prelude::*;
+
+use crate::{
+    config::CoverageGranularity,
+    metadata::{BlockMap, Metadata},
+    Config,
+};
+
+/// The name used for blocks that are not part of any known function.
+const UNKNOWN_FUNCTION: &str = "<unknown>";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// LCOV tracefiles (`.info`).
+    Lcov,
+    /// Cobertura XML reports (`.xml`).
+    Cobertura,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Lcov => "info",
+            Self::Cobertura => "xml",
+        }
+    }
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lcov" => Ok(Self::Lcov),
+            "cobertura" => Ok(Self::Cobertura),
+            _ => anyhow::bail!("unknown export format: {s} (expected `lcov` or `cobertura`)"),
+        }
+    }
+}
+
+/// The blocks of a function, as (line, hits) pairs.
+struct FunctionCoverage<'a> {
+    name: &'a str,
+    blocks: Vec<(usize, u32)>,
+}
+
+/// Writes a coverage file to `dir` for each fuzzer on each binary with a block map (named
+/// `<binary>-<fuzzer>.<ext>`), returning the number of files written, or `None` if
+/// `coverage_metadata` is not configured.
+pub fn export(config: &Config, format: ExportFormat, dir: &Path) -> anyhow::Result<Option<usize>> {
+    let Some(source) = config.coverage_metadata.as_ref() else {
+        return Ok(None);
+    };
+    let metadata = Metadata::from_source(&config.path, source.clone())?;
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create: {}", dir.display()))?;
+
+    // Hits are reported for each block, so coverage is always loaded at block granularity.
+    let config = &Config { coverage_granularity: CoverageGranularity::Block, ..config.clone() };
+    let coverage = crate::load_raw_coverage(config)?
+        .group_by([col("binary"), col("fuzzer"), col("block")])
+        .agg([col("trial").n_unique().cast(DataType::UInt32).alias("trials")])
+        .with_columns([col("binary").cast(DataType::String), col("fuzzer").cast(DataType::String)])
+        .collect()?;
+
+    let mut files = 0;
+    for df in coverage.partition_by_stable(["binary", "fuzzer"], true)? {
+        let binary = df["binary"].str_value(0)?.into_owned();
+        let fuzzer = df["fuzzer"].str_value(0)?.into_owned();
+        let Some(block_map) = metadata.get_block_map_for(&binary) else {
+            eprintln!("WARNING: not exporting coverage of {binary}: no block map");
+            continue;
+        };
+        let hits: HashMap<u64, u32> = df["block"]
+            .u64()?
+            .into_iter()
+            .zip(df["trials"].u32()?)
+            .filter_map(|(block, trials)| Some((block?, trials?)))
+            .collect();
+
+        let functions = function_coverage(block_map, &hits);
+        let output = match format {
+            ExportFormat::Lcov => lcov(&binary, &fuzzer, &functions),
+            ExportFormat::Cobertura => cobertura(&binary, &functions),
+        };
+        let name: String = format!("{binary}-{fuzzer}")
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let path = dir.join(format!("{name}.{}", format.extension()));
+        std::fs::write(&path, output)
+            .with_context(|| format!("failed to write: {}", path.display()))?;
+        files += 1;
+    }
+    Ok(Some(files))
+}
+
+/// Groups the blocks of `block_map` by function, along with the number of trials that reached
+/// each block (from `hits`).
+fn function_coverage<'a>(
+    block_map: &'a BlockMap,
+    hits: &HashMap<u64, u32>,
+) -> Vec<FunctionCoverage<'a>> {
+    let lines: HashMap<u64, usize> =
+        block_map.blocks().enumerate().map(|(i, block)| (block.start, i + 1)).collect();
+    let line_of = |block: u64| Some((lines.get(&block).copied()?, hits.get(&block).copied()));
+
+    let mut functions: Vec<FunctionCoverage> = block_map
+        .functions()
+        .map(|function| {
+            let mut blocks: Vec<_> = function
+                .blocks
+                .iter()
+                .filter_map(|x| line_of(*x))
+                .map(|(line, hits)| (line, hits.unwrap_or(0)))
+                .collect();
+            blocks.sort_unstable();
+            FunctionCoverage { name: &function.name, blocks }
+        })
+        .filter(|x| !x.blocks.is_empty())
+        .collect();
+
+    let unknown: Vec<_> = block_map
+        .blocks()
+        .filter(|block| block.function.is_none())
+        .filter_map(|block| line_of(block.start))
+        .map(|(line, hits)| (line, hits.unwrap_or(0)))
+        .collect();
+    if !unknown.is_empty() {
+        functions.push(FunctionCoverage { name: UNKNOWN_FUNCTION, blocks: unknown });
+    }
+    functions.sort_by_key(|x| x.blocks[0].0);
+    functions
+}
+
+/// Formats the coverage as an LCOV tracefile, with the binary as the source file and the fuzzer as
+/// the test name.
+fn lcov(binary: &str, fuzzer: &str, functions: &[FunctionCoverage]) -> String {
+    let mut output = String::new();
+    let _ = writeln!(output, "TN:{}", lcov_name(fuzzer));
+    let _ = writeln!(output, "SF:{binary}");
+    for function in functions {
+        let _ = writeln!(output, "FN:{},{}", function.blocks[0].0, lcov_name(function.name));
+    }
+    for function in functions {
+        let _ = writeln!(output, "FNDA:{},{}", function.blocks[0].1, lcov_name(function.name));
+    }
+    let functions_hit = functions.iter().filter(|x| x.blocks[0].1 != 0).count();
+    let _ = writeln!(output, "FNF:{}\nFNH:{functions_hit}", functions.len());
+
+    let mut blocks: Vec<_> = functions.iter().flat_map(|x| &x.blocks).collect();
+    blocks.sort_unstable();
+    for (line, hits) in &blocks {
+        let _ = writeln!(output, "DA:{line},{hits}");
+    }
+    let blocks_hit = blocks.iter().filter(|(_, hits)| *hits != 0).count();
+    let _ = writeln!(output, "LF:{}\nLH:{blocks_hit}\nend_of_record", blocks.len());
+    output
+}
+
+/// LCOV names cannot contain commas or whitespace.
+fn lcov_name(name: &str) -> String {
+    name.chars().map(|c| if c == ',' || c.is_whitespace() { '_' } else { c }).collect()
+}
+
+/// Formats the coverage as a Cobertura XML report, with a class for each function of the binary.
+fn cobertura(binary: &str, functions: &[FunctionCoverage]) -> String {
+    let rate = |blocks: &mut dyn Iterator<Item = &(usize, u32)>| {
+        let (total, hit) = blocks.fold((0, 0), |(total, hit), (_, hits)| {
+            (total + 1, hit + usize::from(*hits != 0))
+        });
+        (total, hit, if total == 0 { 0.0 } else { hit as f64 / total as f64 })
+    };
+    let (total, hit, line_rate) = rate(&mut functions.iter().flat_map(|x| &x.blocks));
+    let binary = escape_xml(binary);
+
+    let mut output = String::from("<?xml version=\"1.0\" ?>\n");
+    let _ = writeln!(
+        output,
+        "<coverage line-rate=\"{line_rate:.4}\" branch-rate=\"0\" lines-covered=\"{hit}\" \
+        lines-valid=\"{total}\" branches-covered=\"0\" branches-valid=\"0\" complexity=\"0\" \
+        version=\"plot-data\" timestamp=\"0\">"
+    );
+    let _ = writeln!(output, "<sources><source>{binary}</source></sources>\n<packages>");
+    let _ = writeln!(
+        output,
+        "<package name=\"{binary}\" line-rate=\"{line_rate:.4}\" branch-rate=\"0\" \
+        complexity=\"0\">\n<classes>"
+    );
+    for function in functions {
+        let (_, _, line_rate) = rate(&mut function.blocks.iter());
+        let _ = writeln!(
+            output,
+            "<class name=\"{}\" filename=\"{binary}\" line-rate=\"{line_rate:.4}\" \
+            branch-rate=\"0\" complexity=\"0\">\n<methods/>\n<lines>",
+            escape_xml(function.name)
+        );
+        for (line, hits) in &function.blocks {
+            let _ = writeln!(output, "<line number=\"{line}\" hits=\"{hits}\" branch=\"false\"/>");
+        }
+        output.push_str("</lines>\n</class>\n");
+    }
+    output.push_str("</classes>\n</package>\n</packages>\n</coverage>\n");
+    output
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
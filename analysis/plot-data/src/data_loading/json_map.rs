@@ -1,10 +1,87 @@
-use std::{collections::HashMap, path::Path};
+use std::collections::HashMap;
+
+use anyhow::Context;
+
+use crate::DataFile;
+
+/// The newest version of the coverage format (see [CoverageVersion]) that can be loaded.
+const LATEST_VERSION: u32 = 3;
 
 #[derive(Copy, Clone, Debug)]
 pub struct HailFuzzCoverage {
     pub addr: u64,
     pub time_ms: u64,
     pub input_id: u64,
+    /// The number of times the block was hit by the input that first reached it (only recorded by
+    /// newer versions of MultiFuzz).
+    pub hits: Option<u64>,
+}
+
+/// The layout of the entries in a coverage file. Files containing a `version` field are decoded
+/// using the layout of that version, otherwise the layout is guessed from each entry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum CoverageVersion {
+    /// `[addr, time_ms]`
+    V0,
+    /// `[addr, time_ms, input_id]`
+    V1,
+    /// `[addr, time_ms, input_id, hits]`
+    V2,
+    /// `{ "addr": .., "time_ms": .., "input_id": .., "hits": .. }` (`input_id` and `hits` are
+    /// optional).
+    V3,
+}
+
+impl CoverageVersion {
+    fn from_version(version: u32) -> anyhow::Result<Self> {
+        Ok(match version {
+            0 => Self::V0,
+            1 => Self::V1,
+            2 => Self::V2,
+            3 => Self::V3,
+            _ => anyhow::bail!(
+                "unsupported coverage version: {version} (the latest supported version is \
+                {LATEST_VERSION})"
+            ),
+        })
+    }
+
+    fn decode(self, entry: serde_json::Value) -> anyhow::Result<HailFuzzCoverage> {
+        let entry = match self {
+            Self::V0 => {
+                let (addr, time_ms): (u64, u64) = serde_json::from_value(entry)?;
+                HailFuzzCoverage { addr, time_ms, input_id: 0, hits: None }
+            }
+            Self::V1 => {
+                let (addr, time_ms, input_id) = serde_json::from_value(entry)?;
+                HailFuzzCoverage { addr, time_ms, input_id, hits: None }
+            }
+            Self::V2 => {
+                let (addr, time_ms, input_id, hits) = serde_json::from_value(entry)?;
+                HailFuzzCoverage { addr, time_ms, input_id, hits: Some(hits) }
+            }
+            Self::V3 => serde_json::from_value::<CoverageObject>(entry)?.into(),
+        };
+        Ok(entry)
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct CoverageObject {
+    #[serde(alias = "block")]
+    addr: u64,
+    #[serde(alias = "time")]
+    time_ms: u64,
+    #[serde(default, alias = "input")]
+    input_id: u64,
+    #[serde(default, alias = "hit_count")]
+    hits: Option<u64>,
+}
+
+impl From<CoverageObject> for HailFuzzCoverage {
+    fn from(x: CoverageObject) -> Self {
+        Self { addr: x.addr, time_ms: x.time_ms, input_id: x.input_id, hits: x.hits }
+    }
 }
 
 impl<'de> serde::Deserialize<'de> for HailFuzzCoverage {
@@ -12,42 +89,85 @@ impl<'de> serde::Deserialize<'de> for HailFuzzCoverage {
     where
         D: serde::Deserializer<'de>,
     {
+        // Unversioned files: detect the layout from the shape of the entry.
         #[derive(serde::Deserialize)]
         #[serde(untagged)]
         enum CoverageEntry {
+            V2((u64, u64, u64, u64)),
             V1((u64, u64, u64)),
             V0((u64, u64)),
+            V3(CoverageObject),
         }
         match serde::Deserialize::deserialize(deserializer)? {
-            CoverageEntry::V0((addr, time_ms)) => Ok(Self { addr, time_ms, input_id: 0 }),
-            CoverageEntry::V1((addr, time_ms, input_id)) => Ok(Self { addr, time_ms, input_id }),
+            CoverageEntry::V0((addr, time_ms)) => {
+                Ok(Self { addr, time_ms, input_id: 0, hits: None })
+            }
+            CoverageEntry::V1((addr, time_ms, input_id)) => {
+                Ok(Self { addr, time_ms, input_id, hits: None })
+            }
+            CoverageEntry::V2((addr, time_ms, input_id, hits)) => {
+                Ok(Self { addr, time_ms, input_id, hits: Some(hits) })
+            }
+            CoverageEntry::V3(entry) => Ok(entry.into()),
         }
     }
 }
 
 pub fn load_coverage_data(
-    path: &Path,
-    legacy: bool,
+    file: &DataFile,
 ) -> anyhow::Result<HashMap<String, Vec<HailFuzzCoverage>>> {
     // Handle paths that point to the hail-fuzz directory compared to those that point to an
     // individual coverage file.
-    let input = match path.is_dir() {
-        true => super::open_buffered_file(&path.join("coverage.json"))?,
-        false => super::open_buffered_file(path)?,
+    let path = file.path.as_path();
+    let input: Box<dyn std::io::BufRead> = match path.is_dir() {
+        true => Box::new(super::open_buffered_file(path.join("coverage.json"))?),
+        false => file.open()?,
     };
 
     // Handle the case where the input is merged and unmerged.
     #[derive(serde::Deserialize)]
     #[serde(untagged)]
-    enum HailFuzzCoverageData {
-        Single(Vec<HailFuzzCoverage>),
-        Merged(HashMap<String, Vec<HailFuzzCoverage>>),
+    enum HailFuzzCoverageData<T> {
+        Single(Vec<T>),
+        Merged(HashMap<String, Vec<T>>),
     }
 
-    let data: HailFuzzCoverageData = serde_json::from_reader(input)?;
+    // Newer versions of MultiFuzz wrap the coverage in an object with a version number.
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum CoverageFile {
+        Versioned { version: u32, coverage: HailFuzzCoverageData<serde_json::Value> },
+        Unversioned(HailFuzzCoverageData<HailFuzzCoverage>),
+    }
+
+    let data = match serde_json::from_reader(input)? {
+        CoverageFile::Unversioned(data) => data,
+        CoverageFile::Versioned { version, coverage } => {
+            let version = CoverageVersion::from_version(version)
+                .with_context(|| format!("failed to load: {}", path.display()))?;
+            let decode = |entries: Vec<serde_json::Value>| {
+                entries
+                    .into_iter()
+                    .map(|x| version.decode(x))
+                    .collect::<anyhow::Result<Vec<_>>>()
+                    .with_context(|| format!("invalid {version:?} coverage in: {}", path.display()))
+            };
+            match coverage {
+                HailFuzzCoverageData::Single(entries) => {
+                    HailFuzzCoverageData::Single(decode(entries)?)
+                }
+                HailFuzzCoverageData::Merged(data) => HailFuzzCoverageData::Merged(
+                    data.into_iter()
+                        .map(|(tag, entries)| Ok((tag, decode(entries)?)))
+                        .collect::<anyhow::Result<_>>()?,
+                ),
+            }
+        }
+    };
+
     Ok(match data {
         HailFuzzCoverageData::Single(data) => {
-            let tags = super::bench_tags_from_hail_fuzz_path(Some(path), legacy);
+            let tags = super::bench_tags_from_hail_fuzz_path(Some(path));
             [(tags, data)].into_iter().collect()
         }
         HailFuzzCoverageData::Merged(data) => data,
@@ -1,20 +1,28 @@
-use std::path::Path;
+use std::{borrow::Cow, path::Path};
 
 use anyhow::Context;
+use polars::prelude::*;
 
 pub mod json_map;
 
 /// Generates benchmark tags from a path (if possible).
-pub fn bench_tags_from_hail_fuzz_path(source_path: Option<&Path>, legacy: bool) -> String {
-    const UNKNOWN_TAG: &str = "v1;bench=unknown;fuzzer=unknown;binary=unknown;trial=0";
+///
+/// Results are stored using either the legacy bench-harness layout
+/// (`[bench]/[fuzzer]-[binary]/[trial]`) or the current layout
+/// (`[bench]/[fuzzer]/[group]/[binary]/[trial]`). The layout is detected separately for each path,
+/// falling back to the other layout if the detected one does not match, so that globs can span
+/// both old and new result trees.
+pub fn bench_tags_from_hail_fuzz_path(source_path: Option<&Path>) -> String {
+    const UNKNOWN_TAG: &str = "v2;bench=unknown;fuzzer=unknown;binary=unknown;trial=0";
 
     let Some(mut path) = source_path
     else {
         return UNKNOWN_TAG.to_string();
     };
 
-    // Check if the path points to the main directory or a file in the directory.
-    if path.is_file() {
+    // Check if the path points to the main directory or a file in the directory (files within
+    // archives do not exist on disk, so we check for directories instead).
+    if !path.is_dir() {
         path = match path.parent() {
             Some(dir) => dir,
             None => return UNKNOWN_TAG.to_string(),
@@ -23,22 +31,52 @@ pub fn bench_tags_from_hail_fuzz_path(source_path: Option<&Path>, legacy: bool)
 
     let components = path.components().rev().flat_map(|x| x.as_os_str().to_str());
 
-    if legacy {
-        if let Some((bench, fuzzer, binary, trial)) = tags_from_legacy_path(components.clone()) {
-            return format!("v1;bench={bench};fuzzer={fuzzer};binary={binary};trial={trial}");
-        }
-    }
-    else {
-        if let Some((bench, fuzzer, _, binary, trial)) = tags_from_path(components.clone()) {
-            return format!("v1;bench={bench};fuzzer={fuzzer};binary={binary};trial={trial}");
-        }
+    let current = |components| {
+        let (bench, fuzzer, _, binary, trial) = tags_from_path(components)?;
+        Some((bench, fuzzer, binary, trial))
+    };
+
+    let tags = match is_legacy_path(components.clone()) {
+        true => tags_from_legacy_path(components.clone()).or_else(|| current(components)),
+        false => current(components.clone()).or_else(|| tags_from_legacy_path(components)),
+    };
+
+    match tags {
+        Some((bench, fuzzer, binary, trial)) => format_bench_tags([
+            ("bench", bench),
+            ("fuzzer", fuzzer),
+            ("binary", binary),
+            ("trial", trial),
+        ]),
+        None => UNKNOWN_TAG.to_string(),
     }
+}
+
+/// The names of the directories that bench-harness stores results in.
+const RESULT_ROOTS: &[&str] = &["output", "results"];
 
-    UNKNOWN_TAG.to_string()
+/// Guesses whether the (reversed) path components are from the legacy layout by matching the
+/// position of the results directory (see [RESULT_ROOTS]): the trial directory is three levels
+/// below it in the legacy layout, and five levels below it in the current layout. Paths outside of
+/// a results directory are assumed to be legacy paths only if they are too short for the current
+/// layout.
+fn is_legacy_path<'a>(components: impl Iterator<Item = &'a str>) -> bool {
+    let components: Vec<_> = components.collect();
+    let is_root = |i: usize| components.get(i).map_or(false, |x| RESULT_ROOTS.contains(x));
+    if is_root(3) {
+        return true;
+    }
+    if is_root(5) {
+        return false;
+    }
+    components.len() < 5
 }
 
 /// Extract tags from the old bench-harness directory format, i.e.:
 /// `[bench]/[fuzzer]-[binary]/[trial]`
+///
+/// Fuzzer names never contain `-` (unlike some binary names, e.g. `riot-gnrc_networking`), so the
+/// target is split at the first `-`.
 fn tags_from_legacy_path<'a>(
     mut components: impl Iterator<Item = &'a str>,
 ) -> Option<(&'a str, &'a str, &'a str, &'a str)> {
@@ -47,9 +85,10 @@ fn tags_from_legacy_path<'a>(
     else {
         return None;
     };
-    let (fuzzer, binary) = target.rsplit_once("-")?;
+    let (fuzzer, binary) = target.split_once("-")?;
     Some((bench, fuzzer, binary, trial))
 }
+
 /// Extract tags new bench-harness directory format, i.e.:
 /// `[bench]/[fuzzer]/[group]/[binary]/[trial]`
 fn tags_from_path<'a>(
@@ -63,21 +102,164 @@ fn tags_from_path<'a>(
     }
 }
 
-/// Parses tags in the format: `v1;<key>=<value>;...`
-pub fn parse_bench_tags(tag: &str) -> anyhow::Result<impl Iterator<Item = (&str, &str)>> {
+/// Formats tags using the v2 format: `v2;<key>=<value>;...`, where `%`, `;`, `=`, braces and
+/// control characters in keys and values are percent-encoded (e.g. `;` is written as `%3B`).
+pub fn format_bench_tags<'a>(tags: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let mut output = String::from("v2");
+    for (key, value) in tags {
+        output.push(';');
+        escape_tag_component(key, &mut output);
+        output.push('=');
+        escape_tag_component(value, &mut output);
+    }
+    output
+}
+
+/// Escapes a key or value of a v2 tag. This must match `escape_tag_value` in bench-harness (which
+/// also escapes braces to avoid variable expansion), so that the tags generated from paths are
+/// identical to the tags generated by the harness.
+fn escape_tag_component(value: &str, output: &mut String) {
+    for c in value.chars() {
+        match c {
+            '%' | ';' | '=' | '{' | '}' => output.push_str(&format!("%{:02X}", c as u8)),
+            c if c.is_ascii_control() => output.push_str(&format!("%{:02X}", c as u8)),
+            c => output.push(c),
+        }
+    }
+}
+
+fn unescape_tag_component(value: &str) -> anyhow::Result<Cow<'_, str>> {
+    if !value.contains('%') {
+        return Ok(Cow::Borrowed(value));
+    }
+
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.bytes();
+    while let Some(byte) = iter.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        let hex = [iter.next().unwrap_or(b'?'), iter.next().unwrap_or(b'?')];
+        let escaped = std::str::from_utf8(&hex).ok().and_then(|x| u8::from_str_radix(x, 16).ok());
+        bytes.push(escaped.ok_or_else(|| anyhow::format_err!("invalid escape in tag: {value}"))?);
+    }
+    Ok(Cow::Owned(String::from_utf8(bytes).context("invalid utf-8 in tag")?))
+}
+
+/// Parses tags in either the v1 format: `v1;<key>=<value>;...` or the v2 format (see
+/// [format_bench_tags]).
+pub fn parse_bench_tags(tag: &str) -> anyhow::Result<Vec<(Cow<'_, str>, Cow<'_, str>)>> {
     let mut iter = tag.split(';');
-    match iter.next() {
-        Some(tag) if tag == "v1" => {}
+    let escaped = match iter.next() {
+        Some("v1") => false,
+        Some("v2") => true,
         Some(tag) => anyhow::bail!("Unknown tag version ({tag})"),
         None => anyhow::bail!("Unknown tag version (None)"),
+    };
+
+    let entries = iter.filter(|x| !x.is_empty()).filter_map(|x| x.split_once("="));
+    if !escaped {
+        return Ok(entries.map(|(key, value)| (key.into(), value.into())).collect());
     }
-    Ok(iter.filter(|x| !x.is_empty()).filter_map(|x| x.split_once("=")))
+    entries
+        .map(|(key, value)| Ok((unescape_tag_component(key)?, unescape_tag_component(value)?)))
+        .collect()
+}
+
+/// Extracts the value associated with `key` from a column of tags (see [parse_bench_tags]).
+pub fn extract_tag_value(tags: Expr, key: &str) -> Expr {
+    let key = key.to_owned();
+    tags.map(
+        move |s| {
+            let values: StringChunked = s
+                .str()?
+                .into_iter()
+                .map(|tag| {
+                    let tags = parse_bench_tags(tag?).ok()?;
+                    tags.into_iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+                })
+                .collect();
+            Ok(Some(values.into_series()))
+        },
+        GetOutput::from_type(DataType::String),
+    )
 }
 
 fn open_buffered_file(path: impl AsRef<Path>) -> anyhow::Result<std::io::BufReader<std::fs::File>> {
     let path = path.as_ref();
     Ok(std::io::BufReader::new(
-        std::fs::File::open(&path)
+        std::fs::File::open(path)
             .with_context(|| format!("failed to open: {}", path.display()))?,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(path: &str) -> String {
+        bench_tags_from_hail_fuzz_path(Some(Path::new(path)))
+    }
+
+    fn expected(bench: &str, fuzzer: &str, binary: &str, trial: &str) -> String {
+        let tags = [("bench", bench), ("fuzzer", fuzzer), ("binary", binary), ("trial", trial)];
+        format_bench_tags(tags)
+    }
+
+    #[test]
+    fn current_layout() {
+        let path = "output/ext/multifuzz/default/CNC/0/coverage.json";
+        assert_eq!(tags(path), expected("ext", "multifuzz", "CNC", "0"));
+    }
+
+    #[test]
+    fn current_layout_with_dash_in_binary() {
+        let path = "../output/ext/multifuzz/default/riot-gnrc_networking/3/coverage.json";
+        let tags = tags(path);
+        assert_eq!(tags, expected("ext", "multifuzz", "riot-gnrc_networking", "3"));
+    }
+
+    #[test]
+    fn legacy_layout() {
+        let path = "../results/ext/fuzzware-CNC/1/coverage.json";
+        assert_eq!(tags(path), expected("ext", "fuzzware", "CNC", "1"));
+    }
+
+    #[test]
+    fn legacy_layout_with_dash_in_binary() {
+        let path = "output/ext/multifuzz-riot-gnrc_networking/2/coverage.json";
+        let tags = tags(path);
+        assert_eq!(tags, expected("ext", "multifuzz", "riot-gnrc_networking", "2"));
+    }
+
+    #[test]
+    fn mixed_layouts() {
+        // A glob such as `output/ext/**/coverage.json` can match trials from both layouts.
+        let paths = [
+            ("output/ext/multifuzz-riot-filesystem/0/coverage.json", "riot-filesystem"),
+            ("output/ext/multifuzz/default/riot-filesystem/1/coverage.json", "riot-filesystem"),
+            ("output/ext/multifuzz-Gateway/2/coverage.json", "Gateway"),
+            ("output/ext/multifuzz/default/Gateway/3/coverage.json", "Gateway"),
+        ];
+        for (i, (path, binary)) in paths.into_iter().enumerate() {
+            let trial = i.to_string();
+            assert_eq!(tags(path), expected("ext", "multifuzz", binary, &trial));
+        }
+    }
+
+    #[test]
+    fn without_results_directory() {
+        let legacy = "ext/multifuzz-CNC/0/coverage.json";
+        assert_eq!(tags(legacy), expected("ext", "multifuzz", "CNC", "0"));
+        let current = "data/ext/multifuzz/default/riot-filesystem/0/coverage.json";
+        let tags = tags(current);
+        assert_eq!(tags, expected("ext", "multifuzz", "riot-filesystem", "0"));
+    }
+
+    #[test]
+    fn unknown_layout() {
+        let tags = tags("ext/0/coverage.json");
+        assert_eq!(tags, expected("unknown", "unknown", "unknown", "0"));
+    }
+}
@@ -2,7 +2,7 @@ use std::path::Path;
 
 use polars::prelude::*;
 
-use crate::{load_glob, parse_u64_with_prefix};
+use crate::{load_glob, parse_u64_with_prefix, DataFile};
 
 pub fn read_all(glob: &str, resampled: bool) -> anyhow::Result<Option<LazyFrame>> {
     let loader = if resampled { read_resampled_csv } else { read_raw_csv };
@@ -15,7 +15,8 @@ pub fn read_all(glob: &str, resampled: bool) -> anyhow::Result<Option<LazyFrame>
 }
 
 /// Read ember data from tab delimited CSV files (e.g. `P2IM-PLC-run-05.txt`).
-fn read_raw_csv(path: &Path) -> anyhow::Result<LazyFrame> {
+fn read_raw_csv(file: &DataFile) -> anyhow::Result<LazyFrame> {
+    let path = file.path.as_path();
     let (_target, binary, trial) = extract_group_binary_trial_from_path(path).ok_or_else(|| {
         anyhow::format_err!("Failed to parse binary and trial ID from path: {}", path.display())
     })?;
@@ -28,17 +29,19 @@ fn read_raw_csv(path: &Path) -> anyhow::Result<LazyFrame> {
     let mut schema = Schema::new();
     schema.with_column("seconds".into(), DataType::Float64);
     schema.with_column("blocks".into(), DataType::UInt32);
-    Ok(LazyCsvReader::new(path)
+    let options = CsvReadOptions::default()
         .with_has_header(false)
         .with_skip_rows(1)
-        .with_separator(b'\t')
         .with_schema(Some(schema.into()))
-        .finish()?
+        .map_parse_options(|x| x.with_separator(b'\t'));
+    Ok(file
+        .scan_csv(options)?
         .with_columns([lit(binary).alias("binary"), lit(trial).alias("trial")]))
 }
 
 /// Read resampled Ember-IO data from comma delimeted CSV files (e.g. `P2IM-PLC-run-05.csv`).
-fn read_resampled_csv(path: &Path) -> anyhow::Result<LazyFrame> {
+fn read_resampled_csv(file: &DataFile) -> anyhow::Result<LazyFrame> {
+    let path = file.path.as_path();
     let (_target, binary, trial) = extract_group_binary_trial_from_path(path).ok_or_else(|| {
         anyhow::format_err!("Failed to parse binary and trial ID from path: {}", path.display())
     })?;
@@ -46,11 +49,12 @@ fn read_resampled_csv(path: &Path) -> anyhow::Result<LazyFrame> {
     let mut schema = Schema::new();
     schema.with_column("seconds".into(), DataType::Float64);
     schema.with_column("blocks".into(), DataType::UInt32);
-    Ok(LazyCsvReader::new(path)
+    let options = CsvReadOptions::default()
         .with_has_header(false)
-        .with_separator(b',')
         .with_schema(Some(schema.into()))
-        .finish()?
+        .map_parse_options(|x| x.with_separator(b','));
+    Ok(file
+        .scan_csv(options)?
         .with_column(col("seconds").floor().cast(DataType::Int64))
         .with_columns([lit(binary).alias("binary"), lit(trial).alias("trial")]))
 }
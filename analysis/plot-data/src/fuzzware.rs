@@ -3,7 +3,7 @@ use std::path::Path;
 use anyhow::ensure;
 use polars::prelude::*;
 
-use crate::{load_glob, parse_u64_with_prefix, polars_parse_u64};
+use crate::{load_glob, parse_u64_with_prefix, polars_parse_u64, DataFile};
 
 pub fn read_all(glob: &str) -> anyhow::Result<Option<LazyFrame>> {
     let data = load_glob(glob, read_raw_csv, |_| true)?;
@@ -23,7 +23,7 @@ pub fn read_all(glob: &str) -> anyhow::Result<Option<LazyFrame>> {
 }
 
 /// Read fuzzware data from raw CSV files.
-fn read_raw_csv(path: &Path) -> anyhow::Result<LazyFrame> {
+fn read_raw_csv(file: &DataFile) -> anyhow::Result<LazyFrame> {
     fn extract_binary_and_trial_path(path: &Path) -> Option<(&str, u32)> {
         // Check which format the path is in by checking the parent directory.
         let parent_name = path.parent()?.file_name()?.to_str()?;
@@ -47,6 +47,7 @@ fn read_raw_csv(path: &Path) -> anyhow::Result<LazyFrame> {
         Some((binary, trial))
     }
 
+    let path = file.path.as_path();
     let (binary, trial) = extract_binary_and_trial_path(path).ok_or_else(|| {
         anyhow::format_err!(
             "Failed to parse binary and trial ID from path: {}",
@@ -58,13 +59,15 @@ fn read_raw_csv(path: &Path) -> anyhow::Result<LazyFrame> {
     schema.with_column("seconds".into(), DataType::Int64);
     schema.with_column("num_bbs_total".into(), DataType::UInt32);
     schema.with_column("blocks".into(), DataType::String);
-    Ok(LazyCsvReader::new(path)
+    let options = CsvReadOptions::default()
         .with_has_header(false)
-        .with_comment_prefix(Some("#"))
-        .with_separator(b'\t')
         .with_schema(Some(schema.into()))
-        .finish()?
-        .with_columns([lit(binary).alias("binary"), lit(trial).alias("trial")]))
+        .map_parse_options(|x| x.with_comment_prefix(Some("#")).with_separator(b'\t'));
+    Ok(file.scan_csv(options)?.with_columns([
+        lit(binary).alias("binary"),
+        lit(trial).alias("trial"),
+        lit(path.to_string_lossy().as_ref()).alias("source"),
+    ]))
 }
 
 pub mod legacy {
@@ -93,7 +96,8 @@ pub mod legacy {
     }
 
     /// Read data from preprocessed `.dat` files.
-    fn read_fuzzware_dat(path: &Path) -> anyhow::Result<LazyFrame> {
+    fn read_fuzzware_dat(file: &DataFile) -> anyhow::Result<LazyFrame> {
+        let path = file.path.as_path();
         // Fuzzware doesn't include any metadata about the trial run in the file, so extract binary
         // and trial name from the file name instead.
         let (binary, trial) = extract_binary_and_trial_from_dat_path(path).ok_or_else(|| {
@@ -106,11 +110,12 @@ pub mod legacy {
         let mut schema = Schema::new();
         schema.with_column("hours".into(), DataType::Float64);
         schema.with_column("blocks".into(), DataType::UInt32);
-        Ok(LazyCsvReader::new(path)
+        let options = CsvReadOptions::default()
             .with_has_header(false)
-            .with_separator(b' ')
             .with_schema(Some(schema.into()))
-            .finish()?
+            .map_parse_options(|x| x.with_separator(b' '));
+        Ok(file
+            .scan_csv(options)?
             .with_columns([lit(binary).alias("binary"), lit(trial).alias("trial")]))
     }
 }
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::Context;
 use polars::prelude::*;
@@ -6,12 +6,16 @@ use polars::prelude::*;
 pub use crate::config::Config;
 use crate::{
     analysis::BlockHits,
-    config::{DataSource, FilterExpr},
-    metadata::Metadata,
+    config::{CoverageGranularity, DataSource, FilterExpr, PostCampaignEvents, Style},
+    metadata::{Metadata, MetadataSource},
 };
 
 pub mod analysis;
+pub mod archive;
+pub mod bugs;
+mod cache;
 pub mod config;
+pub mod coverage_export;
 mod data_loading;
 pub mod ember;
 pub mod fuzzware;
@@ -106,6 +110,34 @@ pub fn has_bug_exploit(name: &str) -> bool {
     }
 }
 
+/// Adds a `bug_exploit` column marking binaries listed in [Config::bug_exploit] (must be applied
+/// before binary names are mapped).
+pub fn bug_exploit_column(config: &Config) -> Expr {
+    col("binary")
+        .is_in(lit(Series::new("bug_exploit", config.bug_exploit.as_slice())))
+        .alias("bug_exploit")
+}
+
+/// Adds a `weight` column containing the weight of each binary in aggregate metrics (see
+/// [Config::binary_weights]), this must be applied before binary names are mapped.
+pub fn binary_weight_column(config: &Config) -> Expr {
+    let weights = config.binary_weights.clone();
+    col("binary")
+        .cast(DataType::String)
+        .map(
+            move |rows| {
+                let weights: Float64Chunked = rows
+                    .str()?
+                    .into_iter()
+                    .map(|x| Some(x.and_then(|x| weights.get(x).copied()).unwrap_or(1.0)))
+                    .collect();
+                Ok(Some(weights.into_series()))
+            },
+            GetOutput::from_type(DataType::Float64),
+        )
+        .alias("weight")
+}
+
 pub fn map_binary_names(col: Expr) -> Expr {
     col.map(
         |rows| {
@@ -159,10 +191,23 @@ pub fn parse_filter_expr(filter: &FilterExpr) -> Expr {
 }
 
 pub fn load_block_hits(config: &Config) -> anyhow::Result<BlockHits> {
+    load_block_hits_with_metadata(config, config.coverage_metadata.as_ref())
+}
+
+/// Like [load_block_hits] but without removing blocks that are not part of the block map of the
+/// binary (when `coverage_metadata` is configured).
+pub fn load_unfiltered_block_hits(config: &Config) -> anyhow::Result<BlockHits> {
+    load_block_hits_with_metadata(config, None)
+}
+
+fn load_block_hits_with_metadata(
+    config: &Config,
+    metadata: Option<&MetadataSource>,
+) -> anyhow::Result<BlockHits> {
     let mut data = vec![];
     let res = config.time_resolution as i64;
 
-    let valid_blocks = match config.coverage_metadata.as_ref() {
+    let valid_blocks = match metadata {
         Some(metadata) => {
             Some(valid_blocks(&Metadata::from_source(&config.path, metadata.clone())?)?.cache())
         }
@@ -181,102 +226,284 @@ pub fn load_block_hits(config: &Config) -> anyhow::Result<BlockHits> {
         .sort(["time"], SortMultipleOptions::default())
     };
 
+    let functions = function_map(config)?;
+    let to_functions = |lf: LazyFrame| match functions.as_ref() {
+        Some(f) => map_blocks_to_functions(lf, f.clone(), "time", &["binary", "trial"]),
+        None => lf,
+    };
+
+    let mut base_key = cache::Key::new("block_hits")
+        .param(&(res, config.align_first_block, config.coverage_granularity))
+        .param(&config.duplicate_trials)
+        .param(&metadata.is_some());
+    for (binary, path) in metadata.map_or(vec![], |x| x.block_map_files(&config.path)) {
+        base_key = base_key.param(&(binary, &path));
+        if let Some(path) = path {
+            base_key = base_key.file(&path);
+        }
+    }
+
     let group = &[col("binary"), col("trial")];
     for (id, name, entry) in config.datasets() {
-        let filter = parse_filter_expr(&entry.filter);
-        let dataset = match &entry.source {
-            DataSource::FuzzwareBlocksCsv { glob, duration } => {
-                let Some(data) = fuzzware::read_all(glob)? else {
-                    continue;
-                };
-                let raw = filter_valid(data.filter(filter).rename(["seconds"], ["time"]));
-                analysis::blocks_hit_per_period(raw, duration.as_secs() as i64, res, "time", group)?
-                    .with_column(secs_to_hours(col("time")))
-                    .drop(["time"])
-            }
-            DataSource::MultiFuzzBench { glob, duration } => {
-                let Some(data) = multifuzz::read_all(glob)? else {
-                    continue;
-                };
-                let raw = filter_valid(data.filter(filter));
-                let duration_ms = duration.as_millis() as i64;
-                analysis::blocks_hit_per_period(raw, duration_ms, res, "time", group)?
-                    .with_column(millis_to_hours(col("time")))
-                    .drop(["time"])
-            }
-            DataSource::EmberCsv {
-                glob,
-                duration,
-                resampled,
-            } => {
-                let Some(data) = ember::read_all(glob, *resampled)? else {
-                    continue;
-                };
-                let raw = data
-                    .filter(filter)
-                    .rename(["seconds"], ["time"])
-                    .with_column(lit(name.as_str()).alias("fuzzer"));
-                analysis::fill_missing(raw, duration.as_secs() as i64, res, "time", group)?
-                    .with_column(secs_to_hours(col("time")))
-                    .drop(["time"])
-            }
+        let key = base_key.clone().param(&entry.source).param(&entry.filter);
+        let load = || -> anyhow::Result<Option<LazyFrame>> {
+            let filter = parse_filter_expr(&entry.filter);
+            Ok(Some(match &entry.source {
+                DataSource::FuzzwareBlocksCsv { glob, duration } => {
+                    let Some(data) = fuzzware::read_all(glob)? else {
+                        return Ok(None);
+                    };
+                    let raw = filter_valid(data.filter(filter).rename(["seconds"], ["time"]));
+                    let raw = to_functions(raw);
+                    let raw = align_trials(config, raw, "time", group);
+                    let duration = duration.as_secs() as i64;
+                    analysis::blocks_hit_per_period(raw, duration, res, "time", group)?
+                        .with_column(secs_to_hours(col("time")))
+                        .drop(["time"])
+                }
+                DataSource::MultiFuzzBench { glob, duration } => {
+                    let Some(data) = multifuzz::read_all(glob, config.duplicate_trials)? else {
+                        return Ok(None);
+                    };
+                    let raw = to_functions(filter_valid(data.filter(filter)));
+                    let raw = align_trials(config, raw, "time", group);
+                    let duration_ms = duration.as_millis() as i64;
+                    analysis::blocks_hit_per_period(raw, duration_ms, res, "time", group)?
+                        .with_column(millis_to_hours(col("time")))
+                        .drop(["time"])
+                }
+                DataSource::EmberCsv {
+                    glob,
+                    duration,
+                    resampled,
+                } => {
+                    if functions.is_some() {
+                        eprintln!("WARNING: skipping {name}, Ember data has no block addresses");
+                        return Ok(None);
+                    }
+                    let Some(data) = ember::read_all(glob, *resampled)? else {
+                        return Ok(None);
+                    };
+                    let raw = data
+                        .filter(filter)
+                        .rename(["seconds"], ["time"])
+                        .with_column(lit(name.as_str()).alias("fuzzer"));
+                    let raw = align_trials(config, raw, "time", group);
+                    analysis::fill_missing(raw, duration.as_secs() as i64, res, "time", group)?
+                        .with_column(secs_to_hours(col("time")))
+                        .drop(["time"])
+                }
+                // AFL only reports the number of edges found, which is not comparable with block
+                // coverage (see [analysis::afl_stats]).
+                DataSource::BugCsv { .. } | DataSource::AflPlotData { .. } => return Ok(None),
+            }))
         };
-        data.push(dataset.with_columns([
-            lit(name.as_str()).alias("fuzzer"),
-            lit(id as u32).alias("dataset"),
-        ]))
+        let Some(dataset) = cache::cached(config, key.glob(entry.source.glob()), load)? else {
+            continue;
+        };
+        data.push(
+            dataset
+                .with_columns(fuzzer_columns(config, name))
+                .with_column(lit(id as u32).alias("dataset"))
+                .with_columns(style_columns(config.fuzzer_family(name).0, &entry.style)),
+        )
     }
     let global_filter = parse_filter_expr(&config.filter);
     Ok(concat_lf_diagonal(data, UnionArgs::default())?.filter(global_filter))
 }
 
+/// Columns identifying the fuzzer of the dataset `name`: `fuzzer` is the family of the dataset
+/// (see [Config::fuzzer_families]) and `variant` is the name of the dataset. The trials of variants
+/// are renumbered so that they are unique within the family.
+pub fn fuzzer_columns(config: &Config, name: &str) -> Vec<Expr> {
+    let (family, offset) = config.fuzzer_family(name);
+    let mut columns = vec![lit(family).alias("fuzzer"), lit(name).alias("variant")];
+    if offset != 0 {
+        columns.push((col("trial").cast(DataType::UInt32) + lit(offset)).alias("trial"));
+    }
+    columns
+}
+
+/// Columns containing the presentation hints of each dataset (see [config::Style]).
+pub const STYLE_COLUMNS: [&str; 4] = ["style_label", "style_color", "style_marker", "style_line"];
+
+/// Selects all style columns, e.g. `style().first()` keeps the style when aggregating (the style is
+/// constant for each dataset).
+pub fn style() -> Expr {
+    cols(STYLE_COLUMNS)
+}
+
+fn style_columns(name: &str, style: &Style) -> [Expr; 4] {
+    let opt_u32 = |value: Option<u32>| match value {
+        Some(value) => lit(value).cast(DataType::UInt32),
+        None => lit(NULL).cast(DataType::UInt32),
+    };
+    [
+        lit(style.label.as_deref().unwrap_or(name)).alias("style_label"),
+        opt_u32(style.color).alias("style_color"),
+        opt_u32(style.marker).alias("style_marker"),
+        lit(style.line.as_str()).alias("style_line"),
+    ]
+}
+
 /// Represents a lazy frame generated by `load_raw_coverage`
 pub type Coverage = LazyFrame;
 
 pub fn load_raw_coverage(config: &Config) -> anyhow::Result<Coverage> {
+    // Filter coverage to only include valid blocks (if metadata is available).
+    let data = load_untrimmed_coverage(config, config.coverage_metadata.as_ref())?;
+    let mut data = trim_post_campaign_events(config, data);
+
+    if let Some(functions) = function_map(config)? {
+        data = map_blocks_to_functions(data, functions, "hours", &["dataset", "binary", "trial"]);
+    }
+    Ok(data)
+}
+
+/// Like [load_raw_coverage] but without removing blocks that are not part of the block map of the
+/// binary (when `coverage_metadata` is configured).
+pub fn load_unfiltered_coverage(config: &Config) -> anyhow::Result<Coverage> {
+    Ok(trim_post_campaign_events(config, load_untrimmed_coverage(config, None)?))
+}
+
+/// Removes the events recorded after the duration of the dataset (if configured) and the
+/// `duration_hours` column from `data`.
+fn trim_post_campaign_events(config: &Config, data: Coverage) -> Coverage {
+    let data = match config.post_campaign_events {
+        PostCampaignEvents::Keep => data,
+        PostCampaignEvents::Trim => data.filter(col("hours").lt_eq(col("duration_hours"))),
+    };
+    data.drop(["duration_hours"])
+}
+
+/// Counts the number of coverage events in each trial that were recorded after the duration of the
+/// dataset (these are removed from the raw coverage when `post_campaign_events` is `Trim`).
+pub fn trimmed_events(config: &Config) -> anyhow::Result<LazyFrame> {
+    Ok(load_untrimmed_coverage(config, None)?
+        .group_by_stable([col("fuzzer"), col("variant"), col("binary"), col("trial")])
+        .agg([
+            col("hours").count().alias("events"),
+            col("hours").gt(col("duration_hours")).sum().alias("trimmed"),
+            col("hours").max().alias("last_event_hours"),
+            col("duration_hours").first(),
+        ]))
+}
+
+/// Loads the raw coverage of all datasets with an additional `duration_hours` column containing
+/// the duration of the dataset that each event is part of.
+///
+/// If `metadata` is set, blocks that are not part of the block map of the binary are removed
+/// before aligning the trials (like [load_block_hits_with_metadata]), so that invalid blocks do not
+/// affect the alignment.
+fn load_untrimmed_coverage(
+    config: &Config,
+    metadata: Option<&MetadataSource>,
+) -> anyhow::Result<Coverage> {
+    let valid_blocks = match metadata {
+        Some(metadata) => {
+            Some(valid_blocks(&Metadata::from_source(&config.path, metadata.clone())?)?.cache())
+        }
+        None => None,
+    };
+    let filter_valid = |lf: LazyFrame, index: &str| {
+        let Some(valid_blocks) = valid_blocks.clone() else {
+            return lf;
+        };
+        lf.join(
+            valid_blocks,
+            [col("binary"), col("block")],
+            [col("binary"), col("block")],
+            JoinType::Inner.into(),
+        )
+        .sort([index], SortMultipleOptions::default())
+    };
+
+    let mut base_key = cache::Key::new("raw_coverage")
+        .param(&(&config.filter, config.align_first_block))
+        .param(&config.duplicate_trials)
+        .param(&metadata.is_some());
+    for (binary, path) in metadata.map_or(vec![], |x| x.block_map_files(&config.path)) {
+        base_key = base_key.param(&(binary, &path));
+        if let Some(path) = path {
+            base_key = base_key.file(&path);
+        }
+    }
+
     let global_filter = parse_filter_expr(&config.filter);
+    let trial_group = &[col("binary"), col("trial")];
     let mut data = vec![];
     for (id, name, entry) in config.datasets() {
-        let filter = global_filter.clone().and(parse_filter_expr(&entry.filter));
-        let dataset = match &entry.source {
-            DataSource::FuzzwareBlocksCsv { glob, .. } => {
-                let Some(data) = fuzzware::read_all(glob)? else {
-                    continue;
-                };
-                data.filter(filter)
-                    .with_column(secs_to_hours(col("seconds")))
-                    .drop(["seconds"])
-            }
-            DataSource::MultiFuzzBench { glob, .. } => {
-                let Some(data) = multifuzz::read_all(glob)? else {
-                    continue;
-                };
-                data.filter(filter)
-                    .with_column(millis_to_hours(col("time")))
-                    .drop(["time"])
-            }
-            DataSource::EmberCsv { .. } => {
-                // Raw coverage unsupported
-                continue;
-            }
+        let key = base_key.clone().param(&entry.filter).param(&entry.source);
+        let load = || -> anyhow::Result<Option<LazyFrame>> {
+            let filter = global_filter.clone().and(parse_filter_expr(&entry.filter));
+            Ok(Some(match &entry.source {
+                DataSource::FuzzwareBlocksCsv { glob, duration } => {
+                    let Some(data) = fuzzware::read_all(glob)? else {
+                        return Ok(None);
+                    };
+                    let data = filter_valid(data.filter(filter), "seconds");
+                    align_trials(config, data, "seconds", trial_group)
+                        .with_column(secs_to_hours(col("seconds")))
+                        .drop(["seconds"])
+                        .with_column(duration_hours(duration))
+                }
+                DataSource::MultiFuzzBench { glob, duration } => {
+                    let Some(data) = multifuzz::read_all(glob, config.duplicate_trials)? else {
+                        return Ok(None);
+                    };
+                    let data = filter_valid(data.filter(filter), "time");
+                    align_trials(config, data, "time", trial_group)
+                        .with_column(millis_to_hours(col("time")))
+                        .drop(["time"])
+                        .with_column(duration_hours(duration))
+                }
+                DataSource::EmberCsv { .. } | DataSource::AflPlotData { .. } => {
+                    // Raw coverage unsupported
+                    return Ok(None);
+                }
+                DataSource::BugCsv { .. } => return Ok(None),
+            }))
         };
-        data.push(dataset.with_columns([
-            lit(name.as_str()).alias("fuzzer"),
-            lit(id as u32).alias("dataset"),
-        ]));
+        let Some(dataset) = cache::cached(config, key.glob(entry.source.glob()), load)? else {
+            continue;
+        };
+        data.push(
+            dataset
+                .with_columns(fuzzer_columns(config, name))
+                .with_column(lit(id as u32).alias("dataset"))
+                .with_columns(style_columns(config.fuzzer_family(name).0, &entry.style)),
+        );
     }
-    let data = concat_lf_diagonal(data, UnionArgs::default())?;
+    Ok(concat_lf_diagonal(data, UnionArgs::default())?)
+}
 
-    // Filter coverage to only include valid blocks (if metadata is available).
-    if let Some(metadata) = config.coverage_metadata.as_ref() {
-        let valid_blocks = valid_blocks(&Metadata::from_source(&config.path, metadata.clone())?)?;
-        let join_key = [col("binary"), col("block")];
-        Ok(data
-            .join(valid_blocks, &join_key, &join_key, JoinType::Inner.into())
-            .sort(["hours"], SortMultipleOptions::default()))
-    } else {
-        Ok(data)
+/// Loads the verified bugs from all `BugCsv` datasets, returning the time (in hours) that each bug
+/// was found in each trial.
+pub fn load_bugs(config: &Config) -> anyhow::Result<LazyFrame> {
+    let global_filter = parse_filter_expr(&config.filter);
+    let mut data = vec![];
+    for (id, name, entry) in config.datasets() {
+        let DataSource::BugCsv { glob, duration } = &entry.source
+        else {
+            continue;
+        };
+        let Some(bugs) = bugs::read_bugs_all(glob)? else {
+            continue;
+        };
+        let filter = global_filter
+            .clone()
+            .and(parse_filter_expr(&entry.filter))
+            .and(col("time").lt_eq(lit(duration.as_millis() as i64)));
+        data.push(
+            bugs.filter(filter)
+                .with_column(millis_to_hours(col("time")))
+                .drop(["time"])
+                .with_columns(fuzzer_columns(config, name))
+                .with_column(lit(id as u32).alias("dataset"))
+                .with_columns(style_columns(config.fuzzer_family(name).0, &entry.style)),
+        );
     }
+    Ok(concat_lf_diagonal(data, UnionArgs::default())?)
 }
 
 pub fn valid_blocks(metadata: &Metadata) -> PolarsResult<LazyFrame> {
@@ -297,6 +524,120 @@ pub fn valid_blocks(metadata: &Metadata) -> PolarsResult<LazyFrame> {
     concat(entries, UnionArgs::default())
 }
 
+/// Gets the edges of the control flow graphs in the block maps of `metadata` as a frame with
+/// `binary`, `from` and `to` (block start address) columns.
+pub fn cfg_edges(metadata: &Metadata) -> PolarsResult<LazyFrame> {
+    let entries = metadata
+        .binary_mapping
+        .iter()
+        .map(|(binary, idx)| {
+            let (from, to): (Vec<u64>, Vec<u64>) =
+                metadata.block_maps[*idx].edges().map(|edge| (edge.from, edge.to)).unzip();
+            df! { "from" => from, "to" => to }
+                .unwrap()
+                .lazy()
+                .with_column(lit(binary.as_str()).alias("binary"))
+        })
+        .collect::<Vec<_>>();
+    concat(entries, UnionArgs::default())
+}
+
+/// Maps each block of the functions in the block maps of `metadata` to the function containing it,
+/// as a frame with `binary`, `block`, `function` (the entry address) and `function_name` columns.
+pub fn block_functions(metadata: &Metadata) -> PolarsResult<LazyFrame> {
+    let entries = metadata
+        .binary_mapping
+        .iter()
+        .map(|(binary, idx)| {
+            let (mut blocks, mut functions, mut names) = (vec![], vec![], vec![]);
+            for function in metadata.block_maps[*idx].functions() {
+                for block in &function.blocks {
+                    blocks.push(*block);
+                    functions.push(function.addr);
+                    names.push(function.name.as_str());
+                }
+            }
+            df! { "block" => blocks, "function" => functions, "function_name" => names }
+                .unwrap()
+                .lazy()
+                .with_column(lit(binary.as_str()).alias("binary"))
+        })
+        .collect::<Vec<_>>();
+    concat(entries, UnionArgs::default())
+}
+
+/// Loads the mapping from blocks to functions (see [block_functions]) if coverage is measured in
+/// functions (see [CoverageGranularity]).
+pub fn function_map(config: &Config) -> anyhow::Result<Option<LazyFrame>> {
+    if config.coverage_granularity != CoverageGranularity::Function {
+        return Ok(None);
+    }
+    let Some(metadata) = config.coverage_metadata.as_ref() else {
+        anyhow::bail!("`coverage_granularity: function` requires `coverage_metadata`");
+    };
+    let metadata = Metadata::from_source(&config.path, metadata.clone())?;
+    Ok(Some(block_functions(&metadata)?.cache()))
+}
+
+/// Replaces each block in `coverage` with the function containing it (see [block_functions]),
+/// keeping only the first event (ordered by `index`) that reached each function within each group
+/// of `by`. Blocks outside of any known function are discarded.
+pub fn map_blocks_to_functions(
+    coverage: LazyFrame,
+    functions: LazyFrame,
+    index: &str,
+    by: &[&str],
+) -> LazyFrame {
+    let join_key = [col("binary"), col("block")];
+    let mut subset: Vec<String> = by.iter().map(|x| x.to_string()).collect();
+    subset.push("function".into());
+    coverage
+        .join(
+            functions.select([col("binary"), col("block"), col("function")]),
+            &join_key,
+            &join_key,
+            JoinType::Inner.into(),
+        )
+        .sort([index], SortMultipleOptions::default())
+        .unique_stable(Some(subset), UniqueKeepStrategy::First)
+        .drop(["block"])
+        .rename(["function"], ["block"])
+}
+
+/// Loads the reachable blocks listed in the `ground_truth` files of `config` as a frame with
+/// `binary` and `block` columns.
+pub fn load_ground_truth(config: &Config) -> anyhow::Result<LazyFrame> {
+    let root = config.path.parent().unwrap_or(Path::new("."));
+    let mut binaries = vec![];
+    let mut blocks = vec![];
+    for (binary, path) in &config.ground_truth {
+        let path = root.join(path);
+        let data = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read ground truth: {}", path.display()))?;
+        for (i, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let block = parse_u64_with_prefix(line).with_context(|| {
+                format!("invalid block address at {}:{}: {line}", path.display(), i + 1)
+            })?;
+            binaries.push(binary.as_str());
+            blocks.push(block);
+        }
+    }
+    let df = DataFrame::new(vec![Series::new("binary", binaries), Series::new("block", blocks)])?;
+    Ok(df.lazy().unique(None, UniqueKeepStrategy::First))
+}
+
+/// If enabled in the config, shifts `index` so that the first event of each trial occurs at zero.
+fn align_trials(config: &Config, lf: LazyFrame, index: &str, by: &[Expr]) -> LazyFrame {
+    if !config.align_first_block {
+        return lf;
+    }
+    lf.with_column((col(index) - col(index).min().over(by)).alias(index))
+}
+
 fn millis_to_hours(time_ms: Expr) -> Expr {
     (time_ms / lit(1000.0 * 60.0 * 60.0)).alias("hours")
 }
@@ -305,6 +646,10 @@ fn secs_to_hours(time_secs: Expr) -> Expr {
     (time_secs / lit(60.0 * 60.0)).alias("hours")
 }
 
+fn duration_hours(duration: &std::time::Duration) -> Expr {
+    lit(duration.as_secs_f64() / (60.0 * 60.0)).alias("duration_hours")
+}
+
 /// Parse a u64 with either no prefix (decimal), '0x' prefix (hex), or '0b' (binary)
 pub fn parse_u64_with_prefix(value: &str) -> Result<u64, std::num::ParseIntError> {
     if value.len() < 2 {
@@ -342,30 +687,95 @@ pub fn polars_format_u64(expr: Expr) -> Expr {
     expr.map(move |x| Ok(Some(format(x)?)), GetOutput::default())
 }
 
+/// Loads all files matching `glob`, which may refer to files within campaign archives (see
+/// [archive]).
 pub fn load_glob(
     glob: &str,
-    mut load: impl FnMut(&Path) -> anyhow::Result<LazyFrame>,
+    mut load: impl FnMut(&DataFile) -> anyhow::Result<LazyFrame>,
     mut filter: impl FnMut(&Path) -> bool,
 ) -> anyhow::Result<Vec<LazyFrame>> {
-    let files = glob::glob(glob)
-        .unwrap()
-        .filter(|p| p.as_ref().ok().map_or(true, |path| filter(path)))
-        .collect::<Result<Vec<_>, glob::GlobError>>()
-        .with_context(|| format!("Error parsing glob: {glob}"))?;
+    let files = match archive::split_glob(glob) {
+        Some((archives, members)) => archive::load_members(archives, members, filter)?,
+        None => glob::glob(glob)
+            .unwrap()
+            .filter(|p| p.as_ref().ok().map_or(true, |path| filter(path)))
+            .map(|p| p.map(DataFile::new))
+            .collect::<Result<Vec<_>, glob::GlobError>>()
+            .with_context(|| format!("Error parsing glob: {glob}"))?,
+    };
     files
         .iter()
-        .map(|p| load(p).with_context(|| format!("error loading: {}", p.display())))
+        .map(|f| load(f).with_context(|| format!("error loading: {}", f.path.display())))
         .collect::<anyhow::Result<Vec<_>>>()
 }
 
-struct LazyJsonReader(pub std::path::PathBuf);
+/// A file matched by [load_glob], either on disk or a member of a campaign archive.
+#[derive(Clone)]
+pub struct DataFile {
+    /// The path to the file. For archive members this is the path of the archive joined with the
+    /// name of the member (e.g. `archive/bench.tar.zst/output/0/coverage.json`).
+    pub path: PathBuf,
+    /// The contents of the file, if it was read from an archive.
+    data: Option<Arc<[u8]>>,
+}
+
+impl DataFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, data: None }
+    }
+
+    pub(crate) fn archived(path: PathBuf, data: Vec<u8>) -> Self {
+        Self { path, data: Some(data.into()) }
+    }
+
+    pub fn open(&self) -> anyhow::Result<Box<dyn std::io::BufRead + Send + '_>> {
+        match &self.data {
+            Some(data) => Ok(Box::new(std::io::Cursor::new(data.as_ref()))),
+            None => Ok(Box::new(std::io::BufReader::new(
+                std::fs::File::open(&self.path)
+                    .with_context(|| format!("failed to read: {}", self.path.display()))?,
+            ))),
+        }
+    }
+
+    /// Scans the file as a CSV file. Only the header, row skipping, schema, separator and comment
+    /// options are used for files on disk.
+    pub fn scan_csv(&self, options: CsvReadOptions) -> anyhow::Result<LazyFrame> {
+        let Some(data) = &self.data else {
+            let parse = &options.parse_options;
+            let comment_prefix = match &parse.comment_prefix {
+                Some(CommentPrefix::Single(c)) => Some((*c as char).to_string()),
+                Some(CommentPrefix::Multi(s)) => Some(s.to_string()),
+                None => None,
+            };
+            return Ok(LazyCsvReader::new(&self.path)
+                .with_has_header(options.has_header)
+                .with_skip_rows(options.skip_rows)
+                .with_separator(parse.separator)
+                .with_comment_prefix(comment_prefix.as_deref())
+                .with_schema(options.schema.clone())
+                .finish()?);
+        };
+        Ok(options
+            .into_reader_with_file_handle(std::io::Cursor::new(data.as_ref()))
+            .finish()?
+            .lazy())
+    }
+}
+
+struct LazyJsonReader(pub DataFile);
 
 impl AnonymousScan for LazyJsonReader {
     fn scan(&self, scan_opts: AnonymousScanArgs) -> PolarsResult<DataFrame> {
-        let path = self.0.as_path();
-        let reader = std::io::BufReader::new(std::fs::File::open(path).map_err(|e| {
-            polars::error::to_compute_err(format!("{e}: failed to read {}", path.display()))
-        })?);
+        let reader: Box<dyn polars::io::mmap::MmapBytesReader + '_> = match &self.0.data {
+            Some(data) => Box::new(std::io::Cursor::new(data.as_ref())),
+            None => {
+                let path = self.0.path.as_path();
+                Box::new(std::io::BufReader::new(std::fs::File::open(path).map_err(|e| {
+                    polars::error::to_compute_err(format!("{e}: failed to read {}", path.display()))
+                })?))
+            }
+        };
         JsonReader::new(reader)
             .with_schema(scan_opts.schema)
             .finish()
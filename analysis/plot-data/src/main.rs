@@ -1,6 +1,6 @@
 use std::path::{Path, PathBuf};
 
-use plot_data::order_by_binary;
+use plot_data::{order_by_binary, polars_format_u64};
 use polars::prelude::*;
 
 use mimalloc::MiMalloc;
@@ -16,7 +16,15 @@ fn main() -> anyhow::Result<()> {
     let config = plot_data::Config::from_path(&config_path)?;
     let _ = std::fs::create_dir_all("output");
 
-    let show = std::env::args().nth(1).map(|x| {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // `--format <format>` selects the output format of the export targets.
+    let format = match args.iter().position(|x| x == "--format") {
+        Some(i) if i + 1 < args.len() => args.drain(i..i + 2).nth(1),
+        Some(_) => anyhow::bail!("missing value for `--format`"),
+        None => None,
+    };
+
+    let show = args.first().map(|x| {
         x.split(',')
             .map(|x| x.trim().to_owned())
             .collect::<Vec<_>>()
@@ -26,6 +34,12 @@ fn main() -> anyhow::Result<()> {
             .map_or(true, |x| x.iter().any(|x| x == target))
     };
 
+    // Exports are large, so they are only generated when explicitly requested.
+    let should_export = |target: &str| {
+        show.as_ref()
+            .is_some_and(|x| x.iter().any(|x| x == target))
+    };
+
     if should_show("coverage") {
         let mut coverage_table = plot_data::analysis::coverage_table(&config)?
             .sort_by_exprs(
@@ -42,6 +56,68 @@ fn main() -> anyhow::Result<()> {
         println!("block hits: {block_hits}");
     }
 
+    if should_show("function-coverage")
+        && config.coverage_granularity == plot_data::config::CoverageGranularity::Function
+    {
+        let mut function_coverage = plot_data::analysis::function_coverage(&config)?.collect()?;
+        println!("function_coverage: {function_coverage}");
+        write_csv(&mut function_coverage, "output/function_coverage.csv")?;
+    }
+
+    if should_show("edge-coverage") && config.coverage_metadata.is_some() {
+        let mut edge_coverage = plot_data::analysis::edge_coverage(&config)?.collect()?;
+        let mut final_coverage = edge_coverage
+            .clone()
+            .lazy()
+            .group_by(["binary", "fuzzer", "dataset", "trial"])
+            .agg([col("blocks").max(), col("edges").max(), col("edges_same_input").max()])
+            .group_by(["binary", "fuzzer", "dataset"])
+            .agg([
+                col("blocks").median().alias("blocks_median"),
+                col("edges").median().alias("edges_median"),
+                col("edges_same_input").median().alias("edges_same_input_median"),
+            ])
+            .sort_by_exprs(
+                [order_by_binary(), col("dataset")],
+                SortMultipleOptions::new().with_maintain_order(true),
+            )
+            .collect()?;
+        println!("edge coverage (final): {final_coverage}");
+        write_csv(&mut edge_coverage, "output/edge_coverage.csv")?;
+        write_csv(&mut final_coverage, "output/edge_coverage_final.csv")?;
+    }
+
+    if should_show("significance") {
+        let mut significance = plot_data::analysis::significance_table(&config)?;
+        println!("significance (vs. {}): {significance}", config.reference);
+        write_csv(&mut significance, "output/significance.csv")?;
+    }
+
+    if should_show("significance-summary") {
+        let mut summary = plot_data::analysis::significance_summary(&config)?;
+        println!("significance summary (p < {}): {summary}", config.significance_level);
+        write_csv(&mut summary, "output/significance_summary.csv")?;
+        let mut matrix = plot_data::analysis::significance_matrix(&summary)?;
+        write_csv(&mut matrix, "output/significance_matrix.csv")?;
+        let latex = plot_data::analysis::significance_matrix_latex(&matrix)?;
+        std::fs::write("output/significance_matrix.tex", latex)?;
+    }
+
+    if should_show("binary-weights") && !config.binary_weights.is_empty() {
+        let mut weights = plot_data::analysis::binary_weights(&config)?;
+        println!("binary weights: {weights}");
+        write_csv(&mut weights, "output/binary_weights.csv")?;
+    }
+
+    if should_show("best-known") {
+        let coverage = plot_data::load_raw_coverage(&config)?;
+        let mut best_known = plot_data::analysis::best_known_blocks(coverage)
+            .with_column(polars_format_u64(col("block")))
+            .collect()?;
+        println!("best_known: {best_known}");
+        write_csv(&mut best_known, "output/best_known_blocks.csv")?;
+    }
+
     if should_show("median-coverage") {
         let mut median_coverage = plot_data::analysis::median_coverage(&config)?;
         println!("median_coverage: {:?}", median_coverage);
@@ -51,16 +127,246 @@ fn main() -> anyhow::Result<()> {
     if should_show("final-coverage") {
         let coverage = plot_data::load_block_hits(&config)?;
         let final_coverage = coverage
-            .group_by(["dataset", "fuzzer", "binary", "trial"])
+            .group_by(["dataset", "fuzzer", "variant", "binary", "trial"])
             .agg([col("blocks").max().alias("total_blocks")])
             .sort_by_exprs(
-                [col("binary"), col("fuzzer"), col("trial")],
+                [col("binary"), col("fuzzer"), col("variant"), col("trial")],
                 SortMultipleOptions::new().with_nulls_last(true),
             )
             .collect();
         println!("{final_coverage:?}");
     }
 
+    if should_show("outliers") {
+        let mut outliers =
+            plot_data::analysis::outlier_trials(&config, config.outlier_threshold)?.collect()?;
+        if outliers.height() != 0 {
+            eprintln!("WARNING: {} outlier trial(s) found", outliers.height());
+        }
+        println!("outliers: {outliers}");
+        write_csv(&mut outliers, "output/outliers.csv")?;
+    }
+
+    if should_show("completeness") {
+        let mut completeness =
+            plot_data::analysis::trial_completeness(&config, config.runtime_tolerance)?.collect()?;
+        let anomalies = completeness.column("runtime_anomaly")?.bool()?.sum().unwrap_or(0);
+        if anomalies != 0 {
+            eprintln!("WARNING: {anomalies} trial(s) with an unexpected runtime found");
+        }
+        println!("completeness: {completeness}");
+        write_csv(&mut completeness, "output/completeness.csv")?;
+
+        let mut expected = plot_data::analysis::expected_trials(&config)?;
+        let problems = expected.clone().lazy().filter(col("status").neq(lit("ok"))).collect()?;
+        match problems.height() {
+            0 => println!("all trials found ({} trials per dataset)", config.trials),
+            n => {
+                eprintln!("WARNING: {n} dataset(s) with missing or incomplete trials found");
+                println!("expected trials: {problems}");
+            }
+        }
+        write_csv(&mut expected, "output/expected_trials.csv")?;
+    }
+
+    if should_show("trimmed") {
+        let mut trimmed = plot_data::trimmed_events(&config)?
+            .sort_by_exprs(
+                [col("fuzzer"), order_by_binary(), col("trial")],
+                SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+            )
+            .collect()?;
+        let affected = trimmed.column("trimmed")?.cast(&DataType::UInt64)?;
+        let total = affected.u64()?.sum().unwrap_or(0);
+        if total != 0 {
+            let trials = affected.u64()?.into_iter().filter(|x| x.is_some_and(|x| x > 0)).count();
+            eprintln!(
+                "WARNING: {total} coverage event(s) recorded after the end of {trials} trial(s), \
+                see: output/trimmed_events.csv"
+            );
+        }
+        println!("trimmed events: {trimmed}");
+        write_csv(&mut trimmed, "output/trimmed_events.csv")?;
+    }
+
+    if should_show("crashes") && !config.crashes.is_empty() {
+        let crashes = plot_data::analysis::crashes_vs_bugs(&config)?.collect()?;
+        let summary = |by: &[&str]| {
+            plot_data::analysis::crash_bug_summary(crashes.clone().lazy(), by)
+                .sort_by_exprs(
+                    by.iter().map(|x| col(x)).collect::<Vec<_>>(),
+                    SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+                )
+                .collect()
+        };
+        let mut by_binary = summary(&["fuzzer", "binary"])?;
+        let mut by_fuzzer = summary(&["fuzzer"])?;
+        println!("crashes: {by_fuzzer}");
+        write_csv(&mut crashes.clone(), "output/crashes_vs_bugs.csv")?;
+        write_csv(&mut by_binary, "output/crash_summary.csv")?;
+        write_csv(&mut by_fuzzer, "output/crash_summary_by_fuzzer.csv")?;
+    }
+
+    if let Some(story) = config.story.as_ref().filter(|_| should_show("new-blocks")) {
+        let mut new_blocks = plot_data::analysis::new_blocks_by_hour(&config, story)?;
+        println!("new blocks: {new_blocks}");
+        write_csv(&mut new_blocks, "output/new_blocks_by_hour.csv")?;
+    }
+
+    let coverage_delta = config.coverage_delta.as_ref().filter(|_| should_show("coverage-delta"));
+    if let Some(delta) = coverage_delta {
+        let mut blocks = plot_data::analysis::coverage_delta(&config, delta)?;
+        let mut summary = plot_data::analysis::coverage_delta_summary(&blocks).collect()?;
+        println!("coverage delta: {summary}");
+        write_csv(&mut blocks, "output/coverage_delta.csv")?;
+        write_csv(&mut summary, "output/coverage_delta_summary.csv")?;
+    }
+
+    let overlap_fuzzers = plot_data::analysis::overlap_fuzzers(&config);
+    if let Some(fuzzers) = overlap_fuzzers.filter(|_| should_show("overlap")) {
+        let mut overlap = plot_data::analysis::block_overlap(&config, &fuzzers)?.collect()?;
+        println!("block overlap: {overlap}");
+        write_csv(&mut overlap, "output/block_overlap.csv")?;
+    }
+
+    if let Some(diff) = config.diff.as_ref().filter(|_| should_show("diff")) {
+        let mut blocks = plot_data::analysis::unique_blocks(&config, diff)?;
+        let report = plot_data::analysis::unique_blocks_markdown(&blocks, diff)?;
+        println!("blocks reached by only one of {} and {}: {blocks}", diff.fuzzer_a, diff.fuzzer_b);
+        write_csv(&mut blocks, "output/block_diff.csv")?;
+        std::fs::write("output/block_diff.md", report)?;
+    }
+
+    if should_show("ground-truth") && !config.ground_truth.is_empty() {
+        let metrics = plot_data::analysis::ground_truth_metrics(&config)?.collect()?;
+        let mut summary =
+            plot_data::analysis::ground_truth_summary(metrics.clone().lazy()).collect()?;
+        println!("ground truth: {summary}");
+        write_csv(&mut metrics.clone(), "output/ground_truth_trials.csv")?;
+        write_csv(&mut summary, "output/ground_truth.csv")?;
+    }
+
+    if should_show("bugs") && config.has_bug_data() {
+        let mut time_to_bug = plot_data::analysis::time_to_bug(&config)?.collect()?;
+        let mut survival = plot_data::analysis::bug_survival(&config)?.collect()?;
+        println!("time to bug: {time_to_bug}");
+        write_csv(&mut time_to_bug, "output/time_to_bug.csv")?;
+        write_csv(&mut survival, "output/bug_survival.csv")?;
+    }
+
+    if should_show("efficiency") && !config.throughput.is_empty() {
+        let efficiency = plot_data::analysis::coverage_efficiency(&config)?.collect()?;
+        let mut summary =
+            plot_data::analysis::coverage_efficiency_summary(efficiency.clone().lazy()).collect()?;
+        println!("coverage efficiency: {summary}");
+        write_csv(&mut efficiency.clone(), "output/efficiency_over_time.csv")?;
+        write_csv(&mut summary, "output/efficiency.csv")?;
+    }
+
+    if should_show("throughput") && (!config.throughput.is_empty() || config.has_afl_plot_data()) {
+        let execs = plot_data::analysis::execs_over_time(&config)?.collect()?;
+        let mut summary =
+            plot_data::analysis::summarize_throughput(execs.clone().lazy()).collect()?;
+        write_csv(&mut execs.clone(), "output/throughput_over_time.csv")?;
+        write_csv(&mut summary, "output/throughput.csv")?;
+    }
+
+    if should_show("afl-stats") && config.has_afl_plot_data() {
+        let stats = plot_data::analysis::afl_stats(&config)?.collect()?;
+        let mut summary =
+            plot_data::analysis::summarize_afl_stats(stats.clone().lazy()).collect()?;
+        write_csv(&mut stats.clone(), "output/afl_stats.csv")?;
+        write_csv(&mut summary, "output/afl_stats_summary.csv")?;
+    }
+
+    if should_show("check-metadata") {
+        if let Some(mut issues) = plot_data::analysis::check_metadata(&config)? {
+            println!("block map issues: {issues}");
+            write_csv(&mut issues, "output/metadata_issues.csv")?;
+            if issues.height() != 0 {
+                eprintln!(
+                    "WARNING: found {} kind(s) of block map issue(s), see: \
+                    output/metadata_issues.csv",
+                    issues.height()
+                );
+            }
+        }
+    }
+
+    if should_show("coverage-filter") {
+        if let Some(blocks) = plot_data::analysis::coverage_filter_blocks(&config)? {
+            let blocks = blocks.cache();
+            let mut summary =
+                plot_data::analysis::coverage_filter_summary(blocks.clone()).collect()?;
+            let mut offenders = plot_data::analysis::top_invalid_blocks(blocks, 10).collect()?;
+            println!("coverage filter: {summary}");
+            println!("top invalid blocks: {offenders}");
+            write_csv(&mut summary, "output/coverage_filter.csv")?;
+            write_csv(&mut offenders, "output/invalid_blocks.csv")?;
+
+            let affected = summary
+                .clone()
+                .lazy()
+                .filter(col("invalid_blocks").gt(lit(0)))
+                .select([col("binary").unique_stable()])
+                .collect()?;
+            if affected.height() != 0 {
+                eprintln!(
+                    "WARNING: coverage metadata filtering discarded blocks from {} binary(s), see: \
+                    output/coverage_filter.csv",
+                    affected.height()
+                );
+            }
+        }
+    }
+
+    if should_export("export") {
+        // Parquet is used by default since it is much faster to load for large campaigns.
+        let format = format.as_deref().unwrap_or("parquet");
+        let mut block_hits = plot_data::load_block_hits(&config)?.collect()?;
+        let mut raw_coverage = plot_data::load_raw_coverage(&config)?.collect()?;
+        match format {
+            "parquet" => {
+                write_parquet(&mut block_hits, "output/block_hits.parquet")?;
+                write_parquet(&mut raw_coverage, "output/raw_coverage.parquet")?;
+            }
+            "csv" => {
+                write_csv(&mut block_hits, "output/block_hits.csv")?;
+                write_csv(&mut raw_coverage, "output/raw_coverage.csv")?;
+            }
+            other => anyhow::bail!("unknown export format: {other} (expected `parquet` or `csv`)"),
+        }
+        println!(
+            "exported {} block hit row(s) and {} raw coverage row(s) as {format}",
+            block_hits.height(),
+            raw_coverage.height()
+        );
+    }
+
+    if should_export("coverage-export") {
+        let format: plot_data::coverage_export::ExportFormat =
+            format.as_deref().unwrap_or("lcov").parse()?;
+        let dir = std::path::Path::new("output/coverage-export");
+        if let Some(files) = plot_data::coverage_export::export(&config, format, dir)? {
+            println!("exported coverage to {files} file(s) in {}", dir.display());
+        }
+    }
+
+    if should_show("discovery") {
+        let coverage = plot_data::load_raw_coverage(&config)?;
+        let bucket_hours = config.discovery_bucket.as_secs_f64() / (60.0 * 60.0);
+        let mut histogram =
+            plot_data::analysis::discovery_histogram(coverage, bucket_hours)?.collect()?;
+        println!("discovery histogram: {histogram}");
+        write_csv(&mut histogram, "output/discovery_histogram.csv")?;
+    }
+
+    if should_show("block-timeline") {
+        let mut timeline = plot_data::analysis::block_timeline(&config)?.collect()?;
+        println!("block timeline: {timeline}");
+        write_csv(&mut timeline, "output/block_timeline.csv")?;
+    }
+
     if should_show("survival") {
         let coverage = plot_data::load_raw_coverage(&config)?;
         let survival = plot_data::analysis::block_survival(coverage, &config.survival)?;
@@ -80,3 +386,10 @@ fn write_csv(df: &mut DataFrame, path: impl AsRef<Path>) -> anyhow::Result<()> {
         .with_separator(b',')
         .finish(df)?)
 }
+
+fn write_parquet(df: &mut DataFrame, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    ParquetWriter::new(&mut std::fs::File::create(path)?)
+        .with_compression(ParquetCompression::Zstd(None))
+        .finish(df)?;
+    Ok(())
+}
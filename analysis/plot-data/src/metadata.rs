@@ -11,6 +11,19 @@ pub struct MetadataSource {
     block_maps: HashMap<String, PathBuf>,
 }
 
+impl MetadataSource {
+    /// Returns each binary along with the path of its block map (if it exists), ordered by binary.
+    pub fn block_map_files(&self, metadata_root: &Path) -> Vec<(&str, Option<PathBuf>)> {
+        let mut files: Vec<_> = self
+            .block_maps
+            .iter()
+            .map(|(binary, path)| (binary.as_str(), try_find_file(metadata_root, path)))
+            .collect();
+        files.sort();
+        files
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct Metadata {
     /// Information about the blocks for a particular binary.
@@ -246,6 +259,11 @@ impl BlockMap {
         })
     }
 
+    /// Returns an iterator over all known functions in the binary.
+    pub fn functions(&self) -> impl Iterator<Item = &Function> {
+        self.functions.values()
+    }
+
     /// Get the function with entrypoint `addr`.
     pub fn get_function(&self, addr: u64) -> Option<&Function> {
         self.functions.get(&addr)
@@ -271,6 +289,45 @@ impl BlockMap {
         nice_name.unwrap_or_else(|| format!("{:#0x}", addr))
     }
 
+    /// Checks the block map for structural problems (typically caused by a broken export),
+    /// returning each problem along with the address it was found at.
+    pub fn check(&self) -> Vec<(Issue, u64)> {
+        let mut issues = vec![];
+
+        let mut prev: Option<&Block> = None;
+        for block in self.interval_tree.values() {
+            if prev.is_some_and(|prev| block.start <= prev.end) {
+                issues.push((Issue::OverlappingBlock, block.start));
+            }
+            prev = Some(block);
+        }
+
+        for function in self.functions.values().filter(|x| x.blocks.is_empty()) {
+            issues.push((Issue::EmptyFunction, function.addr));
+        }
+
+        for &(from, to) in self.edges.keys() {
+            if !self.interval_tree.contains_key(&from) {
+                issues.push((Issue::UnknownEdgeSource, from));
+            }
+            if !self.interval_tree.contains_key(&to) {
+                issues.push((Issue::UnknownEdgeTarget, to));
+            }
+        }
+
+        issues
+    }
+
+    /// Checks that `addr` (recorded as a block in the coverage data) is the start of a block,
+    /// returning the problem if it is not.
+    pub fn check_hit(&self, addr: u64) -> Option<Issue> {
+        match self.get_containing_block(addr) {
+            Some(block) if block.start == addr => None,
+            Some(_) => Some(Issue::MisalignedHit),
+            None => Some(Issue::UnmappedHit),
+        }
+    }
+
     /// Relocate all addresses by a fixed offset
     pub fn relocate(self, offset: u64) -> Self {
         let interval_tree = self
@@ -295,6 +352,36 @@ impl BlockMap {
     }
 }
 
+/// A problem found when checking a block map (see [BlockMap::check] and [BlockMap::check_hit]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Issue {
+    /// A block that starts before the end of the previous block.
+    OverlappingBlock,
+    /// A function that does not contain any blocks.
+    EmptyFunction,
+    /// An edge from an address that is not the start of a block.
+    UnknownEdgeSource,
+    /// An edge to an address that is not the start of a block.
+    UnknownEdgeTarget,
+    /// An address reached in the coverage data that is not inside any block.
+    UnmappedHit,
+    /// An address reached in the coverage data that is inside a block, but is not its start.
+    MisalignedHit,
+}
+
+impl Issue {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::OverlappingBlock => "overlapping_block",
+            Self::EmptyFunction => "empty_function",
+            Self::UnknownEdgeSource => "unknown_edge_source",
+            Self::UnknownEdgeTarget => "unknown_edge_target",
+            Self::UnmappedHit => "unmapped_hit",
+            Self::MisalignedHit => "misaligned_hit",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Function {
     /// The name of the function.
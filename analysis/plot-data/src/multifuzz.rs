@@ -1,12 +1,13 @@
-use std::{
-    io::BufRead,
-    path::{Path, PathBuf},
-};
+use std::{io::BufRead, path::Path};
 
 use anyhow::{ensure, Context};
+use indexmap::IndexMap;
 use polars::prelude::*;
 
-use crate::{data_loading, load_glob, parse_u64_with_prefix, polars_parse_u64};
+use crate::{
+    config::DuplicateTrials, data_loading, load_glob, parse_u64_with_prefix, polars_parse_u64,
+    DataFile,
+};
 
 /// Load data from the "live" coverage file that is generated while MultiFuzz is running. Useful for
 /// checking incomplete or unprocessed runs, however may contain inaccurate coverage information
@@ -20,7 +21,7 @@ pub fn read_raw_coverage_csv_all(glob: &str) -> anyhow::Result<LazyFrame> {
     Ok(concat(data, UnionArgs::default())?)
 }
 
-fn read_raw_v1_csv(path: &Path) -> anyhow::Result<LazyFrame> {
+fn read_raw_v1_csv(file: &DataFile) -> anyhow::Result<LazyFrame> {
     let mut schema = Schema::new();
     schema.with_column("block".into(), DataType::String);
     schema.with_column("time".into(), DataType::Int64);
@@ -30,9 +31,7 @@ fn read_raw_v1_csv(path: &Path) -> anyhow::Result<LazyFrame> {
     // the file line-by-line searching for tagged chunks.
     let mut buf = vec![];
     let mut current_tag: Option<String> = None;
-    let mut reader = std::io::BufReader::new(
-        std::fs::File::open(path).with_context(|| format!("failed to read: {}", path.display()))?,
-    );
+    let mut reader = file.open()?;
 
     let read_csv_chunk = |buf: &[u8], tags: &str| -> anyhow::Result<LazyFrame> {
         let df = CsvReadOptions::default()
@@ -45,7 +44,7 @@ fn read_raw_v1_csv(path: &Path) -> anyhow::Result<LazyFrame> {
 
         let mut lf = df.lazy().with_column(polars_parse_u64(col("block")));
         for (key, value) in data_loading::parse_bench_tags(tags)? {
-            lf = lf.with_column(lit(value).alias(key));
+            lf = lf.with_column(lit(value.as_ref()).alias(key.as_ref()));
         }
 
         Ok(lf)
@@ -79,51 +78,187 @@ fn read_raw_v1_csv(path: &Path) -> anyhow::Result<LazyFrame> {
     }
 
     if chunks.is_empty() {
-        anyhow::bail!("No tagged chunks were found in file: {}", path.display());
+        anyhow::bail!("No tagged chunks were found in file: {}", file.path.display());
     }
 
     // Merge all the chunks together.
     Ok(concat(chunks, UnionArgs::default())?.with_columns(add_metadata()))
 }
 
-pub fn read_all(glob: &String) -> anyhow::Result<Option<LazyFrame>> {
-    let data = load_glob(glob, |path| Ok(read_coverage_json(path)?), |_| true)?;
+/// Load all MultiFuzz coverage files matching `glob`.
+///
+/// Long running trials may shard their coverage output across multiple files (e.g.
+/// `coverage.json`, `coverage.1.json`, ...) within the trial directory, so all files in the same
+/// directory are merged together keeping only the first hit of each block (for each tag). Trials
+/// found in more than one directory are handled according to `duplicates`.
+pub fn read_all(glob: &String, duplicates: DuplicateTrials) -> anyhow::Result<Option<LazyFrame>> {
+    let mut paths = vec![];
+    let data = load_glob(
+        glob,
+        |file| {
+            paths.push(file.path.clone());
+            Ok(read_coverage_json(file)?)
+        },
+        |_| true,
+    )?;
     if data.is_empty() {
         eprintln!("WARNING: No raw MultiFuzz json files found for: {glob}");
         return Ok(None);
     }
-    Ok(Some(concat(data, UnionArgs::default())?))
+
+    let mut trials: IndexMap<&Path, Vec<LazyFrame>> = IndexMap::new();
+    for (path, lf) in paths.iter().zip(data) {
+        let dir = match path.is_dir() {
+            true => path.as_path(),
+            false => path.parent().unwrap_or(path),
+        };
+        trials.entry(dir).or_default().push(lf);
+    }
+
+    let data = trials
+        .into_iter()
+        .map(|(dir, mut shards)| {
+            let trial = match shards.len() {
+                1 => shards.pop().unwrap(),
+                _ => merge_shards(shards)?,
+            };
+            Ok(trial.with_column(lit(dir.to_string_lossy().as_ref()).alias("trial_dir")))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let data = concat(data, UnionArgs::default())?.collect()?;
+    let data = remove_duplicate_trials(data, glob, duplicates)?;
+    Ok(Some(data.lazy().drop(["trial_dir"])))
 }
 
-pub fn read_coverage_json(path: &Path) -> PolarsResult<LazyFrame> {
+/// Checks whether the same trial (label, binary, fuzzer and trial number) was loaded from more than
+/// one directory, which would otherwise skew the statistics of the trial's group. Duplicates are
+/// reported, then either removed (keeping the most recently modified directory) or rejected.
+fn remove_duplicate_trials(
+    data: DataFrame,
+    glob: &str,
+    duplicates: DuplicateTrials,
+) -> anyhow::Result<DataFrame> {
+    let key = ["label", "binary", "fuzzer", "trial"];
+    let dirs = data
+        .clone()
+        .lazy()
+        .select(
+            key.iter()
+                .chain(&["trial_dir"])
+                .map(|x| col(x).cast(DataType::String))
+                .collect::<Vec<_>>(),
+        )
+        .unique_stable(None, UniqueKeepStrategy::First)
+        .collect()?;
+
+    let mut trials: IndexMap<[Option<&str>; 4], Vec<&str>> = IndexMap::new();
+    let columns = dirs.columns(key.iter().chain(&["trial_dir"]))?;
+    let [label, binary, fuzzer, trial, dir] = [0, 1, 2, 3, 4].map(|i| columns[i].str());
+    let entries = label?.into_iter().zip(binary?).zip(fuzzer?).zip(trial?).zip(dir?);
+    for ((((label, binary), fuzzer), trial), dir) in entries {
+        trials.entry([label, binary, fuzzer, trial]).or_default().extend(dir);
+    }
+    trials.retain(|_, dirs| dirs.len() > 1);
+    if trials.is_empty() {
+        return Ok(data);
+    }
+
+    let modified = |dir: &str| std::fs::metadata(dir).and_then(|x| x.modified()).ok();
+    let mut removed: [Vec<Option<&str>>; 5] = Default::default();
+    for ([label, binary, fuzzer, trial], dirs) in &trials {
+        let mut name = format!(
+            "binary={}, fuzzer={}, trial={}",
+            binary.unwrap_or("?"),
+            fuzzer.unwrap_or("?"),
+            trial.unwrap_or("?")
+        );
+        if let Some(label) = label.filter(|x| !x.is_empty()) {
+            name.push_str(&format!(", label={label}"));
+        }
+        eprintln!("WARNING: {name} found in {} directories: {}", dirs.len(), dirs.join(", "));
+        let keep = dirs.iter().copied().max_by_key(|dir| modified(dir)).unwrap();
+        for dir in dirs.iter().copied().filter(|dir| *dir != keep) {
+            let values = [*label, *binary, *fuzzer, *trial, Some(dir)];
+            for (column, value) in removed.iter_mut().zip(values) {
+                column.push(value);
+            }
+        }
+    }
+    if let DuplicateTrials::Error = duplicates {
+        anyhow::bail!(
+            "{} duplicate trial(s) found for: {glob} (set `duplicate_trials: dedup` to keep the \
+            most recently modified directory of each trial)",
+            trials.len()
+        );
+    }
+
+    let [label, binary, fuzzer, trial, dir] = removed;
+    let n_removed = dir.len();
+    let removed = df! {
+        "label" => label,
+        "binary" => binary,
+        "fuzzer" => fuzzer,
+        "trial" => trial,
+        "trial_dir" => dir,
+        "duplicate" => vec![true; n_removed],
+    }?
+    .lazy()
+    // The key was converted to strings above, so convert the trial number back for the join.
+    .with_column(col("trial").cast(DataType::UInt32));
+    let join_key: Vec<_> = key.iter().chain(&["trial_dir"]).map(|x| col(x)).collect();
+    Ok(data
+        .lazy()
+        .join(removed, &join_key, &join_key, JoinType::Left.into())
+        .filter(col("duplicate").is_null())
+        .drop(["duplicate"])
+        .collect()?)
+}
+
+/// Merge coverage from multiple files that belong to the same trial, removing blocks that were
+/// already hit in an earlier shard.
+fn merge_shards(shards: Vec<LazyFrame>) -> anyhow::Result<LazyFrame> {
+    Ok(concat(shards, UnionArgs::default())?
+        .sort(["time"], SortMultipleOptions::default().with_maintain_order(true))
+        .unique_stable(Some(vec!["tag".into(), "block".into()]), UniqueKeepStrategy::First))
+}
+
+pub fn read_coverage_json(file: &DataFile) -> PolarsResult<LazyFrame> {
     let args = ScanArgsAnonymous { name: "scan_coverage_json", ..ScanArgsAnonymous::default() };
-    LazyFrame::anonymous_scan(Arc::new(LazyCoverageJson(path.into())), args)
+    LazyFrame::anonymous_scan(Arc::new(LazyCoverageJson(file.clone())), args)
 }
 
-struct LazyCoverageJson(PathBuf);
+struct LazyCoverageJson(DataFile);
 
 impl AnonymousScan for LazyCoverageJson {
     fn scan(&self, scan_opts: AnonymousScanArgs) -> PolarsResult<DataFrame> {
-        let path = self.0.as_path();
+        let path = self.0.path.as_path();
         let mut entries = vec![];
-        for (tags, data) in data_loading::json_map::load_coverage_data(path, false)
+        for (tags, data) in data_loading::json_map::load_coverage_data(&self.0)
             .map_err(polars::error::to_compute_err)?
         {
             let mut lf = df! {
                 "block" => data.iter().map(|x| x.addr).collect::<Series>(),
                 "time" => data.iter().map(|x| x.time_ms as i64).collect::<Series>(),
                 "input" => data.iter().map(|x| x.input_id).collect::<Series>(),
+                "hits" => data.iter().map(|x| x.hits).collect::<Series>(),
             }?
-            .lazy();
+            .lazy()
+            .with_columns([
+                lit(tags.as_str()).alias("tag"),
+                lit(path.to_string_lossy().as_ref()).alias("source"),
+                // Runs without a label (see `bench-harness bench --label`) have an empty label.
+                lit("").alias("label"),
+            ]);
             let schema: &Schema = scan_opts.schema.as_ref();
-            for (key, mut value) in
+            for (key, value) in
                 data_loading::parse_bench_tags(&tags).map_err(polars::error::to_compute_err)?
             {
-                if let Some(dtype) = schema.get(key) {
-                    if key == "binary" {
-                        value = normalize_binary_name(value);
-                    }
-                    lf = lf.with_column(lit(value).alias(key).cast(dtype.clone()));
+                if let Some(dtype) = schema.get(&key) {
+                    let value = match key.as_ref() {
+                        "binary" => normalize_binary_name(&value),
+                        _ => &value,
+                    };
+                    lf = lf.with_column(lit(value).alias(&key).cast(dtype.clone()));
                 }
             }
             entries.push(lf);
@@ -136,9 +271,14 @@ impl AnonymousScan for LazyCoverageJson {
         schema.with_column("block".into(), DataType::String);
         schema.with_column("time".into(), DataType::Int64);
         schema.with_column("input".into(), DataType::UInt64);
+        // Only recorded by newer versions of MultiFuzz (null otherwise).
+        schema.with_column("hits".into(), DataType::UInt64);
         schema.with_column("trial".into(), DataType::String);
         schema.with_column("binary".into(), DataType::String);
         schema.with_column("fuzzer".into(), DataType::String);
+        schema.with_column("tag".into(), DataType::String);
+        schema.with_column("source".into(), DataType::String);
+        schema.with_column("label".into(), DataType::String);
         Ok(Arc::new(schema))
     }
 
@@ -155,12 +295,12 @@ pub fn read_testcases_json(glob: &str) -> anyhow::Result<LazyFrame> {
     schema.with_column("untrimed_len".into(), DataType::UInt32);
     let schema = Arc::new(schema);
 
-    let data = load_glob(glob, |path| read_trial_json(path, schema.clone()), |_| true)?;
+    let data = load_glob(glob, |file| read_trial_json(file, schema.clone()), |_| true)?;
     ensure!(!data.is_empty(), "No files found for: {glob}");
     Ok(concat_lf_diagonal(data, UnionArgs::default())?)
 }
 
-pub fn read_trial_json(path: &Path, schema: Arc<Schema>) -> anyhow::Result<LazyFrame> {
+pub fn read_trial_json(file: &DataFile, schema: Arc<Schema>) -> anyhow::Result<LazyFrame> {
     // Parse target, binary name and file from path: e.g.
     // `[bench]/[target]-[binary]/[trial]/file.json`
     fn extract_metadata_from_path(path: &Path) -> Option<(&str, &str, u32)> {
@@ -178,8 +318,8 @@ pub fn read_trial_json(path: &Path, schema: Arc<Schema>) -> anyhow::Result<LazyF
 
         Some((bench, binary, trial))
     }
-    let (bench, binary, trial) = extract_metadata_from_path(path).ok_or_else(|| {
-        anyhow::format_err!("failed to read metadata from path: {}", path.display())
+    let (bench, binary, trial) = extract_metadata_from_path(&file.path).ok_or_else(|| {
+        anyhow::format_err!("failed to read metadata from path: {}", file.path.display())
     })?;
 
     let binary = normalize_binary_name(binary);
@@ -189,12 +329,12 @@ pub fn read_trial_json(path: &Path, schema: Arc<Schema>) -> anyhow::Result<LazyF
         name: "scan_json",
         ..ScanArgsAnonymous::default()
     };
-    Ok(LazyFrame::anonymous_scan(Arc::new(crate::LazyJsonReader(path.into())), args)?.with_columns(
+    Ok(LazyFrame::anonymous_scan(Arc::new(crate::LazyJsonReader(file.clone())), args)?.with_columns(
         [lit(bench).alias("bench"), lit(trial).alias("trial"), lit(binary).alias("binary")],
     ))
 }
 
-fn add_metadata() -> [Expr; 2] {
+pub(crate) fn add_metadata() -> [Expr; 2] {
     [
         col("trial").str().to_integer(lit(10), false).cast(DataType::UInt32),
         (col("binary").str().replace(lit(".*/"), lit(""), false)),
@@ -215,29 +355,30 @@ pub mod legacy {
         Ok(concat(data, UnionArgs::default())?)
     }
 
-    fn read_resampled_v1_csv(path: &Path, keys: &[impl AsRef<str>]) -> anyhow::Result<LazyFrame> {
+    fn read_resampled_v1_csv(
+        file: &DataFile,
+        keys: &[impl AsRef<str>],
+    ) -> anyhow::Result<LazyFrame> {
         let mut schema = Schema::new();
         schema.with_column("tag".into(), DataType::String);
         schema.with_column("time".into(), DataType::Int64);
         schema.with_column("count".into(), DataType::UInt32);
 
-        let mut lf = LazyCsvReader::new(path)
-            .with_has_header(true)
-            .with_schema(Some(schema.into()))
-            .finish()?;
+        let mut lf = file.scan_csv(
+            CsvReadOptions::default().with_has_header(true).with_schema(Some(schema.into())),
+        )?;
 
         // Check the first tag to determine how we are going to parse tags for this binary
         let tags = &lf.clone().first().collect()?["tag"];
         let first_tag = tags.str_value(0)?;
-        if !first_tag.starts_with("v1;") {
+        if data_loading::parse_bench_tags(&first_tag).is_err() {
             anyhow::bail!("Unsupported tag format: {first_tag}");
         }
 
         // Extract keys from tag and add them as new columns.
         for key in keys {
             let key = key.as_ref();
-            lf = lf
-                .with_column(col("tag").str().extract(lit(format!("{key}=([^;]+)")), 1).alias(key))
+            lf = lf.with_column(data_loading::extract_tag_value(col("tag"), key).alias(key))
         }
 
         Ok(lf.with_columns(add_metadata()))
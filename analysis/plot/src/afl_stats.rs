@@ -0,0 +1,131 @@
+use anyhow::Context;
+use plot_data::{name_of_binary, Config};
+use plotters::{
+    coord::Shift,
+    prelude::*,
+    style::text_anchor::{HPos, Pos, VPos},
+};
+use polars::prelude::*;
+
+use crate::utils::{
+    draw_subtitle, draw_x_axis_label, polygon_between, split_with_columns, Legend, SeriesStyle,
+    StepIter,
+};
+
+/// The metrics plotted for each binary: the prefix of the `_median`, `_min` and `_max` columns of
+/// the data, and the title of the panel.
+const METRICS: [(&str, &str); 3] =
+    [("edges", "edges found"), ("execs_per_sec", "execs/sec"), ("corpus_count", "corpus size")];
+
+/// Plots the edges found, execution speed and corpus size of the fuzzers with AFL plot data (see
+/// [plot_data::analysis::summarize_afl_stats]) next to each other, with one row per binary.
+pub fn afl_stats_over_time<DB>(
+    root: &DrawingArea<DB, Shift>,
+    config: &Config,
+    stats: &DataFrame,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    let (legend_area, x_axis_area, plot_area) = {
+        let (plot_area, legend_area) = root.split_vertically(root.dim_in_pixel().1 - 45);
+        let (plot_area, x_axis_area) = plot_area.split_vertically(plot_area.dim_in_pixel().1 - 25);
+        (legend_area, x_axis_area, plot_area)
+    };
+
+    let legend_label_style = TextStyle::from(("Arial", 18).into_font())
+        .with_anchor::<RGBAColor>(Pos::new(HPos::Left, VPos::Bottom))
+        .into_text_style(&legend_area);
+    let mut legend = Legend::from_config(legend_label_style, config);
+
+    let by_binary = stats.partition_by_stable(["binary"], true).context("partition_by(binary)")?;
+    let n_cells = METRICS.len() * by_binary.len();
+    let plot_regions = split_with_columns(&plot_area, n_cells, METRICS.len());
+    let mut regions = plot_regions.iter();
+    for stats in &by_binary {
+        let name = stats["binary"].str_value(0)?;
+        for (metric, label) in METRICS {
+            let title = format!("{} ({label})", name_of_binary(&name));
+            let region = regions.next().unwrap();
+            draw_metric(region, &title, stats, metric, &mut legend)?;
+        }
+    }
+
+    let axis_label_style = TextStyle::from(("Arial", 20).into_font());
+    draw_x_axis_label(x_axis_area, "Duration (hours)", &axis_label_style)?;
+    legend.draw(&legend_area.margin(5, 0, 0, 0))?;
+
+    root.present()?;
+    Ok(())
+}
+
+/// Draws the median of `metric` for each fuzzer, with the area between the min and max shaded.
+pub fn draw_metric<DB>(
+    region: &DrawingArea<DB, Shift>,
+    title: &str,
+    df: &DataFrame,
+    metric: &str,
+    legend: &mut Legend,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let (subtitle, plot) = region.split_vertically(18);
+
+    let left_axis_padding = 45;
+    draw_subtitle(title, &subtitle, left_axis_padding, 16)?;
+
+    let values = |df: &DataFrame, suffix: &str| -> anyhow::Result<Vec<f32>> {
+        let column = df[format!("{metric}_{suffix}").as_str()].cast(&DataType::Float64)?;
+        Ok(column.f64()?.into_iter().map(|x| x.unwrap_or(0.0) as f32).collect())
+    };
+    let max_hours = df["hours"].f64()?.max().map_or(24.0, |x| x.ceil().max(1.0) as f32);
+    let max_y = values(df, "max")?.into_iter().fold(1.0_f32, f32::max);
+
+    let mut subchart = ChartBuilder::on(&plot);
+    let mut ctx = subchart
+        .margin(4)
+        .set_label_area_size(LabelAreaPosition::Bottom, 15)
+        .set_label_area_size(LabelAreaPosition::Left, left_axis_padding)
+        .build_cartesian_2d(0_f32..max_hours, 0_f32..max_y)?;
+    ctx.configure_mesh()
+        .max_light_lines(0)
+        .x_label_formatter(&|value| format!("{}", *value as u64))
+        .x_labels(6)
+        .x_label_style(TextStyle::from(("Arial", 14).into_font()))
+        .y_label_formatter(&|value| format!("{}", *value as u64))
+        .y_labels(8)
+        .y_label_style(TextStyle::from(("Arial", 14).into_font()))
+        .draw()
+        .unwrap();
+
+    for df in df.partition_by_stable(["fuzzer"], true).context("partition_by(fuzzer)")? {
+        if df.height() == 0 {
+            continue;
+        }
+        let entry = legend.get_or_insert_style(&SeriesStyle::from_df(&df)?);
+        let hours: Vec<f32> = df["hours"].f64()?.into_no_null_iter().map(|x| x as f32).collect();
+        let (median, min, max) = (values(&df, "median")?, values(&df, "min")?, values(&df, "max")?);
+        let points = |values: &[f32]| -> Vec<(f32, f32)> {
+            hours.iter().copied().zip(values.iter().copied()).collect()
+        };
+
+        ctx.draw_series([Polygon::new(
+            polygon_between(points(&max).into_iter(), points(&min).into_iter()),
+            entry.color.mix(0.2).filled(),
+        )])?;
+        let median = points(&median);
+        entry.line.draw_line(&mut ctx, StepIter::new(median.iter().copied()), &entry.color)?;
+
+        // Draw markers along the median every 2 hours.
+        let step_size = ((df.height() * 2) / 24).max(1);
+        let markers = median.iter().copied().step_by(step_size);
+        entry.marker.draw_markers(&mut ctx, markers, &entry.color)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use anyhow::Context;
+use plot_data::{config::BlockOrder, name_of_binary, Config};
+use plotters::{coord::Shift, prelude::*};
+use polars::prelude::*;
+
+use crate::utils::{draw_subtitle, draw_x_axis_label, split_with_columns, CustomPalette};
+
+/// Plots the time that each block was first reached as a heatmap for each fuzzer on each binary
+/// (see [plot_data::analysis::block_timeline]). Each row is a block, filled from the time it was
+/// first reached until the end of the run and colored by the trial that reached it first, so
+/// plateaus (periods without new blocks) appear as columns where no new rows start.
+pub fn block_timeline<DB>(
+    root: &DrawingArea<DB, Shift>,
+    config: &Config,
+    data: &DataFrame,
+    n_cols: u32,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    let (x_axis_area, plot_area) = {
+        let (plot_area, x_axis_area) = root.split_vertically(root.dim_in_pixel().1 - 25);
+        (x_axis_area, plot_area)
+    };
+
+    // Fuzzers are shown in legend order (then config order) within each binary.
+    let ranks = config.legend_ranks();
+    let mut fuzzers: Vec<(&str, String)> = config
+        .data
+        .iter()
+        .map(|(name, datasets)| {
+            let label = datasets.iter().find_map(|x| x.style.label.clone());
+            (name.as_str(), label.unwrap_or_else(|| name.clone()))
+        })
+        .collect();
+    fuzzers.sort_by_key(|(_, label)| ranks.get(label).copied().unwrap_or(usize::MAX));
+
+    let max_hours = data["hours"].f64()?.max().map_or(24.0, |x| x.ceil().max(1.0) as f32);
+    let y_label = match config.block_timeline_order {
+        BlockOrder::Address => "Blocks (by address)",
+        BlockOrder::Function => "Blocks (by function)",
+    };
+
+    let mut cells = vec![];
+    for df in data.partition_by_stable(["binary"], true).context("partition_by(binary)")? {
+        let name = df["binary"].str_value(0)?;
+        let title = name_of_binary(&name);
+        // All fuzzers on the same binary share the same rows.
+        let n_rows = df["row"].u32()?.max().map_or(0, |x| x + 1);
+        for (fuzzer, label) in &fuzzers {
+            let rows = df.clone().lazy().filter(col("fuzzer").eq(lit(*fuzzer))).collect()?;
+            if rows.height() != 0 {
+                cells.push((format!("{title} ({label})"), n_rows, rows));
+            }
+        }
+    }
+
+    let plot_regions = split_with_columns(&plot_area, cells.len(), n_cols as usize);
+    for ((title, n_rows, df), region) in cells.iter().zip(plot_regions) {
+        draw_fuzzer_timeline(&region, title, df, *n_rows, max_hours, y_label)?;
+    }
+
+    let axis_label_style = TextStyle::from(("Arial", 20).into_font());
+    draw_x_axis_label(x_axis_area, "Duration (hours)", &axis_label_style)?;
+
+    root.present()?;
+    Ok(())
+}
+
+fn draw_fuzzer_timeline<DB>(
+    region: &DrawingArea<DB, Shift>,
+    title: &str,
+    df: &DataFrame,
+    n_rows: u32,
+    max_hours: f32,
+    y_label: &str,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let (subtitle, plot) = region.split_vertically(18);
+
+    let left_axis_padding = 50;
+    draw_subtitle(title, &subtitle, left_axis_padding, 16)?;
+
+    let mut subchart = ChartBuilder::on(&plot);
+    let mut ctx = subchart
+        .margin(4)
+        .set_label_area_size(LabelAreaPosition::Bottom, 15)
+        .set_label_area_size(LabelAreaPosition::Left, left_axis_padding)
+        .build_cartesian_2d(0_f32..max_hours, 0_f32..n_rows.max(1) as f32)?;
+    ctx.configure_mesh()
+        .disable_mesh()
+        .x_label_formatter(&|value| format!("{}", *value as u64))
+        .x_labels(6)
+        .x_label_style(TextStyle::from(("Arial", 14).into_font()))
+        .y_label_formatter(&|value| format!("{}", *value as u64))
+        .y_labels(4)
+        .y_label_style(TextStyle::from(("Arial", 12).into_font()))
+        .y_desc(y_label)
+        .axis_desc_style(TextStyle::from(("Arial", 12).into_font()))
+        .draw()
+        .unwrap();
+
+    let hours = df["hours"].f64()?;
+    let row = df["row"].u32()?;
+    let trial = df["trial"].cast(&DataType::UInt32)?;
+    let trial = trial.u32()?;
+    let blocks = hours.into_iter().zip(row).zip(trial).filter_map(|((hours, row), trial)| {
+        let (x, y) = (hours? as f32, row? as f32);
+        let color = CustomPalette::pick(trial.unwrap_or(0) as usize);
+        Some(Rectangle::new([(x, y), (max_hours, y + 1.0)], color.filled()))
+    });
+    ctx.draw_series(blocks)?;
+
+    Ok(())
+}
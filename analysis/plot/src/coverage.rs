@@ -6,11 +6,11 @@ use plotters::{
 };
 use polars::prelude::*;
 
-use plot_data::{name_of_binary, Config};
+use plot_data::{config::BugExploitMarker, name_of_binary, Config};
 
 use crate::utils::{
-    draw_subtitle, draw_x_axis_label, draw_y_axis_label, polygon_between, split_with_columns,
-    CustomPalette, Legend, Marker, StepIter,
+    draw_hatching, draw_subtitle, draw_x_axis_label, draw_y_axis_label, polygon_between,
+    split_with_columns, CustomPalette, Legend, LineStyle, Marker, SeriesStyle, StepIter,
 };
 
 pub fn coverage_over_time<DB>(
@@ -39,52 +39,34 @@ where
     let legend_label_style = TextStyle::from(("Arial", 18).into_font())
         .with_anchor::<RGBAColor>(Pos::new(HPos::Left, VPos::Bottom))
         .into_text_style(&legend_area);
-    let mut legend = Legend::new_with_mapping(legend_label_style, config.legend_mapping.clone());
+    let mut legend = Legend::from_config(legend_label_style, config);
 
+    let max_x = config.max_duration_hours();
     let plot_regions = split_with_columns(&plot_area, n_binaries, n_cols as usize);
     for (df, region) in by_binary.iter().zip(plot_regions) {
         let name = df["binary"].str_value(0)?;
         let max_y = df["blocks_max"].u32()?.max().unwrap();
 
-        // Split the region into a chart area and a subtitle area. (We don't use `chart.caption(..)`
-        // to add subtitles to each plot because we want to center the labels excluding the axis
-        // ticks).
-        let (subtitle, plot) = region.split_vertically(18);
-
-        let left_axis_padding = 35;
-        draw_subtitle(&name_of_binary(&name), &subtitle, left_axis_padding, 16)?;
-
-        let mut subchart = ChartBuilder::on(&plot);
-        let mut ctx = subchart
-            .margin(4)
-            .set_label_area_size(LabelAreaPosition::Bottom, 15)
-            .set_label_area_size(LabelAreaPosition::Left, left_axis_padding)
-            .build_cartesian_2d(0_f32..24_f32, 0_f32..max_y as f32)?;
-        ctx.configure_mesh()
-            .max_light_lines(0)
-            .x_label_formatter(&|value| format!("{}", *value as u64))
-            .x_labels(6)
-            .x_label_style(TextStyle::from(("Arial", 14).into_font()))
-            .y_label_formatter(&|value| format!("{}", *value as u64))
-            .y_labels(8)
-            .y_label_style(TextStyle::from(("Arial", 14).into_font()))
-            .draw()
-            .unwrap();
-
-        for df in df.partition_by_stable(["fuzzer"], true).context("partition_by(fuzzer)")? {
-            let fuzzer = df["fuzzer"].str_value(0)?;
-            let entry = legend.get_or_insert(fuzzer.as_ref());
-            draw_coverage_subplot(&mut ctx, &df, &entry.color, entry.marker)?;
+        let bug_exploit = config.has_bug_exploit(&name);
+        let mut title = name_of_binary(&name).to_owned();
+        if bug_exploit && matches!(config.bug_exploit_marker, BugExploitMarker::Asterisk) {
+            title.push('*');
         }
+        let plot = draw_binary_coverage(&region, &title, df, max_x, max_y, None, &mut legend)?;
 
-        // Fade plots of binaries that have bug exploits.
-        if config.has_bug_exploit(&name) {
-            region.fill(&RGBColor(230, 230, 230).mix(0.4))?;
+        // Mark plots of binaries that have bug exploits.
+        if bug_exploit {
+            match config.bug_exploit_marker {
+                BugExploitMarker::Shade => region.fill(&RGBColor(230, 230, 230).mix(0.4))?,
+                BugExploitMarker::Hatch => draw_hatching(&plot, &RGBColor(200, 200, 200), 8)?,
+                BugExploitMarker::Asterisk => {}
+            }
         }
     }
 
     let axis_label_style = TextStyle::from(("Arial", 20).into_font());
-    draw_y_axis_label(y_axis_area, "#Blocks Hit", &axis_label_style)?;
+    let y_label = format!("#{} Hit", config.coverage_granularity.unit());
+    draw_y_axis_label(y_axis_area, &y_label, &axis_label_style)?;
     draw_x_axis_label(x_axis_area, "Duration (hours)", &axis_label_style)?;
 
     legend.draw(&legend_area.margin(5, 0, 0, 0))?;
@@ -94,11 +76,406 @@ where
     Ok(())
 }
 
+/// Debug variant of [coverage_over_time] that plots the coverage of each binary computed without
+/// (left) and with (right) the block map validity filter next to each other. Both plots use the
+/// same scale so that blocks discarded by the filter show up as a drop in coverage.
+pub fn filtered_coverage_over_time<DB>(
+    root: &DrawingArea<DB, Shift>,
+    config: &Config,
+    data: &DataFrame,
+    n_cols: u32,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    let by_binary = data.partition_by_stable(["binary"], true).context("partition_by(binary)")?;
+    let n_binaries = by_binary.len();
+
+    let (legend_area, y_axis_area, x_axis_area, plot_area) = {
+        let (plot_area, legend_area) = root.split_vertically(root.dim_in_pixel().1 - 45);
+        let (y_axis_area, plot_area) = plot_area.split_horizontally(20);
+        let (plot_area, x_axis_area) = plot_area.split_vertically(plot_area.dim_in_pixel().1 - 25);
+        (legend_area, y_axis_area, x_axis_area, plot_area)
+    };
+
+    let legend_label_style = TextStyle::from(("Arial", 18).into_font())
+        .with_anchor::<RGBAColor>(Pos::new(HPos::Left, VPos::Bottom))
+        .into_text_style(&legend_area);
+    let mut legend = Legend::from_config(legend_label_style, config);
+
+    let max_x = config.max_duration_hours();
+    // Each binary uses two adjacent cells, so pairs are never split across rows.
+    let mut plot_regions =
+        split_with_columns(&plot_area, 2 * n_binaries, 2 * n_cols as usize).into_iter();
+    for df in &by_binary {
+        let name = df["binary"].str_value(0)?;
+        let max_y = df["blocks_max"].u32()?.max().unwrap();
+
+        for (filtered, label) in [(false, "unfiltered"), (true, "filtered")] {
+            let Some(region) = plot_regions.next() else {
+                break;
+            };
+            let title = format!("{} ({label})", name_of_binary(&name));
+            let df = df.clone().lazy().filter(col("filtered").eq(lit(filtered))).collect()?;
+            draw_binary_coverage(&region, &title, &df, max_x, max_y, None, &mut legend)?;
+        }
+    }
+
+    let axis_label_style = TextStyle::from(("Arial", 20).into_font());
+    let y_label = format!("#{} Hit", config.coverage_granularity.unit());
+    draw_y_axis_label(y_axis_area, &y_label, &axis_label_style)?;
+    draw_x_axis_label(x_axis_area, "Duration (hours)", &axis_label_style)?;
+
+    legend.draw(&legend_area.margin(5, 0, 0, 0))?;
+
+    root.present()?;
+    Ok(())
+}
+
+/// Alternative layout of [coverage_over_time] with a separate panel for each fuzzer (in dataset
+/// order) and a row of panels for each binary. If `config.reference_band` is set, the median
+/// coverage of the reference fuzzer is drawn in the background of the panels of the other fuzzers.
+pub fn coverage_per_fuzzer<DB>(
+    root: &DrawingArea<DB, Shift>,
+    config: &Config,
+    data: &DataFrame,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    let data = data.clone().lazy().with_column(col("fuzzer").cast(DataType::String)).collect()?;
+    let by_dataset = data
+        .clone()
+        .lazy()
+        .group_by(["fuzzer"])
+        .agg([col("dataset").min()])
+        .sort(["dataset"], Default::default())
+        .collect()?;
+    let fuzzers: Vec<String> =
+        by_dataset["fuzzer"].str()?.into_iter().flatten().map(str::to_owned).collect();
+    let by_binary = data.partition_by_stable(["binary"], true).context("partition_by(binary)")?;
+
+    let (legend_area, y_axis_area, x_axis_area, plot_area) = {
+        let (plot_area, legend_area) = root.split_vertically(root.dim_in_pixel().1 - 45);
+        let (y_axis_area, plot_area) = plot_area.split_horizontally(20);
+        let (plot_area, x_axis_area) = plot_area.split_vertically(plot_area.dim_in_pixel().1 - 25);
+        (legend_area, y_axis_area, x_axis_area, plot_area)
+    };
+
+    let legend_label_style = TextStyle::from(("Arial", 18).into_font())
+        .with_anchor::<RGBAColor>(Pos::new(HPos::Left, VPos::Bottom))
+        .into_text_style(&legend_area);
+    let mut legend = Legend::from_config(legend_label_style, config);
+
+    let max_x = config.max_duration_hours();
+    let n_cells = by_binary.len() * fuzzers.len();
+    let mut plot_regions = split_with_columns(&plot_area, n_cells, fuzzers.len()).into_iter();
+    for df in &by_binary {
+        let name = df["binary"].str_value(0)?;
+        // All panels of a binary use the same scale so that they can be compared.
+        let max_y = df["blocks_max"].u32()?.max().unwrap();
+        let of_fuzzer = |fuzzer: &str| df.clone().lazy().filter(col("fuzzer").eq(lit(fuzzer)));
+        let reference = match config.reference_band {
+            true => Some(of_fuzzer(&config.reference).collect()?).filter(|x| x.height() != 0),
+            false => None,
+        };
+
+        for fuzzer in &fuzzers {
+            let Some(region) = plot_regions.next() else {
+                break;
+            };
+            let df = of_fuzzer(fuzzer).collect()?;
+            let label = match df.height() {
+                0 => fuzzer.clone(),
+                _ => df["style_label"].str_value(0)?.into_owned(),
+            };
+            let title = format!("{} ({label})", name_of_binary(&name));
+            let reference = reference.as_ref().filter(|_| *fuzzer != config.reference);
+            draw_binary_coverage(&region, &title, &df, max_x, max_y, reference, &mut legend)?;
+        }
+    }
+
+    let axis_label_style = TextStyle::from(("Arial", 20).into_font());
+    let y_label = format!("#{} Hit", config.coverage_granularity.unit());
+    draw_y_axis_label(y_axis_area, &y_label, &axis_label_style)?;
+    draw_x_axis_label(x_axis_area, "Duration (hours)", &axis_label_style)?;
+
+    legend.draw(&legend_area.margin(5, 0, 0, 0))?;
+
+    root.present()?;
+    Ok(())
+}
+
+/// Draws the coverage of each fuzzer for a single binary in `region`, returning the area
+/// containing the chart (excluding the subtitle). The median coverage of `reference` (if any) is
+/// drawn as a light band behind the other fuzzers.
+fn draw_binary_coverage<DB>(
+    region: &DrawingArea<DB, Shift>,
+    title: &str,
+    df: &DataFrame,
+    max_x: f32,
+    max_y: u32,
+    reference: Option<&DataFrame>,
+    legend: &mut Legend,
+) -> anyhow::Result<DrawingArea<DB, Shift>>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    // Split the region into a chart area and a subtitle area. (We don't use `chart.caption(..)`
+    // to add subtitles to each plot because we want to center the labels excluding the axis
+    // ticks).
+    let (subtitle, plot) = region.split_vertically(18);
+
+    let left_axis_padding = 35;
+    draw_subtitle(title, &subtitle, left_axis_padding, 16)?;
+
+    let mut subchart = ChartBuilder::on(&plot);
+    let mut ctx = subchart
+        .margin(4)
+        .set_label_area_size(LabelAreaPosition::Bottom, 15)
+        .set_label_area_size(LabelAreaPosition::Left, left_axis_padding)
+        .build_cartesian_2d(0_f32..max_x, 0_f32..max_y as f32)?;
+    ctx.configure_mesh()
+        .max_light_lines(0)
+        .x_label_formatter(&|value| format!("{}", *value as u64))
+        .x_labels(6)
+        .x_label_style(TextStyle::from(("Arial", 14).into_font()))
+        .y_label_formatter(&|value| format!("{}", *value as u64))
+        .y_labels(8)
+        .y_label_style(TextStyle::from(("Arial", 14).into_font()))
+        .draw()
+        .unwrap();
+
+    if let Some(reference) = reference {
+        let entry = legend.get_or_insert_style(&SeriesStyle::from_df(reference)?);
+        let hours = reference["hours"].f64()?.into_no_null_iter().map(|x| x as f32);
+        let median = reference["blocks_median"].f64()?.into_no_null_iter().map(|x| x as f32);
+        ctx.draw_series([PathElement::new(
+            StepIter::new(hours.zip(median)).collect::<Vec<_>>(),
+            entry.color.mix(0.25).stroke_width(8),
+        )])?;
+    }
+
+    for df in df.partition_by_stable(["fuzzer"], true).context("partition_by(fuzzer)")? {
+        let entry = legend.get_or_insert_style(&SeriesStyle::from_df(&df)?);
+        draw_coverage_subplot(&mut ctx, &df, &entry.color, entry.marker, entry.line)?;
+    }
+
+    // The chart borrows the plot area, so it must be dropped before returning the area.
+    drop(ctx);
+    Ok(plot)
+}
+
+/// Plots the final coverage of each fuzzer for each binary as a dot (median) with whiskers (min and
+/// max), see [plot_data::analysis::coverage_endpoints].
+pub fn coverage_endpoints<DB>(
+    root: &DrawingArea<DB, Shift>,
+    config: &Config,
+    data: &DataFrame,
+    n_cols: u32,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    let (legend_area, y_axis_area, plot_area) = {
+        let (plot_area, legend_area) = root.split_vertically(root.dim_in_pixel().1 - 45);
+        let (y_axis_area, plot_area) = plot_area.split_horizontally(20);
+        (legend_area, y_axis_area, plot_area)
+    };
+
+    let legend_label_style = TextStyle::from(("Arial", 18).into_font())
+        .with_anchor::<RGBAColor>(Pos::new(HPos::Left, VPos::Bottom))
+        .into_text_style(&legend_area);
+    let mut legend = Legend::from_config(legend_label_style, config);
+
+    // Use the same position for each fuzzer in every binary (in dataset order).
+    let by_dataset = data
+        .clone()
+        .lazy()
+        .group_by(["fuzzer"])
+        .agg([col("dataset").min()])
+        .sort(["dataset"], Default::default())
+        .collect()?;
+    let fuzzers: Vec<String> =
+        by_dataset["fuzzer"].str()?.into_iter().flatten().map(str::to_owned).collect();
+
+    let by_binary = data.partition_by_stable(["binary"], true).context("partition_by(binary)")?;
+    let plot_regions = split_with_columns(&plot_area, by_binary.len(), n_cols as usize);
+    for (df, region) in by_binary.iter().zip(plot_regions) {
+        let name = df["binary"].str_value(0)?;
+        let mut title = name_of_binary(&name).to_owned();
+        if config.has_bug_exploit(&name)
+            && matches!(config.bug_exploit_marker, BugExploitMarker::Asterisk)
+        {
+            title.push('*');
+        }
+        draw_binary_endpoints(&region, &title, df, &fuzzers, &mut legend)?;
+    }
+
+    let axis_label_style = TextStyle::from(("Arial", 20).into_font());
+    let y_label = format!("#{} Hit (final)", config.coverage_granularity.unit());
+    draw_y_axis_label(y_axis_area, &y_label, &axis_label_style)?;
+
+    legend.draw(&legend_area.margin(5, 0, 0, 0))?;
+
+    root.present()?;
+    Ok(())
+}
+
+fn draw_binary_endpoints<DB>(
+    region: &DrawingArea<DB, Shift>,
+    title: &str,
+    df: &DataFrame,
+    fuzzers: &[String],
+    legend: &mut Legend,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let (subtitle, plot) = region.split_vertically(18);
+
+    let left_axis_padding = 35;
+    draw_subtitle(title, &subtitle, left_axis_padding, 16)?;
+
+    // Leave some space around the whiskers so that differences between fuzzers are visible.
+    let min_y = df["blocks_min"].u32()?.min().unwrap_or(0) as f32;
+    let max_y = df["blocks_max"].u32()?.max().unwrap_or(0) as f32;
+    let padding = f32::max((max_y - min_y) * 0.1, 1.0);
+    let y_range = f32::max(min_y - padding, 0.0)..max_y + padding;
+
+    let mut subchart = ChartBuilder::on(&plot);
+    let mut ctx = subchart
+        .margin(4)
+        .set_label_area_size(LabelAreaPosition::Bottom, 5)
+        .set_label_area_size(LabelAreaPosition::Left, left_axis_padding)
+        .build_cartesian_2d(0_f32..fuzzers.len() as f32, y_range)?;
+    ctx.configure_mesh()
+        .max_light_lines(0)
+        .disable_x_mesh()
+        .x_labels(0)
+        .y_label_formatter(&|value| format!("{}", *value as u64))
+        .y_labels(6)
+        .y_label_style(TextStyle::from(("Arial", 14).into_font()))
+        .draw()
+        .unwrap();
+
+    for df in df.partition_by_stable(["fuzzer"], true).context("partition_by(fuzzer)")? {
+        let fuzzer = df["fuzzer"].str_value(0)?;
+        let Some(index) = fuzzers.iter().position(|x| *x == fuzzer) else {
+            continue;
+        };
+        let entry = legend.get_or_insert_style(&SeriesStyle::from_df(&df)?);
+
+        let x = index as f32 + 0.5;
+        let median = df["blocks_median"].f64()?.get(0).unwrap_or(0.0) as f32;
+        let min = df["blocks_min"].u32()?.get(0).unwrap_or(0) as f32;
+        let max = df["blocks_max"].u32()?.get(0).unwrap_or(0) as f32;
+
+        // Whisker between the min and max with caps at each end.
+        let cap = 0.15;
+        let style = entry.color.stroke_width(2);
+        ctx.draw_series([
+            PathElement::new([(x, min), (x, max)], style),
+            PathElement::new([(x - cap, min), (x + cap, min)], style),
+            PathElement::new([(x - cap, max), (x + cap, max)], style),
+        ])?;
+        entry.marker.draw_markers(&mut ctx, std::iter::once((x, median)), &entry.color)?;
+    }
+
+    Ok(())
+}
+
+/// Plots the aggregate (normalized) coverage of each fuzzer across all binaries, see
+/// [plot_data::analysis::normalized_coverage].
+pub fn aggregate_coverage_over_time<DB>(
+    root: &DrawingArea<DB, Shift>,
+    config: &Config,
+    data: &DataFrame,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    let (plot_area, legend_area) = root.split_vertically(root.dim_in_pixel().1 - 45);
+
+    let legend_label_style = TextStyle::from(("Arial", 18).into_font())
+        .with_anchor::<RGBAColor>(Pos::new(HPos::Left, VPos::Bottom))
+        .into_text_style(&legend_area);
+    let mut legend = Legend::from_config(legend_label_style, config);
+
+    let mut chart = ChartBuilder::on(&plot_area);
+    let mut ctx = chart
+        .margin(8)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40)
+        .set_label_area_size(LabelAreaPosition::Left, 55)
+        .build_cartesian_2d(0_f32..config.max_duration_hours(), 0_f32..1_f32)?;
+    ctx.configure_mesh()
+        .max_light_lines(0)
+        .x_desc("Duration (hours)")
+        .x_label_formatter(&|value| format!("{}", *value as u64))
+        .x_labels(6)
+        .y_desc("Fraction of best-known blocks")
+        .y_label_formatter(&|value| format!("{value:.1}"))
+        .y_labels(6)
+        .label_style(TextStyle::from(("Arial", 14).into_font()))
+        .axis_desc_style(TextStyle::from(("Arial", 16).into_font()))
+        .draw()
+        .unwrap();
+
+    for df in data.partition_by_stable(["dataset"], true).context("partition_by(dataset)")? {
+        if df.height() == 0 {
+            continue;
+        }
+        let entry = legend.get_or_insert_style(&SeriesStyle::from_df(&df)?);
+
+        let hours = df["hours"].f64()?;
+        let mean = df["frac_mean"].f64()?;
+        let lower = df["frac_lower"].f64()?;
+        let upper = df["frac_upper"].f64()?;
+
+        let hours = || hours.into_no_null_iter().map(|x| x as f32);
+        let mean = || mean.into_no_null_iter().map(|x| x as f32);
+        let lower = || lower.into_no_null_iter().map(|x| x as f32);
+        let upper = || upper.into_no_null_iter().map(|x| x as f32);
+
+        // Draw a polygon covering the confidence interval.
+        ctx.draw_series([Polygon::new(
+            polygon_between(hours().zip(upper()), hours().zip(lower())),
+            entry.color.mix(0.2).filled(),
+        )])?;
+
+        let data = || hours().zip(mean());
+        entry.line.draw_line(&mut ctx, StepIter::new(data()), &entry.color)?;
+
+        let step_size = ((df.height() * 2) / 24).max(1);
+        entry.marker.draw_markers(&mut ctx, data().step_by(step_size), &entry.color)?;
+    }
+
+    legend.draw(&legend_area.margin(5, 0, 0, 0))?;
+
+    root.present()?;
+    Ok(())
+}
+
 pub fn draw_coverage_subplot<DB>(
     ctx: &mut ChartContext<DB, Cartesian2d<RangedCoordf32, RangedCoordf32>>,
     df: &DataFrame,
     color: &PaletteColor<CustomPalette>,
     marker: Marker,
+    line: LineStyle,
 ) -> anyhow::Result<()>
 where
     DB: DrawingBackend,
@@ -130,7 +507,7 @@ where
 
     // Draw line showing median coverage.
     let data = || hours().zip(blocks_med());
-    ctx.draw_series(LineSeries::new(StepIter::new(data()), &color))?;
+    line.draw_line(ctx, StepIter::new(data()), color)?;
 
     // Draw markers along the median every 2 hours.
     let step_size = ((data_len * 2) / 24).max(1);
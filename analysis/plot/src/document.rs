@@ -0,0 +1,127 @@
+//! Combines generated figures into a single multi-page PDF (see `figure.document` in the config).
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+
+/// Margin around the content of each page (in points).
+const MARGIN: f32 = 36.0;
+const TITLE_SIZE: f32 = 12.0;
+const FOOTER_SIZE: f32 = 7.0;
+/// Space between the title and the figure (in points).
+const GAP: f32 = 12.0;
+/// The number of points in a pixel of an SVG figure.
+const POINTS_PER_PIXEL: f32 = 72.0 / crate::export::SVG_DPI;
+
+pub struct Page {
+    pub path: PathBuf,
+    pub title: String,
+    pub footer: String,
+}
+
+/// Writes a PDF to `output` with a page for each of `pages`, sized to fit the figure.
+pub fn write_pdf(output: &Path, pages: &[Page]) -> anyhow::Result<()> {
+    let mut alloc = Ref::new(1);
+    let catalog_id = alloc.bump();
+    let page_tree_id = alloc.bump();
+    let font_id = alloc.bump();
+    let page_ids: Vec<_> = pages.iter().map(|_| alloc.bump()).collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids(page_ids.iter().copied()).count(pages.len() as i32);
+    pdf.type1_font(font_id).base_font(Name(b"Helvetica"));
+
+    let font_name = Name(b"F1");
+    let figure_name = Name(b"Fig");
+    for (page, &page_id) in pages.iter().zip(&page_ids) {
+        let tree = crate::export::load_svg(&page.path)?;
+        let (chunk, figure_id) = svg2pdf::to_chunk(&tree, svg2pdf::ConversionOptions::default());
+        // Object IDs in the chunk start from 1, so remap them to avoid conflicts with other pages.
+        let mut ids = HashMap::new();
+        let chunk = chunk.renumber(|old| *ids.entry(old).or_insert_with(|| alloc.bump()));
+        let figure_id = ids[&figure_id];
+
+        let width = tree.size().width() * POINTS_PER_PIXEL;
+        let height = tree.size().height() * POINTS_PER_PIXEL;
+        let title = wrap(&page.title, width, TITLE_SIZE);
+        let title_height = title.len() as f32 * TITLE_SIZE * 1.2;
+        let page_width = width + 2.0 * MARGIN;
+        let page_height = height + title_height + GAP + 2.0 * MARGIN;
+
+        let mut content = Content::new();
+        content.begin_text().set_font(font_name, TITLE_SIZE);
+        content.next_line(MARGIN, page_height - MARGIN - TITLE_SIZE);
+        for (i, line) in title.iter().enumerate() {
+            if i != 0 {
+                content.next_line(0.0, -TITLE_SIZE * 1.2);
+            }
+            content.show(Str(line.as_bytes()));
+        }
+        content.end_text();
+
+        // Figures are converted to a 1x1 XObject, so are scaled to their size on the page.
+        content
+            .save_state()
+            .transform([width, 0.0, 0.0, height, MARGIN, MARGIN])
+            .x_object(figure_name)
+            .restore_state();
+
+        content.set_fill_gray(0.4);
+        content
+            .begin_text()
+            .set_font(font_name, FOOTER_SIZE)
+            .next_line(MARGIN, (MARGIN - FOOTER_SIZE) / 2.0)
+            .show(Str(to_latin(&page.footer).as_bytes()))
+            .end_text();
+
+        let content_id = alloc.bump();
+        pdf.stream(content_id, &content.finish());
+
+        let mut page_writer = pdf.page(page_id);
+        page_writer
+            .media_box(Rect::new(0.0, 0.0, page_width, page_height))
+            .parent(page_tree_id)
+            .contents(content_id);
+        let mut resources = page_writer.resources();
+        resources.x_objects().pair(figure_name, figure_id);
+        resources.fonts().pair(font_name, font_id);
+        resources.finish();
+        page_writer.finish();
+
+        pdf.extend(&chunk);
+    }
+
+    std::fs::write(output, pdf.finish())?;
+    Ok(())
+}
+
+/// Splits `text` into lines that approximately fit in `width` points at `font_size`.
+fn wrap(text: &str, width: f32, font_size: f32) -> Vec<String> {
+    // Assume the average width of a character in Helvetica is about half of the font size.
+    let max_chars = ((width / (font_size * 0.5)) as usize).max(20);
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in to_latin(text).split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > max_chars {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Replaces characters that cannot be displayed with the standard PDF fonts without embedding a
+/// font.
+fn to_latin(text: &str) -> String {
+    text.chars().map(|c| if c.is_ascii_graphic() || c == ' ' { c } else { '?' }).collect()
+}
@@ -0,0 +1,54 @@
+//! Exports generated SVG figures to other formats (see `figure.export` in the config).
+
+use std::path::Path;
+
+use anyhow::Context;
+use plot_data::config::FigureFormat;
+use resvg::{tiny_skia, usvg};
+
+/// The resolution that SVG figures are laid out at.
+pub const SVG_DPI: f32 = 96.0;
+
+/// Parses the SVG file at `path`, resolving text using the fonts installed on the system.
+pub fn load_svg(path: &Path) -> anyhow::Result<usvg::Tree> {
+    let svg = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read: {}", path.display()))?;
+
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    usvg::Tree::from_str(&svg, &options)
+        .with_context(|| format!("failed to parse: {}", path.display()))
+}
+
+/// Converts the SVG file at `path` to `format`, saving it next to the original file.
+pub fn export(path: &Path, format: FigureFormat, dpi: f32) -> anyhow::Result<()> {
+    let tree = load_svg(path)?;
+    let output = path.with_extension(format.extension());
+    match format {
+        FigureFormat::Png => {
+            let scale = dpi / SVG_DPI;
+            let size = tree
+                .size()
+                .to_int_size()
+                .scale_by(scale)
+                .with_context(|| format!("invalid scale for {dpi} DPI"))?;
+            let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+                .context("failed to allocate image")?;
+            let transform = tiny_skia::Transform::from_scale(scale, scale);
+            resvg::render(&tree, transform, &mut pixmap.as_mut());
+            pixmap
+                .save_png(&output)
+                .with_context(|| format!("failed to write: {}", output.display()))?;
+        }
+        FigureFormat::Pdf => {
+            let pdf = svg2pdf::to_pdf(
+                &tree,
+                svg2pdf::ConversionOptions::default(),
+                svg2pdf::PageOptions::default(),
+            );
+            std::fs::write(&output, pdf)
+                .with_context(|| format!("failed to write: {}", output.display()))?;
+        }
+    }
+    Ok(())
+}
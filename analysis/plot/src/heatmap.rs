@@ -0,0 +1,144 @@
+use anyhow::Context;
+use plot_data::{name_of_binary, Config};
+use plotters::{coord::Shift, prelude::*};
+use polars::prelude::*;
+
+use crate::utils::{draw_subtitle, draw_x_axis_label, split_with_columns};
+
+/// The color used for the time buckets with the most new blocks (empty buckets are white).
+const HOT_COLOR: RGBColor = RGBColor(8, 48, 107);
+
+/// Plots the number of new blocks discovered in each time bucket as a heatmap for each binary, with
+/// one row for each fuzzer (see [plot_data::analysis::discovery_histogram]).
+pub fn discovery_heatmap<DB>(
+    root: &DrawingArea<DB, Shift>,
+    config: &Config,
+    data: &DataFrame,
+    n_cols: u32,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    let (x_axis_area, plot_area) = {
+        let (plot_area, x_axis_area) = root.split_vertically(root.dim_in_pixel().1 - 25);
+        (x_axis_area, plot_area)
+    };
+
+    // Use the same row for each fuzzer in every binary (in legend order, then config order).
+    let present = data["fuzzer"].unique()?;
+    let present = present.str()?;
+    let mut fuzzers: Vec<(&str, String)> = config
+        .data
+        .iter()
+        .filter(|(name, _)| present.into_iter().flatten().any(|x| x == name.as_str()))
+        .map(|(name, datasets)| {
+            let label = datasets.iter().find_map(|x| x.style.label.clone());
+            (name.as_str(), label.unwrap_or_else(|| name.clone()))
+        })
+        .collect();
+    let ranks = config.legend_ranks();
+    fuzzers.sort_by_key(|(_, label)| ranks.get(label).copied().unwrap_or(usize::MAX));
+
+    let bucket_hours = config.discovery_bucket.as_secs_f32() / (60.0 * 60.0);
+    let max_hours = data["bucket"].f64()?.max().map_or(24.0, |x| x as f32 + bucket_hours);
+
+    let by_binary = data.partition_by_stable(["binary"], true).context("partition_by(binary)")?;
+    let plot_regions = split_with_columns(&plot_area, by_binary.len(), n_cols as usize);
+    for (df, region) in by_binary.iter().zip(plot_regions) {
+        let name = df["binary"].str_value(0)?;
+        let title = name_of_binary(&name);
+        draw_binary_heatmap(&region, &title, df, &fuzzers, bucket_hours, max_hours)?;
+    }
+
+    let axis_label_style = TextStyle::from(("Arial", 20).into_font());
+    draw_x_axis_label(x_axis_area, "Duration (hours)", &axis_label_style)?;
+
+    root.present()?;
+    Ok(())
+}
+
+fn draw_binary_heatmap<DB>(
+    region: &DrawingArea<DB, Shift>,
+    title: &str,
+    df: &DataFrame,
+    fuzzers: &[(&str, String)],
+    bucket_hours: f32,
+    max_hours: f32,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let (subtitle, plot) = region.split_vertically(18);
+
+    let left_axis_padding = 80;
+    draw_subtitle(title, &subtitle, left_axis_padding, 16)?;
+
+    let n_rows = fuzzers.len() as i32;
+    let mut subchart = ChartBuilder::on(&plot);
+    let mut ctx = subchart
+        .margin(4)
+        .set_label_area_size(LabelAreaPosition::Bottom, 15)
+        .set_label_area_size(LabelAreaPosition::Left, left_axis_padding)
+        .build_cartesian_2d(0_f32..max_hours, (0..n_rows).into_segmented())?;
+    ctx.configure_mesh()
+        .disable_mesh()
+        .x_label_formatter(&|value| format!("{}", *value as u64))
+        .x_labels(6)
+        .x_label_style(TextStyle::from(("Arial", 14).into_font()))
+        .y_label_formatter(&|value| match value {
+            SegmentValue::CenterOf(i) => fuzzers
+                .get(*i as usize)
+                .map_or_else(String::new, |(_, label)| label.clone()),
+            _ => String::new(),
+        })
+        .y_labels(fuzzers.len())
+        .y_label_style(TextStyle::from(("Arial", 12).into_font()))
+        .draw()
+        .unwrap();
+
+    // Discovery is typically concentrated at the start of a trial, so colors use a logarithmic
+    // scale to keep later bursts visible.
+    let max_blocks = df["new_blocks_mean"].f64()?.max().unwrap_or(0.0);
+    let scale = |value: f64| {
+        if max_blocks > 0.0 {
+            (value.ln_1p() / max_blocks.ln_1p()).clamp(0.0, 1.0)
+        }
+        else {
+            0.0
+        }
+    };
+    // The end of the final row is represented by `SegmentValue::Last`.
+    let row_end = |row: i32| {
+        if row + 1 < n_rows {
+            SegmentValue::Exact(row + 1)
+        }
+        else {
+            SegmentValue::Last
+        }
+    };
+
+    let fuzzer = df["fuzzer"].str()?;
+    let bucket = df["bucket"].f64()?;
+    let new_blocks = df["new_blocks_mean"].f64()?;
+    let cells = fuzzer.into_iter().zip(bucket).zip(new_blocks).filter_map(|((f, x), value)| {
+        let row = fuzzers.iter().position(|(name, _)| Some(*name) == f)? as i32;
+        let (x, value) = (x? as f32, value?);
+        Some(Rectangle::new(
+            [(x, SegmentValue::Exact(row)), (x + bucket_hours, row_end(row))],
+            heat_color(scale(value)).filled(),
+        ))
+    });
+    ctx.draw_series(cells)?;
+
+    Ok(())
+}
+
+/// Interpolates between white (0.0) and [HOT_COLOR] (1.0).
+fn heat_color(t: f64) -> RGBColor {
+    let mix = |hot: u8| (255.0 + (hot as f64 - 255.0) * t).round() as u8;
+    RGBColor(mix(HOT_COLOR.0), mix(HOT_COLOR.1), mix(HOT_COLOR.2))
+}
@@ -39,7 +39,7 @@ where
     let legend_label_style = TextStyle::from(("Arial", 18).into_font())
         .with_anchor::<RGBAColor>(Pos::new(HPos::Left, VPos::Bottom))
         .into_text_style(&legend_area);
-    let mut legend = Legend::new_with_mapping(legend_label_style, config.legend_mapping.clone());
+    let mut legend = Legend::from_config(legend_label_style, config);
 
     let plot_regions = split_with_columns(&plot_area, n_arch, n_cols as usize);
     for (df, region) in by_arch.iter().zip(plot_regions) {
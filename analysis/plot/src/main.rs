@@ -1,10 +1,19 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::Context;
-use plotters::{backend::SVGBackend, prelude::IntoDrawingArea};
+use polars::prelude::*;
 
+mod afl_stats;
+mod block_timeline;
 mod coverage;
+mod document;
+mod export;
+mod heatmap;
+mod overlap;
+mod provenance;
+mod report;
 mod survival;
+mod throughput;
 mod utils;
 
 use mimalloc::MiMalloc;
@@ -26,50 +35,341 @@ fn run() -> anyhow::Result<()> {
         std::env::var_os("CONFIG").map_or_else(|| "config.ron".into(), |x| x.into());
 
     let config = plot_data::Config::from_path(&config)?;
+    let provenance = provenance::Provenance::new(&config)?;
     let _ = std::fs::create_dir_all("output");
 
-    let plots = std::env::args().nth(1).map(|x| {
+    // `plot report [targets]` generates the target figures (or the default report figures) and
+    // combines them into an HTML report.
+    let mut args = std::env::args().skip(1).peekable();
+    let report = args.next_if(|x| x == "report").is_some();
+    let plots = args.next().map(|x| {
         x.split(',')
             .map(|x| x.trim().to_owned())
             .collect::<Vec<_>>()
     });
+    let plots = match report {
+        true => plots.or_else(|| {
+            Some(report::DEFAULT_FIGURES.iter().map(|x| x.to_string()).collect())
+        }),
+        false => plots,
+    };
     let should_plot = |target: &str| {
         plots
             .as_ref()
             .map_or(true, |x| x.iter().any(|x| x == target))
     };
 
+    // Debug plots and per-binary plots are only generated when explicitly requested.
+    let debug_plot = |target: &str| {
+        plots
+            .as_ref()
+            .is_some_and(|x| x.iter().any(|x| x == target))
+    };
+
     if should_plot("coverage") {
         eprintln!("plotting coverage");
 
+        let mut data = plot_data::analysis::summarize_coverage(
+            plot_data::load_block_hits(&config).context("failed to load block hits")?,
+        )
+        .collect()?;
+        write_plot_data(&mut data, "output/coverage.csv")?;
+
+        let n_binaries = data["binary"].n_unique()?;
+        let path = Path::new("output/coverage.svg");
+        match config.coverage_layout {
+            plot_data::config::CoverageLayout::Combined => {
+                let (n_col, dims) = config.plot_layout.get_layout(n_binaries as u32);
+                let out = provenance.svg_area(path, dims.into(), "coverage")?;
+                coverage::coverage_over_time(&out, &config, &data, n_col)?;
+                out.present()?;
+            }
+            plot_data::config::CoverageLayout::PerFuzzer => {
+                // Each binary is shown on its own row, with a column for each fuzzer.
+                let n_fuzzers = data["fuzzer"].n_unique()? as u32;
+                let layout = plot_data::config::PlotLayout {
+                    max_columns: n_fuzzers,
+                    ..config.plot_layout.clone()
+                };
+                let (_, dims) = layout.get_layout(n_fuzzers * n_binaries as u32);
+                let out = provenance.svg_area(path, dims.into(), "coverage")?;
+                coverage::coverage_per_fuzzer(&out, &config, &data)?;
+                out.present()?;
+            }
+        }
+        provenance.finish(path, "coverage")?;
+    }
+
+    // Full size plots for each binary (e.g. for slides).
+    if debug_plot("coverage-binaries") {
+        eprintln!("plotting coverage of each binary");
+
         let data = plot_data::analysis::summarize_coverage(
             plot_data::load_block_hits(&config).context("failed to load block hits")?,
         )
         .collect()?;
 
+        let (_, dims) = config.plot_layout.get_layout(1);
+        for binary in data.partition_by_stable(["binary"], true)? {
+            let name = plot_data::name_of_binary(&binary["binary"].str_value(0)?);
+            let name: String =
+                name.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect();
+            let path = PathBuf::from(format!("output/coverage-{name}.svg"));
+            let out = provenance.svg_area(&path, dims.clone().into(), "coverage")?;
+            coverage::coverage_over_time(&out, &config, &binary, 1)?;
+            out.present()?;
+            provenance.finish(&path, "coverage")?;
+        }
+    }
+
+    if should_plot("endpoints") {
+        eprintln!("plotting coverage endpoints");
+
+        let mut data = plot_data::analysis::coverage_endpoints(
+            plot_data::load_block_hits(&config).context("failed to load block hits")?,
+        )
+        .collect()?;
+        write_plot_data(&mut data, "output/endpoints.csv")?;
+
         let n_binaries = data["binary"].n_unique()?;
         let (n_col, dims) = config.plot_layout.get_layout(n_binaries as u32);
-        let out =
-            SVGBackend::new(Path::new("output/coverage.svg"), dims.into()).into_drawing_area();
-        coverage::coverage_over_time(&out, &config, &data, n_col)?;
+        let path = Path::new("output/endpoints.svg");
+        let out = provenance.svg_area(path, dims.into(), "endpoints")?;
+        coverage::coverage_endpoints(&out, &config, &data, n_col)?;
+        out.present()?;
+        provenance.finish(path, "endpoints")?;
+    }
+
+    if debug_plot("coverage-filter") && config.coverage_metadata.is_some() {
+        eprintln!("plotting filtered vs. unfiltered coverage");
+
+        let summarize = |block_hits, filtered: bool| {
+            plot_data::analysis::summarize_coverage(block_hits)
+                .with_column(lit(filtered).alias("filtered"))
+        };
+        let filtered = plot_data::load_block_hits(&config).context("failed to load block hits")?;
+        let unfiltered = plot_data::load_unfiltered_block_hits(&config)
+            .context("failed to load unfiltered block hits")?;
+        let mut data = concat(
+            [summarize(unfiltered, false), summarize(filtered, true)],
+            UnionArgs::default(),
+        )?
+        .collect()?;
+        write_plot_data(&mut data, "output/filtered_coverage.csv")?;
+
+        let n_binaries = data["binary"].n_unique()?;
+        let (n_col, dims) = config.plot_layout.get_layout(n_binaries as u32);
+        let (width, height) = dims.into();
+        let path = Path::new("output/filtered_coverage.svg");
+        let out = provenance.svg_area(path, (2 * width, height), "coverage-filter")?;
+        coverage::filtered_coverage_over_time(&out, &config, &data, n_col)?;
+        out.present()?;
+        provenance.finish(path, "coverage-filter")?;
+    }
+
+    if should_plot("aggregate") {
+        eprintln!("plotting aggregate coverage");
+
+        let mut data = plot_data::analysis::normalized_coverage(&config)
+            .context("failed to compute normalized coverage")?
+            .collect()?;
+        write_plot_data(&mut data, "output/aggregate.csv")?;
+
+        let path = Path::new("output/aggregate.svg");
+        let out = provenance.svg_area(path, (640, 420), "aggregate")?;
+        coverage::aggregate_coverage_over_time(&out, &config, &data)?;
+        out.present()?;
+        provenance.finish(path, "aggregate")?;
     }
 
     if should_plot("survival") && !config.survival.is_empty() {
         eprintln!("plotting survival");
 
         let coverage = plot_data::load_raw_coverage(&config)?.cache();
-        let block_survival =
-            plot_data::analysis::block_survival(coverage.clone(), &config.survival)?;
-        let block_hits = plot_data::analysis::raw_blocks_hit(coverage);
+        let mut block_survival =
+            plot_data::analysis::block_survival(coverage.clone(), &config.survival)?.collect()?;
+        write_plot_data(&mut block_survival, "output/survival.csv")?;
+
+        // Only the coverage of binaries with a survival region is plotted.
+        let in_survival_region = config
+            .survival
+            .values()
+            .fold(lit(false), |acc, x| acc.or(col("binary").eq(lit(x.binary.as_str()))));
+        let mut block_hits = plot_data::analysis::raw_blocks_hit(coverage)
+            .filter(in_survival_region)
+            .collect()?;
+        write_plot_data(&mut block_hits, "output/survival_coverage.csv")?;
 
         let (n_col, dims) = config
             .survival_layout
             .get_layout(config.survival.len() as u32);
-        let out =
-            SVGBackend::new(Path::new("output/survival.svg"), dims.into()).into_drawing_area();
+        let path = Path::new("output/survival.svg");
+        let out = provenance.svg_area(path, dims.into(), "survival")?;
+
+        let block_hits = block_hits.lazy();
+        survival::plot_survival(
+            &out,
+            &config,
+            n_col as usize,
+            block_hits.clone(),
+            block_survival.clone().lazy(),
+        )?;
+        out.present()?;
+        provenance.finish(path, "survival")?;
+
+        if should_plot("survival-regions") {
+            let (_, dims) = config.survival_layout.get_layout(1);
+            let regions = block_survival.partition_by_stable(["label"], true)?;
+            for (i, region) in regions.iter().enumerate() {
+                // Different labels may be sanitized to the same name, so the index of the region
+                // is included to keep the paths unique.
+                let label = region["label"].str_value(0)?;
+                let name: String =
+                    label.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect();
+                let path = PathBuf::from(format!("output/survival-{name}-{i}.svg"));
+                let out = provenance.svg_area(&path, dims.clone().into(), "survival")?;
+                survival::plot_survival_region(&out, &config, block_hits.clone(), region)?;
+                out.present()?;
+                provenance.finish(&path, "survival")?;
+            }
+        }
+    }
+
+    if should_plot("discovery") {
+        eprintln!("plotting discovery heatmap");
+
+        let coverage = plot_data::load_raw_coverage(&config)?;
+        let bucket_hours = config.discovery_bucket.as_secs_f64() / (60.0 * 60.0);
+        let mut data = plot_data::analysis::discovery_histogram(coverage, bucket_hours)?
+            .with_columns([
+                col("fuzzer").cast(DataType::String),
+                col("binary").cast(DataType::String),
+            ])
+            .collect()?;
+        write_plot_data(&mut data, "output/discovery.csv")?;
+
+        let n_binaries = data["binary"].n_unique()?;
+        let (n_col, dims) = config.heatmap_layout.get_layout(n_binaries as u32);
+        let path = Path::new("output/discovery.svg");
+        let out = provenance.svg_area(path, dims.into(), "discovery")?;
+        heatmap::discovery_heatmap(&out, &config, &data, n_col)?;
+        out.present()?;
+        provenance.finish(path, "discovery")?;
+    }
+
+    let overlap_fuzzers = plot_data::analysis::overlap_fuzzers(&config);
+    if let Some(fuzzers) = overlap_fuzzers.filter(|_| should_plot("overlap")) {
+        eprintln!("plotting block overlap");
+
+        let mut data = plot_data::analysis::block_overlap(&config, &fuzzers)?
+            .with_column(col("binary").cast(DataType::String))
+            .collect()?;
+        write_plot_data(&mut data, "output/overlap.csv")?;
+
+        let n_binaries = data["binary"].n_unique()?;
+        let (n_col, dims) = config.overlap_layout.get_layout(n_binaries as u32);
+        let path = Path::new("output/overlap.svg");
+        let out = provenance.svg_area(path, dims.into(), "overlap")?;
+        overlap::upset_plot(&out, &config, &data, &fuzzers, n_col)?;
+        out.present()?;
+        provenance.finish(path, "overlap")?;
+    }
+
+    if should_plot("block-timeline") {
+        eprintln!("plotting block timeline");
+
+        let mut data = plot_data::analysis::block_timeline(&config)?
+            .with_columns([
+                col("fuzzer").cast(DataType::String),
+                col("binary").cast(DataType::String),
+            ])
+            .collect()?;
+        write_plot_data(&mut data, "output/block_timeline.csv")?;
+
+        // One cell is plotted for each fuzzer on each binary.
+        let n_cells = data
+            .clone()
+            .lazy()
+            .select([col("binary"), col("fuzzer")])
+            .unique(None, UniqueKeepStrategy::Any)
+            .collect()?
+            .height();
+        let (n_col, dims) = config.block_timeline_layout.get_layout(n_cells as u32);
+        let path = Path::new("output/block_timeline.svg");
+        let out = provenance.svg_area(path, dims.into(), "block-timeline")?;
+        block_timeline::block_timeline(&out, &config, &data, n_col)?;
+        out.present()?;
+        provenance.finish(path, "block-timeline")?;
+    }
+
+    if should_plot("throughput") && (!config.throughput.is_empty() || config.has_afl_plot_data()) {
+        eprintln!("plotting throughput");
+
+        let mut data = plot_data::analysis::summarize_throughput(
+            plot_data::analysis::execs_over_time(&config).context("failed to load throughput")?,
+        )
+        .with_columns([col("fuzzer").cast(DataType::String), col("binary").cast(DataType::String)])
+        .collect()?;
+        write_plot_data(&mut data, "output/throughput.csv")?;
 
-        survival::plot_survival(&out, &config, n_col as usize, block_hits, block_survival)?
+        let n_binaries = data["binary"].n_unique()?;
+        let (n_col, dims) = config.plot_layout.get_layout(n_binaries as u32);
+        let path = Path::new("output/throughput.svg");
+        let out = provenance.svg_area(path, dims.into(), "throughput")?;
+        throughput::throughput_over_time(&out, &config, &data, n_col)?;
+        out.present()?;
+        provenance.finish(path, "throughput")?;
+    }
+
+    if should_plot("afl-stats") && config.has_afl_plot_data() {
+        eprintln!("plotting AFL stats");
+
+        let strings =
+            [col("fuzzer").cast(DataType::String), col("binary").cast(DataType::String)];
+        let mut stats = plot_data::analysis::summarize_afl_stats(
+            plot_data::analysis::afl_stats(&config).context("failed to load AFL plot data")?,
+        )
+        .with_columns(strings)
+        .collect()?;
+        write_plot_data(&mut stats, "output/afl_stats_summary.csv")?;
+
+        // The edges found, execs/sec and corpus size are plotted next to each other for each
+        // binary.
+        let n_binaries = stats["binary"].n_unique()? as u32;
+        let layout = plot_data::config::PlotLayout { max_columns: 3, ..config.plot_layout.clone() };
+        let (_, dims) = layout.get_layout(3 * n_binaries);
+        let path = Path::new("output/afl_stats.svg");
+        let out = provenance.svg_area(path, dims.into(), "afl-stats")?;
+        afl_stats::afl_stats_over_time(&out, &config, &stats)?;
+        out.present()?;
+        provenance.finish(path, "afl-stats")?;
+    }
+
+    if should_plot("legend") {
+        eprintln!("plotting legend");
+
+        let (_, dims) = config.plot_layout.get_layout(1);
+        let path = Path::new("output/legend.svg");
+        let out = provenance.svg_area(path, (dims.width, 30), "legend")?;
+        utils::plot_legend(&out, &config)?;
+        out.present()?;
+        provenance.finish(path, "legend")?;
+    }
+
+    provenance.write_document()?;
+    if report {
+        provenance.write_report(&config, Path::new("output/report.html"))?;
     }
 
     Ok(())
 }
+
+/// Saves the data used for generating a plot so that values can be traced back to the figure.
+fn write_plot_data(df: &mut DataFrame, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    CsvWriter::new(&mut std::fs::File::create(path)?)
+        .include_header(true)
+        .with_separator(b',')
+        .finish(df)
+        .with_context(|| format!("failed to write plot data to: {}", path.display()))
+}
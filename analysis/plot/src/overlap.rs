@@ -0,0 +1,153 @@
+use anyhow::Context;
+use plot_data::{name_of_binary, Config};
+use plotters::{
+    coord::Shift,
+    prelude::*,
+    style::text_anchor::{HPos, Pos, VPos},
+};
+use polars::prelude::*;
+
+use crate::utils::{draw_subtitle, split_with_columns};
+
+const BAR_COLOR: RGBColor = RGBColor(8, 48, 107);
+const INACTIVE_COLOR: RGBColor = RGBColor(220, 220, 220);
+
+/// Height of each row of the combination matrix (in pixels).
+const ROW_HEIGHT: u32 = 14;
+
+/// Plots the overlap between the blocks reached by `fuzzers` as an UpSet plot for each binary (see
+/// [plot_data::analysis::block_overlap]): bars show the number of blocks reached by exactly the
+/// combination of fuzzers marked in the matrix below them.
+pub fn upset_plot<DB>(
+    root: &DrawingArea<DB, Shift>,
+    config: &Config,
+    data: &DataFrame,
+    fuzzers: &[String],
+    n_cols: u32,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    let labels: Vec<String> = fuzzers
+        .iter()
+        .map(|name| {
+            let mut datasets = config.data.get(name).into_iter().flatten();
+            datasets.find_map(|x| x.style.label.clone()).unwrap_or_else(|| name.clone())
+        })
+        .collect();
+
+    // Combinations are ordered by the number of fuzzers they contain, matching the table.
+    let mut masks: Vec<u32> = (1..1 << fuzzers.len()).collect();
+    masks.sort_by_key(|x| (x.count_ones(), *x));
+
+    let by_binary = data.partition_by_stable(["binary"], true).context("partition_by(binary)")?;
+    let plot_regions = split_with_columns(root, by_binary.len(), n_cols as usize);
+    for (df, region) in by_binary.iter().zip(plot_regions) {
+        let name = df["binary"].str_value(0)?;
+        let title = name_of_binary(&name);
+
+        let mask = df["mask"].u32()?;
+        let blocks = df["blocks"].u32()?;
+        let counts: Vec<u32> = masks
+            .iter()
+            .map(|x| {
+                let row = mask.into_iter().position(|m| m == Some(*x));
+                row.and_then(|i| blocks.get(i)).unwrap_or(0)
+            })
+            .collect();
+        draw_binary_upset(&region, &title, &labels, &masks, &counts)?;
+    }
+
+    root.present()?;
+    Ok(())
+}
+
+fn draw_binary_upset<DB>(
+    region: &DrawingArea<DB, Shift>,
+    title: &str,
+    labels: &[String],
+    masks: &[u32],
+    counts: &[u32],
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let (subtitle, plot) = region.split_vertically(18);
+
+    let left_axis_padding = 80;
+    draw_subtitle(title, &subtitle, left_axis_padding, 16)?;
+
+    let n_rows = labels.len() as i32;
+    let n_cols = masks.len() as i32;
+    let matrix_height = ROW_HEIGHT * labels.len() as u32 + 8;
+    let (bars, matrix) =
+        plot.split_vertically(plot.dim_in_pixel().1.saturating_sub(matrix_height));
+
+    // Leave space above the tallest bar for its label.
+    let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+    let mut bar_chart = ChartBuilder::on(&bars);
+    let mut ctx = bar_chart
+        .margin(4)
+        .set_label_area_size(LabelAreaPosition::Left, left_axis_padding)
+        .build_cartesian_2d((0..n_cols).into_segmented(), 0..max_count + max_count / 5 + 1)?;
+    ctx.configure_mesh()
+        .disable_x_mesh()
+        .disable_x_axis()
+        .y_labels(4)
+        .y_label_style(TextStyle::from(("Arial", 12).into_font()))
+        .draw()
+        .unwrap();
+    ctx.draw_series(counts.iter().enumerate().map(|(i, count)| {
+        let i = i as i32;
+        Rectangle::new(
+            [(SegmentValue::Exact(i), 0), (SegmentValue::Exact(i + 1), *count)],
+            BAR_COLOR.filled(),
+        )
+    }))?;
+    let count_style =
+        TextStyle::from(("Arial", 10).into_font()).pos(Pos::new(HPos::Center, VPos::Bottom));
+    ctx.draw_series(counts.iter().enumerate().map(|(i, count)| {
+        let pos = (SegmentValue::CenterOf(i as i32), *count);
+        Text::new(count.to_string(), pos, count_style.clone())
+    }))?;
+
+    let mut matrix_chart = ChartBuilder::on(&matrix);
+    let mut ctx = matrix_chart
+        .margin(4)
+        .set_label_area_size(LabelAreaPosition::Left, left_axis_padding)
+        .build_cartesian_2d((0..n_cols).into_segmented(), (0..n_rows).into_segmented())?;
+    ctx.configure_mesh()
+        .disable_mesh()
+        .disable_x_axis()
+        .y_label_formatter(&|value| match value {
+            SegmentValue::CenterOf(i) => labels.get(*i as usize).cloned().unwrap_or_default(),
+            _ => String::new(),
+        })
+        .y_labels(labels.len())
+        .y_label_style(TextStyle::from(("Arial", 12).into_font()))
+        .draw()
+        .unwrap();
+
+    let radius = (ROW_HEIGHT / 3) as i32;
+    for (i, mask) in masks.iter().enumerate() {
+        let x = SegmentValue::CenterOf(i as i32);
+        let members: Vec<i32> = (0..n_rows).filter(|row| mask & (1 << row) != 0).collect();
+        if let (Some(first), Some(last)) = (members.first(), members.last()) {
+            let (start, end) = (SegmentValue::CenterOf(*first), SegmentValue::CenterOf(*last));
+            ctx.draw_series(std::iter::once(PathElement::new(
+                vec![(x.clone(), start), (x.clone(), end)],
+                BAR_COLOR.stroke_width(2),
+            )))?;
+        }
+        ctx.draw_series((0..n_rows).map(|row| {
+            let color = if members.contains(&row) { BAR_COLOR } else { INACTIVE_COLOR };
+            Circle::new((x.clone(), SegmentValue::CenterOf(row)), radius, color.filled())
+        }))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! Records which config and commit generated a figure, so that figures can be traced back to the
+//! data that produced them.
+
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use plotters::{
+    backend::SVGBackend,
+    coord::Shift,
+    prelude::*,
+    style::text_anchor::{HPos, Pos, VPos},
+};
+
+use plot_data::{config::FigureFormat, Config};
+
+/// The height (in pixels) of the footer added below figures when `figure.footer` is enabled.
+const FOOTER_HEIGHT: u32 = 20;
+
+pub struct Provenance {
+    config_path: String,
+    config_hash: String,
+    git_commit: Option<String>,
+    captions: std::collections::HashMap<String, String>,
+    footer: bool,
+    export: Vec<FigureFormat>,
+    dpi: f32,
+    document: Option<PathBuf>,
+    /// The figures finished so far (as `(path, name)`), included in the combined document.
+    figures: RefCell<Vec<(PathBuf, String)>>,
+}
+
+impl Provenance {
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(&config.path)
+            .with_context(|| format!("failed to read: {}", config.path.display()))?;
+        let config_dir = config.path.parent().filter(|x| !x.as_os_str().is_empty());
+        Ok(Self {
+            config_path: config.path.display().to_string(),
+            config_hash: format!("{:016x}", fnv1a(&bytes)),
+            git_commit: git_commit(config_dir.unwrap_or(Path::new("."))),
+            captions: config.figure.captions.clone(),
+            footer: config.figure.footer,
+            export: config.figure.export.clone(),
+            dpi: config.figure.dpi,
+            document: config.figure.document.clone(),
+            figures: RefCell::new(vec![]),
+        })
+    }
+
+    /// Creates an SVG drawing area of `dims` for the figure `name` at `path`, adding a footer below
+    /// the figure if enabled.
+    pub fn svg_area<'a>(
+        &self,
+        path: &'a Path,
+        dims: (u32, u32),
+        name: &str,
+    ) -> anyhow::Result<DrawingArea<SVGBackend<'a>, Shift>> {
+        if !self.footer {
+            return Ok(SVGBackend::new(path, dims).into_drawing_area());
+        }
+
+        let root = SVGBackend::new(path, (dims.0, dims.1 + FOOTER_HEIGHT)).into_drawing_area();
+        let (figure, footer) = root.split_vertically(dims.1);
+        footer.fill(&WHITE)?;
+        let style = TextStyle::from(("Arial", 11).into_font())
+            .color(&RGBColor(100, 100, 100))
+            .with_anchor::<RGBAColor>(Pos::new(HPos::Left, VPos::Center))
+            .into_text_style(&footer);
+        footer.draw_text(&self.summary(name), &style, (5, FOOTER_HEIGHT as i32 / 2))?;
+        Ok(figure)
+    }
+
+    /// Finishes the figure `name` at `path` after it has been written: embedding provenance
+    /// information and exporting it to any additional formats.
+    pub fn finish(&self, path: &Path, name: &str) -> anyhow::Result<()> {
+        self.embed(path, name)?;
+        for format in &self.export {
+            crate::export::export(path, *format, self.dpi)
+                .with_context(|| format!("failed to export {} as {format:?}", path.display()))?;
+        }
+        self.figures.borrow_mut().push((path.to_owned(), name.to_owned()));
+        Ok(())
+    }
+
+    /// Combines all finished figures into a single PDF (if `figure.document` is configured).
+    pub fn write_document(&self) -> anyhow::Result<()> {
+        let Some(output) = self.document.as_ref() else { return Ok(()) };
+        let pages: Vec<_> = self
+            .figures
+            .borrow()
+            .iter()
+            .map(|(path, name)| crate::document::Page {
+                path: path.clone(),
+                title: self.captions.get(name).cloned().unwrap_or_else(|| name.clone()),
+                footer: self.provenance(),
+            })
+            .collect();
+        if pages.is_empty() {
+            return Ok(());
+        }
+        crate::document::write_pdf(output, &pages)
+            .with_context(|| format!("failed to write: {}", output.display()))?;
+        eprintln!("{} figures combined into: {}", pages.len(), output.display());
+        Ok(())
+    }
+
+    /// Writes an HTML report to `output` containing all finished figures (see [crate::report]).
+    pub fn write_report(&self, config: &Config, output: &Path) -> anyhow::Result<()> {
+        let figures: Vec<_> = self
+            .figures
+            .borrow()
+            .iter()
+            .map(|(path, name)| crate::report::Figure {
+                path: path.clone(),
+                title: self.captions.get(name).cloned().unwrap_or_else(|| name.clone()),
+            })
+            .collect();
+        crate::report::write_html(output, config, &figures, &self.provenance())?;
+        eprintln!("report with {} figures saved to: {}", figures.len(), output.display());
+        Ok(())
+    }
+
+    /// Embeds the caption and provenance information for the figure `name` in the SVG metadata of
+    /// the file at `path` (the figure must have already been written).
+    fn embed(&self, path: &Path, name: &str) -> anyhow::Result<()> {
+        let svg = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read: {}", path.display()))?;
+        let Some(end) =
+            svg.find("<svg").and_then(|start| svg[start..].find('>').map(|x| start + x))
+        else {
+            anyhow::bail!("{} is not an SVG file", path.display());
+        };
+
+        let mut metadata = String::new();
+        if let Some(caption) = self.captions.get(name) {
+            metadata.push_str(&format!("\n<title>{}</title>", escape_xml(caption)));
+        }
+        metadata.push_str(&format!(
+            "\n<metadata>\nconfig: {}\nconfig-hash: {}\ngit-commit: {}\n</metadata>",
+            escape_xml(&self.config_path),
+            self.config_hash,
+            escape_xml(self.git_commit.as_deref().unwrap_or("unknown")),
+        ));
+
+        let mut output = svg;
+        output.insert_str(end + 1, &metadata);
+        std::fs::write(path, output).with_context(|| format!("failed to write: {}", path.display()))
+    }
+
+    fn provenance(&self) -> String {
+        let commit = self.git_commit.as_deref().unwrap_or("unknown");
+        format!("config: {} ({}), commit: {commit}", self.config_path, self.config_hash)
+    }
+
+    fn summary(&self, name: &str) -> String {
+        let provenance = self.provenance();
+        match self.captions.get(name) {
+            Some(caption) => format!("{caption} | {provenance}"),
+            None => provenance,
+        }
+    }
+}
+
+/// Gets the commit of the git repository containing `dir`, marked as `-dirty` if there are
+/// uncommitted changes.
+fn git_commit(dir: &Path) -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(["describe", "--always", "--dirty", "--abbrev=12"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// 64-bit FNV-1a hash, used instead of `DefaultHasher` so that hashes are stable across builds.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &x| (hash ^ x as u64).wrapping_mul(0x100000001b3))
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
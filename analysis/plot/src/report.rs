@@ -0,0 +1,139 @@
+//! Generates a self-contained HTML report of a benchmark (see the `report` command), embedding the
+//! generated figures along with the coverage and significance tables, so that results can be shared
+//! as a single file.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use plot_data::{order_by_binary, Config};
+use polars::prelude::*;
+
+/// The figures included in a report unless other targets are specified.
+pub const DEFAULT_FIGURES: &[&str] = &["coverage", "survival"];
+
+pub struct Figure {
+    pub path: PathBuf,
+    pub title: String,
+}
+
+const STYLE: &str = "\
+body { font-family: Arial, sans-serif; margin: 2em auto; max-width: 1400px; color: #222; }
+h1, h2 { font-weight: normal; }
+figure { margin: 1em 0 2em 0; }
+figure svg { max-width: 100%; height: auto; }
+figcaption { font-style: italic; color: #555; }
+.table { overflow-x: auto; margin-bottom: 2em; }
+table { border-collapse: collapse; font-size: 13px; }
+th, td { border: 1px solid #ccc; padding: 2px 6px; text-align: right; white-space: nowrap; }
+th { background: #f0f0f0; }
+td.text { text-align: left; }
+tr:nth-child(even) td { background: #fafafa; }
+.error { color: #a00; }
+footer { color: #777; font-size: 12px; border-top: 1px solid #ccc; padding-top: 0.5em; }
+";
+
+/// Writes the report to `output`, with the SVG figures in `figures` embedded inline.
+pub fn write_html(
+    output: &Path,
+    config: &Config,
+    figures: &[Figure],
+    provenance: &str,
+) -> anyhow::Result<()> {
+    let title = config
+        .path
+        .file_stem()
+        .map_or_else(|| "benchmark".into(), |x| x.to_string_lossy().into_owned());
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape_html(&title)));
+    html.push_str(&format!("<style>\n{STYLE}</style>\n</head>\n<body>\n"));
+    html.push_str(&format!("<h1>{}</h1>\n", escape_html(&title)));
+
+    for figure in figures {
+        let svg = std::fs::read_to_string(&figure.path)
+            .with_context(|| format!("failed to read: {}", figure.path.display()))?;
+        // Only the `<svg>` element itself can be embedded (i.e. without any XML declaration).
+        let svg = svg.find("<svg").map_or(svg.as_str(), |start| &svg[start..]);
+        html.push_str(&format!(
+            "<figure>\n{svg}\n<figcaption>{}</figcaption>\n</figure>\n",
+            escape_html(&figure.title)
+        ));
+    }
+
+    let coverage_table = || {
+        Ok(plot_data::analysis::coverage_table(config)?
+            .sort_by_exprs(
+                [col("fuzzer"), order_by_binary()],
+                SortMultipleOptions::new().with_nulls_last(true).with_maintain_order(true),
+            )
+            .collect()?)
+    };
+    push_table(&mut html, "Coverage", coverage_table());
+    push_table(&mut html, "Median coverage", plot_data::analysis::median_coverage(config));
+    push_table(
+        &mut html,
+        &format!("Significance (vs. {})", config.reference),
+        plot_data::analysis::significance_table(config),
+    );
+    push_table(
+        &mut html,
+        &format!("Significance summary (p < {})", config.significance_level),
+        plot_data::analysis::significance_summary(config),
+    );
+
+    html.push_str(&format!("<footer>{}</footer>\n", escape_html(provenance)));
+    html.push_str("</body>\n</html>\n");
+
+    std::fs::write(output, html).with_context(|| format!("failed to write: {}", output.display()))
+}
+
+/// Adds a section containing `table`, or the reason the table could not be generated (since not
+/// every table is available for every benchmark, e.g. without a reference fuzzer).
+fn push_table(html: &mut String, title: &str, table: anyhow::Result<DataFrame>) {
+    html.push_str(&format!("<h2>{}</h2>\n", escape_html(title)));
+    match table.and_then(|df| html_table(&df)) {
+        Ok(table) => html.push_str(&format!("<div class=\"table\">\n{table}</div>\n")),
+        Err(e) => {
+            eprintln!("WARNING: {title} table omitted from report: {e:#}");
+            let error = escape_html(&format!("{e:#}"));
+            html.push_str(&format!("<p class=\"error\">Not available: {error}</p>\n"));
+        }
+    }
+}
+
+fn html_table(df: &DataFrame) -> anyhow::Result<String> {
+    let mut table = String::from("<table>\n<tr>");
+    for name in df.get_column_names() {
+        table.push_str(&format!("<th>{}</th>", escape_html(name)));
+    }
+    table.push_str("</tr>\n");
+
+    for row in 0..df.height() {
+        table.push_str("<tr>");
+        for column in df.get_columns() {
+            let value = match column.dtype() {
+                DataType::Float32 | DataType::Float64 => {
+                    let value = column.cast(&DataType::Float64)?.f64()?.get(row);
+                    value.map_or_else(String::new, |x| format!("{x:.2}"))
+                }
+                _ => match column.get(row)? {
+                    AnyValue::Null => String::new(),
+                    _ => column.str_value(row)?.into_owned(),
+                },
+            };
+            let class = match column.dtype().is_numeric() {
+                true => "",
+                false => " class=\"text\"",
+            };
+            table.push_str(&format!("<td{class}>{}</td>", escape_html(&value)));
+        }
+        table.push_str("</tr>\n");
+    }
+    table.push_str("</table>\n");
+    Ok(table)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
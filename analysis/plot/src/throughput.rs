@@ -0,0 +1,57 @@
+use anyhow::Context;
+use plot_data::{name_of_binary, Config};
+use plotters::{
+    coord::Shift,
+    prelude::*,
+    style::text_anchor::{HPos, Pos, VPos},
+};
+use polars::prelude::*;
+
+use crate::{
+    afl_stats::draw_metric,
+    utils::{draw_x_axis_label, draw_y_axis_label, split_with_columns, Legend},
+};
+
+/// Plots the execution speed of each fuzzer over time for each binary (see
+/// [plot_data::analysis::summarize_throughput]), using the same grid as the coverage plot so the
+/// two can be compared side by side.
+pub fn throughput_over_time<DB>(
+    root: &DrawingArea<DB, Shift>,
+    config: &Config,
+    data: &DataFrame,
+    n_cols: u32,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    let by_binary = data.partition_by_stable(["binary"], true).context("partition_by(binary)")?;
+
+    let (legend_area, y_axis_area, x_axis_area, plot_area) = {
+        let (plot_area, legend_area) = root.split_vertically(root.dim_in_pixel().1 - 45);
+        let (y_axis_area, plot_area) = plot_area.split_horizontally(20);
+        let (plot_area, x_axis_area) = plot_area.split_vertically(plot_area.dim_in_pixel().1 - 25);
+        (legend_area, y_axis_area, x_axis_area, plot_area)
+    };
+
+    let legend_label_style = TextStyle::from(("Arial", 18).into_font())
+        .with_anchor::<RGBAColor>(Pos::new(HPos::Left, VPos::Bottom))
+        .into_text_style(&legend_area);
+    let mut legend = Legend::from_config(legend_label_style, config);
+
+    let plot_regions = split_with_columns(&plot_area, by_binary.len(), n_cols as usize);
+    for (df, region) in by_binary.iter().zip(plot_regions) {
+        let name = df["binary"].str_value(0)?;
+        draw_metric(&region, &name_of_binary(&name), df, "execs_per_sec", &mut legend)?;
+    }
+
+    let axis_label_style = TextStyle::from(("Arial", 20).into_font());
+    draw_y_axis_label(y_axis_area, "Execs/sec", &axis_label_style)?;
+    draw_x_axis_label(x_axis_area, "Duration (hours)", &axis_label_style)?;
+    legend.draw(&legend_area.margin(5, 0, 0, 0))?;
+
+    root.present()?;
+    Ok(())
+}
@@ -5,6 +5,7 @@ use plotters::{
     prelude::*,
     style::text_anchor::{HPos, Pos, VPos},
 };
+use polars::prelude::DataFrame;
 
 #[derive(Copy, Clone)]
 pub enum Marker {
@@ -81,6 +82,121 @@ impl Marker {
     }
 }
 
+#[derive(Copy, Clone)]
+pub enum LineStyle {
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl LineStyle {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "dashed" => Self::Dashed,
+            "dotted" => Self::Dotted,
+            _ => Self::Solid,
+        }
+    }
+
+    pub fn draw_line<DB, ColorType, RangeX, RangeY>(
+        &self,
+        ctx: &mut ChartContext<DB, Cartesian2d<RangeX, RangeY>>,
+        points: impl Iterator<Item = (RangeX::ValueType, RangeY::ValueType)>,
+        color: &ColorType,
+    ) -> anyhow::Result<()>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+        RangeX: Ranged,
+        RangeX::ValueType: Clone + 'static,
+        RangeY: Ranged,
+        RangeY::ValueType: Clone + 'static,
+        ColorType: Color,
+    {
+        let style = ShapeStyle::from(color);
+        match self {
+            Self::Solid => {
+                ctx.draw_series(LineSeries::new(points, style))?;
+            }
+            Self::Dashed => {
+                ctx.draw_series(DashedLineSeries::new(points.collect::<Vec<_>>(), 5, 3, style))?;
+            }
+            Self::Dotted => {
+                ctx.draw_series(DashedLineSeries::new(points.collect::<Vec<_>>(), 1, 2, style))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the segments of a line drawn along the bottom of a legend icon of `size` pixels. Solid
+    /// lines are the default, so they are not drawn.
+    fn icon_segments(
+        &self,
+        size: i32,
+        color: PaletteColor<CustomPalette>,
+    ) -> Vec<PathElement<(i32, i32)>> {
+        let (dash, gap) = match self {
+            Self::Solid => return vec![],
+            Self::Dashed => (5, 3),
+            Self::Dotted => (1, 2),
+        };
+        let style = color.stroke_width(2);
+        (0..size)
+            .step_by(dash + gap)
+            .map(|x| PathElement::new(vec![(x, size), ((x + dash as i32).min(size), size)], style))
+            .collect()
+    }
+}
+
+/// Presentation hints for a series read from the style columns generated by `plot-data` (see
+/// [plot_data::STYLE_COLUMNS]).
+pub struct SeriesStyle {
+    /// The fuzzer (or dataset) the series belongs to, used for looking up
+    /// [plot_data::Config::legend_mapping].
+    pub key: String,
+    pub label: String,
+    pub color: Option<usize>,
+    pub marker: Option<usize>,
+    pub line: LineStyle,
+}
+
+impl SeriesStyle {
+    /// Reads the style from the first row of `df`, using the `fuzzer` column as the label if the
+    /// style columns are missing.
+    pub fn from_df(df: &DataFrame) -> anyhow::Result<Self> {
+        let get_str = |name: &str| df.column(name).ok().and_then(|x| x.str().ok()?.get(0));
+        let get_index = |name: &str| {
+            df.column(name).ok().and_then(|x| x.u32().ok()?.get(0)).map(|x| x as usize)
+        };
+        let key = df["fuzzer"].str_value(0)?.into_owned();
+        let label = get_str("style_label").map_or_else(|| key.clone(), |x| x.to_owned());
+        Ok(Self {
+            key,
+            label,
+            color: get_index("style_color"),
+            marker: get_index("style_marker"),
+            line: get_str("style_line").map_or(LineStyle::Solid, LineStyle::from_name),
+        })
+    }
+
+    /// Reads the style of the dataset `name` from the config. Variants are keyed by the fuzzer
+    /// they are a variant of (see [plot_data::Config::fuzzer_families]), like the `fuzzer` column.
+    pub fn from_config(
+        config: &plot_data::Config,
+        name: &str,
+        style: &plot_data::config::Style,
+    ) -> Self {
+        let key = config.fuzzer_family(name).0;
+        Self {
+            key: key.to_owned(),
+            label: style.label.clone().unwrap_or_else(|| key.to_owned()),
+            color: style.color.map(|x| x as usize),
+            marker: style.marker.map(|x| x as usize),
+            line: LineStyle::from_name(style.line.as_str()),
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct CustomPalette;
 impl Palette for CustomPalette {
@@ -109,9 +225,12 @@ impl Palette for CustomPalette {
 }
 
 pub struct LegendEntry {
+    /// The key used for looking up the entry in [Legend::mapping] and [Legend::ranks].
+    pub key: String,
     pub name: String,
     pub color: PaletteColor<CustomPalette>,
     pub marker: Marker,
+    pub line: LineStyle,
     pub bold: bool,
 }
 
@@ -123,6 +242,8 @@ pub struct Legend<'a> {
     pub element_spacing: i32,
     pub mapping: HashMap<String, usize>,
     pub next_id: usize,
+    /// The position of each listed label (see [plot_data::Config::legend_order]).
+    pub ranks: HashMap<String, usize>,
 }
 
 impl<'a> Legend<'a> {
@@ -139,18 +260,27 @@ impl<'a> Legend<'a> {
             icon_size: 12,
             icon_spacing: 6,
             element_spacing: 15,
+            ranks: mapping.clone(),
             mapping,
             next_id,
         }
     }
+
+    /// Creates a legend using the colors and ordering configured in `config`.
+    pub fn from_config(label_style: TextStyle<'a>, config: &plot_data::Config) -> Self {
+        let mut legend = Self::new_with_mapping(label_style, config.legend_mapping.clone());
+        legend.ranks = config.legend_ranks();
+        legend
+    }
 }
 
 impl<'a> Legend<'a> {
-    pub fn get_or_insert(&mut self, name: &str) -> &mut LegendEntry {
+    /// Gets the entry for `key`, inserting a new entry displayed as `name` if it does not exist.
+    fn get_or_insert_keyed(&mut self, key: &str, name: &str) -> &mut LegendEntry {
         let position =
-            self.entries.iter().position(|x| x.name == name).unwrap_or(self.entries.len());
+            self.entries.iter().position(|x| x.key == key).unwrap_or(self.entries.len());
         if position == self.entries.len() {
-            let id = match self.mapping.get(name) {
+            let id = match self.mapping.get(key) {
                 Some(id) => *id,
                 None => {
                     self.next_id += 1;
@@ -159,11 +289,43 @@ impl<'a> Legend<'a> {
             };
             let color = CustomPalette::pick(id);
             let marker = Marker::pick(id);
-            self.entries.push(LegendEntry { name: name.into(), color, marker, bold: false });
+            self.entries.push(LegendEntry {
+                key: key.into(),
+                name: name.into(),
+                color,
+                marker,
+                line: LineStyle::Solid,
+                bold: false,
+            });
         }
         &mut self.entries[position]
     }
 
+    /// Gets the entry for `style.key` (displayed as `style.label`), overriding the default color,
+    /// marker and line style with the values from `style`.
+    pub fn get_or_insert_style(&mut self, style: &SeriesStyle) -> &mut LegendEntry {
+        let entry = self.get_or_insert_keyed(&style.key, &style.label);
+        if let Some(color) = style.color {
+            entry.color = CustomPalette::pick(color);
+        }
+        if let Some(marker) = style.marker {
+            entry.marker = Marker::pick(marker);
+        }
+        entry.line = style.line;
+        entry
+    }
+
+    /// Gets the entries in the order they are drawn: entries with a rank are drawn first (ordered
+    /// by rank), followed by the remaining entries in the order they were added.
+    pub fn ordered_entries(&self) -> Vec<&LegendEntry> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|x| {
+            let rank = self.ranks.get(&x.key).or_else(|| self.ranks.get(&x.name));
+            rank.copied().unwrap_or(usize::MAX)
+        });
+        entries
+    }
+
     pub fn draw<DB>(&self, area: &DrawingArea<DB, Shift>) -> anyhow::Result<()>
     where
         DB: DrawingBackend,
@@ -172,7 +334,7 @@ impl<'a> Legend<'a> {
         // Keep track of the combined legend width for centering.
         let mut total_legend_w = 0;
         let mut elements = vec![];
-        for entry in &self.entries {
+        for entry in self.ordered_entries() {
             let mut label = MultiLineText::<_, String>::new((0, 0), &self.label_style);
             for line in entry.name.split('\n') {
                 label.push_line(line);
@@ -183,7 +345,7 @@ impl<'a> Legend<'a> {
             // baseline.
             label.relocate((self.icon_size + self.icon_spacing, self.icon_size));
             let total_element_w = label_w + self.icon_size + self.icon_spacing;
-            elements.push((label, (entry.color, entry.marker), total_element_w));
+            elements.push((label, (entry.color, entry.marker, entry.line), total_element_w));
 
             total_legend_w += total_element_w + self.element_spacing;
         }
@@ -193,11 +355,14 @@ impl<'a> Legend<'a> {
         // Draw all the elements at the correct position.
         let (area_w, _area_h) = area.dim_in_pixel();
         let mut x = (area_w as i32 - total_legend_w) / 2;
-        for (label, (color, marker), width) in elements {
+        for (label, (color, marker, line), width) in elements {
             let icon_bg =
                 Rectangle::new([(0, 0), (self.icon_size, self.icon_size)], color.mix(0.4).filled());
             let icon_marker = marker.icon_element(self.icon_size, color);
             area.draw(&(EmptyElement::<_, DB>::at((x, 0)) + icon_bg + icon_marker + label))?;
+            for segment in line.icon_segments(self.icon_size, color) {
+                area.draw(&(EmptyElement::<_, DB>::at((x, 0)) + segment))?;
+            }
             x += width + self.element_spacing;
         }
 
@@ -213,7 +378,7 @@ impl<'a> Legend<'a> {
         // Keep track of the maximum legend width for centering.
         let mut max_legend_w = 0;
         let mut elements = vec![];
-        for entry in &self.entries {
+        for entry in self.ordered_entries() {
             let mut label = MultiLineText::<_, String>::new((0, 0), &self.label_style);
             for line in entry.name.split('\n') {
                 label.push_line(line);
@@ -224,7 +389,7 @@ impl<'a> Legend<'a> {
             // baseline.
             label.relocate((self.icon_size + self.icon_spacing, self.icon_size));
             let total_element_w = label_w + self.icon_size + self.icon_spacing;
-            elements.push((label, (entry.color, entry.marker), total_element_w));
+            elements.push((label, (entry.color, entry.marker, entry.line), total_element_w));
 
             max_legend_w = max_legend_w.max(total_element_w);
         }
@@ -233,11 +398,14 @@ impl<'a> Legend<'a> {
         let (area_w, _area_h) = area.dim_in_pixel();
         let x = (area_w as i32 - max_legend_w) / 2;
         let mut y = 0;
-        for (label, (color, marker), _width) in elements {
+        for (label, (color, marker, line), _width) in elements {
             let icon_bg =
                 Rectangle::new([(0, 0), (self.icon_size, self.icon_size)], color.mix(0.4).filled());
             let icon_marker = marker.icon_element(self.icon_size, color);
             area.draw(&(EmptyElement::<_, DB>::at((x, y)) + icon_bg + icon_marker + label))?;
+            for segment in line.icon_segments(self.icon_size, color) {
+                area.draw(&(EmptyElement::<_, DB>::at((x, y)) + segment))?;
+            }
             y += self.icon_size + self.icon_spacing;
         }
 
@@ -245,6 +413,32 @@ impl<'a> Legend<'a> {
     }
 }
 
+/// Draws a legend containing every fuzzer in `config` as a standalone figure, so that a single
+/// legend can be shared by all figures instead of repeating it in each figure. Like the legends of
+/// the other figures, unranked fuzzers are listed in the order they are first seen.
+pub fn plot_legend<DB>(
+    root: &DrawingArea<DB, Shift>,
+    config: &plot_data::Config,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    let legend_label_style = TextStyle::from(("Arial", 18).into_font())
+        .with_anchor::<RGBAColor>(Pos::new(HPos::Left, VPos::Bottom))
+        .into_text_style(root);
+    let mut legend = Legend::from_config(legend_label_style, config);
+    for (_, name, entry) in config.datasets() {
+        legend.get_or_insert_style(&SeriesStyle::from_config(config, name, &entry.style));
+    }
+    legend.draw(&root.margin(5, 0, 0, 0))?;
+
+    root.present()?;
+    Ok(())
+}
+
 pub fn split_with_columns<DB>(
     plot_area: &DrawingArea<DB, Shift>,
     count: usize,
@@ -312,6 +506,30 @@ where
     Ok(())
 }
 
+/// Draws diagonal lines spaced `spacing` pixels apart across `area`.
+pub fn draw_hatching<DB>(
+    area: &DrawingArea<DB, Shift>,
+    color: &RGBColor,
+    spacing: i32,
+) -> anyhow::Result<()>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let (w, h) = area.dim_in_pixel();
+    let (w, h) = (w as i32, h as i32);
+
+    // Each line goes from the bottom-left to the top-right, clipped to the bounds of the area.
+    let mut offset = -h;
+    while offset < w {
+        let (start, end) = (offset.max(0), (offset + h).min(w));
+        let points = vec![(start, h - (start - offset)), (end, h - (end - offset))];
+        area.draw(&PathElement::new(points, color))?;
+        offset += spacing;
+    }
+    Ok(())
+}
+
 pub fn draw_x_axis_label<DB>(
     area: DrawingArea<DB, Shift>,
     label: &str,
@@ -5,13 +5,21 @@ use std::{
     time::Duration,
 };
 
-use crate::{IpcWrapper, Request, Response, RunCommand, RunOutput};
+use crate::{codec::Encoding, IpcWrapper, Request, Response, RunCommand, RunOutput};
 use anyhow::Context;
 
 pub fn connect_to_tcp_agent(addr: &str) -> anyhow::Result<Box<dyn Agent>> {
     const MAX_RETRIES: usize = 3;
     const RETRY_DELAY: Duration = Duration::from_secs(5);
-    let agent = retry(|| Ok(TcpAgent::connect(addr)), MAX_RETRIES, RETRY_DELAY)?;
+    // The address may be a forwarded port (e.g. for the ssh worker) that accepts connections before
+    // the agent is listening, so the connection is only considered established after the agent
+    // responds.
+    let connect = || {
+        let mut agent = TcpAgent::connect(addr)?;
+        agent.set_encoding(Encoding::from_env())?;
+        Ok(agent)
+    };
+    let agent = retry(|| Ok(connect()), MAX_RETRIES, RETRY_DELAY)?;
     Ok(Box::new(agent))
 }
 
@@ -19,7 +27,9 @@ pub fn connect_to_tcp_agent(addr: &str) -> anyhow::Result<Box<dyn Agent>> {
 pub fn connect_to_uds_agent(path: &str) -> anyhow::Result<Box<dyn Agent>> {
     const MAX_RETRIES: usize = 3;
     const RETRY_DELAY: Duration = Duration::from_secs(5);
-    let agent = retry(|| Ok(unix::UnixAgent::connect(path.as_ref())), MAX_RETRIES, RETRY_DELAY)?;
+    let mut agent =
+        retry(|| Ok(unix::UnixAgent::connect(path.as_ref())), MAX_RETRIES, RETRY_DELAY)?;
+    agent.set_encoding(Encoding::from_env())?;
     Ok(Box::new(agent))
 }
 
@@ -56,6 +66,7 @@ pub trait Agent {
     ) -> anyhow::Result<serde_json::Value> {
         match self.send_request(request, read_timeout)? {
             Response::Value(v) => Ok(v),
+            Response::Bytes(bytes) => Ok(serde_json::json!(bytes)),
             Response::Error { error } => anyhow::bail!("{}", error),
         }
     }
@@ -78,8 +89,12 @@ pub trait Agent {
     }
 
     /// Run `task` in the guest and wait for it to complete, returning the result.
+    ///
+    /// The agent kills the process if it exceeds the timeout of the task (or the default timeout of
+    /// the agent), returning [crate::ExitKind::Hang]. We wait slightly longer than the timeout so
+    /// that the response from the agent is received instead of timing out the connection.
     fn run_task(&mut self, task: RunCommand) -> anyhow::Result<RunOutput> {
-        let timeout = task.timeout;
+        let timeout = task.timeout.map(|x| x + Duration::from_secs(10));
         let value = self
             .send_with_timeout(Request::RunProcess(task), timeout)
             .context("error running process")?;
@@ -95,18 +110,85 @@ pub trait Agent {
         Ok(value.as_i64())
     }
 
-    /// Get the status of the process associated `pid`.
-    fn get_status(&mut self, pid: u32) -> anyhow::Result<Option<i64>> {
+    /// Get the status of the process associated `pid` (and its descendants), returning `None` if
+    /// the process has exited.
+    fn get_status(&mut self, pid: u32) -> anyhow::Result<Option<crate::ProcessStatus>> {
         let value = self.send(Request::GetStatus(pid)).context("error checking process status")?;
-        Ok(value.as_i64())
+        match value {
+            serde_json::Value::Null => Ok(None),
+            // Older agents only report the PID of the process.
+            serde_json::Value::Number(_) => Ok(Some(crate::ProcessStatus::unknown(pid))),
+            value => Ok(Some(
+                serde_json::from_value(value)
+                    .context("invalid process status, invalid response from agent")?,
+            )),
+        }
+    }
+
+    /// Get the most recent stdout and stderr of the process associated with `pid`, returning `None`
+    /// if the agent does not know about the process.
+    fn get_recent_output(&mut self, pid: u32) -> anyhow::Result<Option<crate::RecentOutput>> {
+        let value =
+            self.send(Request::GetRecentOutput(pid)).context("error getting process output")?;
+        match value {
+            serde_json::Value::Null => Ok(None),
+            value => Ok(Some(
+                serde_json::from_value(value)
+                    .context("invalid process output, invalid response from agent")?,
+            )),
+        }
+    }
+
+    /// Get the current wall clock time of the guest (as a duration since the Unix epoch).
+    fn get_time(&mut self) -> anyhow::Result<Duration> {
+        let value = self.send(Request::GetTime).context("error getting guest time")?;
+        Ok(serde_json::from_value(value).context("invalid time, invalid response from agent")?)
     }
 
     /// Read the file at `path` from the guest.
     fn read_file(&mut self, path: PathBuf) -> anyhow::Result<Vec<u8>> {
-        let value = self
-            .send(Request::ReadFile { path: path.clone(), offset: 0, len: None })
+        self.read_file_range(path, 0, None)
+    }
+
+    /// Read up to `len` bytes (or the rest of the file if `None`) starting at `offset` from the
+    /// file at `path` on the guest. Large files should be read using a [FileReader] instead.
+    fn read_file_range(
+        &mut self,
+        path: PathBuf,
+        offset: u64,
+        len: Option<u64>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let request = Request::ReadFile { path: path.clone(), offset, len };
+        let response = self
+            .send_request(request, Some(std::time::Duration::from_secs(10)))
             .with_context(|| format!("error reading file: {}", path.display()))?;
-        serde_json::from_value(value).context("failed to read file, invalid response from agent")
+        match response {
+            Response::Bytes(bytes) => Ok(bytes),
+            Response::Value(value) => serde_json::from_value(value)
+                .context("failed to read file, invalid response from agent"),
+            Response::Error { error } => {
+                anyhow::bail!("error reading file: {}: {error}", path.display())
+            }
+        }
+    }
+
+    /// Write `data` to the file at `path` on the guest, replacing any existing content. Large files
+    /// are sent in multiple requests.
+    fn write_file(&mut self, path: PathBuf, data: &[u8]) -> anyhow::Result<()> {
+        const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+        // Always send at least one request, so that empty files are created.
+        let mut chunks: Vec<&[u8]> = data.chunks(CHUNK_SIZE).collect();
+        if chunks.is_empty() {
+            chunks.push(&[]);
+        }
+        let mut offset = 0;
+        for chunk in chunks {
+            let request = Request::WriteFile { path: path.clone(), offset, data: chunk.to_vec() };
+            self.send_with_timeout(request, Some(std::time::Duration::from_secs(30)))
+                .with_context(|| format!("error writing file: {}", path.display()))?;
+            offset += chunk.len() as u64;
+        }
+        Ok(())
     }
 
     /// Get metadata about the file at `path`.
@@ -127,6 +209,63 @@ pub trait Agent {
             .context("failed to read directory, invalid response from agent")
     }
 
+    /// Hash every file in the directory at `path` on the guest (recursively). Hashing large
+    /// directories can take a while, so a longer timeout is used than for other requests.
+    fn hash_dir(
+        &mut self,
+        path: PathBuf,
+        algorithm: crate::HashAlgorithm,
+    ) -> anyhow::Result<Vec<crate::FileHash>> {
+        let request = Request::HashDir { path: path.clone(), algorithm };
+        let value = self
+            .send_with_timeout(request, Some(std::time::Duration::from_secs(10 * 60)))
+            .with_context(|| format!("error hashing directory: {}", path.display()))?;
+        serde_json::from_value(value).context("failed to read hashes, invalid response from agent")
+    }
+
+    /// Create the directory at `path` (and any missing parents) on the guest.
+    fn create_dir(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        self.send(Request::CreateDir(path.clone()))
+            .with_context(|| format!("error creating directory: {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Recursively remove the file or directory at `path` on the guest.
+    fn remove_path(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        self.send_with_timeout(Request::RemovePath(path.clone()), None)
+            .with_context(|| format!("error removing: {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Move the file or directory at `src` to `dst` on the guest.
+    fn move_path(&mut self, src: PathBuf, dst: PathBuf) -> anyhow::Result<()> {
+        let (src_str, dst_str) = (src.display().to_string(), dst.display().to_string());
+        self.send(Request::MovePath { src, dst })
+            .with_context(|| format!("error moving {src_str} to {dst_str}"))?;
+        Ok(())
+    }
+
+    /// Recursively copy the file or directory at `src` to `dst` on the guest.
+    fn copy_path(&mut self, src: PathBuf, dst: PathBuf) -> anyhow::Result<()> {
+        let (src_str, dst_str) = (src.display().to_string(), dst.display().to_string());
+        self.send_with_timeout(Request::CopyPath { src, dst }, None)
+            .with_context(|| format!("error copying {src_str} to {dst_str}"))?;
+        Ok(())
+    }
+
+    /// Set the permission bits of the file or directory at `path` on the guest.
+    fn set_permissions(&mut self, path: PathBuf, mode: u32) -> anyhow::Result<()> {
+        self.send(Request::SetPermissions { path: path.clone(), mode })
+            .with_context(|| format!("error setting permissions of: {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Apply the sysctls, resource limits, clock and environment in `setup` to the guest.
+    fn setup_guest(&mut self, setup: crate::GuestSetup) -> anyhow::Result<()> {
+        self.send(Request::SetupGuest(setup)).context("error configuring guest")?;
+        Ok(())
+    }
+
     /// Send `signal` to the process `pid` running on the guest.
     fn kill_process(&mut self, pid: u32, signal: i32) -> anyhow::Result<()> {
         self.send(Request::KillProcess { pid, signal })
@@ -134,6 +273,14 @@ pub trait Agent {
         Ok(())
     }
 
+    /// Write `data` to the stdin of the process `pid` (which must have been spawned with
+    /// [crate::Stdio::Piped] stdin), closing its stdin afterwards if `close` is set.
+    fn write_stdin(&mut self, pid: u32, data: &[u8], close: bool) -> anyhow::Result<()> {
+        self.send(Request::WriteStdin { pid, data: data.to_vec(), close })
+            .with_context(|| format!("error writing to stdin of process {pid}"))?;
+        Ok(())
+    }
+
     /// Shutdown the VM by sending a reboot command.
     fn shutdown_vm(&mut self) -> anyhow::Result<()> {
         self.send(Request::Reboot).context("error shutting down vm")?;
@@ -145,6 +292,71 @@ pub trait Agent {
         self.send(Request::RestartAgent).context("error restarting agent")?;
         Ok(())
     }
+
+    /// Re-establish the connection to the agent after it was lost (e.g. because of a transient
+    /// vsock error). Processes running in the guest are unaffected.
+    fn reconnect(&mut self) -> anyhow::Result<()> {
+        anyhow::bail!("reconnecting is not supported by this agent")
+    }
+}
+
+/// Reads a file from the guest in fixed size chunks, so that large files can be copied without
+/// loading the entire file into memory.
+pub struct FileReader<'a> {
+    agent: &'a mut dyn Agent,
+    path: PathBuf,
+    offset: u64,
+    /// The offset to stop reading at, or `None` to read until the end of the file.
+    end: Option<u64>,
+    chunk: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+impl<'a> FileReader<'a> {
+    /// The maximum number of bytes requested from the agent at once.
+    pub const CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+    /// Creates a reader for the first `len` bytes of the file at `path` (or the entire file if
+    /// `len` is `None`).
+    pub fn new(agent: &'a mut dyn Agent, path: PathBuf, len: Option<u64>) -> Self {
+        Self { agent, path, offset: 0, end: len, chunk: vec![], pos: 0, eof: false }
+    }
+
+    fn next_chunk(&mut self) -> anyhow::Result<()> {
+        let len = match self.end {
+            Some(end) => end.saturating_sub(self.offset).min(Self::CHUNK_SIZE),
+            None => Self::CHUNK_SIZE,
+        };
+        if len == 0 {
+            self.eof = true;
+            return Ok(());
+        }
+        self.chunk = self.agent.read_file_range(self.path.clone(), self.offset, Some(len))?;
+        self.pos = 0;
+        self.offset += self.chunk.len() as u64;
+        // A short read means that we reached the end of the file.
+        if (self.chunk.len() as u64) < len {
+            self.eof = true;
+        }
+        Ok(())
+    }
+}
+
+impl std::io::Read for FileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.chunk.len() {
+            if self.eof {
+                return Ok(0);
+            }
+            self.next_chunk()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{e:#}")))?;
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
 }
 
 pub trait SetReadTimeout<R> {
@@ -156,6 +368,7 @@ pub struct RpcAgent<R: BufRead, W: Write, S: SetReadTimeout<R>> {
     pub writer: W,
     buf: Vec<u8>,
     next_request: u64,
+    encoding: Encoding,
     set_read_timeout: std::marker::PhantomData<S>,
 }
 
@@ -171,25 +384,48 @@ where
             writer,
             buf: vec![],
             next_request: 1,
+            encoding: Encoding::Json,
             set_read_timeout: std::marker::PhantomData,
         }
     }
 
+    /// Requests that the agent switches to `encoding` for all subsequent messages, returning
+    /// whether the agent supports it. Older agents only support JSON and reject the request, in
+    /// which case JSON continues to be used.
+    pub fn set_encoding(&mut self, encoding: Encoding) -> anyhow::Result<bool> {
+        if encoding == self.encoding {
+            return Ok(true);
+        }
+        match self.send_request(Request::SetEncoding(encoding), Some(Duration::from_secs(10)))? {
+            Response::Error { error } => {
+                tracing::debug!("agent does not support {encoding:?} encoding: {error}");
+                Ok(false)
+            }
+            _ => {
+                self.encoding = encoding;
+                Ok(true)
+            }
+        }
+    }
+
     fn read_response(
         &mut self,
         read_timeout: Option<Duration>,
     ) -> anyhow::Result<IpcWrapper<Response>> {
-        self.buf.clear();
         S::set_read_timeout(&mut self.reader, read_timeout)?;
-        self.reader.read_until(b'\n', &mut self.buf).context("failed to read response")?;
-        Ok(serde_json::from_slice(&self.buf).context("invalid response from agent")?)
+        let read = self
+            .encoding
+            .read_frame(&mut self.reader, &mut self.buf)
+            .context("failed to read response")?;
+        anyhow::ensure!(read, "failed to read response: connection closed");
+        self.encoding.decode(&self.buf).context("invalid response from agent")
     }
 
     fn write_request(&mut self, request_id: u64, request: Request) -> anyhow::Result<()> {
-        self.buf.clear();
-        serde_json::to_writer(&mut self.buf, &IpcWrapper { id: request_id, body: request })?;
-        self.buf.push(b'\n');
-        self.writer.write_all(&mut self.buf).context("failed to send request")
+        let message = IpcWrapper { id: request_id, body: request };
+        self.encoding
+            .write_message(&mut self.writer, &mut self.buf, &message)
+            .context("failed to send request")
     }
 }
 
@@ -282,3 +518,83 @@ pub mod unix {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    struct NoTimeout;
+
+    impl SetReadTimeout<Cursor<Vec<u8>>> for NoTimeout {
+        fn set_read_timeout(_: &mut Cursor<Vec<u8>>, _: Option<Duration>) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    type TestAgent = RpcAgent<Cursor<Vec<u8>>, Vec<u8>, NoTimeout>;
+
+    /// Creates an agent that reads `responses` (already encoded) as the replies from the agent.
+    fn agent_with_responses(responses: Vec<u8>) -> TestAgent {
+        RpcAgent::new(Cursor::new(responses), vec![])
+    }
+
+    fn encode(encoding: Encoding, out: &mut Vec<u8>, id: u64, response: Response) {
+        encoding.write_message(out, &mut vec![], &IpcWrapper { id, body: response }).unwrap();
+    }
+
+    /// Decodes all requests written by the agent.
+    fn sent_requests(agent: &TestAgent, encodings: &[Encoding]) -> Vec<IpcWrapper<Request>> {
+        let mut reader = Cursor::new(&agent.writer[..]);
+        let mut buf = vec![];
+        let mut requests = vec![];
+        for encoding in encodings {
+            assert!(encoding.read_frame(&mut reader, &mut buf).unwrap());
+            requests.push(encoding.decode(&buf).unwrap());
+        }
+        assert_eq!(reader.position() as usize, agent.writer.len(), "unexpected trailing data");
+        requests
+    }
+
+    #[test]
+    fn set_encoding_switches_after_response() {
+        let mut responses = vec![];
+        encode(Encoding::Json, &mut responses, 1, Response::Value(serde_json::Value::Null));
+        encode(Encoding::MessagePack, &mut responses, 2, Response::Value(serde_json::json!(7)));
+        let mut agent = agent_with_responses(responses);
+
+        assert!(agent.set_encoding(Encoding::MessagePack).unwrap());
+        assert_eq!(agent.send(Request::GetStats).unwrap(), serde_json::json!(7));
+
+        let requests = sent_requests(&agent, &[Encoding::Json, Encoding::MessagePack]);
+        assert!(matches!(requests[0], IpcWrapper {
+            id: 1,
+            body: Request::SetEncoding(Encoding::MessagePack)
+        }));
+        assert!(matches!(requests[1], IpcWrapper { id: 2, body: Request::GetStats }));
+    }
+
+    #[test]
+    fn set_encoding_falls_back_to_json() {
+        // Agents without support for `SetEncoding` fail to parse the request and return an error.
+        let mut responses = vec![];
+        let error = "unknown variant `set_encoding`".to_owned();
+        encode(Encoding::Json, &mut responses, 1, Response::Error { error });
+        encode(Encoding::Json, &mut responses, 2, Response::Value(serde_json::json!(7)));
+        let mut agent = agent_with_responses(responses);
+
+        assert!(!agent.set_encoding(Encoding::MessagePack).unwrap());
+        assert_eq!(agent.send(Request::GetStats).unwrap(), serde_json::json!(7));
+
+        let requests = sent_requests(&agent, &[Encoding::Json, Encoding::Json]);
+        assert!(matches!(requests[1], IpcWrapper { id: 2, body: Request::GetStats }));
+    }
+
+    #[test]
+    fn set_encoding_to_current_encoding_is_a_no_op() {
+        let mut agent = agent_with_responses(vec![]);
+        assert!(agent.set_encoding(Encoding::Json).unwrap());
+        assert!(agent.writer.is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+//! Encodings used for messages sent between the harness and the agent.
+//!
+//! All connections start using newline delimited JSON. A client can request a different encoding
+//! by sending [crate::Request::SetEncoding], after the agent responds (using the old encoding) all
+//! subsequent messages on the connection use the new encoding. Agents that do not support the
+//! request return an error, in which case the client should continue to use JSON.
+
+use std::{
+    convert::TryFrom,
+    io::{BufRead, Write},
+};
+
+use anyhow::Context;
+
+/// Messages larger than this are rejected to avoid allocating unbounded memory for corrupt frames.
+const MAX_FRAME_LEN: u32 = 1 << 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// Newline delimited JSON.
+    Json,
+    /// MessagePack with a 4-byte (little-endian) length prefix.
+    MessagePack,
+}
+
+impl Encoding {
+    /// Gets the encoding configured by the `AGENT_ENCODING` environment variable (defaults to
+    /// MessagePack).
+    pub fn from_env() -> Self {
+        match std::env::var("AGENT_ENCODING").as_deref() {
+            Ok("json") => Self::Json,
+            Ok("msgpack") | Err(_) => Self::MessagePack,
+            Ok(other) => {
+                tracing::warn!("unknown AGENT_ENCODING: {other} (using msgpack)");
+                Self::MessagePack
+            }
+        }
+    }
+
+    /// Reads a single message from `reader` into `buf`, returning `false` if the stream was closed.
+    pub fn read_frame(self, reader: &mut impl BufRead, buf: &mut Vec<u8>) -> anyhow::Result<bool> {
+        buf.clear();
+        match self {
+            Self::Json => Ok(reader.read_until(b'\n', buf)? != 0),
+            Self::MessagePack => {
+                let mut len = [0; 4];
+                match reader.read_exact(&mut len) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(false),
+                    Err(e) => return Err(e.into()),
+                }
+                let len = u32::from_le_bytes(len);
+                anyhow::ensure!(len <= MAX_FRAME_LEN, "frame too large ({len} bytes)");
+                buf.resize(len as usize, 0);
+                reader.read_exact(buf).context("truncated frame")?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Decodes a message read using [Self::read_frame].
+    pub fn decode<T: serde::de::DeserializeOwned>(self, buf: &[u8]) -> anyhow::Result<T> {
+        Ok(match self {
+            Self::Json => serde_json::from_slice(buf)?,
+            Self::MessagePack => rmp_serde::from_slice(buf)?,
+        })
+    }
+
+    /// Encodes `message` (using `buf` as scratch space) and writes it to `writer`.
+    pub fn write_message<T: serde::Serialize>(
+        self,
+        writer: &mut impl Write,
+        buf: &mut Vec<u8>,
+        message: &T,
+    ) -> anyhow::Result<()> {
+        buf.clear();
+        match self {
+            Self::Json => {
+                serde_json::to_writer(&mut *buf, message)?;
+                buf.push(b'\n');
+            }
+            Self::MessagePack => {
+                buf.extend_from_slice(&[0; 4]);
+                rmp_serde::encode::write_named(buf, message)?;
+                let len = u32::try_from(buf.len() - 4).context("message too large")?;
+                buf[..4].copy_from_slice(&len.to_le_bytes());
+            }
+        }
+        writer.write_all(buf)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, path::PathBuf};
+
+    use super::*;
+    use crate::{IpcWrapper, Request, Response};
+
+    const ENCODINGS: [Encoding; 2] = [Encoding::Json, Encoding::MessagePack];
+
+    /// Writes each message in `messages` to a single stream, returning the encoded stream.
+    fn encode_all<T: serde::Serialize>(encoding: Encoding, messages: &[T]) -> Vec<u8> {
+        let (mut out, mut buf) = (vec![], vec![]);
+        for message in messages {
+            encoding.write_message(&mut out, &mut buf, message).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn request_round_trip() {
+        for encoding in ENCODINGS {
+            let requests = [
+                IpcWrapper {
+                    id: 1,
+                    body: Request::ReadFile {
+                        path: PathBuf::from("/tmp/a b\nc"),
+                        offset: 4,
+                        len: None,
+                    },
+                },
+                IpcWrapper { id: 2, body: Request::SetEncoding(Encoding::MessagePack) },
+                IpcWrapper { id: 3, body: Request::Bulk(vec![Request::GetStats, Request::Reboot]) },
+            ];
+            let mut reader = Cursor::new(encode_all(encoding, &requests));
+
+            let mut buf = vec![];
+            let mut decoded = vec![];
+            while encoding.read_frame(&mut reader, &mut buf).unwrap() {
+                decoded.push(encoding.decode::<IpcWrapper<Request>>(&buf).unwrap());
+            }
+
+            assert_eq!(decoded.len(), 3, "{:?}", encoding);
+            assert!(matches!(&decoded[0], IpcWrapper {
+                id: 1,
+                body: Request::ReadFile { path, offset: 4, len: None }
+            } if path.as_os_str() == "/tmp/a b\nc"));
+            assert!(matches!(decoded[1], IpcWrapper {
+                id: 2,
+                body: Request::SetEncoding(Encoding::MessagePack)
+            }));
+            assert!(matches!(&decoded[2], IpcWrapper { id: 3, body: Request::Bulk(inner) }
+                if matches!(inner[..], [Request::GetStats, Request::Reboot])));
+        }
+    }
+
+    #[test]
+    fn response_round_trip() {
+        for encoding in ENCODINGS {
+            let responses = [
+                IpcWrapper { id: 1, body: Response::Error { error: "failed".into() } },
+                IpcWrapper { id: 2, body: Response::Bytes(vec![0, 1, 2, b'\n', 255]) },
+                IpcWrapper { id: 3, body: Response::Value(serde_json::json!({ "pid": 10 })) },
+                IpcWrapper { id: 4, body: Response::Value(serde_json::json!([0, 1, 2])) },
+            ];
+            let mut reader = Cursor::new(encode_all(encoding, &responses));
+
+            let mut buf = vec![];
+            let mut next = || {
+                assert!(encoding.read_frame(&mut reader, &mut buf).unwrap());
+                encoding.decode::<IpcWrapper<Response>>(&buf).unwrap()
+            };
+            assert!(matches!(next(), IpcWrapper { id: 1, body: Response::Error { error } }
+                if error == "failed"));
+            // JSON has no native byte arrays, so bytes are sent as an array of integers instead.
+            match (encoding, next()) {
+                (Encoding::Json, IpcWrapper { id: 2, body: Response::Value(value) }) => {
+                    assert_eq!(value, serde_json::json!([0, 1, 2, b'\n', 255]))
+                }
+                (Encoding::MessagePack, IpcWrapper { id: 2, body: Response::Bytes(bytes) }) => {
+                    assert_eq!(bytes, [0, 1, 2, b'\n', 255])
+                }
+                _ => panic!("unexpected response for bytes using {:?}", encoding),
+            }
+            assert!(matches!(next(), IpcWrapper { id: 3, body: Response::Value(value) }
+                if value == serde_json::json!({ "pid": 10 })));
+            // An array of integers must not be mistaken for raw bytes.
+            assert!(matches!(next(), IpcWrapper { id: 4, body: Response::Value(value) }
+                if value == serde_json::json!([0, 1, 2])));
+
+            assert!(!encoding.read_frame(&mut reader, &mut buf).unwrap(), "{:?}", encoding);
+        }
+    }
+
+    #[test]
+    fn message_pack_frame_errors() {
+        let mut buf = vec![];
+
+        let mut truncated = Cursor::new(encode_all(Encoding::MessagePack, &[Request::GetStats]));
+        truncated.get_mut().pop();
+        assert!(Encoding::MessagePack.read_frame(&mut truncated, &mut buf).is_err());
+
+        let mut oversized = Cursor::new((MAX_FRAME_LEN + 1).to_le_bytes().to_vec());
+        assert!(Encoding::MessagePack.read_frame(&mut oversized, &mut buf).is_err());
+
+        // A stream closed part way through the length prefix is treated as closed.
+        let mut closed = Cursor::new(vec![1, 0]);
+        assert!(!Encoding::MessagePack.read_frame(&mut closed, &mut buf).unwrap());
+    }
+}
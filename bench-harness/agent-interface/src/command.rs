@@ -2,8 +2,8 @@
 
 use std::{
     io,
-    process::{Command, Stdio},
-    time::Duration,
+    process::{Child, Command, ExitStatus, Stdio},
+    time::{Duration, Instant},
 };
 
 use crate::{ExitKind, RunOutput};
@@ -11,18 +11,17 @@ use crate::{ExitKind, RunOutput};
 pub fn run_command(mut cmd: Command, timeout: Option<Duration>) -> io::Result<RunOutput> {
     let cmd = cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
 
+    let start = Instant::now();
     let mut child = cmd.spawn()?;
     let out = child.stdout.take().unwrap();
     let err = child.stderr.take().unwrap();
-    let (stdout, stderr, timeout) = imp::read2_or_timeout(out, err, timeout)?;
+    let (stdout, stderr, timed_out) = imp::read2_or_timeout(out, err, timeout)?;
 
-    let result = match timeout {
-        true => {
-            let _ = child.kill();
-            child.wait()?;
-            None
-        }
-        false => Some(child.wait()?),
+    // The process may keep running after closing its output, so the timeout also applies to waiting
+    // for it to exit.
+    let result = match timed_out {
+        true => None,
+        false => wait_timeout(&mut child, timeout.map(|x| x.saturating_sub(start.elapsed())))?,
     };
 
     let exit = match result {
@@ -33,7 +32,7 @@ pub fn run_command(mut cmd: Command, timeout: Option<Duration>) -> io::Result<Ru
         },
         None => {
             let _ = child.kill();
-            let _ = child.try_wait();
+            child.wait()?;
             ExitKind::Hang
         }
     };
@@ -41,6 +40,23 @@ pub fn run_command(mut cmd: Command, timeout: Option<Duration>) -> io::Result<Ru
     Ok(RunOutput { exit, stdout, stderr })
 }
 
+/// Waits for `child` to exit, returning `None` if it is still running after `timeout`.
+fn wait_timeout(child: &mut Child, timeout: Option<Duration>) -> io::Result<Option<ExitStatus>> {
+    let Some(timeout) = timeout else {
+        return child.wait().map(Some);
+    };
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if start.elapsed() >= timeout {
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
 /// Based on code from: https://github.com/rust-lang/cargo/blob/905af549966f23a9288e9993a85d1249a5436556/crates/cargo-util/src/read2.rs
 #[cfg(unix)]
 mod imp {
@@ -129,13 +145,52 @@ mod imp {
     }
 }
 
+/// There is no portable way of polling pipes, so each pipe is read on a separate thread instead.
+/// The threads exit once the pipes are closed (e.g. after the process is killed on timeout), any
+/// output that was not read before the timeout is discarded.
 #[cfg(not(unix))]
 mod imp {
+    use std::{
+        io::{self, Read},
+        sync::mpsc::{self, Receiver, RecvTimeoutError},
+        time::{Duration, Instant},
+    };
+
     pub(crate) fn read2_or_timeout(
-        _out_pipe: std::process::ChildStdout,
-        _err_pipe: std::process::ChildStderr,
-        _timeout: Option<std::time::Duration>,
-    ) -> std::io::Result<(Vec<u8>, Vec<u8>, bool)> {
-        unimplemented!()
+        out_pipe: std::process::ChildStdout,
+        err_pipe: std::process::ChildStderr,
+        timeout: Option<Duration>,
+    ) -> io::Result<(Vec<u8>, Vec<u8>, bool)> {
+        let out = spawn_reader(out_pipe);
+        let err = spawn_reader(err_pipe);
+
+        let deadline = timeout.map(|x| Instant::now() + x);
+        let recv = |rx: &Receiver<io::Result<Vec<u8>>>| match deadline {
+            Some(deadline) => {
+                match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(result) => result.map(Some),
+                    Err(RecvTimeoutError::Timeout) => Ok(None),
+                    Err(RecvTimeoutError::Disconnected) => Ok(Some(vec![])),
+                }
+            }
+            None => rx.recv().unwrap_or_else(|_| Ok(vec![])).map(Some),
+        };
+
+        let Some(stdout) = recv(&out)? else {
+            return Ok((vec![], vec![], true));
+        };
+        let Some(stderr) = recv(&err)? else {
+            return Ok((stdout, vec![], true));
+        };
+        Ok((stdout, stderr, false))
+    }
+
+    fn spawn_reader(mut pipe: impl Read + Send + 'static) -> Receiver<io::Result<Vec<u8>>> {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = vec![];
+            let _ = tx.send(pipe.read_to_end(&mut buf).map(|_| buf));
+        });
+        rx
     }
 }
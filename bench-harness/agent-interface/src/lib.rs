@@ -1,8 +1,9 @@
 pub mod client;
+pub mod codec;
 pub mod command;
 pub mod utils;
 
-use std::{ffi::OsString, path::PathBuf};
+use std::{collections::BTreeMap, ffi::OsString, path::PathBuf};
 
 use anyhow::Context;
 
@@ -14,6 +15,22 @@ pub struct DirEntry {
     pub modified: std::time::SystemTime,
 }
 
+/// The hash of a file computed by the agent (see [Request::HashDir]).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FileHash {
+    pub path: PathBuf,
+    pub len: u64,
+    /// The hash of the content of the file as a lowercase hex string.
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+}
+
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Request {
@@ -29,41 +46,293 @@ pub enum Request {
     /// Run a raw process in the background.
     SpawnProcess(RunCommand),
 
-    /// Run a subprocess to completion, returning stdout and stderr.
+    /// Run a subprocess to completion, returning stdout and stderr. The subprocess is killed if it
+    /// runs for longer than its timeout (or the default timeout of the agent if not set).
     RunProcess(RunCommand),
 
     /// Waits for a subprocess to exit, returning the exit code.
     WaitPid(u32),
 
-    /// Get the status of the process associated with the given PID.
+    /// Get the status of the process associated with the given PID, including the state of all of
+    /// its descendants (see [ProcessStatus]).
     GetStatus(u32),
 
+    /// Get the most recent stdout and stderr of a process started with `SpawnProcess` (see
+    /// [RecentOutput]), even if its output was redirected to a file.
+    GetRecentOutput(u32),
+
+    /// Get the current wall clock time of the guest (as a duration since the Unix epoch).
+    GetTime,
+
     /// Send a signal to a process managed by the VM.
     KillProcess { pid: u32, signal: i32 },
 
+    /// Write `data` to the stdin of a process started with `SpawnProcess` using [Stdio::Piped] for
+    /// its stdin, closing the pipe afterwards if `close` is set (e.g. to signal the end of input).
+    /// Data is written in the background in the order it was sent, so a process that is not reading
+    /// its input does not block the agent.
+    WriteStdin {
+        pid: u32,
+        #[serde(with = "byte_buf")]
+        data: Vec<u8>,
+        #[serde(default)]
+        close: bool,
+    },
+
     /// Read a file from the file system.
     ReadFile { path: PathBuf, offset: u64, len: Option<u64> },
 
+    /// Write `data` to the file at `offset`, creating the file if it does not exist. The file is
+    /// truncated if `offset` is 0, so large files can be written in multiple chunks.
+    WriteFile {
+        path: PathBuf,
+        offset: u64,
+        #[serde(with = "byte_buf")]
+        data: Vec<u8>,
+    },
+
     /// Read metadata about a file.
     StatFile(PathBuf),
 
     /// Read the content of a directory from the file system.
     ReadDir(PathBuf),
 
+    /// Hash every file in a directory (recursively), returning a list of [FileHash] entries.
+    HashDir {
+        path: PathBuf,
+        #[serde(default)]
+        algorithm: HashAlgorithm,
+    },
+
     /// Add entropy to the system.
     AddEntropy(Vec<u32>),
 
+    /// Apply sysctls, resource limits and the initial clock to the system (see [GuestSetup]).
+    SetupGuest(GuestSetup),
+
+    /// Create a directory and any missing parents (`mkdir -p`).
+    CreateDir(PathBuf),
+
+    /// Remove a file or directory recursively, ignoring paths that do not exist (`rm -rf`).
+    RemovePath(PathBuf),
+
+    /// Move a file or directory (`mv`).
+    MovePath { src: PathBuf, dst: PathBuf },
+
+    /// Copy a file or directory recursively (`cp -r`).
+    CopyPath { src: PathBuf, dst: PathBuf },
+
+    /// Set the permission bits of a file or directory (`chmod`).
+    SetPermissions { path: PathBuf, mode: u32 },
+
     /// Perform multiple commands in a single request.
     Bulk(Vec<Request>),
+
+    /// Switch the encoding used for all subsequent messages on the current connection.
+    SetEncoding(codec::Encoding),
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(untagged)]
 pub enum Response {
     Error { error: String },
+    /// Raw bytes (e.g. file content), encoded compactly when using a binary encoding.
+    Bytes(#[serde(with = "raw_bytes")] Vec<u8>),
     Value(serde_json::Value),
 }
 
+mod raw_bytes {
+    //! Serializes byte arrays using `serialize_bytes`. Unlike the default implementation, this only
+    //! deserializes from native byte arrays, so a JSON array or string is never mistaken for bytes
+    //! when used in an untagged enum.
+
+    pub fn serialize<S: serde::Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a byte array")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(v.to_vec())
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(v)
+            }
+        }
+
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+mod byte_buf {
+    //! Serializes byte arrays using `serialize_bytes` (compact with binary encodings), while still
+    //! accepting the sequences of integers generated by JSON.
+
+    pub fn serialize<S: serde::Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        struct ByteBufVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ByteBufVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a byte array")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(v.to_vec())
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(v)
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut out = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    out.push(byte);
+                }
+                Ok(out)
+            }
+        }
+
+        deserializer.deserialize_byte_buf(ByteBufVisitor)
+    }
+}
+
+/// System configuration applied by the agent before any tasks are run.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct GuestSetup {
+    /// Kernel parameters to set, e.g. `"kernel.core_pattern" = "core"`.
+    #[serde(default)]
+    pub sysctls: BTreeMap<String, String>,
+
+    /// Resource limits applied to the agent (and inherited by all processes it starts), e.g.
+    /// `core = "unlimited"` or `nofile = 65536`. Both the soft and hard limits are set.
+    #[serde(default)]
+    pub ulimits: BTreeMap<String, Ulimit>,
+
+    /// Fixed wall clock time (seconds since the Unix epoch) to set before running any tasks, making
+    /// timestamps generated inside the guest reproducible across runs. Requires permission to set
+    /// the system time (not available inside docker containers by default).
+    #[serde(default)]
+    pub clock: Option<u64>,
+
+    /// Environment variables added to every process spawned by the agent after the setup is
+    /// applied. Variables set by the command itself take precedence.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+impl GuestSetup {
+    pub fn is_empty(&self) -> bool {
+        self.sysctls.is_empty()
+            && self.ulimits.is_empty()
+            && self.clock.is_none()
+            && self.env.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Ulimit {
+    Value(u64),
+    /// Only `"unlimited"` is supported.
+    Named(String),
+}
+
+impl Ulimit {
+    /// Gets the value of the limit, returning `None` for unlimited.
+    pub fn value(&self) -> anyhow::Result<Option<u64>> {
+        match self {
+            Self::Value(x) => Ok(Some(*x)),
+            Self::Named(x) if x == "unlimited" => Ok(None),
+            Self::Named(x) => anyhow::bail!("invalid limit: {x}"),
+        }
+    }
+}
+
+/// The status of a process managed by the agent and all of its descendants.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ProcessStatus {
+    pub pid: u32,
+    /// The name of the executable (the `comm` field of `/proc/<pid>/stat`).
+    #[serde(default)]
+    pub name: String,
+    /// The state of the process as reported in `/proc/<pid>/stat` (e.g. `R`, `S` or `Z`), or `?`
+    /// if the state is unknown.
+    #[serde(default = "ProcessStatus::unknown_state")]
+    pub state: char,
+    #[serde(default)]
+    pub children: Vec<ProcessStatus>,
+}
+
+impl ProcessStatus {
+    /// Creates a status entry for `pid` without any information about its state or children (used
+    /// for responses from older agents that only report the PID).
+    pub fn unknown(pid: u32) -> Self {
+        Self { pid, name: String::new(), state: Self::unknown_state(), children: vec![] }
+    }
+
+    fn unknown_state() -> char {
+        '?'
+    }
+
+    /// Returns whether the process has exited but has not yet been reaped by its parent.
+    pub fn is_zombie(&self) -> bool {
+        matches!(self.state, 'Z' | 'X' | 'x')
+    }
+
+    /// Returns all descendants of this process that have exited but have not been reaped.
+    pub fn dead_descendants(&self) -> Vec<&ProcessStatus> {
+        let mut dead = vec![];
+        let mut stack: Vec<_> = self.children.iter().collect();
+        while let Some(process) = stack.pop() {
+            if process.is_zombie() {
+                dead.push(process);
+            }
+            stack.extend(process.children.iter());
+        }
+        dead
+    }
+}
+
+impl std::fmt::Display for ProcessStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}(pid={}, state={})", self.name, self.pid, self.state)?;
+        if !self.children.is_empty() {
+            f.write_str(" [")?;
+            for (i, child) in self.children.iter().enumerate() {
+                if i != 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{child}")?;
+            }
+            f.write_str("]")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct IpcWrapper<T> {
     pub id: u64,
@@ -76,6 +345,9 @@ pub enum Stdio {
     Null,
     File(PathBuf),
     Inherit,
+    /// Keeps a pipe to the process open so that input can be sent later (see
+    /// [Request::WriteStdin]). Only supported for the stdin of spawned processes.
+    Piped,
 }
 
 impl Stdio {
@@ -89,10 +361,11 @@ impl Stdio {
                     .append(true)
                     .create(true)
                     .open(&path)
-                    .with_context(|| format!("failed to open: {}", path.display()))?;
+                    .map_err(|e| utils::write_error(e, &path))?;
                 Ok(file.into())
             }
             Stdio::Inherit => Ok(std::process::Stdio::inherit()),
+            Stdio::Piped => Ok(std::process::Stdio::piped()),
         }
     }
 }
@@ -120,6 +393,10 @@ pub struct RunCommand {
     pub timeout: Option<std::time::Duration>,
     #[serde(default)]
     pub current_dir: Option<PathBuf>,
+    /// Whether the agent should keep the recent output of the process in memory so that it can be
+    /// retrieved with [Request::GetRecentOutput] (only used for spawned processes).
+    #[serde(default)]
+    pub capture_output: bool,
 }
 
 impl RunCommand {
@@ -133,6 +410,7 @@ impl RunCommand {
             stdout: Stdio::default(),
             stderr: Stdio::default(),
             current_dir: None,
+            capture_output: false,
         }
     }
 
@@ -175,6 +453,11 @@ impl RunCommand {
         self
     }
 
+    pub fn capture_output(mut self, capture_output: bool) -> Self {
+        self.capture_output = capture_output;
+        self
+    }
+
     pub fn run(&self) -> anyhow::Result<RunOutput> {
         let mut command = std::process::Command::new(&self.program);
         command.args(&self.args);
@@ -245,9 +528,17 @@ pub enum ExitKind {
     Success,
     Exit(i32),
     Crash,
+    /// The process was killed after exceeding its timeout.
     Hang,
 }
 
+/// The most recent output (up to a fixed number of bytes) of a spawned process.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct RecentOutput {
+    pub stdout: String,
+    pub stderr: String,
+}
+
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct RunOutput {
     pub exit: ExitKind,
@@ -22,6 +22,19 @@ pub fn read_dir_entries(path: &Path) -> anyhow::Result<Vec<crate::DirEntry>> {
     Ok(entries)
 }
 
+/// Converts an error from opening `path` for writing to an error message, calling out writes to
+/// read-only file systems since they otherwise fail with confusing errors inside of the VM.
+pub fn write_error(err: std::io::Error, path: &Path) -> anyhow::Error {
+    match err.kind() {
+        std::io::ErrorKind::ReadOnlyFilesystem => anyhow::format_err!(
+            "failed to open: {} (path is on a read-only file system, write to the scratch \
+            directory instead)",
+            path.display()
+        ),
+        _ => anyhow::Error::new(err).context(format!("failed to open: {}", path.display())),
+    }
+}
+
 /// Split a shell-like command string into three components, `vars`, `bin`, and `args`
 pub fn split_command(input: &str) -> Option<(Vec<(String, String)>, String, Vec<String>)> {
     let mut input = shlex::split(input)?.into_iter().peekable();
@@ -0,0 +1,180 @@
+//! Native implementations of common file system commands, allowing tasks to manage files on guests
+//! without depending on the tools available in the root file system.
+
+use std::path::Path;
+
+use anyhow::Context;
+
+/// Equivalent to `mkdir -p`.
+pub fn create_dir(path: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(path).with_context(|| format!("failed to create: {}", path.display()))
+}
+
+/// Writes `data` to `path` at `offset`, creating the file if it does not exist and truncating it
+/// if `offset` is 0.
+pub fn write_file(path: &Path, offset: u64, data: &[u8]) -> anyhow::Result<()> {
+    use std::io::{Seek, Write};
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(offset == 0)
+        .open(path)
+        .with_context(|| format!("failed to open: {}", path.display()))?;
+    file.seek(std::io::SeekFrom::Start(offset))?;
+    file.write_all(data).with_context(|| format!("failed to write: {}", path.display()))
+}
+
+/// Equivalent to `rm -rf`.
+pub fn remove_path(path: &Path) -> anyhow::Result<()> {
+    let result = match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => Err(e),
+    };
+    result.with_context(|| format!("failed to remove: {}", path.display()))
+}
+
+/// Equivalent to `mv`. If `dst` is an existing directory `src` is moved inside of it.
+pub fn move_path(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    let dst = target_path(src, dst)?;
+    match std::fs::rename(src, &dst) {
+        Ok(()) => Ok(()),
+        // `rename` does not support moving files across file systems, so fallback to copying.
+        Err(e) if is_cross_device(&e) => {
+            copy_recursive(src, &dst)?;
+            remove_path(src)
+        }
+        Err(e) => Err(anyhow::Error::new(e).context(format!(
+            "failed to move {} to {}",
+            src.display(),
+            dst.display()
+        ))),
+    }
+}
+
+/// Equivalent to `cp -r`. If `dst` is an existing directory `src` is copied inside of it.
+pub fn copy_path(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    copy_recursive(src, &target_path(src, dst)?)
+}
+
+/// Hashes every file in `path` (recursively). Symlinks are not followed.
+pub fn hash_dir(
+    path: &Path,
+    algorithm: agent_interface::HashAlgorithm,
+) -> anyhow::Result<Vec<agent_interface::FileHash>> {
+    let mut hashes = vec![];
+    let mut pending = vec![path.canonicalize()?];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            std::fs::read_dir(&dir).with_context(|| format!("failed to read: {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                let path = entry.path();
+                let (len, hash) = hash_file(&path, algorithm)
+                    .with_context(|| format!("failed to hash: {}", path.display()))?;
+                hashes.push(agent_interface::FileHash { path, len, hash });
+            }
+        }
+    }
+    Ok(hashes)
+}
+
+fn hash_file(
+    path: &Path,
+    algorithm: agent_interface::HashAlgorithm,
+) -> anyhow::Result<(u64, String)> {
+    use sha2::Digest;
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut len = 0;
+    let hash = match algorithm {
+        agent_interface::HashAlgorithm::Sha256 => {
+            let mut hasher = sha2::Sha256::new();
+            let mut buf = vec![0; 64 * 1024];
+            loop {
+                match file.read(&mut buf)? {
+                    0 => break,
+                    n => {
+                        hasher.update(&buf[..n]);
+                        len += n as u64;
+                    }
+                }
+            }
+            hasher.finalize()
+        }
+    };
+    Ok((len, hash.iter().map(|x| format!("{x:02x}")).collect()))
+}
+
+/// Equivalent to `chmod`.
+pub fn set_permissions(path: &Path, mode: u32) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .with_context(|| format!("failed to set permissions of: {}", path.display()))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+        anyhow::bail!("setting permissions is not supported on this platform")
+    }
+}
+
+fn is_cross_device(err: &std::io::Error) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        err.raw_os_error() == Some(nix::errno::Errno::EXDEV as i32)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = err;
+        false
+    }
+}
+
+/// Gets the final destination of `src`, handling the case where `dst` is an existing directory.
+fn target_path(src: &Path, dst: &Path) -> anyhow::Result<std::path::PathBuf> {
+    if !dst.is_dir() {
+        return Ok(dst.to_owned());
+    }
+    let name = src.file_name().with_context(|| format!("invalid source: {}", src.display()))?;
+    Ok(dst.join(name))
+}
+
+fn copy_recursive(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    let metadata = std::fs::symlink_metadata(src)
+        .with_context(|| format!("failed to read: {}", src.display()))?;
+
+    if metadata.is_dir() {
+        create_dir(dst)?;
+        for entry in std::fs::read_dir(src)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+        std::fs::set_permissions(dst, metadata.permissions())?;
+    }
+    else if metadata.file_type().is_symlink() {
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(std::fs::read_link(src)?, dst)
+            .with_context(|| format!("failed to create symlink: {}", dst.display()))?;
+        #[cfg(not(unix))]
+        std::fs::copy(src, dst)
+            .with_context(|| format!("failed to copy {} to {}", src.display(), dst.display()))?;
+    }
+    else {
+        std::fs::copy(src, dst)
+            .with_context(|| format!("failed to copy {} to {}", src.display(), dst.display()))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! Support for running the agent as the init process (PID 1) of a VM, avoiding the need for systemd
+//! (or custom init scripts) in the root file system. Enabled by adding `init=/bin/agent` to the
+//! kernel boot arguments.
+
+use std::path::Path;
+
+use anyhow::Context;
+use nix::{mount::MsFlags, sys::wait::WaitPidFlag, unistd::Pid};
+
+/// Returns whether the agent is running as the init process.
+pub fn is_init() -> bool {
+    std::process::id() == 1
+}
+
+/// Performs the system setup that would normally be handled by the init system.
+pub fn setup() -> anyhow::Result<()> {
+    mount("proc", "/proc", "proc", MsFlags::empty(), None)?;
+    mount("sysfs", "/sys", "sysfs", MsFlags::empty(), None)?;
+    mount("devtmpfs", "/dev", "devtmpfs", MsFlags::empty(), None)?;
+    mount_fstab(Path::new("/etc/fstab"))?;
+
+    // The kernel starts init with an almost empty environment.
+    if std::env::var_os("PATH").is_none() {
+        std::env::set_var("PATH", "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin");
+    }
+    if std::env::var_os("HOME").is_none() {
+        std::env::set_var("HOME", "/root");
+    }
+    // Enable the statsd collector, this is also inherited by processes spawned by the agent.
+    if std::env::var_os("STATSD").is_none() {
+        std::env::set_var("STATSD", "127.0.0.1:8125");
+    }
+
+    Ok(())
+}
+
+fn mount(
+    src: &str,
+    dst: &str,
+    fstype: &str,
+    flags: MsFlags,
+    data: Option<&str>,
+) -> anyhow::Result<()> {
+    let _ = std::fs::create_dir_all(dst);
+    match nix::mount::mount(Some(src), dst, Some(fstype), flags, data) {
+        Ok(()) => Ok(()),
+        // Already mounted (e.g. by the kernel).
+        Err(nix::errno::Errno::EBUSY) => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("failed to mount {src} at {dst} ({fstype})")),
+    }
+}
+
+/// Mounts all file systems listed in `path` (using the same format as `/etc/fstab`).
+fn mount_fstab(path: &Path) -> anyhow::Result<()> {
+    let fstab = match std::fs::read_to_string(path) {
+        Ok(fstab) => fstab,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read: {}", path.display())),
+    };
+
+    for line in fstab.lines().map(str::trim).filter(|x| !x.is_empty() && !x.starts_with('#')) {
+        let mut fields = line.split_whitespace();
+        let (Some(src), Some(dst), Some(fstype)) = (fields.next(), fields.next(), fields.next())
+        else {
+            eprintln!("[agent] invalid fstab entry: {line}");
+            continue;
+        };
+        if fstype == "swap" {
+            continue;
+        }
+
+        let mut flags = MsFlags::empty();
+        let mut data = vec![];
+        for option in fields.next().unwrap_or("defaults").split(',') {
+            match option {
+                "defaults" | "rw" => {}
+                "ro" => flags |= MsFlags::MS_RDONLY,
+                "bind" => flags |= MsFlags::MS_BIND,
+                "nosuid" => flags |= MsFlags::MS_NOSUID,
+                "nodev" => flags |= MsFlags::MS_NODEV,
+                "noexec" => flags |= MsFlags::MS_NOEXEC,
+                "noatime" => flags |= MsFlags::MS_NOATIME,
+                other => data.push(other),
+            }
+        }
+        let data = data.join(",");
+        let data = (!data.is_empty()).then_some(data.as_str());
+        mount(src, dst, fstype, flags, data)?;
+    }
+
+    Ok(())
+}
+
+/// Reaps any exited processes that are not in `owned`. When running as init, orphaned processes are
+/// re-parented to the agent, so they must be reaped to avoid accumulating zombies.
+pub fn reap_orphans(owned: impl Fn(u32) -> bool) {
+    let Ok(entries) = std::fs::read_dir("/proc")
+    else {
+        return;
+    };
+    // `waitid` always reports the same process first, so our own subprocesses (which are reaped by
+    // the caller) would hide any orphans that exited after them. Instead find all zombie children
+    // and reap the ones that we do not own individually.
+    for pid in entries.filter_map(|x| x.ok()?.file_name().to_str()?.parse::<u32>().ok()) {
+        if owned(pid) || !is_zombie_child(pid) {
+            continue;
+        }
+        let _ = nix::sys::wait::waitpid(Pid::from_raw(pid as i32), Some(WaitPidFlag::WNOHANG));
+    }
+}
+
+/// Returns whether `pid` is an exited child of the current process that has not been reaped yet.
+fn is_zombie_child(pid: u32) -> bool {
+    let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat"))
+    else {
+        return false;
+    };
+    // The format is `pid (comm) state ppid ...`, where `comm` may contain spaces or parentheses.
+    let mut fields = stat.rsplit_once(')').map_or("", |(_, rest)| rest).split_whitespace();
+    let (Some(state), Some(ppid)) = (fields.next(), fields.next())
+    else {
+        return false;
+    };
+    state == "Z" && ppid.parse::<u32>().ok() == Some(std::process::id())
+}
+
+/// Shuts down the VM directly, since there is no init system available to do it for us.
+pub fn shutdown() -> anyhow::Result<()> {
+    nix::unistd::sync();
+    nix::sys::reboot::reboot(nix::sys::reboot::RebootMode::RB_AUTOBOOT)
+        .context("failed to reboot")?;
+    Ok(())
+}
@@ -0,0 +1,33 @@
+//! Writes data sent with [Request::WriteStdin] to the stdin of spawned processes.
+//!
+//! [Request::WriteStdin]: agent_interface::Request::WriteStdin
+
+use std::{io::Write, process::ChildStdin};
+
+/// The stdin pipe of a spawned process. Data is written by a background thread, so a process that
+/// is not reading its input does not block the agent. The pipe is closed once this is dropped and
+/// all pending data has been written.
+pub struct StdinWriter {
+    sender: crossbeam_channel::Sender<Vec<u8>>,
+}
+
+impl StdinWriter {
+    pub fn new(pid: u32, mut stdin: ChildStdin) -> Self {
+        let (sender, receiver) = crossbeam_channel::unbounded::<Vec<u8>>();
+        std::thread::spawn(move || {
+            for data in receiver {
+                if let Err(e) = stdin.write_all(&data).and_then(|_| stdin.flush()) {
+                    eprintln!("[agent] failed to write to stdin of PID={pid}: {e}");
+                    break;
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    /// Queues `data` to be written to the process, failing if writing to the pipe already failed
+    /// (e.g. because the process exited).
+    pub fn write(&self, data: Vec<u8>) -> anyhow::Result<()> {
+        self.sender.send(data).map_err(|_| anyhow::format_err!("stdin was closed"))
+    }
+}
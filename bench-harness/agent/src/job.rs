@@ -0,0 +1,71 @@
+//! Support for killing the entire process tree of a subprocess on Windows (where there are no
+//! process groups) using job objects.
+
+use std::{io, os::windows::io::AsRawHandle, process::Child};
+
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, HANDLE},
+    System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+        SetInformationJobObject, TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    },
+};
+
+/// A job object containing a process and all processes it spawns. All processes in the job are
+/// killed when the job is dropped.
+///
+/// Note: processes spawned by the child before it is assigned to the job are not included.
+pub struct Job(HANDLE);
+
+// Safety: job object handles can be used from any thread.
+unsafe impl Send for Job {}
+
+impl Job {
+    /// Creates a new job object containing `child`.
+    pub fn for_child(child: &Child) -> io::Result<Self> {
+        // Safety: the handle is checked before use and is owned by the returned job.
+        let handle = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+        if handle == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let job = Self(handle);
+
+        // Safety: `info` is a valid `JOBOBJECT_EXTENDED_LIMIT_INFORMATION` struct and the process
+        // handle is valid for the lifetime of `child`.
+        unsafe {
+            let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+            info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            let result = SetInformationJobObject(
+                job.0,
+                JobObjectExtendedLimitInformation,
+                std::ptr::addr_of!(info).cast(),
+                std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            );
+            if result == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if AssignProcessToJobObject(job.0, child.as_raw_handle() as HANDLE) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(job)
+    }
+
+    /// Kills all processes in the job.
+    pub fn terminate(&self, exit_code: u32) -> io::Result<()> {
+        // Safety: the handle is valid until the job is dropped.
+        match unsafe { TerminateJobObject(self.0, exit_code) } {
+            0 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        // Safety: the handle is owned by the job and is not used after this point.
+        unsafe { CloseHandle(self.0) };
+    }
+}
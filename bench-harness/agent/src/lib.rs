@@ -1,14 +1,23 @@
+pub mod builtins;
+#[cfg(target_os = "linux")]
+pub mod init;
+mod input;
+#[cfg(windows)]
+mod job;
 pub mod log_collector;
+mod output;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     io::{Read, Seek},
+    ffi::OsString,
     path::PathBuf,
     process,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
-use agent_interface::{client::Agent, Request, Response};
+use agent_interface::{client::Agent, ExitKind, ProcessStatus, Request, Response};
 use anyhow::Context;
 
 use crate::log_collector::StatsdData;
@@ -50,7 +59,7 @@ pub fn spawn_local_agent(
         for req in req_rx {
             state.reap_dead();
 
-            let res = map_response(state.handle_request(req));
+            let res = state.handle(req);
             if res_tx.send(res).is_err() {
                 break;
             }
@@ -67,6 +76,88 @@ pub fn spawn_local_agent(
     Ok((Box::new(LocalAgent { sender: req_tx, receiver: res_rx }), handle))
 }
 
+/// A writable directory used as the default working directory for the agent, allowing the agent to
+/// run on images with a read-only root file system.
+pub struct ScratchConfig {
+    pub dir: PathBuf,
+    /// If set, a tmpfs of this size (e.g. `512M`) is mounted at `dir` on startup.
+    pub tmpfs_size: Option<String>,
+}
+
+impl ScratchConfig {
+    /// Reads the scratch configuration from the `AGENT_SCRATCH_DIR` and `AGENT_SCRATCH_SIZE`
+    /// environment variables, falling back to the `agent.scratch_dir` and `agent.scratch_size`
+    /// kernel parameters (since the agent is not always started with a custom environment).
+    pub fn from_env() -> Option<Self> {
+        let dir = env_or_kernel_param("AGENT_SCRATCH_DIR", "agent.scratch_dir=")?;
+        let tmpfs_size = env_or_kernel_param("AGENT_SCRATCH_SIZE", "agent.scratch_size=");
+        Some(Self { dir: dir.into(), tmpfs_size })
+    }
+
+    /// Ensures that the scratch directory exists (mounting a tmpfs if configured) and is writable.
+    pub fn prepare(&self) -> anyhow::Result<()> {
+        let dir = &self.dir;
+        if !dir.exists() {
+            std::fs::create_dir_all(dir).with_context(|| {
+                format!(
+                    "failed to create scratch directory: {} (the directory must already exist \
+                    when using a read-only root file system)",
+                    dir.display()
+                )
+            })?;
+        }
+
+        if let Some(size) = self.tmpfs_size.as_ref() {
+            let status = process::Command::new("mount")
+                .args(["-t", "tmpfs", "-o"])
+                .arg(format!("size={size},mode=1777"))
+                .arg("tmpfs")
+                .arg(dir)
+                .status()
+                .context("failed to run `mount`")?;
+            if !status.success() {
+                anyhow::bail!("failed to mount tmpfs at {}: {status}", dir.display());
+            }
+        }
+
+        let probe = dir.join(".agent-write-test");
+        std::fs::write(&probe, b"")
+            .with_context(|| format!("scratch directory is not writable: {}", dir.display()))?;
+        let _ = std::fs::remove_file(probe);
+
+        Ok(())
+    }
+}
+
+/// Reads a configuration value from the environment variable `var`, falling back to the kernel
+/// parameter `param` (since the agent is not always started with a custom environment).
+fn env_or_kernel_param(var: &str, param: &str) -> Option<String> {
+    std::env::var(var).ok().or_else(|| {
+        let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
+        cmdline.split_whitespace().find_map(|x| Some(x.strip_prefix(param)?.to_owned()))
+    })
+}
+
+/// The timeout used for `RunProcess` requests that do not specify a timeout.
+pub const DEFAULT_RUN_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// The maximum number of exited processes to keep the recent output of.
+const MAX_EXITED_OUTPUTS: usize = 32;
+
+/// Reads the default timeout for `RunProcess` requests (in seconds) from the `AGENT_RUN_TIMEOUT`
+/// environment variable or the `agent.run_timeout` kernel parameter, where `0` disables the
+/// timeout.
+pub fn run_timeout_from_env() -> anyhow::Result<Option<Duration>> {
+    let Some(value) = env_or_kernel_param("AGENT_RUN_TIMEOUT", "agent.run_timeout=")
+    else {
+        return Ok(Some(DEFAULT_RUN_TIMEOUT));
+    };
+    match value.parse::<u64>().with_context(|| format!("invalid run timeout: {value}"))? {
+        0 => Ok(None),
+        secs => Ok(Some(Duration::from_secs(secs))),
+    }
+}
+
 pub enum Exit {
     RestartAgent,
     Shutdown,
@@ -75,7 +166,7 @@ pub enum Exit {
 pub fn map_response(result: anyhow::Result<serde_json::Value>) -> Response {
     match result {
         Ok(value) => Response::Value(value),
-        Err(err) => Response::Error { error: err.to_string() },
+        Err(err) => Response::Error { error: format!("{err:#}") },
     }
 }
 
@@ -85,11 +176,75 @@ pub struct AgentState {
     stats: Arc<Mutex<StatsdData>>,
     buf: Vec<u8>,
     subprocesses: HashMap<u32, process::Child>,
+    /// The recent output of spawned processes, kept for a while after the process exits.
+    outputs: HashMap<u32, output::ProcessOutput>,
+    /// The PIDs of exited processes that we still have output for, oldest first.
+    exited: VecDeque<u32>,
+    /// The stdin pipes of subprocesses spawned with [agent_interface::Stdio::Piped] stdin.
+    stdin: HashMap<u32, input::StdinWriter>,
+    /// The job objects used for killing the process tree of each subprocess.
+    #[cfg(windows)]
+    jobs: HashMap<u32, job::Job>,
+    reap_orphans: bool,
+    run_timeout: Option<Duration>,
+    /// Environment variables added to every spawned process (see [agent_interface::GuestSetup]).
+    env: Vec<(OsString, OsString)>,
 }
 
 impl AgentState {
     pub fn new(stats: Arc<Mutex<StatsdData>>) -> Self {
-        Self { stats, buf: vec![], exit: None, subprocesses: HashMap::new(), workdir: None }
+        Self {
+            stats,
+            buf: vec![],
+            exit: None,
+            subprocesses: HashMap::new(),
+            outputs: HashMap::new(),
+            exited: VecDeque::new(),
+            stdin: HashMap::new(),
+            #[cfg(windows)]
+            jobs: HashMap::new(),
+            workdir: None,
+            reap_orphans: false,
+            run_timeout: Some(DEFAULT_RUN_TIMEOUT),
+            env: vec![],
+        }
+    }
+
+    /// Configures the agent to also reap exited processes that it did not spawn (required when
+    /// running as init).
+    pub fn enable_orphan_reaping(&mut self) {
+        self.reap_orphans = true;
+    }
+
+    /// Sets the timeout used for `RunProcess` requests that do not specify a timeout (`None`
+    /// allows processes to run indefinitely).
+    pub fn set_run_timeout(&mut self, timeout: Option<Duration>) {
+        self.run_timeout = timeout;
+    }
+
+    /// Sets the directory that relative paths and processes without a working directory use.
+    pub fn set_workdir(&mut self, workdir: PathBuf) {
+        self.workdir = Some(workdir);
+    }
+
+    /// Fills in the working directory and environment that `subprocess` inherits from the agent.
+    fn apply_defaults(&self, subprocess: &mut agent_interface::RunCommand) {
+        if subprocess.current_dir.is_none() {
+            subprocess.current_dir = self.workdir.clone();
+        }
+        // Variables set later take precedence, so the command's own variables are kept last.
+        subprocess.vars.splice(0..0, self.env.iter().cloned());
+    }
+
+    /// Handles `request`, returning file content as raw bytes so that it can be compactly encoded.
+    pub fn handle(&mut self, request: Request) -> Response {
+        match request {
+            Request::ReadFile { path, offset, len } => match self.read_file(path, offset, len) {
+                Ok(bytes) => Response::Bytes(bytes),
+                Err(err) => Response::Error { error: err.to_string() },
+            },
+            request => map_response(self.handle_request(request)),
+        }
     }
 
     pub fn handle_request(&mut self, request: Request) -> anyhow::Result<serde_json::Value> {
@@ -104,27 +259,61 @@ impl AgentState {
                 self.buf.clear();
                 for entry in self.stats.lock().unwrap().drain_all() {
                     self.buf.extend_from_slice(entry);
+                    // Ensure metrics from different packets are never merged together.
+                    if !entry.ends_with(b"\n") {
+                        self.buf.push(b'\n');
+                    }
                 }
 
                 let entries = std::str::from_utf8(&self.buf)?;
                 return Ok(serde_json::json!(entries));
             }
             Request::RunProcess(mut subprocess) => {
-                if subprocess.current_dir.is_none() {
-                    subprocess.current_dir = self.workdir.clone();
+                self.apply_defaults(&mut subprocess);
+                anyhow::ensure!(
+                    !matches!(subprocess.stdin, agent_interface::Stdio::Piped),
+                    "stdin can only be piped for spawned processes"
+                );
+                if subprocess.timeout.is_none() {
+                    subprocess.timeout = self.run_timeout;
                 }
                 eprintln!("[agent] running: {}", subprocess);
                 let output = subprocess.run()?;
+                if let ExitKind::Hang = output.exit {
+                    eprintln!(
+                        "[agent] killed after exceeding timeout ({:?}): {}",
+                        subprocess.timeout.unwrap_or_default(),
+                        subprocess
+                    );
+                }
                 return Ok(serde_json::json!(output));
             }
             Request::SpawnProcess(mut subprocess) => {
-                if subprocess.current_dir.is_none() {
-                    subprocess.current_dir = self.workdir.clone();
-                }
+                self.apply_defaults(&mut subprocess);
                 eprintln!("[agent] spawning: {}", subprocess);
-                let child = subprocess.spawn()?;
+                let (mut child, output) = match subprocess.capture_output {
+                    true => output::spawn_captured(&subprocess).map(|(c, o)| (c, Some(o)))?,
+                    false => (subprocess.spawn()?, None),
+                };
                 let pid = child.id();
                 eprintln!("[agent] spawned PID={}", pid);
+                // The PID may have been reused, so forget about the output of the old process.
+                self.exited.retain(|x| *x != pid);
+                match output {
+                    Some(output) => self.outputs.insert(pid, output),
+                    None => self.outputs.remove(&pid),
+                };
+                match child.stdin.take() {
+                    Some(stdin) => self.stdin.insert(pid, input::StdinWriter::new(pid, stdin)),
+                    None => self.stdin.remove(&pid),
+                };
+                #[cfg(windows)]
+                match job::Job::for_child(&child) {
+                    Ok(job) => {
+                        self.jobs.insert(pid, job);
+                    }
+                    Err(e) => eprintln!("[agent] failed to create job object for PID={pid}: {e}"),
+                }
                 self.subprocesses.insert(pid, child);
                 return Ok(serde_json::json!(pid));
             }
@@ -133,6 +322,9 @@ impl AgentState {
                     Some(p) => {
                         let exit = p.wait()?;
                         let _ = self.subprocesses.remove(&pid);
+                        self.mark_exited(pid);
+                        #[cfg(windows)]
+                        self.jobs.remove(&pid);
                         Ok(serde_json::json!(exit.code()))
                     }
                     None => Ok(serde_json::json!(null)),
@@ -140,38 +332,49 @@ impl AgentState {
             }
             Request::GetStatus(id) => {
                 return match self.subprocesses.get(&id) {
-                    Some(c) => Ok(serde_json::json!(c.id())),
+                    Some(c) => Ok(serde_json::json!(process_status(c.id()))),
                     None => Ok(serde_json::json!(null)),
                 };
             }
+            Request::GetRecentOutput(pid) => {
+                let Some(output) = self.outputs.get(&pid)
+                else {
+                    return Ok(serde_json::json!(null));
+                };
+                // Make sure the tail includes everything written before an exited process stopped.
+                let running = self
+                    .subprocesses
+                    .get_mut(&pid)
+                    .map_or(false, |child| matches!(child.try_wait(), Ok(None)));
+                if !running {
+                    output.join(Duration::from_secs(1));
+                }
+                return Ok(serde_json::json!(output.recent()));
+            }
+            Request::GetTime => {
+                let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+                return Ok(serde_json::json!(now));
+            }
             Request::KillProcess { pid, signal } => {
                 let result = self.kill_subprocess(pid, signal)?;
                 return Ok(serde_json::json!(result));
             }
+            Request::WriteStdin { pid, data, close } => {
+                let stdin = self
+                    .stdin
+                    .get(&pid)
+                    .with_context(|| format!("no stdin pipe for PID={pid}"))?;
+                let result = stdin.write(data);
+                if close || result.is_err() {
+                    self.stdin.remove(&pid);
+                }
+                result.with_context(|| format!("failed to write to stdin of PID={pid}"))?;
+            }
             Request::ReadFile { path, offset, len } => {
-                let path = match self.workdir.as_ref() {
-                    Some(workdir) => workdir.join(path),
-                    None => path,
-                };
-                let mut file = std::fs::File::open(&path)?;
-
-                let remaining_len = file.metadata()?.len().saturating_sub(offset);
-                let len = match len {
-                    Some(len) => len.min(remaining_len),
-                    None => remaining_len,
-                };
-
-                file.seek(std::io::SeekFrom::Start(offset))?;
-                let mut buf = vec![0; len as usize];
-                file.read_exact(&mut buf)?;
-
-                return Ok(serde_json::json!(buf));
+                return Ok(serde_json::json!(self.read_file(path, offset, len)?));
             }
             Request::StatFile(path) => {
-                let path = match self.workdir.as_ref() {
-                    Some(workdir) => workdir.join(path),
-                    None => path,
-                };
+                let path = self.resolve_path(path);
                 let metadata = std::fs::metadata(&path)?;
                 return Ok(serde_json::json!(agent_interface::DirEntry {
                     path: path.canonicalize()?,
@@ -181,17 +384,49 @@ impl AgentState {
                 }));
             }
             Request::ReadDir(path) => {
-                let path = match self.workdir.as_ref() {
-                    Some(workdir) => workdir.join(path),
-                    None => path,
-                };
+                let path = self.resolve_path(path);
                 let entries = agent_interface::utils::read_dir_entries(&path)
                     .with_context(|| format!("failed to read {}", path.display()))?;
                 return Ok(serde_json::json!(entries));
             }
+            Request::HashDir { path, algorithm } => {
+                let path = self.resolve_path(path);
+                let hashes = builtins::hash_dir(&path, algorithm)?;
+                return Ok(serde_json::json!(hashes));
+            }
             Request::AddEntropy(data) => {
                 add_entropy(&data)?;
             }
+            Request::SetupGuest(setup) => {
+                setup_guest(&setup)?;
+                for (key, value) in setup.env {
+                    eprintln!("[agent] env {key}={value}");
+                    let key = OsString::from(key);
+                    self.env.retain(|(x, _)| *x != key);
+                    self.env.push((key, value.into()));
+                }
+            }
+            Request::WriteFile { path, offset, data } => {
+                builtins::write_file(&self.resolve_path(path), offset, &data)?;
+            }
+            Request::CreateDir(path) => {
+                builtins::create_dir(&self.resolve_path(path))?;
+            }
+            Request::RemovePath(path) => {
+                builtins::remove_path(&self.resolve_path(path))?;
+            }
+            Request::MovePath { src, dst } => {
+                builtins::move_path(&self.resolve_path(src), &self.resolve_path(dst))?;
+            }
+            Request::CopyPath { src, dst } => {
+                builtins::copy_path(&self.resolve_path(src), &self.resolve_path(dst))?;
+            }
+            Request::SetPermissions { path, mode } => {
+                builtins::set_permissions(&self.resolve_path(path), mode)?;
+            }
+            Request::SetEncoding(_) => {
+                anyhow::bail!("encoding can only be changed using a standalone request");
+            }
             Request::Bulk(requests) => {
                 let out: Vec<_> = requests
                     .into_iter()
@@ -203,6 +438,31 @@ impl AgentState {
         Ok(serde_json::json!(null))
     }
 
+    /// Resolves `path` relative to the working directory of the agent.
+    fn resolve_path(&self, path: PathBuf) -> PathBuf {
+        match self.workdir.as_ref() {
+            Some(workdir) => workdir.join(path),
+            None => path,
+        }
+    }
+
+    fn read_file(&self, path: PathBuf, offset: u64, len: Option<u64>) -> anyhow::Result<Vec<u8>> {
+        let path = self.resolve_path(path);
+        let mut file = std::fs::File::open(&path)?;
+
+        let remaining_len = file.metadata()?.len().saturating_sub(offset);
+        let len = match len {
+            Some(len) => len.min(remaining_len),
+            None => remaining_len,
+        };
+
+        file.seek(std::io::SeekFrom::Start(offset))?;
+        let mut buf = vec![0; len as usize];
+        file.read_exact(&mut buf)?;
+
+        Ok(buf)
+    }
+
     pub fn reap_dead(&mut self) {
         let mut dead = vec![];
         for (name, process) in &mut self.subprocesses {
@@ -213,7 +473,32 @@ impl AgentState {
         }
         dead.into_iter().for_each(|dead| {
             self.subprocesses.remove(&dead);
+            self.mark_exited(dead);
+            // Closing the job also kills any descendants that outlived the process.
+            #[cfg(windows)]
+            self.jobs.remove(&dead);
         });
+
+        #[cfg(target_os = "linux")]
+        if self.reap_orphans {
+            init::reap_orphans(|pid| self.subprocesses.contains_key(&pid));
+        }
+    }
+
+    /// Keeps the output of `pid` available after it exits, discarding the output of the oldest
+    /// exited processes once more than [MAX_EXITED_OUTPUTS] are kept.
+    fn mark_exited(&mut self, pid: u32) {
+        self.stdin.remove(&pid);
+        if !self.outputs.contains_key(&pid) {
+            return;
+        }
+        self.exited.retain(|x| *x != pid);
+        self.exited.push_back(pid);
+        while self.exited.len() > MAX_EXITED_OUTPUTS {
+            if let Some(old) = self.exited.pop_front() {
+                self.outputs.remove(&old);
+            }
+        }
     }
 
     fn kill_subprocess(&mut self, key: u32, signal: i32) -> Result<bool, anyhow::Error> {
@@ -227,6 +512,11 @@ impl AgentState {
             #[cfg(not(unix))]
             {
                 let _signal = signal;
+                // Kill the entire process tree (if the process was assigned to a job).
+                #[cfg(windows)]
+                if let Some(job) = self.jobs.remove(&key) {
+                    job.terminate(1)?;
+                }
                 process.kill()?;
             }
 
@@ -235,6 +525,7 @@ impl AgentState {
 
             // Managed to actually kill the subprocess so drop the handle.
             let _ = self.subprocesses.remove(&key);
+            self.mark_exited(key);
             Ok(true)
         }
         else {
@@ -246,10 +537,13 @@ impl AgentState {
         for (_, process) in &mut self.subprocesses {
             let _ = process.kill();
         }
+        self.stdin.clear();
         for (pid, mut process) in self.subprocesses.drain() {
             let exit = process.wait()?;
             eprintln!("[agent] pid={} exit: {:?}", pid, exit);
         }
+        #[cfg(windows)]
+        self.jobs.clear();
         Ok(())
     }
 }
@@ -260,6 +554,122 @@ impl Drop for AgentState {
     }
 }
 
+#[cfg(not(target_os = "linux"))]
+fn process_status(pid: u32) -> ProcessStatus {
+    ProcessStatus::unknown(pid)
+}
+
+/// Gets the status of `pid` and all of its descendants by scanning `/proc`.
+#[cfg(target_os = "linux")]
+fn process_status(pid: u32) -> ProcessStatus {
+    let mut processes = HashMap::new();
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for entry in std::fs::read_dir("/proc").into_iter().flatten().flatten() {
+        let Some(id) = entry.file_name().to_str().and_then(|x| x.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if let Some((ppid, name, state)) = read_proc_stat(id) {
+            children.entry(ppid).or_default().push(id);
+            processes.insert(id, (name, state));
+        }
+    }
+
+    fn build(
+        pid: u32,
+        processes: &HashMap<u32, (String, char)>,
+        children: &HashMap<u32, Vec<u32>>,
+    ) -> ProcessStatus {
+        let Some((name, state)) = processes.get(&pid)
+        else {
+            return ProcessStatus::unknown(pid);
+        };
+        ProcessStatus {
+            pid,
+            name: name.clone(),
+            state: *state,
+            children: children
+                .get(&pid)
+                .into_iter()
+                .flatten()
+                .map(|child| build(*child, processes, children))
+                .collect(),
+        }
+    }
+    build(pid, &processes, &children)
+}
+
+/// Reads the parent PID, name and state of `pid` from `/proc/<pid>/stat`.
+#[cfg(target_os = "linux")]
+fn read_proc_stat(pid: u32) -> Option<(u32, String, char)> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The name is wrapped in parentheses and may itself contain spaces or parentheses.
+    let name = &stat[stat.find('(')? + 1..stat.rfind(')')?];
+    let mut fields = stat[stat.rfind(')')? + 1..].split_whitespace();
+    let state = fields.next()?.chars().next()?;
+    let ppid = fields.next()?.parse().ok()?;
+    Some((ppid, name.to_owned(), state))
+}
+
+fn setup_guest(setup: &agent_interface::GuestSetup) -> anyhow::Result<()> {
+    for (key, value) in &setup.sysctls {
+        eprintln!("[agent] sysctl {key}={value}");
+        let path = PathBuf::from("/proc/sys").join(key.replace('.', "/"));
+        std::fs::write(&path, value).with_context(|| format!("failed to set sysctl: {key}"))?;
+    }
+    for (resource, limit) in &setup.ulimits {
+        eprintln!("[agent] ulimit {resource}={limit:?}");
+        set_ulimit(resource, limit.value()?)
+            .with_context(|| format!("failed to set ulimit: {resource}"))?;
+    }
+    if let Some(secs) = setup.clock {
+        eprintln!("[agent] setting clock to {secs}");
+        set_clock(secs).context("failed to set clock")?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_clock(_secs: u64) -> anyhow::Result<()> {
+    anyhow::bail!("Unable to set clock on target platform")
+}
+
+#[cfg(unix)]
+fn set_clock(secs: u64) -> anyhow::Result<()> {
+    use nix::{
+        sys::time::TimeSpec,
+        time::{clock_settime, ClockId},
+    };
+    clock_settime(ClockId::CLOCK_REALTIME, TimeSpec::new(secs.try_into()?, 0))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_ulimit(_resource: &str, _limit: Option<u64>) -> anyhow::Result<()> {
+    anyhow::bail!("Unable to set resource limits on target platform")
+}
+
+#[cfg(unix)]
+fn set_ulimit(resource: &str, limit: Option<u64>) -> anyhow::Result<()> {
+    use nix::sys::resource::{setrlimit, Resource, RLIM_INFINITY};
+
+    let resource = match resource {
+        "as" => Resource::RLIMIT_AS,
+        "core" => Resource::RLIMIT_CORE,
+        "cpu" => Resource::RLIMIT_CPU,
+        "data" => Resource::RLIMIT_DATA,
+        "fsize" => Resource::RLIMIT_FSIZE,
+        "memlock" => Resource::RLIMIT_MEMLOCK,
+        "nofile" => Resource::RLIMIT_NOFILE,
+        "nproc" => Resource::RLIMIT_NPROC,
+        "stack" => Resource::RLIMIT_STACK,
+        _ => anyhow::bail!("unknown resource"),
+    };
+    let limit = limit.unwrap_or(RLIM_INFINITY);
+    setrlimit(resource, limit, limit)?;
+    Ok(())
+}
+
 #[cfg(not(unix))]
 fn add_entropy(_bytes: &[u32]) -> anyhow::Result<()> {
     anyhow::bail!("Unable add entropy on target platform")
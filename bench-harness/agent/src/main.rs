@@ -5,7 +5,7 @@ use std::{
 };
 
 use agent::{log_collector, AgentState, Exit};
-use agent_interface::{IpcWrapper, Request};
+use agent_interface::{codec::Encoding, IpcWrapper, Request, Response};
 use anyhow::Context;
 
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
@@ -23,30 +23,94 @@ fn main() {
     if let Err(_) = error {
         eprintln!("[agent] encountered fatal error!");
     }
+
+    // The kernel panics if init exits, so keep reaping processes until the VM is shut down.
+    #[cfg(target_os = "linux")]
+    if is_init() {
+        eprintln!("[agent] agent stopped, waiting for shutdown");
+        loop {
+            agent::init::reap_orphans(|_| false);
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
 }
 
 fn run() -> anyhow::Result<()> {
+    let is_init = is_init();
+    #[cfg(target_os = "linux")]
+    if is_init {
+        eprintln!("[agent] running as init");
+        // Errors are not fatal here, since exiting would panic the kernel.
+        if let Err(e) = agent::init::setup() {
+            eprintln!("[agent] init setup failed: {e:?}");
+        }
+    }
+
     let statsd = match std::env::var_os("STATSD") {
         Some(_) => log_collector::spawn(),
         None => Arc::new(Mutex::new(log_collector::StatsdData::new(0))),
     };
     let mut state = AgentState::new(statsd);
+    if is_init {
+        state.enable_orphan_reaping();
+    }
+    match agent::run_timeout_from_env() {
+        Ok(timeout) => state.set_run_timeout(timeout),
+        // A malformed kernel parameter should not prevent the agent from starting.
+        Err(e) => eprintln!(
+            "[agent] WARNING: {e:#}, using default run timeout: {:?}",
+            agent::DEFAULT_RUN_TIMEOUT
+        ),
+    }
 
-    let mut args = std::env::args();
-    let _ = args.next();
-
-    match (args.next().as_deref(), args.next().as_deref()) {
-        (Some("-u"), Some(path)) => listen_unix_socket(&mut state, path)?,
-        (Some("-t"), Some(addr)) => listen_tcp(&mut state, addr)?,
-        (None, None) => listen_vsock(&mut state)?,
-        (_, _) => eprintln!("[agent] invalid arguments"),
+    if let Some(scratch) = agent::ScratchConfig::from_env() {
+        match scratch.prepare() {
+            Ok(()) => {
+                eprintln!("[agent] using scratch directory: {}", scratch.dir.display());
+                state.set_workdir(scratch.dir);
+            }
+            Err(e) if is_init => eprintln!("[agent] failed to prepare scratch directory: {e:?}"),
+            Err(e) => return Err(e),
+        }
     }
 
-    state.kill_all()?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    loop {
+        state.exit = None;
+        let result = match (args.first().map(String::as_str), args.get(1).map(String::as_str)) {
+            (Some("-u"), Some(path)) => listen_unix_socket(&mut state, path),
+            (Some("-t"), Some(addr)) => listen_tcp(&mut state, addr),
+            (None, None) => listen_vsock(&mut state),
+            (_, _) => Err(anyhow::format_err!("invalid arguments")),
+        };
+        let result = result.and_then(|()| state.kill_all());
+
+        // When running as init there is nothing to restart the agent, and returning would panic the
+        // kernel, so log any errors and start listening again.
+        if !is_init {
+            result?;
+            break;
+        }
+        if let Err(e) = result {
+            eprintln!("[agent] error: {e:?}");
+            let _ = state.kill_all();
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    }
 
     Ok(())
 }
 
+#[cfg(target_os = "linux")]
+fn is_init() -> bool {
+    agent::init::is_init()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_init() -> bool {
+    false
+}
+
 fn listen_tcp(state: &mut AgentState, addr: &str) -> anyhow::Result<()> {
     let listener = std::net::TcpListener::bind(addr)
         .with_context(|| format!("Failed to bind to: {}", addr))?;
@@ -97,7 +161,7 @@ fn listen_unix_socket(state: &mut AgentState, path: &str) -> anyhow::Result<()>
 }
 
 #[cfg(not(unix))]
-fn listen_unix_socket(_state: &mut AgentState) -> anyhow::Result<()> {
+fn listen_unix_socket(_state: &mut AgentState, _path: &str) -> anyhow::Result<()> {
     anyhow::bail!("unix connection not supported on current platform");
 }
 
@@ -140,31 +204,38 @@ where
     R: BufRead,
     W: Write,
 {
+    let mut encoding = Encoding::Json;
     let mut request_id = 0;
     let mut buf = vec![];
-    while state.exit.is_none() && reader.read_until(b'\n', &mut buf).is_ok() {
+    while state.exit.is_none() && matches!(encoding.read_frame(&mut reader, &mut buf), Ok(true)) {
         state.reap_dead();
 
-        let result = match serde_json::from_slice::<IpcWrapper<Request>>(&buf) {
+        let mut next_encoding = None;
+        let response = match encoding.decode::<IpcWrapper<Request>>(&buf) {
+            Ok(IpcWrapper { id, body: Request::SetEncoding(new_encoding) }) => {
+                // The response is sent using the current encoding, then we switch.
+                request_id = id;
+                next_encoding = Some(new_encoding);
+                Response::Value(serde_json::Value::Null)
+            }
             Ok(request) => {
                 request_id = request.id;
-                state.handle_request(request.body)
+                state.handle(request.body)
             }
             Err(err) => {
                 request_id += 1;
-                Err(anyhow::format_err!("{}", err))
+                agent::map_response(Err(anyhow::format_err!("{}", err)))
             }
         };
-        buf.clear();
-
-        serde_json::to_writer(&mut std::io::Cursor::new(&mut buf), &IpcWrapper {
-            id: request_id,
-            body: agent::map_response(result),
-        })
-        .context("failed to encode response")?;
-        buf.push(b'\n');
-        writer.write_all(&buf).context("failed to send response")?;
-        buf.clear();
+
+        encoding
+            .write_message(&mut writer, &mut buf, &IpcWrapper { id: request_id, body: response })
+            .context("failed to send response")?;
+
+        if let Some(new_encoding) = next_encoding {
+            eprintln!("[agent] using {new_encoding:?} encoding");
+            encoding = new_encoding;
+        }
     }
 
     match state.exit {
@@ -183,6 +254,11 @@ where
 }
 
 fn shutdown_vm() -> anyhow::Result<()> {
+    #[cfg(target_os = "linux")]
+    if is_init() {
+        return agent::init::shutdown();
+    }
+
     let result = process::Command::new("reboot").spawn().context("failed to run `reboot`")?.wait();
     match result {
         Ok(status) if status.success() => Ok(()),
@@ -0,0 +1,147 @@
+//! Keeps the most recent output of spawned processes in memory, so that it can be included in error
+//! reports even when the output is redirected to files (see [Request::GetRecentOutput]).
+//!
+//! [Request::GetRecentOutput]: agent_interface::Request::GetRecentOutput
+
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use agent_interface::{RecentOutput, RunCommand, Stdio};
+use anyhow::Context;
+
+/// The maximum number of bytes kept for each stream.
+const CAPACITY: usize = 64 * 1024;
+
+/// A bounded buffer containing the last [CAPACITY] bytes written to a stream.
+#[derive(Clone, Default)]
+struct RingBuffer(Arc<Mutex<VecDeque<u8>>>);
+
+impl RingBuffer {
+    fn push(&self, data: &[u8]) {
+        let mut buf = self.0.lock().unwrap();
+        let data = &data[data.len().saturating_sub(CAPACITY)..];
+        let overflow = (buf.len() + data.len()).saturating_sub(CAPACITY);
+        buf.drain(..overflow);
+        buf.extend(data);
+    }
+
+    fn to_string_lossy(&self) -> String {
+        let buf = self.0.lock().unwrap();
+        let (a, b) = buf.as_slices();
+        String::from_utf8_lossy(&[a, b].concat()).into_owned()
+    }
+}
+
+/// The recent stdout and stderr of a spawned process.
+#[derive(Clone, Default)]
+pub struct ProcessOutput {
+    stdout: RingBuffer,
+    stderr: RingBuffer,
+    /// The threads copying the output of the process.
+    relays: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl ProcessOutput {
+    /// Waits (for at most `timeout`) for the output of an exited process to be fully copied.
+    ///
+    /// The relay threads only exit once every process holding the pipes exits, so this gives up
+    /// after `timeout` if, for example, a descendant of the process is still running.
+    pub fn join(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let mut relays = self.relays.lock().unwrap();
+        while relays.iter().any(|x| !x.is_finished()) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        for relay in std::mem::take(&mut *relays) {
+            if relay.is_finished() {
+                let _ = relay.join();
+            }
+        }
+    }
+
+    pub fn recent(&self) -> RecentOutput {
+        RecentOutput {
+            stdout: self.stdout.to_string_lossy(),
+            stderr: self.stderr.to_string_lossy(),
+        }
+    }
+}
+
+/// Spawns `cmd` with its stdout and stderr captured through pipes. The output is copied to the
+/// destination configured in `cmd` by background threads, while keeping the most recent output.
+pub fn spawn_captured(cmd: &RunCommand) -> anyhow::Result<(std::process::Child, ProcessOutput)> {
+    let dir = cmd.current_dir.as_deref();
+    let stdout_dst = open_writer(&cmd.stdout, dir, || Box::new(std::io::stdout()))?;
+    let stderr_dst = open_writer(&cmd.stderr, dir, || Box::new(std::io::stderr()))?;
+
+    let mut command = cmd.get_command()?;
+    command.stdout(std::process::Stdio::piped()).stderr(std::process::Stdio::piped());
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("failed to start {}", cmd.program.display()))?;
+
+    let output = ProcessOutput::default();
+    let mut relays = output.relays.lock().unwrap();
+    if let Some(stdout) = child.stdout.take() {
+        relays.push(copy_in_background(stdout, stdout_dst, output.stdout.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        relays.push(copy_in_background(stderr, stderr_dst, output.stderr.clone()));
+    }
+    drop(relays);
+    Ok((child, output))
+}
+
+fn open_writer(
+    stdio: &Stdio,
+    dir: Option<&Path>,
+    inherit: impl FnOnce() -> Box<dyn Write + Send>,
+) -> anyhow::Result<Option<Box<dyn Write + Send>>> {
+    Ok(match stdio {
+        Stdio::Null => None,
+        Stdio::Inherit => Some(inherit()),
+        Stdio::File(path) => {
+            let path = dir.map(|dir| dir.join(path)).unwrap_or_else(|| path.clone());
+            let file = std::fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(&path)
+                .map_err(|e| agent_interface::utils::write_error(e, &path))?;
+            Some(Box::new(file))
+        }
+        Stdio::Piped => anyhow::bail!("only stdin can be piped"),
+    })
+}
+
+/// Copies `src` to `dst` (if any) and `recent` until `src` is closed. The thread exits when the
+/// process (and any descendants that inherited the pipe) exits.
+fn copy_in_background(
+    mut src: impl Read + Send + 'static,
+    mut dst: Option<Box<dyn Write + Send>>,
+    recent: RingBuffer,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut buf = vec![0; 16 * 1024];
+        loop {
+            let n = match src.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            recent.push(&buf[..n]);
+            if let Some(writer) = dst.as_mut() {
+                if let Err(e) = writer.write_all(&buf[..n]).and_then(|_| writer.flush()) {
+                    eprintln!("[agent] failed to write process output: {e}");
+                    dst = None;
+                }
+            }
+        }
+    })
+}
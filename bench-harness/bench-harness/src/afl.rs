@@ -3,26 +3,72 @@ use std::path::{Path, PathBuf};
 use agent_interface::{client::Agent, DirEntry};
 use anyhow::Context;
 
-/// AFL plot data in AFL++ v4 format.
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
-pub struct PlotDataRowV4 {
+/// The version of AFL that generated a `plot_data` file, detected from its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlotDataFormat {
+    /// AFL 2.x (`unix_time, cycles_done, cur_path, paths_total, ..., execs_per_sec`).
+    V2,
+    /// AFL++ 3.x, which adds `total_execs` and `edges_found` to the v2 columns (older releases use
+    /// `unix_time` instead of `relative_time`).
+    V3,
+    /// AFL++ 4.x, which renames several columns (e.g. `cur_path` -> `cur_item`).
+    V4,
+}
+
+impl PlotDataFormat {
+    /// Detects the format from the names of the columns in the header.
+    fn from_header(columns: &[String]) -> Self {
+        if columns.iter().any(|x| x == "cur_item" || x == "corpus_count") {
+            Self::V4
+        }
+        else if columns.iter().any(|x| x == "total_execs") {
+            Self::V3
+        }
+        else {
+            Self::V2
+        }
+    }
+
+    /// The columns used by the format when the file has no header.
+    fn default_columns(&self) -> &'static [&'static str] {
+        match self {
+            Self::V2 => &PlotDataRow::FIELDS[..11],
+            Self::V3 | Self::V4 => PlotDataRow::FIELDS,
+        }
+    }
+}
+
+/// Alternative names used by older versions of AFL for the columns of [PlotDataRow].
+const COLUMN_ALIASES: &[(&str, &str)] = &[
+    ("unix_time", "relative_time"),
+    ("cur_path", "cur_item"),
+    ("paths_total", "corpus_count"),
+    ("unique_crashes", "saved_crashes"),
+    ("unique_hangs", "saved_hangs"),
+];
+
+/// AFL plot data normalized to the AFL++ v4 format (see [PlotData::parse]).
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct PlotDataRow {
+    /// Seconds since the start of the fuzzing session.
     pub relative_time: u64,
     pub cycles_done: u64,
     pub cur_item: u64,
     pub corpus_count: u64,
     pub pending_total: u64,
     pub pending_favs: u64,
-    #[serde(deserialize_with = "deserialize_percent")]
     pub map_size: f64,
     pub saved_crashes: u64,
     pub saved_hangs: u64,
     pub max_depth: u64,
     pub execs_per_sec: f64,
+    /// Total executions (estimated from `execs_per_sec` for AFL 2.x).
     pub total_execs: u64,
+    /// Number of edges found (not recorded by AFL 2.x, where it is always 0).
     pub edges_found: u64,
 }
 
-impl PlotDataRowV4 {
+impl PlotDataRow {
     pub const FIELDS: &'static [&'static str] = &[
         "relative_time",
         "cycles_done",
@@ -38,61 +84,130 @@ impl PlotDataRowV4 {
         "total_execs",
         "edges_found",
     ];
+}
 
-    pub fn from_reader<R>(reader: R) -> anyhow::Result<Vec<Self>>
+/// The contents of an AFL `plot_data` file.
+pub struct PlotData {
+    pub format: PlotDataFormat,
+    pub rows: Vec<PlotDataRow>,
+}
+
+impl PlotData {
+    /// Parses `plot_data` generated by AFL 2.x, AFL++ 3.x or AFL++ 4.x, normalizing the rows to
+    /// the AFL++ v4 format.
+    pub fn parse<R>(mut reader: R) -> anyhow::Result<Self>
     where
         R: std::io::Read,
     {
-        parse_plot_data(reader)
-    }
-}
-
-fn deserialize_percent<'de, D>(deserializer: D) -> Result<f64, D::Error>
-where
-    D: serde::de::Deserializer<'de>,
-{
-    use std::borrow::{Borrow, Cow};
+        let mut data = String::new();
+        reader.read_to_string(&mut data).context("failed to read plot data")?;
 
-    let text: Cow<str> = serde::Deserialize::deserialize(deserializer)?;
-    let text_str: &str = text.borrow();
+        // The header is a comment on the first line, e.g. `# relative_time, cycles_done, ...`.
+        let header = data.lines().next().and_then(|x| x.strip_prefix('#')).map(|x| {
+            x.split(',')
+                .map(|name| {
+                    let name = name.trim();
+                    let alias = COLUMN_ALIASES.iter().find(|(old, _)| *old == name);
+                    alias.map_or(name, |(_, new)| *new)
+                })
+                .map(String::from)
+                .collect::<Vec<_>>()
+        });
+        let absolute_time = data.lines().next().is_some_and(|x| x.contains("unix_time"));
 
-    Ok(text_str
-        .trim_end_matches("%")
-        .parse::<f64>()
-        .map_err(|err| serde::de::Error::custom(err.to_string()))?
-        * 100.0)
-}
-
-pub fn parse_plot_data<R, T>(reader: R) -> anyhow::Result<Vec<T>>
-where
-    R: std::io::Read,
-    T: serde::de::DeserializeOwned,
-{
-    let mut reader = csv::ReaderBuilder::new()
-        .trim(csv::Trim::All)
-        .comment(Some(b'#'))
-        .has_headers(false)
-        .from_reader(reader);
-
-    let mut out = vec![];
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .comment(Some(b'#'))
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(data.as_bytes());
 
-    let mut total_errors = 0;
-    for result in reader.deserialize() {
-        let data: T = match result {
-            Ok(data) => data,
-            Err(e) => {
-                total_errors += 1;
-                tracing::warn!("parse error: {:#}", e);
-                if total_errors > 10 {
-                    anyhow::bail!(">10 parse errors: {:#}", e);
+        let mut rows = vec![];
+        let mut total_errors = 0;
+        let mut columns = header;
+        let mut estimated_execs = 0.0;
+        let mut start_time = None;
+        let mut prev_time = 0;
+        for result in reader.records() {
+            let row = result.map_err(anyhow::Error::from).and_then(|record| {
+                let columns = columns.get_or_insert_with(|| {
+                    let format = match record.len() {
+                        11 => PlotDataFormat::V2,
+                        _ => PlotDataFormat::V4,
+                    };
+                    format.default_columns().iter().map(|x| x.to_string()).collect()
+                });
+                parse_row(columns, &record)
+            });
+            let mut row = match row {
+                Ok(row) => row,
+                Err(e) => {
+                    total_errors += 1;
+                    tracing::warn!("parse error: {:#}", e);
+                    if total_errors > 10 {
+                        anyhow::bail!(">10 parse errors: {:#}", e);
+                    }
+                    continue;
                 }
-                continue;
+            };
+
+            if absolute_time {
+                let start = *start_time.get_or_insert(row.relative_time);
+                row.relative_time = row.relative_time.saturating_sub(start);
+            }
+            if !columns.as_ref().is_some_and(|x| x.iter().any(|x| x == "total_execs")) {
+                let elapsed = row.relative_time.saturating_sub(prev_time);
+                estimated_execs += row.execs_per_sec * elapsed as f64;
+                row.total_execs = estimated_execs as u64;
             }
+            prev_time = row.relative_time;
+            rows.push(row);
+        }
+
+        let format = match columns.as_deref() {
+            Some(columns) => PlotDataFormat::from_header(columns),
+            None => PlotDataFormat::V4,
         };
-        out.push(data);
+        Ok(Self { format, rows })
     }
+}
 
-    Ok(out)
+fn parse_row(columns: &[String], record: &csv::StringRecord) -> anyhow::Result<PlotDataRow> {
+    if record.len() < columns.len() {
+        anyhow::bail!("expected {} columns, found {}: {record:?}", columns.len(), record.len());
+    }
+
+    let mut row = PlotDataRow::default();
+    for (name, value) in columns.iter().zip(record.iter()) {
+        let int = || value.parse::<u64>().with_context(|| format!("invalid {name}: {value}"));
+        let float = || value.parse::<f64>().with_context(|| format!("invalid {name}: {value}"));
+        match name.as_str() {
+            "relative_time" => row.relative_time = int()?,
+            "cycles_done" => row.cycles_done = int()?,
+            "cur_item" => row.cur_item = int()?,
+            "corpus_count" => row.corpus_count = int()?,
+            "pending_total" => row.pending_total = int()?,
+            "pending_favs" => row.pending_favs = int()?,
+            "map_size" => row.map_size = parse_percent(value)?,
+            "saved_crashes" => row.saved_crashes = int()?,
+            "saved_hangs" => row.saved_hangs = int()?,
+            "max_depth" => row.max_depth = int()?,
+            "execs_per_sec" => row.execs_per_sec = float()?,
+            "total_execs" => row.total_execs = int()?,
+            "edges_found" => row.edges_found = int()?,
+            // Columns added by newer versions (e.g. `var_byte_count` in AFL++ 4.x) are ignored.
+            _ => {}
+        }
+    }
+    Ok(row)
+}
+
+fn parse_percent(value: &str) -> anyhow::Result<f64> {
+    let percent = value
+        .trim_end_matches('%')
+        .parse::<f64>()
+        .with_context(|| format!("invalid percentage: {value}"))?;
+    Ok(percent * 100.0)
 }
 
 /// Get the list of afl inputs inside of `path`
@@ -132,7 +247,6 @@ pub fn get_relative_time(file: &DirEntry, start_time: std::time::SystemTime) ->
 }
 
 /// Get the input id encoded in the filename by AFL++.
-#[allow(unused)]
 pub fn get_input_id(file: &Path) -> Option<u64> {
     let name = file.file_name().and_then(|x| x.to_str())?;
     let (id, _) = name
@@ -0,0 +1,125 @@
+//! Support for running the analysis pipeline (`plot-data` and `plot`) on the results of a
+//! benchmark.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use indexmap::IndexMap;
+
+use crate::{config::TaskConfig, XShellExt};
+
+/// The name of the artifact that contains the coverage data for a trial.
+const COVERAGE_FILE: &str = "coverage.json";
+
+/// A subset of the analysis configuration format (see `analysis/plot-data/src/config.rs`).
+#[derive(serde::Serialize)]
+struct AnalysisConfig {
+    data: IndexMap<String, Vec<AnalysisDataset>>,
+    time_resolution: u64,
+    trials: u32,
+    reference: String,
+}
+
+#[derive(serde::Serialize)]
+struct AnalysisDataset {
+    source: AnalysisSource,
+}
+
+#[derive(serde::Serialize)]
+enum AnalysisSource {
+    MultiFuzzBench { glob: String, duration: u64 },
+}
+
+struct Group {
+    trials: u32,
+    duration: Duration,
+}
+
+/// Generates an analysis config for the coverage artifacts declared by `tasks`.
+///
+/// Trials are expected to be stored as: `[fuzzer]/[binary]/[trial]/coverage.json` and each
+/// `[fuzzer]` directory is treated as a separate dataset.
+fn generate_config(tasks: &[TaskConfig], time_resolution: u64) -> anyhow::Result<String> {
+    let cwd = std::env::current_dir()?;
+
+    let mut groups: IndexMap<PathBuf, Group> = IndexMap::new();
+    for task in tasks {
+        let Some(coverage) = task.artifacts.iter().find(|x| x.path.ends_with(COVERAGE_FILE)) else {
+            continue;
+        };
+        let Some(fuzzer_dir) = Path::new(&coverage.path).ancestors().nth(3) else {
+            continue;
+        };
+        let duration: Duration = task.tasks.iter().map(|x| x.estimate_duration()).sum();
+        let group = groups
+            .entry(cwd.join(fuzzer_dir.strip_prefix(".").unwrap_or(fuzzer_dir)))
+            .or_insert(Group { trials: 0, duration: Duration::ZERO });
+        group.trials += 1;
+        group.duration = group.duration.max(duration);
+    }
+    anyhow::ensure!(
+        !groups.is_empty(),
+        "benchmark does not declare any `{COVERAGE_FILE}` artifacts"
+    );
+
+    let mut data = IndexMap::new();
+    for (dir, group) in &groups {
+        let name = dir
+            .file_name()
+            .map_or_else(|| dir.display().to_string(), |x| x.to_string_lossy().into());
+        let glob = dir.join("*").join("*").join(COVERAGE_FILE).display().to_string();
+        let duration = match group.duration.is_zero() {
+            true => Duration::from_secs(60 * 60 * 24),
+            false => group.duration,
+        };
+        let source = AnalysisSource::MultiFuzzBench { glob, duration: duration.as_millis() as u64 };
+        data.entry(name).or_insert_with(Vec::new).push(AnalysisDataset { source });
+    }
+
+    let config = AnalysisConfig {
+        reference: data.keys().next().cloned().unwrap_or_default(),
+        trials: groups.values().map(|x| x.trials).max().unwrap_or(0),
+        time_resolution,
+        data,
+    };
+    Ok(ron::ser::to_string_pretty(&config, ron::ser::PrettyConfig::default())?)
+}
+
+/// Generates an analysis config for `tasks` in `output` then runs `plot-data` and `plot` from the
+/// analysis workspace at `analysis_dir`. Results are written to `output/output`.
+pub(crate) fn run_analysis(
+    tasks: &[TaskConfig],
+    analysis_dir: &Path,
+    output: &Path,
+    time_resolution: u64,
+) -> anyhow::Result<()> {
+    let config = generate_config(tasks, time_resolution)?;
+
+    std::fs::create_dir_all(output)
+        .with_context(|| format!("failed to create: {}", output.display()))?;
+    let config_path = output.join("config.ron");
+    std::fs::write(&config_path, config)
+        .with_context(|| format!("failed to write: {}", config_path.display()))?;
+    tracing::info!("analysis config written to: {}", config_path.display());
+
+    let manifest = analysis_dir
+        .join("Cargo.toml")
+        .canonicalize()
+        .with_context(|| format!("analysis workspace not found at: {}", analysis_dir.display()))?;
+
+    let sh = xshell::Shell::new()?;
+    sh.change_dir(output);
+    for bin in ["plot-data", "plot"] {
+        xshell::cmd!(sh, "cargo run --release --manifest-path {manifest} --bin {bin}")
+            .env("CONFIG", "config.ron")
+            .trace_cmd()
+            .run()
+            .with_context(|| format!("failed to run {bin}"))?;
+    }
+    tracing::info!("analysis results saved to: {}", output.join("output").display());
+
+    Ok(())
+}
@@ -0,0 +1,403 @@
+//! Packaging of completed campaigns into a single compressed archive for cold storage (`archive`).
+//!
+//! Archives are zstd compressed tar files. The first entry is a manifest (`MANIFEST.json`) listing
+//! the size and SHA256 digest of every other file in the archive, a copy of the manifest is also
+//! saved next to the archive so that the contents can be inspected without decompressing it.
+//!
+//! Each entry is compressed as a separate zstd frame, and the offset of each frame is saved to an
+//! index next to the archive (`[archive].index.json`), so that a single file can be extracted
+//! without decompressing the entries before it.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{Read, Seek, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+    config::{Config, TaskConfig},
+    setup::sha256_for_path,
+    utils::{HumanReadableSize, Variables},
+};
+
+const MANIFEST_NAME: &str = "MANIFEST.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum EntryKind {
+    /// Files used to configure the campaign (never deleted).
+    Config,
+    /// Files saved by the tasks of the campaign.
+    Result,
+    /// Additional files (e.g. reports or logs) included by the user.
+    Extra,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ManifestEntry {
+    path: String,
+    kind: EntryKind,
+    size: u64,
+    sha256: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Manifest {
+    bench: String,
+    created: String,
+    files: Vec<ManifestEntry>,
+}
+
+/// The location of the zstd frame containing the tar entry of a file in the archive.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct IndexEntry {
+    path: String,
+    offset: u64,
+    compressed_size: u64,
+}
+
+pub(crate) struct ArchiveOptions<'a> {
+    /// Path to the benchmark configuration file.
+    pub bench: &'a Path,
+    /// Path to the harness configuration file (included files are also archived).
+    pub config: &'a Path,
+    pub output: &'a Path,
+    /// The directory containing the results of the campaign. Only files within this directory are
+    /// deleted.
+    pub results: &'a Path,
+    /// Additional files or directories to include in the archive.
+    pub extra: &'a [PathBuf],
+    /// The zstd compression level.
+    pub level: i32,
+    /// Delete the archived results and extra files (within `results`) after verifying the archive.
+    pub delete: bool,
+}
+
+/// Packages the results of `tasks` (the directories containing the artifacts of each task) along
+/// with the config files and any extra files into an archive, then verifies it.
+pub(crate) fn archive_campaign(
+    config: &Config,
+    tasks: &[TaskConfig],
+    options: ArchiveOptions,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(!options.output.exists(), "{} already exists", options.output.display());
+
+    let mut files: BTreeMap<String, (PathBuf, EntryKind)> = BTreeMap::new();
+    let mut add = |path: &Path, kind: EntryKind| -> anyhow::Result<()> {
+        for entry in walkdir::WalkDir::new(path).sort_by_file_name() {
+            let entry = entry.with_context(|| format!("error reading: {}", path.display()))?;
+            if entry.file_type().is_file() {
+                let name = archive_name(entry.path())?;
+                files.entry(name).or_insert_with(|| (entry.path().to_owned(), kind));
+            }
+        }
+        Ok(())
+    };
+
+    add(options.bench, EntryKind::Config)?;
+    for path in config_files(config, tasks, options.config) {
+        add(&path, EntryKind::Config)?;
+    }
+    let mut missing = 0;
+    for dir in result_dirs(config, tasks, options.results)? {
+        match dir.exists() {
+            true => add(&dir, EntryKind::Result)?,
+            false => missing += 1,
+        }
+    }
+    if missing != 0 {
+        tracing::warn!("{missing} result directories do not exist (task not run?)");
+    }
+    for path in options.extra {
+        anyhow::ensure!(path.exists(), "{} does not exist", path.display());
+        add(path, EntryKind::Extra)?;
+    }
+
+    let mut manifest = Manifest {
+        bench: options.bench.display().to_string(),
+        created: time::OffsetDateTime::now_utc()
+            .format(&time::format_description::well_known::Rfc3339)?,
+        files: vec![],
+    };
+    let mut total_size = 0;
+    for (name, (path, kind)) in &files {
+        let size = path.metadata()?.len();
+        total_size += size;
+        manifest.files.push(ManifestEntry {
+            path: name.clone(),
+            kind: *kind,
+            size,
+            sha256: sha256_for_path(path)?,
+        });
+    }
+    tracing::info!(
+        "archiving {} files ({}) to: {}",
+        files.len(),
+        HumanReadableSize(total_size),
+        options.output.display()
+    );
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    let index = write_archive(options.output, options.level, &manifest_json, &files)
+        .with_context(|| format!("failed to write: {}", options.output.display()))?;
+    let manifest_path = sidecar_path(options.output, "manifest.json");
+    std::fs::write(&manifest_path, &manifest_json)
+        .with_context(|| format!("failed to write: {}", manifest_path.display()))?;
+    let index_path = sidecar_path(options.output, "index.json");
+    std::fs::write(&index_path, serde_json::to_vec_pretty(&index)?)
+        .with_context(|| format!("failed to write: {}", index_path.display()))?;
+
+    verify_archive(options.output, &manifest)
+        .with_context(|| format!("failed to verify: {}", options.output.display()))?;
+    tracing::info!(
+        "archive verified: {} ({})",
+        options.output.display(),
+        HumanReadableSize(options.output.metadata()?.len())
+    );
+
+    if options.delete {
+        delete_originals(&manifest, &files, options.results)?;
+    }
+
+    Ok(())
+}
+
+/// The harness config, any files it includes, and the templates used by `tasks`.
+fn config_files(config: &Config, tasks: &[TaskConfig], config_path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![config_path.to_owned()];
+    for entry in &config.include {
+        files.push(match config_path.parent() {
+            Some(parent) => parent.join(entry),
+            None => entry.clone(),
+        });
+    }
+    let mut used: Vec<&str> = tasks.iter().map(|x| x.template.as_str()).collect();
+    used.sort_unstable();
+    used.dedup();
+    files.extend(used.into_iter().filter_map(|name| config.templates.get(name).cloned()));
+    files
+}
+
+/// The directories containing the artifacts of each task. Directories outside of `results` (e.g.
+/// the current directory for artifacts without a parent directory) are skipped.
+fn result_dirs(
+    config: &Config,
+    tasks: &[TaskConfig],
+    results: &Path,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut dirs: Vec<PathBuf> = vec![];
+    for task in tasks {
+        let mut vars = Variables::default();
+        vars.insert_all(config.vars.iter().chain(&task.vars).map(|x| x.clone().into()));
+        for artifact in &task.artifacts {
+            if let Some(parent) = Path::new(&vars.expand_vars(&artifact.path)).parent() {
+                dirs.push(parent.to_owned());
+            }
+        }
+    }
+    dirs.sort();
+    dirs.dedup();
+
+    let mut outside = 0;
+    let mut within = vec![];
+    for dir in dirs {
+        match is_within(&dir, results)? {
+            true => within.push(dir),
+            false => outside += 1,
+        }
+    }
+    if outside != 0 {
+        tracing::warn!("skipping {outside} result directories outside of: {}", results.display());
+    }
+    Ok(within)
+}
+
+/// Returns whether `path` is strictly inside the directory `root`.
+fn is_within(path: &Path, root: &Path) -> anyhow::Result<bool> {
+    let path = normalize(path)?;
+    let root = normalize(root)?;
+    Ok(path != root && path.starts_with(&root))
+}
+
+/// Gets the absolute path of `path` (without resolving symlinks).
+fn normalize(path: &Path) -> anyhow::Result<PathBuf> {
+    let absolute = std::env::current_dir()?.join(path);
+    Ok(absolute.components().fold(PathBuf::new(), |mut acc, x| {
+        match x {
+            Component::ParentDir => {
+                acc.pop();
+            }
+            Component::CurDir => {}
+            x => acc.push(x),
+        }
+        acc
+    }))
+}
+
+/// Gets the name to store `path` as in the archive. Paths within the current directory are stored
+/// relative to it, other paths are stored under `external/`.
+fn archive_name(path: &Path) -> anyhow::Result<String> {
+    let cwd = std::env::current_dir()?;
+    let normalized = normalize(path)?;
+    let name = match normalized.strip_prefix(&cwd) {
+        Ok(relative) => relative.to_owned(),
+        Err(_) => Path::new("external").join(normalized.strip_prefix("/").unwrap_or(&normalized)),
+    };
+    Ok(name.to_string_lossy().into_owned())
+}
+
+/// Gets the path of a file saved next to `archive`, e.g. `[archive].manifest.json`.
+fn sidecar_path(archive: &Path, extension: &str) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    path.into()
+}
+
+/// Writes a zstd stream as a sequence of independent frames.
+struct FrameWriter {
+    encoder: Option<zstd::Encoder<'static, std::fs::File>>,
+    level: i32,
+}
+
+impl FrameWriter {
+    fn new(file: std::fs::File, level: i32) -> std::io::Result<Self> {
+        Ok(Self { encoder: Some(zstd::Encoder::new(file, level)?), level })
+    }
+
+    /// Ends the current frame, returning the offset of the next frame in the file.
+    fn end_frame(&mut self) -> std::io::Result<u64> {
+        let mut file = self.encoder.take().unwrap().finish()?;
+        let offset = file.stream_position()?;
+        self.encoder = Some(zstd::Encoder::new(file, self.level)?);
+        Ok(offset)
+    }
+
+    fn finish(mut self) -> std::io::Result<std::fs::File> {
+        self.encoder.take().unwrap().finish()
+    }
+}
+
+impl Write for FrameWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.encoder.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.encoder.as_mut().unwrap().flush()
+    }
+}
+
+/// Writes the archive, returning the location of each file in it.
+fn write_archive(
+    output: &Path,
+    level: i32,
+    manifest: &[u8],
+    files: &BTreeMap<String, (PathBuf, EntryKind)>,
+) -> anyhow::Result<Vec<IndexEntry>> {
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::fs::File::create(output)?;
+    let mut builder = tar::Builder::new(FrameWriter::new(file, level)?);
+    let mut index = vec![];
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST_NAME, manifest)?;
+    let mut offset = builder.get_mut().end_frame()?;
+    index.push(IndexEntry { path: MANIFEST_NAME.into(), offset: 0, compressed_size: offset });
+
+    for (name, (path, _)) in files {
+        builder
+            .append_path_with_name(path, name)
+            .with_context(|| format!("failed to add: {}", path.display()))?;
+        let end = builder.get_mut().end_frame()?;
+        index.push(IndexEntry { path: name.clone(), offset, compressed_size: end - offset });
+        offset = end;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(index)
+}
+
+/// Checks that every file in the manifest is present in the archive with the expected digest.
+fn verify_archive(path: &Path, manifest: &Manifest) -> anyhow::Result<()> {
+    use sha2::Digest;
+
+    let mut expected: HashMap<&str, &str> =
+        manifest.files.iter().map(|x| (x.path.as_str(), x.sha256.as_str())).collect();
+
+    let file = std::fs::File::open(path)?;
+    let mut archive = tar::Archive::new(zstd::Decoder::new(file)?);
+    let mut buf = vec![0; 64 * 1024];
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        if name == MANIFEST_NAME {
+            continue;
+        }
+
+        let mut hasher = sha2::Sha256::new();
+        loop {
+            match entry.read(&mut buf)? {
+                0 => break,
+                n => hasher.update(&buf[..n]),
+            }
+        }
+        let digest = crate::setup::hex(&hasher.finalize());
+        match expected.remove(name.as_str()) {
+            Some(sha256) if sha256 == digest => {}
+            Some(_) => anyhow::bail!("digest mismatch for: {name}"),
+            None => anyhow::bail!("unexpected file in archive: {name}"),
+        }
+    }
+
+    if !expected.is_empty() {
+        anyhow::bail!("{} file(s) missing from archive", expected.len());
+    }
+    Ok(())
+}
+
+/// Deletes the archived results and extra files within `results`, along with any directories left
+/// empty.
+fn delete_originals(
+    manifest: &Manifest,
+    files: &BTreeMap<String, (PathBuf, EntryKind)>,
+    results: &Path,
+) -> anyhow::Result<()> {
+    let mut deleted = 0;
+    let mut kept = 0;
+    let mut dirs = vec![];
+    for entry in manifest.files.iter().filter(|x| x.kind != EntryKind::Config) {
+        let (path, _) = &files[&entry.path];
+        if !is_within(path, results)? {
+            kept += 1;
+            continue;
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("failed to delete: {}", path.display()))?;
+        deleted += 1;
+        dirs.extend(path.parent().map(Path::to_owned));
+    }
+
+    // Remove directories (within `results`) that are now empty, starting with the deepest.
+    dirs.sort_by_key(|x| std::cmp::Reverse(x.components().count()));
+    dirs.dedup();
+    for dir in dirs {
+        for dir in dir.ancestors() {
+            if !is_within(dir, results)? || std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+
+    tracing::info!("deleted {deleted} archived files");
+    if kept != 0 {
+        tracing::warn!("kept {kept} archived files outside of: {}", results.display());
+    }
+    Ok(())
+}
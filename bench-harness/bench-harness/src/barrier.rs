@@ -0,0 +1,115 @@
+//! Synchronized start of grouped trials (see `--sync-start`). Trials that are compared head-to-head
+//! (e.g. every fuzzer on the same binary) are held at a barrier once their worker is ready, and
+//! start together once every trial in the group has arrived. This avoids confounds caused by the
+//! trials of a comparison running at different times (e.g. under different host load).
+
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+/// How often a waiting task checks whether the benchmark was canceled.
+const CANCEL_POLL: Duration = Duration::from_secs(1);
+
+struct State {
+    /// The number of tasks in the group that have not arrived yet.
+    remaining: usize,
+    /// When the first task of the group arrived.
+    first_arrival: Option<Instant>,
+    released: bool,
+}
+
+/// Holds the tasks of a group until all of them are ready to start.
+pub(crate) struct StartBarrier {
+    group: String,
+    /// The number of tasks in the group.
+    size: usize,
+    /// The maximum time to hold a task for, after which the group is started without the tasks
+    /// that have not arrived (e.g. because they failed to boot).
+    timeout: Duration,
+    state: Mutex<State>,
+    released: Condvar,
+}
+
+impl StartBarrier {
+    pub fn new(group: String, size: usize, timeout: Duration) -> Self {
+        Self {
+            group,
+            size,
+            timeout,
+            state: Mutex::new(State { remaining: size, first_arrival: None, released: false }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// The number of tasks in the group.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Waits until every task in the group is ready to start (or the timeout expires). Tasks that
+    /// arrive after the group was started (e.g. retries) are not held.
+    pub fn wait(&self, task: &str) {
+        let mut state = self.state.lock();
+        if state.released {
+            tracing::info!("{task} started after the other tasks of sync group {}", self.group);
+            return;
+        }
+
+        let now = Instant::now();
+        let first_arrival = *state.first_arrival.get_or_insert(now);
+        state.remaining = state.remaining.saturating_sub(1);
+        if state.remaining == 0 {
+            tracing::info!(
+                "sync group {} started (held for {:.1}s)",
+                self.group,
+                first_arrival.elapsed().as_secs_f64()
+            );
+            state.released = true;
+            self.released.notify_all();
+            return;
+        }
+
+        tracing::debug!(
+            "{task} waiting for {} task(s) in sync group {}",
+            state.remaining,
+            self.group
+        );
+        let deadline = now + self.timeout;
+        while !state.released {
+            if crate::should_stop() {
+                return;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                tracing::warn!(
+                    "sync group {} started without {} task(s) that were not ready within {}",
+                    self.group,
+                    state.remaining,
+                    crate::utils::HumanReadableDuration(self.timeout)
+                );
+                state.released = true;
+                self.released.notify_all();
+                return;
+            }
+            self.released.wait_for(&mut state, CANCEL_POLL.min(deadline - now));
+        }
+    }
+}
+
+/// Gets the sync group of a task from its tag: the values of `keys` in the tag (e.g. `binary` and
+/// `trial`), or `None` if the tag is missing any of the keys.
+pub(crate) fn group_of(tag: &str, keys: &[String]) -> Option<String> {
+    if !crate::utils::is_tag(tag) {
+        return None;
+    }
+    let entries: Vec<(&str, &str)> =
+        tag.split(';').skip(1).filter_map(|entry| entry.split_once('=')).collect();
+    let values = keys
+        .iter()
+        .map(|key| {
+            let (_, value) = entries.iter().find(|(k, _)| k == key)?;
+            Some(format!("{key}={value}"))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(values.join(";"))
+}
@@ -0,0 +1,108 @@
+//! Checks the guest clock against the host clock while tasks are running. Coverage timestamps are
+//! recorded inside the guest, so a drifting guest clock invalidates time-based results.
+//!
+//! Only changes in the offset between the guest and the host are considered drift, since the guest
+//! clock may have intentionally been set to a fixed time (see [agent_interface::GuestSetup]).
+
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use agent_interface::client::Agent;
+
+/// The maximum drift (in milliseconds) allowed before a warning is reported (see
+/// [set_max_drift]).
+static MAX_DRIFT_MS: AtomicU64 = AtomicU64::new(1000);
+
+/// Sets the maximum drift of the guest clock allowed before a warning is reported.
+pub(crate) fn set_max_drift(max: Duration) {
+    MAX_DRIFT_MS.store(max.as_millis() as u64, Ordering::Relaxed);
+}
+
+fn drift_threshold() -> Duration {
+    Duration::from_millis(MAX_DRIFT_MS.load(Ordering::Relaxed))
+}
+
+thread_local! {
+    /// The largest drift (in seconds) observed on the current thread since the last call to
+    /// [take_max_drift]. Each worker runs its tasks on its own thread, so this is the drift of the
+    /// task the worker is running.
+    static MAX_DRIFT: Cell<Option<f64>> = const { Cell::new(None) };
+}
+
+/// Gets the largest drift (in seconds) observed by any [ClockMonitor] on the current thread since
+/// the last call, or `None` if the clock was never measured.
+pub(crate) fn take_max_drift() -> Option<f64> {
+    MAX_DRIFT.with(|x| x.take())
+}
+
+/// Tracks the drift of the guest clock over the lifetime of a task.
+#[derive(Default)]
+pub(crate) struct ClockMonitor {
+    /// The offset between the guest and host clocks (in seconds) when first checked.
+    baseline: Option<f64>,
+    /// The largest drift observed so far (in seconds).
+    max_drift: f64,
+    warned: bool,
+    /// Set if the agent does not support reading the clock.
+    disabled: bool,
+}
+
+impl ClockMonitor {
+    /// Compares the guest clock with the host clock, returning the current drift (in seconds) or
+    /// `None` if no measurement was taken.
+    pub fn check(&mut self, agent: &mut dyn Agent) -> Option<f64> {
+        if self.disabled {
+            return None;
+        }
+
+        let start = Instant::now();
+        let host_before = unix_secs(SystemTime::now());
+        let guest = match agent.get_time() {
+            Ok(guest) => guest.as_secs_f64(),
+            Err(e) => {
+                tracing::debug!("disabling guest clock checks: {e:#}");
+                self.disabled = true;
+                return None;
+            }
+        };
+        // The guest time of a replayed transcript is unrelated to the current host time.
+        if crate::transcript::is_replaying() {
+            return None;
+        }
+        // Skip measurements where the round trip alone is comparable to the allowed drift.
+        let round_trip = start.elapsed();
+        if round_trip > drift_threshold() / 2 {
+            tracing::debug!("skipping guest clock check (round trip: {round_trip:?})");
+            return None;
+        }
+        let host = host_before + round_trip.as_secs_f64() / 2.0;
+
+        let offset = guest - host;
+        let drift = offset - *self.baseline.get_or_insert(offset);
+        self.max_drift = self.max_drift.max(drift.abs());
+        MAX_DRIFT.with(|x| x.set(Some(x.get().unwrap_or(0.0).max(drift.abs()))));
+
+        if !self.warned && drift.abs() > drift_threshold().as_secs_f64() {
+            tracing::warn!(
+                "guest clock drifted by {:.3}s relative to the host (max: {:?}), time-based \
+                coverage data may be inaccurate",
+                drift,
+                drift_threshold()
+            );
+            self.warned = true;
+        }
+        Some(drift)
+    }
+
+    /// The largest drift (in seconds) observed by [Self::check].
+    pub fn max_drift(&self) -> f64 {
+        self.max_drift
+    }
+}
+
+pub(crate) fn unix_secs(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
@@ -1,9 +1,17 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
 
 use anyhow::Context;
 use indexmap::IndexMap;
 
-use crate::{firecracker, image_builder::ImageSource, tasks::DynamicTask, worker::LocalWorker};
+use crate::{
+    firecracker,
+    image_builder::ImageSource,
+    tasks::{Artifact, DynamicTask},
+    worker::LocalWorker,
+};
 
 fn default_cache_dir() -> PathBuf {
     ".harness-cache".into()
@@ -69,6 +77,7 @@ pub(crate) struct Config {
     #[serde(default)]
     pub vars: Vec<KeyValue>,
     pub local_worker: Option<LocalWorker>,
+    pub ssh_worker: Option<crate::ssh::SshConfig>,
     #[serde(flatten)]
     pub cache: CacheConfig,
     pub firecracker: Option<FirecrackerBin>,
@@ -79,11 +88,30 @@ pub(crate) struct Config {
     #[serde(default)]
     pub templates: HashMap<String, PathBuf>,
 
+    /// Agent binaries (built for each architecture) that are copied to `/bin/agent` in root file
+    /// systems. If the host architecture is missing, the agent built by the image is used.
+    #[serde(default)]
+    pub agents: HashMap<Arch, PathBuf>,
+
+    /// Environment variables passed to every process started by the agents of all workers (e.g.
+    /// `ASAN_OPTIONS`). Variables set by an instance's `env` or by a task take precedence.
+    #[serde(default)]
+    pub guest_env: BTreeMap<String, String>,
+
     #[serde(flatten)]
     pub data: ConfigData,
 }
 
 impl Config {
+    /// Gets the agent binary to copy into root file systems for `arch` instances.
+    pub(crate) fn get_agent(&self, arch: Arch) -> anyhow::Result<Option<&Path>> {
+        match self.agents.get(&arch) {
+            Some(path) => Ok(Some(path)),
+            None if arch == Arch::host() => Ok(None),
+            None => anyhow::bail!("no agent binary configured for {arch} (add it to `[agents]`)"),
+        }
+    }
+
     pub(crate) fn get_task(&self, name: &str) -> anyhow::Result<TaskConfig> {
         self.data
             .tasks
@@ -133,6 +161,11 @@ pub enum MountKind {
 
     /// Re-use a duplicated image from a prior run.
     ReuseDuplicate,
+
+    /// Like [MountKind::Duplicate], except the copy shares unmodified blocks with the original
+    /// image (using a reflink) when supported by the filesystem of the tmp directory. Falls back
+    /// to a sparse copy otherwise.
+    CopyOnWrite,
 }
 
 #[derive(serde::Deserialize)]
@@ -150,28 +183,145 @@ fn default_5s() -> u64 {
     5
 }
 
+/// The architecture of an instance.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Arch {
+    X86_64,
+    Aarch64,
+}
+
+impl Arch {
+    /// The architecture of the host running the harness.
+    pub fn host() -> Self {
+        match std::env::consts::ARCH {
+            "aarch64" => Self::Aarch64,
+            _ => Self::X86_64,
+        }
+    }
+
+    /// The platform to pass to `docker build` when building images for this architecture.
+    pub fn docker_platform(self) -> &'static str {
+        match self {
+            Self::X86_64 => "linux/amd64",
+            Self::Aarch64 => "linux/arm64",
+        }
+    }
+}
+
+impl Default for Arch {
+    fn default() -> Self {
+        Self::host()
+    }
+}
+
+impl std::fmt::Display for Arch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::X86_64 => f.write_str("x86_64"),
+            Self::Aarch64 => f.write_str("aarch64"),
+        }
+    }
+}
+
 #[derive(serde::Deserialize)]
 pub(crate) struct Instance {
     #[serde(default = "default_5s")]
     pub boot_delay_sec: u64,
+    /// The architecture of the VM, selects the agent copied into the root file system.
+    #[serde(default)]
+    pub arch: Arch,
     pub machine: firecracker::MachineConfig,
     pub rootfs: DriveConfig,
     pub drives: Vec<DriveConfig>,
     #[serde(default = "default_true")]
     pub recreate_workdir: bool,
+    /// A TAP network device attached to the VM (the VM has no network access if not specified).
+    #[serde(default)]
+    pub network: Option<firecracker::NetworkConfig>,
+    /// Sysctls and ulimits applied by the agent before any tasks are run.
+    #[serde(flatten)]
+    pub setup: agent_interface::GuestSetup,
 }
 
 #[derive(serde::Deserialize)]
 pub(crate) struct DockerInstance {
     pub build_path: PathBuf,
     pub mount: Vec<DriveConfig>,
+    /// Additional patterns for files ignored when checking whether the image needs to be rebuilt.
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// Sysctls and ulimits applied by the agent before any tasks are run.
+    #[serde(flatten)]
+    pub setup: agent_interface::GuestSetup,
+}
+
+impl DockerInstance {
+    /// Checks for setup that cannot be applied inside of the (unprivileged) container, so that it
+    /// is reported when the config is loaded instead of when each worker starts.
+    pub(crate) fn validate(&self, name: &str) -> anyhow::Result<()> {
+        if !self.setup.sysctls.is_empty() {
+            anyhow::bail!(
+                "docker instance {name}: sysctls cannot be set inside unprivileged containers \
+                (set them on the host instead)"
+            );
+        }
+        if self.setup.clock.is_some() {
+            anyhow::bail!(
+                "docker instance {name}: the clock cannot be set inside unprivileged containers"
+            );
+        }
+        Ok(())
+    }
 }
 
+/// Added to the default timeout of each task to allow for booting the VM and collecting results.
+const TIMEOUT_GRACE: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+
 #[derive(Clone, Debug, serde::Deserialize)]
 pub(crate) struct TaskConfig {
     pub instance: String,
     pub vars: Vec<KeyValue>,
     pub tasks: Vec<DynamicTask>,
+    /// Artifacts that are verified to exist after all tasks have completed.
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+    /// The maximum time the task may run for before the VM (or container) running it is killed
+    /// (see `--timeout-factor` for the default).
+    #[serde(default, deserialize_with = "crate::utils::parse_duration_opt")]
+    pub timeout: Option<std::time::Duration>,
+    /// How the task is retried after infrastructure failures.
+    #[serde(default)]
+    pub retry: crate::tasks::RetryPolicy,
+    /// The name of the template this task was expanded from.
+    #[serde(skip)]
+    pub template: String,
+    /// The trial this task was expanded for.
+    #[serde(skip)]
+    pub trial: usize,
+    /// The tag used to attribute data collected by this task, exposed to the task as `TAG`.
+    #[serde(skip)]
+    pub tag: String,
+}
+
+impl TaskConfig {
+    /// The expected duration of running all of the tasks.
+    pub fn estimate_duration(&self) -> std::time::Duration {
+        self.tasks.iter().map(|x| x.estimate_duration()).sum()
+    }
+
+    /// The time after which the task is considered to be stuck, either configured explicitly or
+    /// derived from the estimated duration. Tasks without an estimate have no default timeout.
+    pub fn timeout(&self, factor: f64) -> Option<std::time::Duration> {
+        if self.timeout.is_some() {
+            return self.timeout;
+        }
+        let estimate = self.estimate_duration();
+        if factor <= 0.0 || estimate.is_zero() {
+            return None;
+        }
+        Some(estimate.mul_f64(factor) + TIMEOUT_GRACE)
+    }
 }
 
 #[derive(Debug, Clone)]
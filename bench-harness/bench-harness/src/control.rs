@@ -0,0 +1,105 @@
+//! Control socket for adjusting the worker pool while a benchmark is running, e.g.:
+//!
+//! ```text
+//! echo "add 4" | nc -U bench.sock
+//! echo "retire 2" | nc -U bench.sock
+//! echo "status" | nc -U bench.sock
+//! ```
+
+#[cfg(unix)]
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+};
+use std::{path::Path, sync::Arc};
+
+#[cfg(unix)]
+use anyhow::Context;
+
+use crate::{
+    utils::DeleteOnDrop,
+    worker::{WorkerFactory, WorkerPool},
+};
+
+#[cfg(not(unix))]
+pub(crate) fn spawn_control_server(
+    _path: &Path,
+    _pool: Arc<WorkerPool>,
+    _factory: WorkerFactory,
+) -> anyhow::Result<DeleteOnDrop> {
+    anyhow::bail!("the control socket is not supported on the current platform")
+}
+
+/// Starts listening for commands on `path`. The socket is removed when the returned guard is
+/// dropped.
+#[cfg(unix)]
+pub(crate) fn spawn_control_server(
+    path: &Path,
+    pool: Arc<WorkerPool>,
+    factory: WorkerFactory,
+) -> anyhow::Result<DeleteOnDrop> {
+    // Remove the socket from a previous run (if it exists).
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("failed to bind control socket: {}", path.display()))?;
+    let guard = DeleteOnDrop(Some(path.to_owned()));
+    tracing::info!("listening for commands on: {}", path.display());
+
+    let parent = tracing::Span::current();
+    std::thread::Builder::new().name("control".into()).spawn(move || {
+        let _guard = parent.enter();
+        for stream in listener.incoming() {
+            let result = stream
+                .context("connect error")
+                .and_then(|stream| handle_connection(stream, &pool, &factory));
+            if let Err(e) = result {
+                tracing::warn!("control socket error: {e:#}");
+            }
+        }
+    })?;
+
+    Ok(guard)
+}
+
+#[cfg(unix)]
+fn handle_connection(
+    stream: UnixStream,
+    pool: &WorkerPool,
+    factory: &WorkerFactory,
+) -> anyhow::Result<()> {
+    let mut writer = stream.try_clone().context("error cloning stream")?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let response = match handle_command(line.trim(), pool, factory) {
+            Ok(()) => format!("ok: {} active workers", pool.active_workers()),
+            Err(e) => format!("error: {e:#}"),
+        };
+        writeln!(writer, "{response}")?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn handle_command(command: &str, pool: &WorkerPool, factory: &WorkerFactory) -> anyhow::Result<()> {
+    let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+    let count = || -> anyhow::Result<usize> {
+        arg.trim().parse().with_context(|| format!("invalid worker count: {arg}"))
+    };
+    match name {
+        "add" => {
+            let count = count()?;
+            tracing::info!("adding {count} workers");
+            for _ in 0..count {
+                factory.add_worker(pool)?;
+            }
+        }
+        "retire" => {
+            let count = count()?;
+            tracing::info!("retiring {count} workers");
+            pool.retire_workers(count)?;
+        }
+        "status" => {}
+        _ => anyhow::bail!("unknown command: {command} (expected: add <n>, retire <n>, status)"),
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+//! Support for task types implemented outside of the harness (e.g. site-specific result
+//! collectors), so that they can be used in benchmarks without modifying `tasks.rs`.
+//!
+//! Task types are registered under a `kind` before running the harness:
+//!
+//! ```ignore
+//! fn main() {
+//!     bench_harness::custom_tasks::register("CollectTraces", |config| {
+//!         Ok(Box::new(CollectTraces { dst: config.get("dst").cloned().unwrap_or_default() }))
+//!     });
+//!     bench_harness::main();
+//! }
+//! ```
+//!
+//! Then used in task templates as: `Custom(kind: "CollectTraces", config: {"dst": "..."})`.
+//! Variables in the config are not expanded automatically, instead the variables of the task are
+//! passed to [Runable::run].
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use parking_lot::lock_api::RawMutex;
+
+pub use agent_interface::client::Agent;
+
+pub use crate::{tasks::Runable, utils::Variables};
+
+/// Creates a task from the `config` of a `Custom` task.
+pub type TaskFactory = fn(&BTreeMap<String, String>) -> anyhow::Result<Box<dyn Runable>>;
+
+static REGISTRY: parking_lot::Mutex<BTreeMap<String, TaskFactory>> =
+    parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, BTreeMap::new());
+
+/// Registers `factory` for creating `Custom` tasks of `kind`, replacing any existing factory.
+pub fn register(kind: &str, factory: TaskFactory) {
+    REGISTRY.lock().insert(kind.to_owned(), factory);
+}
+
+/// Creates a task of `kind` from `config`.
+pub(crate) fn create(
+    kind: &str,
+    config: &BTreeMap<String, String>,
+) -> anyhow::Result<Box<dyn Runable>> {
+    let registry = REGISTRY.lock();
+    let Some(factory) = registry.get(kind).copied() else {
+        let known: Vec<_> = registry.keys().collect();
+        anyhow::bail!("unknown custom task kind: {kind} (registered: {known:?})");
+    };
+    drop(registry);
+    factory(config).with_context(|| format!("invalid config for custom task: {kind}"))
+}
+
+/// Checks that all custom tasks in `tasks` can be created, so that mistakes are reported when the
+/// benchmark is loaded rather than after the fuzzer has run.
+pub(crate) fn validate(tasks: &[crate::tasks::DynamicTask]) -> anyhow::Result<()> {
+    for task in tasks {
+        match task {
+            crate::tasks::DynamicTask::Custom { kind, config } => drop(create(kind, config)?),
+            crate::tasks::DynamicTask::TaskList { tasks } => validate(tasks)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
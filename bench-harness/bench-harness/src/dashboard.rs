@@ -0,0 +1,309 @@
+//! Live terminal dashboard showing the progress of a benchmark (`bench --ui`).
+//!
+//! Workers report their state through the functions in this module, which do nothing unless the
+//! dashboard is running. While the dashboard is active, log messages are written to [LOG_PATH]
+//! instead of the terminal, and the most recent messages are shown at the bottom of the screen.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write as _,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use parking_lot::{lock_api::RawMutex, Mutex};
+
+/// File that log messages are written to while the dashboard is running.
+pub(crate) const LOG_PATH: &str = "bench-harness.log";
+
+/// The number of log messages shown at the bottom of the dashboard.
+const LOG_LINES: usize = 8;
+
+/// How often the dashboard is redrawn.
+const REFRESH_RATE: Duration = Duration::from_secs(1);
+
+static STATE: Mutex<Option<State>> =
+    parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, None);
+
+static RECENT_LOGS: Mutex<VecDeque<String>> =
+    parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, VecDeque::new());
+
+struct State {
+    start: Instant,
+    total_tasks: usize,
+    estimated: Duration,
+    completed: usize,
+    failed: usize,
+    workers: BTreeMap<usize, WorkerStatus>,
+    /// The latest statsd metrics reported by each task (keyed by the task's `TAG`).
+    metrics: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+#[derive(Default)]
+struct WorkerStatus {
+    /// The reason the worker stopped (e.g. retired or quarantined), or `None` if it is running.
+    stopped: Option<&'static str>,
+    task: Option<RunningTask>,
+    completed: usize,
+}
+
+struct RunningTask {
+    name: String,
+    tag: Option<String>,
+    start: Instant,
+    estimated: Duration,
+}
+
+/// Keeps the dashboard running until dropped.
+pub(crate) struct Dashboard {
+    stop: Option<crossbeam_channel::Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        // Leave the final state of the dashboard visible after exiting the alternate screen.
+        let frame = STATE.lock().take().map(|state| state.render(usize::MAX, usize::MAX));
+        let mut stdout = std::io::stdout().lock();
+        let _ = write!(stdout, "\x1b[?25h\x1b[?1049l{}", frame.unwrap_or_default());
+        let _ = stdout.flush();
+    }
+}
+
+/// Starts the dashboard for a benchmark consisting of `total_tasks` tasks.
+pub(crate) fn start(total_tasks: usize, estimated: Duration) -> anyhow::Result<Dashboard> {
+    *STATE.lock() = Some(State {
+        start: Instant::now(),
+        total_tasks,
+        estimated,
+        completed: 0,
+        failed: 0,
+        workers: BTreeMap::new(),
+        metrics: BTreeMap::new(),
+    });
+
+    let (stop, stop_rx) = crossbeam_channel::bounded::<()>(0);
+    let thread = std::thread::Builder::new().name("dashboard".into()).spawn(move || {
+        // Switch to the alternate screen, so that the terminal is restored when we exit.
+        print!("\x1b[?1049h\x1b[?25l");
+        loop {
+            let (width, height) = terminal_size();
+            if let Some(state) = STATE.lock().as_ref() {
+                let frame = state.render(width, height);
+                let mut stdout = std::io::stdout().lock();
+                let _ = write!(stdout, "\x1b[H\x1b[2J{frame}");
+                let _ = stdout.flush();
+            }
+            crossbeam_channel::select! {
+                recv(stop_rx) -> _ => break,
+                default(REFRESH_RATE) => {}
+            }
+        }
+    })?;
+
+    Ok(Dashboard { stop: Some(stop), thread: Some(thread) })
+}
+
+fn with_state(f: impl FnOnce(&mut State)) {
+    if let Some(state) = STATE.lock().as_mut() {
+        f(state);
+    }
+}
+
+/// Records that worker `id` has started.
+pub(crate) fn worker_started(id: usize) {
+    with_state(|state| {
+        state.workers.insert(id, WorkerStatus::default());
+    });
+}
+
+/// Records that worker `id` will not run any more tasks.
+pub(crate) fn worker_stopped(id: usize, reason: &'static str) {
+    with_state(|state| {
+        let worker = state.workers.entry(id).or_default();
+        worker.stopped = Some(reason);
+        worker.task = None;
+    });
+}
+
+/// Records that worker `id` started running the task `name`, which is expected to run for
+/// `estimated`.
+pub(crate) fn task_started(id: usize, name: &str, tag: Option<&str>, estimated: Duration) {
+    with_state(|state| {
+        state.workers.entry(id).or_default().task = Some(RunningTask {
+            name: name.to_owned(),
+            tag: tag.map(str::to_owned),
+            start: Instant::now(),
+            estimated,
+        });
+    });
+}
+
+/// Records that the task running on worker `id` finished.
+pub(crate) fn task_finished(id: usize, success: bool) {
+    with_state(|state| {
+        let worker = state.workers.entry(id).or_default();
+        worker.completed += 1;
+        if let Some(tag) = worker.task.take().and_then(|x| x.tag) {
+            state.metrics.remove(&tag);
+        }
+        match success {
+            true => state.completed += 1,
+            false => state.failed += 1,
+        }
+    });
+}
+
+/// Updates the metrics shown for the task tagged with `tag`. Counters are accumulated, all other
+/// metrics replace the previous value.
+pub(crate) fn record_stats<'a>(tag: &str, metrics: impl Iterator<Item = (&'a str, &'a str, f64)>) {
+    with_state(|state| {
+        let entry = state.metrics.entry(tag.to_owned()).or_default();
+        for (name, kind, value) in metrics {
+            match kind {
+                "c" => *entry.entry(name.to_owned()).or_default() += value,
+                _ => {
+                    entry.insert(name.to_owned(), value);
+                }
+            }
+        }
+    });
+}
+
+impl State {
+    fn render(&self, width: usize, height: usize) -> String {
+        let mut lines = vec![];
+
+        let finished = self.completed + self.failed;
+        lines.push(format!(
+            "bench-harness: {finished}/{} tasks finished ({} failed), elapsed: {} (estimated: {})",
+            self.total_tasks,
+            self.failed,
+            hms(self.start.elapsed()),
+            hms(self.estimated),
+        ));
+        lines.push(String::new());
+        lines.push(format!(
+            "{:<10} {:<12} {:<10} {:>23}  {}",
+            "WORKER", "STATE", "TASK", "ELAPSED/ESTIMATED", "TAG"
+        ));
+        for (id, worker) in &self.workers {
+            let state = match (&worker.task, worker.stopped) {
+                (_, Some(reason)) => reason,
+                (Some(_), None) => "running",
+                (None, None) => "idle",
+            };
+            let Some(task) = &worker.task else {
+                lines.push(format!("worker#{id:02}  {state:<12} ({} done)", worker.completed));
+                continue;
+            };
+            let elapsed = task.start.elapsed();
+            let progress = match task.estimated.is_zero() {
+                true => String::new(),
+                false => {
+                    let percent = 100.0 * elapsed.as_secs_f64() / task.estimated.as_secs_f64();
+                    format!(" {percent:>3.0}%")
+                }
+            };
+            let time = format!("{}/{}{progress}", hms(elapsed), hms(task.estimated));
+            let tag = task.tag.as_deref().unwrap_or("");
+            lines.push(format!("worker#{id:02}  {state:<12} {:<10} {time:>23}  {tag}", task.name));
+
+            if let Some(metrics) = task.tag.as_ref().and_then(|x| self.metrics.get(x)) {
+                let mut line = String::from("    ");
+                for (name, value) in metrics {
+                    let _ = write!(line, "{name}={value} ");
+                }
+                lines.push(line);
+            }
+        }
+
+        let logs = RECENT_LOGS.lock();
+        if !logs.is_empty() {
+            lines.push(String::new());
+            lines.push(format!("Recent log messages (full log: {LOG_PATH}):"));
+            lines.extend(logs.iter().cloned());
+        }
+
+        // Keep the header and the most recent log messages visible on small terminals.
+        if lines.len() > height {
+            let skip = lines.len() - height;
+            lines.drain(3..(3 + skip).min(lines.len()));
+        }
+
+        let mut out = String::new();
+        for line in lines {
+            out.extend(line.chars().take(width));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Formats `duration` as `HH:MM:SS`.
+fn hms(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
+
+/// Gets the size of the terminal (in columns and rows).
+#[cfg(unix)]
+fn terminal_size() -> (usize, usize) {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } != 0
+        || size.ws_col == 0
+    {
+        return (120, 40);
+    }
+    (size.ws_col as usize, size.ws_row.max(1) as usize - 1)
+}
+
+#[cfg(not(unix))]
+fn terminal_size() -> (usize, usize) {
+    (120, 40)
+}
+
+/// Log destination used while the dashboard is running, writing messages to [LOG_PATH] and
+/// keeping the most recent messages for display.
+pub(crate) struct LogWriter {
+    file: Mutex<std::fs::File>,
+}
+
+impl LogWriter {
+    pub fn new() -> anyhow::Result<Self> {
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(LOG_PATH)?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for LogWriter {
+    type Writer = LogLine<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogLine(&self.file)
+    }
+}
+
+pub(crate) struct LogLine<'a>(&'a Mutex<std::fs::File>);
+
+impl Write for LogLine<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().write_all(buf)?;
+        let mut logs = RECENT_LOGS.lock();
+        for line in String::from_utf8_lossy(buf).lines().filter(|x| !x.trim().is_empty()) {
+            if logs.len() >= LOG_LINES {
+                logs.pop_front();
+            }
+            logs.push_back(line.to_owned());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.lock().flush()
+    }
+}
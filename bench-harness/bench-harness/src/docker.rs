@@ -27,15 +27,138 @@ pub struct DockerSource {
     /// Empty folders to create in the file system.
     #[serde(default)]
     pub create_dirs: Vec<PathBuf>,
+
+    /// Additional patterns (in `.dockerignore` format) for files in `build_path` that are ignored
+    /// when checking whether the image needs to be rebuilt.
+    #[serde(default)]
+    pub ignore: Vec<String>,
 }
 
-pub(crate) fn build_image(tag: &str, root: &Path, no_cache: bool) -> anyhow::Result<()> {
+/// Builds the docker image `tag` from the context at `root`.
+///
+/// The build is skipped if the image exists and the content of the build context (excluding files
+/// matched by `.dockerignore` or `ignore`) has not changed since the last build. The hash of the
+/// context is recorded in `cache_dir`.
+///
+/// If `platform` is set the image is built for the target platform (e.g. `linux/arm64`) instead
+/// of the platform of the host.
+pub(crate) fn build_image(
+    tag: &str,
+    root: &Path,
+    ignore: &[String],
+    cache_dir: &Path,
+    no_cache: bool,
+    platform: Option<&str>,
+) -> anyhow::Result<()> {
+    let mut hash = context_hash(root, ignore)
+        .with_context(|| format!("error hashing docker context: {}", root.display()))?;
+    if let Some(platform) = platform {
+        hash.push_str(&format!(" {platform}"));
+    }
+    let hash_path = cache_dir.join(format!("docker-{}.hash", tag.replace(['/', ':'], "_")));
+
+    if !no_cache
+        && image_exists(tag)?
+        && std::fs::read_to_string(&hash_path).is_ok_and(|prev| prev.trim() == hash)
+    {
+        tracing::info!("docker context for {tag} unchanged, skipping build");
+        return Ok(());
+    }
+
     let no_cache = no_cache.then(|| "--no-cache");
+    let platform = platform.map(|x| format!("--platform={x}"));
     let sh = xshell::Shell::new()?;
-    xshell::cmd!(sh, "docker build -t {tag} {root} {no_cache...}").trace_cmd().run()?;
+    xshell::cmd!(sh, "docker build -t {tag} {root} {no_cache...} {platform...}")
+        .trace_cmd()
+        .run()?;
+
+    std::fs::create_dir_all(cache_dir)?;
+    std::fs::write(&hash_path, &hash)
+        .with_context(|| format!("failed to write: {}", hash_path.display()))?;
     Ok(())
 }
 
+/// Checks whether the docker image `tag` exists.
+fn image_exists(tag: &str) -> anyhow::Result<bool> {
+    let sh = xshell::Shell::new()?;
+    let output = xshell::cmd!(sh, "docker image inspect {tag}").quiet().output()?;
+    Ok(output.status.success())
+}
+
+/// Computes a hash of the paths and contents of all files in the docker context at `root`.
+fn context_hash(root: &Path, ignore: &[String]) -> anyhow::Result<String> {
+    use sha2::Digest;
+
+    let mut patterns = vec![];
+    let dockerignore = root.join(".dockerignore");
+    if dockerignore.exists() {
+        let content = std::fs::read_to_string(&dockerignore)
+            .with_context(|| format!("failed to read: {}", dockerignore.display()))?;
+        // Note: exceptions (`!`) are not supported, so files that are re-included by an exception
+        // are not part of the hash.
+        patterns.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|x| !x.is_empty() && !x.starts_with('#') && !x.starts_with('!'))
+                .map(ignore_pattern_to_regex),
+        );
+    }
+    patterns.extend(ignore.iter().map(|x| ignore_pattern_to_regex(x)));
+    let patterns = patterns.into_iter().collect::<anyhow::Result<Vec<_>>>()?;
+
+    let is_ignored = |path: &Path| {
+        let path = path.strip_prefix(root).unwrap_or(path).to_string_lossy();
+        !path.is_empty() && patterns.iter().any(|x| x.is_match(&path))
+    };
+
+    let mut hasher = sha2::Sha256::new();
+    let walker = walkdir::WalkDir::new(root).sort_by_file_name().into_iter();
+    for entry in walker.filter_entry(|entry| !is_ignored(entry.path())) {
+        let entry = entry?;
+        let path = entry.path();
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+
+        let file_type = entry.file_type();
+        if file_type.is_file() {
+            let mut file = std::fs::File::open(path)
+                .with_context(|| format!("failed to open: {}", path.display()))?;
+            std::io::copy(&mut file, &mut hasher)
+                .with_context(|| format!("error reading from: {}", path.display()))?;
+        } else if file_type.is_symlink() {
+            hasher.update(std::fs::read_link(path)?.to_string_lossy().as_bytes());
+        }
+        hasher.update([0]);
+    }
+
+    Ok(crate::setup::hex(&hasher.finalize()[..]))
+}
+
+/// Converts a `.dockerignore` style pattern to a regex that matches paths relative to the context
+/// root.
+fn ignore_pattern_to_regex(pattern: &str) -> anyhow::Result<regex::Regex> {
+    let mut regex = String::from("^");
+    let mut chars = pattern.trim_start_matches("./").trim_matches('/').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                match chars.next_if_eq(&'/') {
+                    Some(_) => regex.push_str("(.*/)?"),
+                    None => regex.push_str(".*"),
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push_str("(/.*)?$");
+    regex::Regex::new(&regex).with_context(|| format!("invalid ignore pattern: {pattern}"))
+}
+
 /// Get the size of a docker image
 pub(crate) fn get_image_size(config: &DockerSource) -> anyhow::Result<u64> {
     let tag = &config.tag;
@@ -180,23 +303,52 @@ impl Container {
         Ok(())
     }
 
+    /// Runs `image` in the background, publishing each port in `ports` to a random port on the
+    /// loopback interface of the host (see [Container::host_addr]).
     pub fn run_detached(
         image: &str,
         mounts: &[Mount],
+        ports: &[u16],
         args: &[impl AsRef<OsStr>],
     ) -> anyhow::Result<Self> {
         let mut cmd = Command::new("docker");
 
-        let (uid, gid) = get_uid_gid();
-        cmd.args(["run", "-u", &format!("{uid}:{gid}"), "-d"]);
+        cmd.args(["run", "-d"]);
+        if let Some((uid, gid)) = get_uid_gid() {
+            cmd.args(["-u", &format!("{uid}:{gid}")]);
+        }
         for mount in mounts {
             cmd.args(["--mount", &mount.to_arg()]);
         }
+        for port in ports {
+            cmd.args(["-p", &format!("127.0.0.1::{port}")]);
+        }
         cmd.arg(image);
         cmd.args(args);
         Ok(Self { name: run_with_output(cmd)?, removed: false, active: true })
     }
 
+    /// Gets the address on the host that the container `port` was published to.
+    #[cfg_attr(unix, allow(unused))]
+    pub fn host_addr(&self, port: u16) -> anyhow::Result<String> {
+        let mut cmd = Command::new("docker");
+        cmd.args(["port", self.name.as_str(), &format!("{port}/tcp")]);
+        let output = run_with_output(cmd)?;
+        output
+            .lines()
+            .next()
+            .map(|x| x.trim().to_owned())
+            .ok_or_else(|| anyhow::format_err!("port {port} is not published by {}", self.name))
+    }
+
+    /// Gets the process ID (on the host) of the init process of the container.
+    pub fn host_pid(&self) -> anyhow::Result<u32> {
+        let mut cmd = Command::new("docker");
+        cmd.args(["inspect", "-f", "{{.State.Pid}}", self.name.as_str()]);
+        let output = run_with_output(cmd)?;
+        output.parse().with_context(|| format!("invalid pid for {}: {output}", self.name))
+    }
+
     pub fn attach_command(&self) -> Command {
         let mut cmd = Command::new("docker");
         cmd.args(["attach", self.name.as_str()]);
@@ -204,15 +356,32 @@ impl Container {
     }
 }
 
+/// Gets the user and group that the container runs as, so that files written to mounts are owned by
+/// the current user.
 #[cfg(unix)]
-fn get_uid_gid() -> (u32, u32) {
+fn get_uid_gid() -> Option<(u32, u32)> {
     // Safety: these functions are safe to call.
-    unsafe { (libc::getuid(), libc::getgid()) }
+    Some(unsafe { (libc::getuid(), libc::getgid()) })
 }
 
+/// Docker Desktop maps the ownership of files in bind mounts automatically on Windows hosts, so the
+/// default user of the image is used.
 #[cfg(not(unix))]
-fn get_uid_guid() -> (u32, u32) {
-    (1000, 1000)
+fn get_uid_gid() -> Option<(u32, u32)> {
+    None
+}
+
+/// Gets the path of `path` in the format expected by `docker --mount`.
+fn host_path(path: &Path) -> anyhow::Result<String> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("failed to resolve mount: {}", path.display()))?;
+    let path = path
+        .to_str()
+        .ok_or_else(|| anyhow::format_err!("mount path is not valid UTF-8: {}", path.display()))?;
+    // Docker does not accept the verbatim paths (`\\?\C:\...`) returned by `canonicalize` on
+    // Windows.
+    Ok(path.strip_prefix(r"\\?\").unwrap_or(path).to_owned())
 }
 
 
@@ -263,10 +432,25 @@ pub struct DockerConfig {
     pub image: String,
     pub workdir: PathBuf,
     pub mounts: Vec<(PathBuf, PathBuf)>,
+    pub setup: agent_interface::GuestSetup,
+}
+
+/// The port the agent listens on inside of the container when unix sockets are unavailable.
+#[cfg(not(unix))]
+const AGENT_PORT: u16 = 52;
+
+/// How the agent running inside of the container is reached from the host.
+pub enum AgentAddr {
+    /// A unix socket in the (bind mounted) working directory of the container.
+    #[cfg_attr(not(unix), allow(unused))]
+    Unix(PathBuf),
+    /// A TCP port published on the loopback interface (used on hosts without unix sockets).
+    #[cfg_attr(unix, allow(unused))]
+    Tcp(String),
 }
 
 pub struct Worker {
-    pub api_socket: PathBuf,
+    pub agent_addr: AgentAddr,
     #[allow(unused)] // Currently unused, but we could clean up old directories on exit.
     workdir: PathBuf,
     container: Container,
@@ -274,6 +458,38 @@ pub struct Worker {
 }
 
 impl Worker {
+    /// Connects to the agent running inside of the container.
+    pub fn connect_agent(&self) -> anyhow::Result<Box<dyn agent_interface::client::Agent>> {
+        match &self.agent_addr {
+            #[cfg(unix)]
+            AgentAddr::Unix(path) => {
+                let mut agent = agent_interface::client::unix::UnixAgent::connect(path)?;
+                agent.set_encoding(agent_interface::codec::Encoding::from_env())?;
+                Ok(Box::new(agent))
+            }
+            #[cfg(not(unix))]
+            AgentAddr::Unix(path) => {
+                anyhow::bail!("unix sockets are not supported on this platform: {}", path.display())
+            }
+            AgentAddr::Tcp(addr) => agent_interface::client::connect_to_tcp_agent(addr),
+        }
+    }
+
+    /// Gets the process ID (on the host) of the agent running inside of the container.
+    pub fn host_pid(&self) -> anyhow::Result<u32> {
+        self.container.host_pid()
+    }
+
+    /// Returns a function that forcibly stops the container (e.g. from a watchdog thread).
+    pub fn killer(&self) -> impl FnOnce() -> anyhow::Result<()> + Send + 'static {
+        let name = self.container.name.clone();
+        move || {
+            let sh = xshell::Shell::new()?;
+            xshell::cmd!(sh, "docker kill {name}").run().context("failed to kill container")?;
+            Ok(())
+        }
+    }
+
     pub fn wait_for_exit_timeout(mut self, timeout: std::time::Duration) -> anyhow::Result<()> {
         let mut process =
             self.process.take().ok_or_else(|| anyhow::format_err!("docker exited"))?;
@@ -294,32 +510,45 @@ impl Worker {
 }
 
 pub(crate) fn spawn_docker_worker(id: String, config: &DockerConfig) -> anyhow::Result<Worker> {
-    let workdir = config.workdir.join(&id);
+    let workdir = crate::workdirs::worker_dir(&config.workdir, &id)?;
     let api_socket = workdir.join("api.socket");
     crate::utils::prepare_workdir(&api_socket, &workdir, true, true)?;
 
     let mut mounts = vec![Mount {
         type_: MountType::Bind,
-        source: workdir.canonicalize()?.to_str().unwrap().to_owned(),
+        source: host_path(&workdir)?,
         destination: "/var".into(),
     }];
-    mounts.extend(config.mounts.iter().map(|(source, destination)| Mount {
-        type_: MountType::Bind,
-        source: source.canonicalize().unwrap().to_str().unwrap().to_owned(),
-        destination: destination.to_str().unwrap().to_owned(),
-    }));
+    for (source, destination) in &config.mounts {
+        mounts.push(Mount {
+            type_: MountType::Bind,
+            source: host_path(source)?,
+            destination: destination.to_str().unwrap().to_owned(),
+        });
+    }
 
-    let container = Container::run_detached(&config.image, &mounts, &[
-        "/bin/agent",
-        "-u",
-        "/var/api.socket",
-    ])?;
+    // Unix sockets in bind mounts cannot be reached from Windows hosts, so the agent listens on a
+    // published TCP port instead.
+    #[cfg(unix)]
+    let (container, agent_addr) = {
+        let args = ["/bin/agent", "-u", "/var/api.socket"];
+        let container = Container::run_detached(&config.image, &mounts, &[], &args)?;
+        (container, AgentAddr::Unix(api_socket))
+    };
+    #[cfg(not(unix))]
+    let (container, agent_addr) = {
+        let listen = format!("0.0.0.0:{AGENT_PORT}");
+        let args = ["/bin/agent", "-t", listen.as_str()];
+        let container = Container::run_detached(&config.image, &mounts, &[AGENT_PORT], &args)?;
+        let addr = container.host_addr(AGENT_PORT)?;
+        (container, AgentAddr::Tcp(addr))
+    };
 
     let mut attach_cmd = container.attach_command();
     crate::utils::redirect_stdio(&mut attach_cmd, &workdir)?;
     let process = Some(attach_cmd.spawn().with_context(|| format!("Failed to run docker"))?);
 
-    Ok(Worker { container, api_socket, workdir, process })
+    Ok(Worker { container, agent_addr, workdir, process })
 }
 
 pub(crate) fn prepare_instances(
@@ -327,7 +556,14 @@ pub(crate) fn prepare_instances(
 ) -> anyhow::Result<HashMap<String, DockerConfig>> {
     let mut instances = HashMap::new();
     for (name, docker_config) in &config.data.docker {
-        build_image(&name, &docker_config.build_path, false)?;
+        build_image(
+            &name,
+            &docker_config.build_path,
+            &docker_config.ignore,
+            &config.cache.dir,
+            false,
+            None,
+        )?;
 
         let mut mounts = vec![];
         for mount in &docker_config.mount {
@@ -338,6 +574,7 @@ pub(crate) fn prepare_instances(
             workdir: config.cache.dir.join(format!("{name}-workdir")),
             image: name.clone(),
             mounts,
+            setup: docker_config.setup.clone(),
         });
     }
     Ok(instances)
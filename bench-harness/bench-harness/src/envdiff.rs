@@ -0,0 +1,176 @@
+//! Compares the environment variables saved by `SaveEnv` tasks across the trials of a benchmark
+//! (`env-diff`), highlighting variables that are unexpectedly missing or different.
+//!
+//! Trials are grouped by their `TAG` (ignoring the `trial` component), so that trials of the same
+//! configuration are expected to have identical environments. Variables that are different for
+//! every trial (e.g. `SEED` or `TAG`) are assumed to be intentional.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use indexmap::IndexMap;
+
+use crate::{
+    config::{Config, TaskConfig},
+    tasks::DynamicTask,
+    utils::Variables,
+};
+
+/// The environment saved for a single trial.
+struct Snapshot {
+    path: PathBuf,
+    vars: BTreeMap<String, String>,
+}
+
+/// Generates a report of the differences between the environments saved by each task in `tasks`,
+/// writing it to `output`. Variables in `ignore` are excluded from the comparison.
+pub(crate) fn run_env_diff(
+    config: &Config,
+    tasks: &[TaskConfig],
+    output: &Path,
+    ignore: &[String],
+) -> anyhow::Result<()> {
+    let mut groups: IndexMap<String, Vec<Snapshot>> = IndexMap::new();
+    let mut missing = 0;
+    for (i, task) in tasks.iter().enumerate() {
+        let mut vars = Variables::default();
+        vars.insert_all(config.vars.iter().chain(&task.vars).map(|x| x.clone().into()));
+
+        let mut paths = vec![];
+        collect_env_paths(&task.tasks, &vars, &mut paths);
+        if paths.is_empty() {
+            tracing::debug!("task-{i}: no `SaveEnv` task with a `dst`");
+        }
+        for path in paths {
+            if !path.exists() {
+                missing += 1;
+                tracing::warn!("task-{i}: {} does not exist", path.display());
+                continue;
+            }
+            let vars = read_env(&path, ignore)?;
+            let group = match vars.get("TAG") {
+                Some(tag) => group_of_tag(tag),
+                None => task.template.clone(),
+            };
+            groups.entry(group).or_default().push(Snapshot { path, vars });
+        }
+    }
+    anyhow::ensure!(!groups.is_empty(), "no environment snapshots found");
+
+    let report = generate_report(&groups, missing)?;
+    print!("{report}");
+    if let Some(parent) = output.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    std::fs::write(output, report)
+        .with_context(|| format!("failed to write: {}", output.display()))?;
+    tracing::info!("report saved to: {}", output.display());
+    Ok(())
+}
+
+/// Finds the host paths that `SaveEnv` tasks in `tasks` save the environment to.
+fn collect_env_paths(tasks: &[DynamicTask], vars: &Variables, paths: &mut Vec<PathBuf>) {
+    for task in tasks {
+        match task {
+            DynamicTask::SaveEnv { dst: Some(dst), .. } => {
+                paths.push(vars.expand_vars(dst).into())
+            }
+            DynamicTask::TaskList { tasks } => collect_env_paths(tasks, vars, paths),
+            _ => {}
+        }
+    }
+}
+
+fn read_env(path: &Path, ignore: &[String]) -> anyhow::Result<BTreeMap<String, String>> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read: {}", path.display()))?;
+    Ok(data
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .filter(|(key, _)| !ignore.iter().any(|x| x == key))
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .collect())
+}
+
+/// Removes the `trial` component from `tag`, so that all trials of a configuration share a group.
+fn group_of_tag(tag: &str) -> String {
+    tag.split(';').filter(|x| !x.starts_with("trial=")).collect::<Vec<_>>().join(";")
+}
+
+fn generate_report(
+    groups: &IndexMap<String, Vec<Snapshot>>,
+    missing: usize,
+) -> anyhow::Result<String> {
+    let mut out = String::new();
+    let trials: usize = groups.values().map(|x| x.len()).sum();
+    writeln!(out, "# Environment diff: {trials} trial(s) in {} group(s)", groups.len())?;
+    if missing != 0 {
+        writeln!(out, "WARNING: {missing} environment snapshot(s) are missing")?;
+    }
+
+    let mut problems = 0;
+    let mut per_trial = BTreeSet::new();
+    for (group, snapshots) in groups {
+        let mut lines = vec![];
+        let keys: BTreeSet<&String> = snapshots.iter().flat_map(|x| x.vars.keys()).collect();
+        for key in keys {
+            let mut values: BTreeMap<Option<&str>, Vec<&Path>> = BTreeMap::new();
+            for snapshot in snapshots {
+                let value = snapshot.vars.get(key).map(String::as_str);
+                values.entry(value).or_default().push(&snapshot.path);
+            }
+            if values.len() == 1 {
+                continue;
+            }
+            // Variables that are unique to each trial (e.g. seeds) are expected to differ.
+            if !values.contains_key(&None) && values.len() == snapshots.len() {
+                per_trial.insert(key.as_str());
+                continue;
+            }
+
+            lines.push(format!("  {key}:"));
+            for (value, paths) in &values {
+                let value = value.map_or_else(|| "<missing>".to_owned(), |x| format!("{x:?}"));
+                let example = paths[0].display();
+                lines.push(format!("    {value} in {} trial(s) (e.g. {example})", paths.len()));
+            }
+        }
+        if !lines.is_empty() {
+            problems += 1;
+            writeln!(out, "\n## {group} ({} trial(s))", snapshots.len())?;
+            writeln!(out, "{}", lines.join("\n"))?;
+        }
+    }
+    if problems == 0 {
+        writeln!(out, "\nNo differences found between trials of the same group.")?;
+    }
+    if !per_trial.is_empty() {
+        let names = per_trial.into_iter().collect::<Vec<_>>().join(", ");
+        writeln!(out, "\nVariables that are different for every trial (ignored): {names}")?;
+    }
+
+    // Variables that are only set for some groups (e.g. a flag only set for one of the fuzzers).
+    if groups.len() > 1 {
+        let mut present: BTreeMap<&str, usize> = BTreeMap::new();
+        for snapshots in groups.values() {
+            let keys: BTreeSet<&str> =
+                snapshots.iter().flat_map(|x| x.vars.keys()).map(String::as_str).collect();
+            for key in keys {
+                *present.entry(key).or_default() += 1;
+            }
+        }
+        let partial: Vec<_> = present.iter().filter(|(_, n)| **n != groups.len()).collect();
+        if !partial.is_empty() {
+            writeln!(out, "\n## Variables not set in every group")?;
+            for (key, n) in partial {
+                writeln!(out, "  {key}: set in {n}/{} group(s)", groups.len())?;
+            }
+        }
+    }
+
+    Ok(out)
+}
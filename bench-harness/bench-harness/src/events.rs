@@ -0,0 +1,123 @@
+//! Optional structured log of benchmark events (`--event-log`), written as one JSON object per line
+//! so that runs can be analyzed without parsing the human-readable logs.
+
+use std::{io::Write, path::Path, time::SystemTime};
+
+use anyhow::Context;
+use parking_lot::lock_api::RawMutex;
+
+use crate::journal::TaskState;
+
+/// The file that events are appended to, or `None` if events are not recorded.
+static LOG: parking_lot::Mutex<Option<std::fs::File>> =
+    parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, None);
+
+/// Enables recording of events, appending them to `path`.
+pub(crate) fn enable(path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .with_context(|| format!("failed to open event log: {}", path.display()))?;
+    *LOG.lock() = Some(file);
+    Ok(())
+}
+
+/// Returns whether events are being recorded, used to avoid computing event data that would be
+/// discarded.
+pub(crate) fn is_enabled() -> bool {
+    LOG.lock().is_some()
+}
+
+#[derive(serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum Event<'a> {
+    RunStarted {
+        /// Identifies the harness process (see [crate::workdirs]).
+        run_id: &'a str,
+        benchmark: &'a Path,
+        seed: u64,
+        tasks: usize,
+    },
+    TaskStarted {
+        task: &'a str,
+        worker: usize,
+        tag: Option<&'a str>,
+    },
+    TaskFinished {
+        task: &'a str,
+        worker: usize,
+        status: TaskState,
+        duration_secs: f64,
+        error: Option<String>,
+    },
+    /// `task` failed because of the infrastructure running it and will be run again after
+    /// `delay_secs`.
+    TaskRetried {
+        task: &'a str,
+        worker: usize,
+        attempt: usize,
+        error: String,
+        delay_secs: f64,
+    },
+    /// `task` did not finish within its timeout, so the VM or container running it was killed.
+    TaskTimedOut {
+        task: &'a str,
+        worker: &'a str,
+        timeout_secs: f64,
+    },
+    /// The VM or container used for running `task` is ready to accept requests.
+    WorkerBooted {
+        task: &'a str,
+        worker: &'a str,
+        boot_secs: f64,
+    },
+    /// Data from the guest was saved to `dst` on the host.
+    DataCopied {
+        tag: Option<&'a str>,
+        src: &'a Path,
+        dst: &'a Path,
+        /// The size of `dst` after the copy.
+        bytes: u64,
+    },
+    RunFinished {
+        duration_secs: f64,
+    },
+}
+
+#[derive(serde::Serialize)]
+struct Entry<'a> {
+    /// The time the event was recorded (in seconds since the Unix epoch).
+    time: f64,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// Appends `event` to the event log (if enabled). Failures are logged rather than failing the task.
+pub(crate) fn record(event: Event) {
+    let mut log = LOG.lock();
+    let Some(file) = log.as_mut() else { return };
+
+    let entry = Entry { time: crate::clock::unix_secs(SystemTime::now()), event: &event };
+    let result = serde_json::to_vec(&entry).map_err(anyhow::Error::from).and_then(|mut line| {
+        line.push(b'\n');
+        Ok(file.write_all(&line)?)
+    });
+    if let Err(e) = result {
+        tracing::warn!("failed to write to event log: {e:#}");
+    }
+}
+
+/// The total size (in bytes) of the file or directory at `path`.
+pub(crate) fn host_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
@@ -1,13 +1,14 @@
 use std::{
     collections::HashMap,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use anyhow::Context;
 
 use crate::{
     config::{self, Config, MountKind},
+    image_builder::RootfsAgent,
     setup, utils,
 };
 
@@ -22,6 +23,9 @@ pub(crate) struct VmConfig {
     /// Bytes of entropy to inject into the kernel.
     pub kernel_entropy: Option<Vec<u32>>,
 
+    /// Sysctls and ulimits to apply in the VM before running any tasks.
+    pub setup: agent_interface::GuestSetup,
+
     /// Whether to recreate the working directory for the VM.
     pub recreate_work_dir: bool,
 
@@ -36,6 +40,9 @@ pub(crate) struct VmConfig {
 
     /// Additional file systems that should be mounted in the VM.
     pub drives: Vec<DriveConfig>,
+
+    /// The network device to attach to the VM (if any).
+    pub network: Option<NetworkConfig>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -65,6 +72,58 @@ impl Default for MachineConfig {
     }
 }
 
+/// Configuration for attaching a TAP device on the host to the VM. `{worker}` in any of the fields
+/// is replaced with the index of the worker running the VM, so that each VM can be given its own
+/// device and address.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct NetworkConfig {
+    /// The name of the TAP device on the host (e.g. `tap{worker}`). The device must already exist.
+    pub host_dev_name: String,
+
+    /// The MAC address of the guest interface (chosen by firecracker if not specified).
+    #[serde(default)]
+    pub guest_mac: Option<String>,
+
+    /// A static IPv4 address for the guest, configured by the kernel at boot (e.g.
+    /// `172.16.{worker}.2`).
+    #[serde(default)]
+    pub guest_ip: Option<String>,
+
+    /// The gateway used by the guest (typically the address of the TAP device on the host).
+    #[serde(default)]
+    pub gateway: Option<String>,
+
+    /// The netmask of the guest network.
+    #[serde(default = "default_netmask")]
+    pub netmask: String,
+}
+
+fn default_netmask() -> String {
+    "255.255.255.0".into()
+}
+
+impl NetworkConfig {
+    /// Gets the configuration for the VM run by `worker`.
+    fn for_worker(&self, worker: usize) -> Self {
+        let expand = |value: &str| value.replace("{worker}", &worker.to_string());
+        Self {
+            host_dev_name: expand(&self.host_dev_name),
+            guest_mac: self.guest_mac.as_deref().map(expand),
+            guest_ip: self.guest_ip.as_deref().map(expand),
+            gateway: self.gateway.as_deref().map(expand),
+            netmask: self.netmask.clone(),
+        }
+    }
+
+    /// The kernel argument for statically configuring the guest interface (see
+    /// `Documentation/admin-guide/nfs/nfsroot.rst` in the kernel source).
+    fn kernel_arg(&self) -> Option<String> {
+        let ip = self.guest_ip.as_ref()?;
+        let gateway = self.gateway.as_deref().unwrap_or("");
+        Some(format!("ip={ip}::{gateway}:{}::eth0:off", self.netmask))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct DriveConfig {
     pub name: String,
@@ -87,10 +146,20 @@ pub(crate) struct ActiveVm {
     drives: Vec<Drive>,
     api: curl::easy::Easy,
     vsock_path: PathBuf,
+    network: Option<NetworkConfig>,
     instance: Option<FirecrakerInstance>,
 }
 
 impl ActiveVm {
+    /// Returns a function that forcibly stops the VM (e.g. from a watchdog thread).
+    pub fn killer(&self) -> impl FnOnce() -> anyhow::Result<()> + Send + 'static {
+        let pid = self.instance.as_ref().map(|x| x.process.id());
+        move || match pid {
+            Some(pid) => crate::utils::kill_process(pid),
+            None => Ok(()),
+        }
+    }
+
     pub fn wait_for_exit(mut self) -> anyhow::Result<()> {
         let mut instance = self.instance.take().ok_or_else(|| anyhow::format_err!("VM exited"))?;
 
@@ -156,6 +225,13 @@ impl ActiveVm {
                 }
                 (false, PathBuf::from(copy_path).canonicalize()?)
             }
+            MountKind::CopyOnWrite => {
+                let copy_path = self.workdir.join(format!("{}.ext4", config.name));
+                if !copy_path.exists() {
+                    copy_on_write(&config.path, &copy_path)?;
+                }
+                (false, PathBuf::from(copy_path).canonicalize()?)
+            }
             MountKind::InPlace => (false, config.path.clone()),
         };
 
@@ -169,7 +245,11 @@ impl ActiveVm {
     }
 
     fn send_config(&mut self, config: &VmConfig) -> anyhow::Result<()> {
-        put::<_, ()>(&mut self.api, "http://localhost/boot-source", &config.boot)
+        let mut boot = config.boot.clone();
+        if let Some(arg) = self.network.as_ref().and_then(|x| x.kernel_arg()) {
+            boot.boot_args = format!("{} {arg}", boot.boot_args);
+        }
+        put::<_, ()>(&mut self.api, "http://localhost/boot-source", &boot)
             .context("Error sending boot config")?;
 
         put::<_, ()>(&mut self.api, "http://localhost/machine-config", &config.machine)
@@ -187,6 +267,16 @@ impl ActiveVm {
         })
         .context("Error configuring vsock")?;
 
+        if let Some(network) = &self.network {
+            let interface = NetworkInterface {
+                iface_id: "eth0".into(),
+                host_dev_name: network.host_dev_name.clone(),
+                guest_mac: network.guest_mac.clone(),
+            };
+            put::<_, ()>(&mut self.api, "http://localhost/network-interfaces/eth0", &interface)
+                .with_context(|| format!("Error configuring network: {}", network.host_dev_name))?;
+        }
+
         put::<_, ()>(&mut self.api, "http://localhost/actions", &Action {
             action_type: "InstanceStart".into(),
         })
@@ -196,12 +286,52 @@ impl ActiveVm {
     }
 }
 
+/// Set after warning that reflinks are not supported, to avoid repeating the warning for every VM.
+static REFLINK_UNSUPPORTED: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+/// Creates a copy of `src` at `dst` that shares unmodified blocks with `src` if the filesystem
+/// supports reflinks (e.g. btrfs or XFS), otherwise creates a sparse copy of `src`.
+fn copy_on_write(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    use std::sync::atomic::Ordering;
+
+    if !REFLINK_UNSUPPORTED.load(Ordering::Relaxed) {
+        let output = std::process::Command::new("cp")
+            .arg("--reflink=always")
+            .args([src, dst])
+            .output()
+            .context("failed to run cp")?;
+        if output.status.success() {
+            return Ok(());
+        }
+        if !REFLINK_UNSUPPORTED.swap(true, Ordering::Relaxed) {
+            tracing::warn!(
+                "reflinks are not supported for {}, falling back to sparse copies: {}",
+                dst.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let _ = std::fs::remove_file(dst);
+    }
+
+    let status = std::process::Command::new("cp")
+        .arg("--sparse=always")
+        .args([src, dst])
+        .status()
+        .context("failed to run cp")?;
+    if !status.success() {
+        anyhow::bail!("error copying {} to {}: {status}", src.display(), dst.display());
+    }
+    Ok(())
+}
+
 pub(crate) fn spawn_vm(
     id: String,
+    worker: usize,
     config: &VmConfig,
     interactive: bool,
 ) -> anyhow::Result<ActiveVm> {
-    let workdir = std::env::temp_dir().join("bench-harness").join(&id);
+    let workdir = crate::workdirs::worker_dir(&std::env::temp_dir().join("bench-harness"), &id)?;
     let api_socket = workdir.join("firecracker-api.socket");
     utils::prepare_workdir(&api_socket, &workdir, config.recreate_work_dir, false)?;
 
@@ -239,7 +369,15 @@ pub(crate) fn spawn_vm(
         }
     }
 
-    let mut vm = ActiveVm { workdir, api, instance: Some(instance), drives: vec![], vsock_path };
+    let network = config.network.as_ref().map(|x| x.for_worker(worker));
+    let mut vm = ActiveVm {
+        workdir,
+        api,
+        instance: Some(instance),
+        drives: vec![],
+        vsock_path,
+        network,
+    };
 
     vm.add_drive(&config.rootfs, true)?;
     for drive in &config.drives {
@@ -274,6 +412,14 @@ struct Vsock {
     uds_path: PathBuf,
 }
 
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct NetworkInterface {
+    iface_id: String,
+    host_dev_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guest_mac: Option<String>,
+}
+
 #[derive(serde::Deserialize)]
 #[serde(untagged)]
 enum FirecrakerResult<T> {
@@ -361,6 +507,7 @@ fn connect_firecracker(
     tracing::debug!("Connecting to firecracker agent at: {}:{}", path.display(), port);
     let mut agent = agent_interface::client::unix::UnixAgent::connect(path)?;
     firecracker_handshake(&mut agent.reader, &mut agent.writer, port)?;
+    agent.set_encoding(agent_interface::codec::Encoding::from_env())?;
     Ok(agent)
 }
 
@@ -411,6 +558,11 @@ pub fn get_instance_config(config: &Config) -> anyhow::Result<HashMap<String, Vm
 
     let mut instances = HashMap::new();
     for (name, instance) in &config.data.instances {
+        // Firecracker can only run guests with the same architecture as the host.
+        if instance.arch != config::Arch::host() {
+            tracing::debug!("skipping {name}: {} instances cannot run on this host", instance.arch);
+            continue;
+        }
         let vm_config =
             build_instance(&firecracker, instance, kernel_config, &kernel, &image_paths)
                 .with_context(|| format!("failed to build: {name}"))?;
@@ -432,6 +584,7 @@ pub fn build_instance(
         boot_delay_sec: instance.boot_delay_sec,
         recreate_work_dir: instance.recreate_workdir,
         kernel_entropy: kernel_config.entropy.clone(),
+        setup: instance.setup.clone(),
         boot: BootSource {
             kernel_image_path: kernel.clone(),
             boot_args: kernel_config.boot_args.clone(),
@@ -463,16 +616,20 @@ pub fn build_instance(
                 })
             })
             .collect::<anyhow::Result<Vec<DriveConfig>>>()?,
+        network: instance.network.clone(),
     })
 }
 
 pub fn spawn_debug_vm(config: &VmConfig) -> anyhow::Result<()> {
-    let vm = spawn_vm("vm-debug-data".into(), config, true)?;
+    let vm = spawn_vm("vm-debug-data".into(), 0, config, true)?;
 
     let mut agent = connect_to_vsock_agent(&vm)?;
     if let Some(entropy) = config.kernel_entropy.clone() {
         agent.send(agent_interface::Request::AddEntropy(entropy))?;
     }
+    if !config.setup.is_empty() {
+        agent.setup_guest(config.setup.clone())?;
+    }
 
     let pid = agent.spawn_task(
         agent_interface::RunCommand::from_cmd_string("/bin/bash -i")
@@ -487,11 +644,50 @@ pub fn spawn_debug_vm(config: &VmConfig) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Checks that all instances used by `tasks` can run on the current host.
+pub(crate) fn check_instance_arch(
+    config: &Config,
+    tasks: &[config::TaskConfig],
+) -> anyhow::Result<()> {
+    let host = config::Arch::host();
+    for task in tasks {
+        let Some(instance) = config.data.instances.get(&task.instance) else {
+            continue;
+        };
+        anyhow::ensure!(
+            instance.arch == host,
+            "{} is an {} instance and cannot run on this host ({host})",
+            task.instance,
+            instance.arch
+        );
+    }
+    Ok(())
+}
+
 /// Builds all images used for VMs. This is not done as part of normal execution because it
 /// currently requires root permissions (in order to mount disks).
+///
+/// Images used as the root file system of an instance are built for the architecture of the
+/// instance, with the matching agent binary from `[agents]`.
 pub fn build_images(config: &Config) -> anyhow::Result<()> {
+    let mut rootfs_arch: HashMap<&str, config::Arch> = HashMap::new();
+    for (name, instance) in &config.data.instances {
+        let image = instance.rootfs.image.as_str();
+        match rootfs_arch.insert(image, instance.arch) {
+            Some(arch) if arch != instance.arch => anyhow::bail!(
+                "{image} is used as the root file system for both {arch} and {} instances ({name})",
+                instance.arch
+            ),
+            _ => {}
+        }
+    }
+
     for (name, source) in &config.data.images {
-        crate::image_builder::build_image(&name, &source, &config.cache)
+        let agent = match rootfs_arch.get(name.as_str()) {
+            Some(&arch) => Some(RootfsAgent { arch, path: config.get_agent(arch)? }),
+            None => None,
+        };
+        crate::image_builder::build_image(&name, &source, &config.cache, agent)
             .with_context(|| format!("failed to build: {name}"))?;
     }
     Ok(())
@@ -0,0 +1,184 @@
+//! Estimates of the disk and memory required to run a benchmark (reported by `bench --dry-run`).
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{Config, MountKind, TaskConfig},
+    utils::{HumanReadableSize, Variables},
+    WorkerBackend,
+};
+
+/// Logs the estimated disk usage of each task and the total disk and peak memory usage of running
+/// `tasks` on `workers` concurrent workers, warning if the host does not have enough resources.
+pub(crate) fn report(
+    config: &Config,
+    tasks: &[TaskConfig],
+    workers: usize,
+    backend: WorkerBackend,
+) -> anyhow::Result<()> {
+    // Artifacts are estimated from the size of artifacts saved by previous runs.
+    let mut history = HashMap::new();
+    let mut artifacts_size = 0;
+    let mut unknown_artifacts = 0;
+    for (i, task) in tasks.iter().enumerate() {
+        let mut vars = Variables::default();
+        vars.insert_all(config.vars.iter().chain(&task.vars).map(|x| x.clone().into()));
+
+        let mut task_size = 0;
+        for artifact in &task.artifacts {
+            let path = PathBuf::from(vars.expand_vars(&artifact.path));
+            match history.entry(artifact_group(&path)).or_insert_with(|| historical_size(&path)) {
+                Some(size) => task_size += *size,
+                None => unknown_artifacts += 1,
+            }
+        }
+        tracing::info!(
+            "task-{i} ({} trial={}): ~{} of artifacts",
+            task.template,
+            task.trial,
+            HumanReadableSize(task_size)
+        );
+        artifacts_size += task_size;
+    }
+
+    // Images are only created for firecracker instances. Read only images are shared by all
+    // workers, while duplicated images are copied to the working directory of each worker.
+    let mut shared_images = HashMap::new();
+    let mut per_worker_size = 0;
+    let mut per_worker_mem_mib = 0;
+    if let WorkerBackend::Firecracker = backend {
+        let mut used: Vec<&str> = tasks.iter().map(|x| x.instance.as_str()).collect();
+        used.sort_unstable();
+        used.dedup();
+
+        for name in used {
+            let Some(instance) = config.data.instances.get(name) else {
+                continue;
+            };
+            let mut duplicated = 0;
+            for drive in std::iter::once(&instance.rootfs).chain(&instance.drives) {
+                let size = image_size(config, &drive.image);
+                match drive.mount_as {
+                    MountKind::ReadOnly | MountKind::InPlace => {
+                        shared_images.insert(drive.image.as_str(), size);
+                    }
+                    // Copy-on-write images may use much less space, but are counted in full
+                    // since reflinks may not be supported by the host.
+                    MountKind::Duplicate
+                    | MountKind::ReuseDuplicate
+                    | MountKind::CopyOnWrite => duplicated += size,
+                }
+            }
+            per_worker_size = per_worker_size.max(duplicated);
+            per_worker_mem_mib = per_worker_mem_mib.max(instance.machine.mem_size_mib);
+        }
+    }
+    let images_size = shared_images.values().sum::<u64>() + workers as u64 * per_worker_size;
+    let peak_mem = workers as u64 * per_worker_mem_mib * 1024 * 1024;
+
+    tracing::info!(
+        "Estimated disk usage: {} (artifacts: {}, images: {}). Estimated peak memory: {}",
+        HumanReadableSize(artifacts_size + images_size),
+        HumanReadableSize(artifacts_size),
+        HumanReadableSize(images_size),
+        HumanReadableSize(peak_mem),
+    );
+    if unknown_artifacts != 0 {
+        tracing::warn!(
+            "{unknown_artifacts} artifact(s) have no prior results to estimate size from"
+        );
+    }
+
+    let cwd = std::env::current_dir()?;
+    check_disk_space(&cwd, artifacts_size, "artifacts");
+    check_disk_space(&config.cache.dir, images_size, "images");
+    if let Some(available) = total_memory() {
+        if peak_mem > available {
+            tracing::warn!(
+                "{workers} workers require {} of memory but the host only has {}",
+                HumanReadableSize(peak_mem),
+                HumanReadableSize(available)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Artifacts from different trials of the same configuration are stored as `[..]/[trial]/[name]`,
+/// so we group them by the parent of the trial directory and the name of the artifact.
+fn artifact_group(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    Some((path.parent()?.parent()?.to_owned(), path.file_name()?.into()))
+}
+
+/// Computes the average size of existing artifacts in the same group as `path`.
+fn historical_size(path: &Path) -> Option<u64> {
+    let (group, name) = artifact_group(path)?;
+    let sizes: Vec<u64> = std::fs::read_dir(group)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| entry.path().join(&name).metadata().ok())
+        .map(|metadata| metadata.len())
+        .collect();
+    match sizes.len() {
+        0 => None,
+        n => Some(sizes.iter().sum::<u64>() / n as u64),
+    }
+}
+
+/// Gets the size of an image, either from the configured size or from a previous build.
+fn image_size(config: &Config, name: &str) -> u64 {
+    if let Some(size) = config.data.images.get(name).and_then(|x| x.size) {
+        return size;
+    }
+    match config.cache.dir.join(format!("{name}.ext4")).metadata() {
+        Ok(metadata) => metadata.len(),
+        Err(_) => {
+            tracing::warn!("unknown size for image: {name}");
+            0
+        }
+    }
+}
+
+fn check_disk_space(path: &Path, required: u64, kind: &str) {
+    let Some(available) = available_disk_space(path) else {
+        return;
+    };
+    if required > available {
+        tracing::warn!(
+            "{kind} require {} but only {} is available at: {}",
+            HumanReadableSize(required),
+            HumanReadableSize(available),
+            path.display()
+        );
+    }
+}
+
+#[cfg(unix)]
+fn available_disk_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn available_disk_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Reads the total amount of memory on the host from `/proc/meminfo`.
+fn total_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|x| x.starts_with("MemTotal:"))?;
+    let kib: u64 =
+        line.trim_start_matches("MemTotal:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kib * 1024)
+}
@@ -9,7 +9,7 @@ use std::{
 use anyhow::Context;
 
 use crate::{
-    config::CacheConfig,
+    config::{Arch, CacheConfig},
     docker::{self, DockerSource},
     image_builder::utils::MountHandle,
 };
@@ -34,6 +34,22 @@ impl ImageSource {
     }
 }
 
+/// The agent installed in a root file system image.
+#[derive(Clone, Copy)]
+pub(crate) struct RootfsAgent<'a> {
+    pub arch: Arch,
+    /// The agent binary to copy to `/bin/agent`, if `None` the agent included by the source is
+    /// kept.
+    pub path: Option<&'a Path>,
+}
+
+impl RootfsAgent<'_> {
+    /// Images for other architectures need to be built for a different docker platform.
+    fn docker_platform(&self) -> Option<&'static str> {
+        (self.arch != Arch::host()).then(|| self.arch.docker_platform())
+    }
+}
+
 #[derive(serde::Deserialize)]
 pub(crate) struct HostSource {
     pub paths: Vec<PathToCopy>,
@@ -53,11 +69,16 @@ pub(crate) enum SourceKind {
 }
 
 impl SourceKind {
-    fn build(&self, cache: &CacheConfig) -> anyhow::Result<()> {
+    fn build(&self, cache: &CacheConfig, agent: Option<RootfsAgent>) -> anyhow::Result<()> {
         match self {
-            SourceKind::Docker(inner) => {
-                docker::build_image(&inner.tag, &inner.build_path, cache.disable_image_cache)
-            }
+            SourceKind::Docker(inner) => docker::build_image(
+                &inner.tag,
+                &inner.build_path,
+                &inner.ignore,
+                &cache.dir,
+                cache.disable_image_cache,
+                agent.and_then(|x| x.docker_platform()),
+            ),
             SourceKind::Host(_) => Ok(()),
         }
     }
@@ -106,18 +127,20 @@ pub(crate) fn get_image_path(name: &str, cache: &CacheConfig) -> anyhow::Result<
     if let Err(e) = path.metadata() {
         anyhow::bail!(
             "failed to find image for \"{name}\": {e}\n\n(you may need to run `{} build` first!)",
-            env!("CARGO_BIN_NAME"),
+            env!("CARGO_PKG_NAME"),
         );
     }
 
     Ok(path)
 }
 
-/// Build a disk image from a source.
+/// Build a disk image from a source. If the image is used as a root file system, `agent` selects
+/// the architecture and agent binary to install.
 pub(crate) fn build_image(
     name: &str,
     source: &ImageSource,
     cache: &CacheConfig,
+    agent: Option<RootfsAgent>,
 ) -> anyhow::Result<PathBuf> {
     let path = cache.dir.join(format!("{name}.ext4"));
 
@@ -134,12 +157,19 @@ pub(crate) fn build_image(
         existing_size = metadata.len();
     }
 
-    source.kind.build(cache)?;
+    source.kind.build(cache, agent)?;
 
     // Checks whether we need to rebuild the image based on modification time and changes to the
     // image size.
-    let (measured_size, source_time) =
+    let (mut measured_size, mut source_time) =
         source.kind.get_total_size_and_modified_time().context("error computing metadata")?;
+    let agent_path = agent.and_then(|x| x.path);
+    if let Some(path) = agent_path {
+        let (size, time) = get_total_size_and_modified_time(path)
+            .with_context(|| format!("failed to read agent: {}", path.display()))?;
+        measured_size += size;
+        source_time = source_time.max(time);
+    }
     let size = source.get_size(measured_size)?;
 
     let source_is_newer = image_time.map_or(true, |time| time < source_time);
@@ -161,6 +191,12 @@ pub(crate) fn build_image(
     let mount = utils::mount_file_system(&path, &mount_path)?;
 
     source.kind.copy(&mount)?;
+    if let (Some(path), Some(root)) = (agent_path, mount.path.as_ref()) {
+        let dst = root.join("bin/agent");
+        tracing::info!("Copying agent from {} to {}", path.display(), dst.display());
+        std::fs::copy(path, &dst)
+            .with_context(|| format!("error copying {} to {}", path.display(), dst.display()))?;
+    }
 
     disk.finalize();
 
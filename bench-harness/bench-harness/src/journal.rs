@@ -0,0 +1,190 @@
+//! Persistent record of the state of each task in a benchmark run, allowing an interrupted run to
+//! be resumed with `bench --resume`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TaskState {
+    /// The task has not been started yet.
+    Pending,
+    /// The task was running when the journal was last written (i.e. the harness exited without
+    /// the task finishing).
+    Running,
+    Completed,
+    Failed,
+    /// The task was stopped because the benchmark was canceled.
+    Interrupted,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct TaskEntry {
+    /// The template and trial the task was expanded from, used for checking that the benchmark
+    /// has not changed when resuming.
+    template: String,
+    trial: usize,
+    state: TaskState,
+    /// How long the task ran for the last time it finished (including any retries).
+    #[serde(default)]
+    duration_secs: Option<f64>,
+    /// The largest drift of the guest clock measured the last time the task ran (see
+    /// [crate::clock]).
+    #[serde(default)]
+    max_clock_drift_secs: Option<f64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JournalData {
+    /// The campaign seed used for the run, reused when resuming so that each task keeps its seed.
+    seed: u64,
+    tasks: IndexMap<String, TaskEntry>,
+}
+
+pub(crate) struct Journal {
+    path: PathBuf,
+    data: Mutex<JournalData>,
+}
+
+impl Journal {
+    /// The path of the journal for `benchmark` (e.g. `<cache>/bench-id.state.json`).
+    pub fn path_for(cache_dir: &Path, benchmark: &Path) -> PathBuf {
+        let name = benchmark.file_stem().unwrap_or_default().to_string_lossy();
+        cache_dir.join(format!("{name}.state.json"))
+    }
+
+    /// Creates a new journal at `path` containing `tasks` (as `(name, template, trial)`), replacing
+    /// the journal of any previous run.
+    pub fn create(
+        path: PathBuf,
+        seed: u64,
+        tasks: impl Iterator<Item = (String, String, usize)>,
+    ) -> anyhow::Result<Self> {
+        let tasks = tasks
+            .map(|(name, template, trial)| {
+                let state = TaskState::Pending;
+                let entry = TaskEntry {
+                    template,
+                    trial,
+                    state,
+                    duration_secs: None,
+                    max_clock_drift_secs: None,
+                };
+                (name, entry)
+            })
+            .collect();
+        let journal = Self { path, data: Mutex::new(JournalData { seed, tasks }) };
+        journal.save()?;
+        Ok(journal)
+    }
+
+    /// Loads the journal of a previous run from `path`.
+    pub fn load(path: PathBuf) -> anyhow::Result<Self> {
+        let data = std::fs::read(&path)
+            .with_context(|| format!("failed to read run journal: {}", path.display()))?;
+        let data = serde_json::from_slice(&data)
+            .with_context(|| format!("invalid run journal: {}", path.display()))?;
+        Ok(Self { path, data: Mutex::new(data) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.data.lock().seed
+    }
+
+    pub fn state(&self, name: &str) -> Option<TaskState> {
+        self.data.lock().tasks.get(name).map(|x| x.state)
+    }
+
+    pub fn duration(&self, name: &str) -> Option<std::time::Duration> {
+        let secs = self.data.lock().tasks.get(name)?.duration_secs?;
+        Some(std::time::Duration::from_secs_f64(secs))
+    }
+
+    /// The largest drift of the guest clock (in seconds) measured while running the task `name`.
+    pub fn max_clock_drift(&self, name: &str) -> Option<f64> {
+        self.data.lock().tasks.get(name)?.max_clock_drift_secs
+    }
+
+    /// Checks that the task `name` was expanded from the same template and trial in the previous
+    /// run, since resuming a run after modifying the benchmark would skip the wrong tasks.
+    pub fn check_task(&self, name: &str, template: &str, trial: usize) -> anyhow::Result<()> {
+        let data = self.data.lock();
+        let entry = data
+            .tasks
+            .get(name)
+            .ok_or_else(|| anyhow::format_err!("{name} is not part of the previous run"))?;
+        if entry.template != template || entry.trial != trial {
+            anyhow::bail!(
+                "{name} was expanded from {} (trial={}) in the previous run, but is now {template} \
+                (trial={trial})",
+                entry.template,
+                entry.trial
+            );
+        }
+        Ok(())
+    }
+
+    /// Updates the state of the task `name` (tasks that are not part of the journal are ignored).
+    pub fn set_state(&self, name: &str, state: TaskState) {
+        let mut data = self.data.lock();
+        let Some(entry) = data.tasks.get_mut(name) else {
+            return;
+        };
+        entry.state = state;
+        if let Err(e) = write_atomic(&self.path, &data) {
+            tracing::warn!("failed to update run journal: {e:#}");
+        }
+    }
+
+    /// Updates the state of the task `name` after it finished running for `duration`, recording the
+    /// largest drift of the guest clock measured while it was running (if any).
+    pub fn set_finished(
+        &self,
+        name: &str,
+        state: TaskState,
+        duration: std::time::Duration,
+        max_clock_drift: Option<f64>,
+    ) {
+        let mut data = self.data.lock();
+        let Some(entry) = data.tasks.get_mut(name) else {
+            return;
+        };
+        entry.state = state;
+        entry.duration_secs = Some(duration.as_secs_f64());
+        entry.max_clock_drift_secs = max_clock_drift;
+        if let Err(e) = write_atomic(&self.path, &data) {
+            tracing::warn!("failed to update run journal: {e:#}");
+        }
+    }
+
+    /// Counts the number of tasks in each state.
+    pub fn summary(&self) -> IndexMap<TaskState, usize> {
+        let mut counts = IndexMap::new();
+        for entry in self.data.lock().tasks.values() {
+            *counts.entry(entry.state).or_default() += 1;
+        }
+        counts
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        write_atomic(&self.path, &self.data.lock())
+    }
+}
+
+/// Writes `data` to a temporary file before moving it to `path`, so that the journal is never left
+/// partially written if the harness is killed.
+fn write_atomic(path: &Path, data: &JournalData) -> anyhow::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(data)?)
+        .with_context(|| format!("failed to write: {}", tmp.display()))?;
+    std::fs::rename(&tmp, path)
+        .with_context(|| format!("failed to write: {}", path.display()))?;
+    Ok(())
+}
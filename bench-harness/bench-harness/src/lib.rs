@@ -0,0 +1,1214 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use clap::Parser;
+use parking_lot::lock_api::RawMutex;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+use crate::{
+    config::{Config, TaskConfig},
+    tasks::Task,
+};
+
+pub mod afl;
+mod analyze;
+mod archive;
+mod barrier;
+mod clock;
+mod config;
+mod control;
+pub mod custom_tasks;
+mod dashboard;
+mod docker;
+mod envdiff;
+mod events;
+mod firecracker;
+mod footprint;
+mod image_builder;
+mod journal;
+mod manifest;
+mod metrics;
+mod perf;
+mod setup;
+mod ssh;
+mod statsd;
+mod tasks;
+mod transcript;
+mod trends;
+mod utils;
+mod verify;
+mod workdirs;
+mod worker;
+
+#[derive(Copy, Clone, Debug)]
+enum WorkerBackend {
+    Local,
+    Firecracker,
+    Docker,
+    Ssh,
+    Dummy,
+}
+
+impl std::fmt::Display for WorkerBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local => f.write_str("local"),
+            Self::Firecracker => f.write_str("firecracker"),
+            Self::Docker => f.write_str("docker"),
+            Self::Ssh => f.write_str("ssh"),
+            Self::Dummy => f.write_str("dummy"),
+        }
+    }
+}
+
+impl std::str::FromStr for WorkerBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(WorkerBackend::Local),
+            "firecracker" => Ok(WorkerBackend::Firecracker),
+            "docker" => Ok(WorkerBackend::Docker),
+            "ssh" => Ok(WorkerBackend::Ssh),
+            "dummy" => Ok(WorkerBackend::Dummy),
+            _ => Err(anyhow::anyhow!("Invalid worker backend: {}", s)),
+        }
+    }
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Build any un-cached images and data.
+    Build,
+    /// Run an agent with the target instance name.
+    Debug { instance: String },
+    /// Expand an individual benchmark command.
+    Expand { benchmark: String },
+    /// Run a benchmark.
+    Bench {
+        /// Print information about the benchmark (e.g. estimated duration, disk and memory usage)
+        /// without running it.
+        #[clap(long)]
+        dry_run: bool,
+        /// Seed used for generating the RNG seed of each trial (randomly chosen if not specified).
+        #[clap(long)]
+        seed: Option<u64>,
+        /// File to record the seed and tag used for each trial to.
+        #[clap(long, default_value = "./output/seeds.csv")]
+        seed_report: PathBuf,
+        /// Label used to distinguish this run from others in the same results tree (e.g.
+        /// `exploratory` or `paper`), added to each task's `TAG` as `label=<LABEL>`.
+        #[clap(long)]
+        label: Option<String>,
+        /// Free-form notes about the run, saved in the run manifest.
+        #[clap(long)]
+        notes: Option<String>,
+        /// Resume a previous run of the benchmark, skipping any tasks that already completed and
+        /// re-running tasks that failed or were interrupted.
+        #[clap(long)]
+        resume: bool,
+        /// Show a live dashboard of the progress of each worker. Log messages are written to
+        /// `bench-harness.log` while the dashboard is shown.
+        #[clap(long)]
+        ui: bool,
+        /// Maximum drift (in milliseconds) of the guest clock relative to the host before a
+        /// warning is reported.
+        #[clap(long, default_value_t = 1000)]
+        max_clock_drift_ms: u64,
+        /// Collect host performance counters (e.g. instructions and cache misses) with `perf stat`
+        /// while each task is running, appending them to this file. Only supported by the `local`
+        /// and `docker` backends.
+        #[clap(long)]
+        perf_report: Option<PathBuf>,
+        /// Append a structured log of benchmark events (e.g. tasks starting and finishing, VM
+        /// boot times and data copied from the guest) to this file as JSON lines.
+        #[clap(long)]
+        event_log: Option<PathBuf>,
+        /// File to write a machine-readable summary of the run (listing every trial) to.
+        #[clap(long, default_value = "./output/manifest.json")]
+        manifest: PathBuf,
+        /// Serve the latest statsd metrics of each task from a Prometheus `/metrics` endpoint at
+        /// this address (e.g. `0.0.0.0:9184`).
+        #[clap(long)]
+        metrics_addr: Option<String>,
+        /// Append statsd metrics to this file (in InfluxDB line protocol) as they are collected.
+        #[clap(long)]
+        influx_output: Option<PathBuf>,
+        /// Record every request sent to the agent by each task, along with the response, to
+        /// `<DIR>/<task>.jsonl` (see the `replay` command).
+        #[clap(long, value_name = "DIR")]
+        record_transcripts: Option<PathBuf>,
+        /// Start the trials that share the values of these tag keys (e.g. `binary,trial` to start
+        /// every fuzzer on the same binary together) at the same time, holding workers that are
+        /// ready earlier until the rest of the group is ready.
+        #[clap(long, value_name = "KEYS", value_delimiter = ',')]
+        sync_start: Vec<String>,
+        /// The maximum number of seconds to hold a trial for its sync group, after which the group
+        /// is started without the trials that are not ready. Counts towards the task's timeout.
+        #[clap(long, value_name = "SECS", default_value_t = 600)]
+        sync_timeout: u64,
+        /// Append a summary of each trial (e.g. final coverage, bugs and duration) to this SQLite
+        /// database once the run finishes, for tracking results across runs (see `trends`).
+        #[clap(long, value_name = "FILE")]
+        results_db: Option<PathBuf>,
+        /// The revision of the fuzzer being benchmarked, recorded in the `--results-db`.
+        #[clap(long)]
+        commit: Option<String>,
+        /// Path to benchmark configuration file.
+        bench: PathBuf,
+    },
+    /// Re-run a task of a benchmark against a transcript recorded with `--record-transcripts`
+    /// instead of a real agent, failing if the task sends a request that differs from the
+    /// transcript. Host-side effects of the task (e.g. copying files from the guest) are repeated.
+    Replay {
+        /// Path to benchmark configuration file.
+        bench: PathBuf,
+        /// Path to the transcript to replay.
+        transcript: PathBuf,
+        /// Override a variable of the recorded task (e.g. to redirect its output to a scratch
+        /// directory).
+        #[clap(long = "var", value_name = "KEY=VALUE")]
+        vars: Vec<String>,
+    },
+    /// Run the analysis pipeline on the results of a benchmark.
+    Analyze {
+        /// Path to benchmark configuration file.
+        bench: PathBuf,
+        /// Directory to save the generated analysis config and results to.
+        #[clap(long, default_value = "./analysis-output")]
+        output: PathBuf,
+        /// Path to the analysis workspace containing `plot-data` and `plot`.
+        #[clap(long, default_value = "../analysis")]
+        analysis_dir: PathBuf,
+        /// Number of time steps to use when computing coverage over time.
+        #[clap(long, default_value_t = 240)]
+        time_resolution: u64,
+    },
+    /// Re-run the verifier tasks of a completed benchmark on the host, using the artifacts saved
+    /// by the benchmark.
+    Verify {
+        /// Path to benchmark configuration file.
+        bench: PathBuf,
+        /// Directory used for extracting archived artifacts.
+        #[clap(long, default_value = "./.verify-scratch")]
+        scratch: PathBuf,
+    },
+    /// Package the results, configs and any additional files (e.g. reports and logs) of a completed
+    /// benchmark into a single compressed archive.
+    Archive {
+        /// Path to benchmark configuration file.
+        bench: PathBuf,
+        /// Path to save the archive to (defaults to `./archive/[bench name].tar.zst`).
+        #[clap(long)]
+        output: Option<PathBuf>,
+        /// The directory containing the results of the benchmark. Only results within this
+        /// directory are archived and deleted.
+        #[clap(long, default_value = "./output")]
+        results: PathBuf,
+        /// Additional files or directories to include in the archive.
+        #[clap(long)]
+        include: Vec<PathBuf>,
+        /// The zstd compression level to use.
+        #[clap(long, default_value_t = 19)]
+        level: i32,
+        /// Delete the archived results and included files (within the results directory) once the
+        /// archive has been verified.
+        #[clap(long)]
+        delete: bool,
+    },
+    /// Convert an AFL `plot_data` file (from AFL 2.x, AFL++ 3.x or AFL++ 4.x) to the tagged CSV
+    /// format saved by the `SaveTaggedAflPlotData` task, e.g. for baselines run outside of the
+    /// harness.
+    AflPlotData {
+        /// Path to the `plot_data` file.
+        input: PathBuf,
+        /// The tag to apply to each row.
+        #[clap(long)]
+        tag: String,
+        /// File to append the converted rows to.
+        #[clap(long)]
+        output: PathBuf,
+    },
+    /// Compare the median final coverage of each fuzzer on each binary across the runs recorded in
+    /// a results database (see `bench --results-db`), flagging runs where coverage regressed.
+    Trends {
+        /// Path to the results database.
+        db: PathBuf,
+        /// Only include runs of this benchmark (as passed to `bench`).
+        #[clap(long)]
+        bench: Option<PathBuf>,
+        /// Path to save the table of results over time to.
+        #[clap(long, default_value = "./output/trends.csv")]
+        output: PathBuf,
+        /// The drop in median coverage (as a percentage of the previous run) that is reported as a
+        /// regression.
+        #[clap(long, value_name = "PERCENT", default_value_t = 5.0)]
+        threshold: f64,
+    },
+    /// Compare the environments saved by `SaveEnv` tasks across the trials of a completed
+    /// benchmark, reporting variables that differ between trials of the same configuration.
+    EnvDiff {
+        /// Path to benchmark configuration file.
+        bench: PathBuf,
+        /// Path to save the report to.
+        #[clap(long, default_value = "./output/env-diff.txt")]
+        output: PathBuf,
+        /// Variables to exclude from the comparison.
+        #[clap(long)]
+        ignore: Vec<String>,
+    },
+    /// (Legacy) Run a benchmark.
+    BenchLegacy { id: String, trials: usize, tasks: String },
+    /// (Legacy) Expand the configuration specified for the target task.
+    ExpandLegacy { task: String },
+}
+
+#[derive(clap::Parser)]
+struct Args {
+    /// Path to the file to use for config.
+    #[clap(short, long, value_name = "FILE", default_value = "config.toml")]
+    config: PathBuf,
+    /// Number of workers to use for running benchmarks.
+    #[clap(short, long, default_value_t = 1)]
+    workers: usize,
+    /// The backend to use for workers.
+    #[clap(long, default_value_t = WorkerBackend::Firecracker)]
+    backend: WorkerBackend,
+    /// Path to a unix socket used for adding or retiring workers while a benchmark is running.
+    #[clap(long, value_name = "FILE")]
+    control: Option<PathBuf>,
+    /// Stop assigning tasks to a worker after this many consecutive infrastructure failures (e.g.
+    /// VM boot failures or agent timeouts). Set to 0 to never quarantine workers.
+    #[clap(long, value_name = "N", default_value_t = 3)]
+    quarantine_after: usize,
+    /// Abort the benchmark if more than this percentage of the started tasks fail within the
+    /// `--fail-fast-window` (usually caused by a broken setup, e.g. a wrong image or missing
+    /// binary).
+    #[clap(long, value_name = "PERCENT", default_value_t = 50.0)]
+    fail_fast_rate: f64,
+    /// The number of minutes after the benchmark starts during which failures are counted
+    /// towards `--fail-fast-rate`.
+    #[clap(long, value_name = "MINUTES", default_value_t = 30)]
+    fail_fast_window: u64,
+    /// Never abort the benchmark because of failed tasks (e.g. for intentionally flaky
+    /// experiments).
+    #[clap(long)]
+    no_fail_fast: bool,
+    /// Kill the VM (or container) running a task if the task runs for longer than its estimated
+    /// duration multiplied by this factor (plus a grace period), unless the task sets a `timeout`.
+    /// Set to 0 to only enforce explicit timeouts.
+    #[clap(long, value_name = "FACTOR", default_value_t = 1.0)]
+    timeout_factor: f64,
+    /// The subcommand to run.
+    #[clap(subcommand)]
+    command: Command,
+}
+
+/// Runs the harness with the command line arguments of the current process. Downstream crates that
+/// provide their own tasks (see [custom_tasks]) call this after registering them.
+pub fn main() {
+    let args = Args::parse();
+
+    // Log messages would be drawn over the dashboard, so they are redirected to a file instead.
+    let ui = matches!(args.command, Command::Bench { ui: true, dry_run: false, .. });
+    let writer = match ui {
+        true => match dashboard::LogWriter::new() {
+            Ok(writer) => BoxMakeWriter::new(writer),
+            Err(e) => {
+                eprintln!("failed to open {}: {e:?}", dashboard::LOG_PATH);
+                return;
+            }
+        },
+        false => BoxMakeWriter::new(std::io::stderr),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::builder()
+                .with_env_var("RUST_LOG")
+                .with_default_directive(tracing::Level::INFO.into())
+                .from_env_lossy(),
+        )
+        .with_target(false)
+        .with_ansi(!ui)
+        .with_writer(writer)
+        .init();
+
+    init_cancellation();
+
+    if let Err(e) = run(&args) {
+        eprintln!("{:?}", e);
+    }
+}
+
+fn run(args: &Args) -> anyhow::Result<()> {
+    let mut config: Config = config::toml_from_path(&args.config)?;
+
+    for entry in &config.include {
+        let path = match args.config.parent() {
+            Some(parent) => parent.join(entry),
+            None => entry.clone(),
+        };
+        config
+            .data
+            .merge(config::toml_from_path(&path)?)
+            .with_context(|| format!("error loading config from {}", path.display()))?;
+    }
+    for (name, instance) in &config.data.docker {
+        instance.validate(name)?;
+    }
+
+    let mut loaded_templates = vec![];
+    for (name, path) in &config.templates {
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("error loading template {name} from {}", path.display()))?;
+        loaded_templates.push((name, data));
+    }
+
+    let mut env = minijinja::Environment::new();
+    for (name, template) in &loaded_templates {
+        env.add_template(name, template)?;
+    }
+
+    fn is_containing(v: std::borrow::Cow<'_, str>, other: std::borrow::Cow<'_, str>) -> bool {
+        v.contains(other.as_ref())
+    }
+    env.add_test("containing", is_containing);
+    env.add_filter("tag_value", |value: std::borrow::Cow<'_, str>| utils::escape_tag_value(&value));
+
+    std::fs::create_dir_all(&config.cache.dir).with_context(|| {
+        format!("error creating cache directory {}", config.cache.dir.display())
+    })?;
+
+    match &args.command {
+        Command::Build => firecracker::build_images(&config),
+        Command::Debug { instance } => {
+            let instances = firecracker::get_instance_config(&config)?;
+            let instance = instances
+                .get(instance)
+                .ok_or_else(|| anyhow::format_err!("Unknown instance: {instance}"))?;
+            firecracker::spawn_debug_vm(instance)
+        }
+        Command::Bench {
+            dry_run,
+            seed,
+            seed_report,
+            label,
+            notes,
+            resume,
+            ui,
+            max_clock_drift_ms,
+            perf_report,
+            event_log,
+            manifest,
+            metrics_addr,
+            influx_output,
+            record_transcripts,
+            sync_start,
+            sync_timeout,
+            results_db,
+            commit,
+            bench,
+        } => {
+            clock::set_max_drift(std::time::Duration::from_millis(*max_clock_drift_ms));
+            if let Some(path) = perf_report {
+                if !matches!(args.backend, WorkerBackend::Local | WorkerBackend::Docker) {
+                    anyhow::bail!("--perf-report requires the local or docker backend");
+                }
+                perf::enable(path.clone());
+            }
+            if let Some(path) = event_log.as_ref().filter(|_| !*dry_run) {
+                events::enable(path)?;
+            }
+            if !*dry_run {
+                if let Some(addr) = metrics_addr {
+                    metrics::serve_prometheus(addr)?;
+                }
+                if let Some(path) = influx_output {
+                    metrics::enable_influx(path)?;
+                }
+                if let Some(dir) = record_transcripts {
+                    transcript::enable_recording(dir)?;
+                }
+            }
+            let run_info = RunInfo {
+                label: label.clone().unwrap_or_default(),
+                notes: notes.clone().unwrap_or_default(),
+            };
+            let opts = BenchOptions {
+                dry_run: *dry_run,
+                seed: *seed,
+                resume: *resume,
+                ui: *ui,
+                seed_report,
+                manifest,
+                sync_start,
+                sync_timeout: Duration::from_secs(*sync_timeout),
+                results_db: results_db.as_deref(),
+                commit: commit.as_deref(),
+            };
+            run_bench_v2(args, &config, &env, &opts, &run_info, bench)
+        }
+        Command::Replay { bench, transcript, vars } => {
+            replay_task(&env, bench, transcript, vars)
+        }
+        Command::Analyze { bench, output, analysis_dir, time_resolution } => {
+            let task_list = load_benchmark(&env, bench)?;
+            analyze::run_analysis(&task_list, analysis_dir, output, *time_resolution)
+        }
+        Command::Verify { bench, scratch } => {
+            let task_list = load_benchmark(&env, bench)?;
+            verify::run_verify(&config, &task_list, scratch)
+        }
+        Command::Trends { db, bench, output, threshold } => {
+            trends::report_trends(db, bench.as_deref(), output, *threshold)
+        }
+        Command::EnvDiff { bench, output, ignore } => {
+            let task_list = load_benchmark(&env, bench)?;
+            envdiff::run_env_diff(&config, &task_list, output, ignore)
+        }
+        Command::AflPlotData { input, tag, output } => {
+            let file = std::fs::File::open(input)
+                .with_context(|| format!("failed to open: {}", input.display()))?;
+            let plot_data = afl::PlotData::parse(std::io::BufReader::new(file))?;
+            tracing::info!(
+                "converting {} rows of {:?} plot data to: {}",
+                plot_data.rows.len(),
+                plot_data.format,
+                output.display()
+            );
+            let header = format!("tag,{}", afl::PlotDataRow::FIELDS.join(","));
+            let rows = plot_data.rows.into_iter().map(|row| (tag.as_str(), row));
+            tasks::append_csv(output.clone(), header.as_bytes(), rows)
+        }
+        Command::Archive { bench, output, results, include, level, delete } => {
+            let task_list = load_benchmark(&env, bench)?;
+            let output = output.clone().unwrap_or_else(|| {
+                let name = bench.file_stem().unwrap_or_default().to_string_lossy();
+                PathBuf::from(format!("./archive/{name}.tar.zst"))
+            });
+            let options = archive::ArchiveOptions {
+                bench,
+                config: &args.config,
+                output: &output,
+                results,
+                extra: include,
+                level: *level,
+                delete: *delete,
+            };
+            archive::archive_campaign(&config, &task_list, options)
+        }
+        Command::BenchLegacy { id, trials, tasks } => run_bench(args, config, id, *trials, tasks),
+        Command::ExpandLegacy { task } => {
+            match config.get_task(task) {
+                Ok(task) => eprintln!("{task:#?}"),
+                Err(e) => eprintln!("Error expanding {task}: {e:#}"),
+            }
+            Ok(())
+        }
+        Command::Expand { benchmark } => {
+            match render_tasks_template(&env, &benchmark) {
+                Ok(tasks) => eprintln!("{tasks:#?}"),
+                Err(e) => eprintln!("Error expanding {benchmark}: {e:#}"),
+            }
+            Ok(())
+        }
+    }
+}
+pub(crate) fn render_tasks_template(
+    env: &minijinja::Environment,
+    benchmark: &str,
+) -> anyhow::Result<Vec<TaskConfig>> {
+    let benchmark: Vec<crate::config::BenchGroup> = ron::from_str(benchmark)
+        .with_context(|| format!("{}", StringWithLineNumbers(&benchmark)))?;
+    let mut output = vec![];
+
+    for entry in benchmark {
+        let mut ctx = entry.config;
+        for trial in entry.trials {
+            ctx.insert("trial".into(), format!("{trial}"));
+
+            let template = env.get_template(&entry.template)?;
+            let task_str = template.render(&ctx)?;
+
+            let mut task: TaskConfig = ron::from_str(&task_str).with_context(|| {
+                format!(
+                    "failed expanding template: '{}' (trial={trial})\n{}",
+                    entry.template,
+                    StringWithLineNumbers(&task_str)
+                )
+            })?;
+            custom_tasks::validate(&task.tasks).with_context(|| {
+                format!("invalid task in template: '{}' (trial={trial})", entry.template)
+            })?;
+            task.template = entry.template.clone();
+            task.trial = trial;
+            set_task_tag(&mut task, task_tag(&entry.template, &ctx, trial))?;
+            output.push(task);
+        }
+    }
+
+    Ok(output)
+}
+
+/// Generates the tag for a trial of a benchmark entry from the entry's config, rather than relying
+/// on each template to format the tag correctly.
+fn task_tag(template: &str, ctx: &HashMap<String, String>, trial: usize) -> String {
+    let get = |key: &str, default: &'static str| ctx.get(key).map_or(default, String::as_str);
+    let trial = trial.to_string();
+    let mut entries = vec![("bench", get("bench", "debug"))];
+    entries.push(("fuzzer", ctx.get("fuzzer").map_or(template, String::as_str)));
+    if let Some(mode) = ctx.get("mode") {
+        entries.push(("mode", mode.as_str()));
+    }
+    entries.push(("binary", get("binary", "unknown")));
+    entries.push(("trial", &trial));
+    utils::format_tag(entries)
+}
+
+/// Exposes `tag` to the task as `TAG`. Templates that set their own `TAG` are rejected if it does
+/// not match, to avoid data being attributed to the wrong fuzzer or binary.
+fn set_task_tag(task: &mut TaskConfig, tag: String) -> anyhow::Result<()> {
+    if let Some(i) = task.vars.iter().position(|x| x.key == "TAG") {
+        let existing = task.vars.remove(i).value;
+        if existing != tag {
+            anyhow::bail!(
+                "template '{}' (trial={}) sets TAG={existing}, but the expected tag is {tag} \
+                (TAG is set by the harness and should be removed from the template)",
+                task.template,
+                task.trial
+            );
+        }
+    }
+    task.vars.insert(0, config::KeyValue::new("TAG", tag.clone()));
+    task.tag = tag;
+    Ok(())
+}
+
+struct StringWithLineNumbers<'a>(&'a str);
+
+impl<'a> std::fmt::Display for StringWithLineNumbers<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, line) in self.0.lines().enumerate() {
+            writeln!(f, "{:>3} | {line}", i + 1)?
+        }
+        Ok(())
+    }
+}
+
+fn run_bench(
+    args: &Args,
+    mut config: Config,
+    id: &str,
+    trials: usize,
+    task_list: &str,
+) -> anyhow::Result<()> {
+    let (worker_pool, _control) = start_workers(&config, args, None)?;
+
+    config.vars.push(config::KeyValue::new("BENCH_ID", id));
+
+    for task_name in task_list
+        .split(&[',', '\n'])
+        .map(str::trim)
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+    {
+        let task = match config.get_task(task_name) {
+            Ok(task) => task,
+            Err(e) => {
+                tracing::error!("Error running {task_name}: {e}");
+                continue;
+            }
+        };
+
+        for i in 0..trials {
+            let mut task = task.clone();
+
+            // Merge task specific variables with global variables. Note, the ordering matters here,
+            // as we want to allow task local variables to reference globals.
+            let mut vars = config.vars.clone();
+            vars.push(config::KeyValue::new("TRIAL", format!("{i}")));
+            vars.push(config::KeyValue::new("TASK_NAME", task_name));
+            vars.extend(std::mem::take(&mut task.vars));
+
+            worker_pool.add_task(Task {
+                name: task_name.to_string(),
+                instance: task.instance.clone(),
+                vars,
+                artifacts: task.artifacts.clone(),
+                estimate: task.estimate_duration(),
+                timeout: task.timeout(args.timeout_factor),
+                retry: task.retry.clone(),
+                barrier: None,
+                runable: Box::new(tasks::DynamicTask::TaskList { tasks: task.tasks.clone() }),
+            })?;
+        }
+    }
+    tracing::info!("All pending tasks started");
+
+    let waited = worker_pool.wait_for_workers();
+    if waited.is_ok() {
+        tracing::info!("All tasks complete");
+    }
+    report_degraded_tasks();
+    worker_pool.report_health();
+
+    waited
+}
+
+/// Options controlling how a benchmark is run.
+struct BenchOptions<'a> {
+    dry_run: bool,
+    seed: Option<u64>,
+    resume: bool,
+    ui: bool,
+    seed_report: &'a std::path::Path,
+    manifest: &'a std::path::Path,
+    /// The tag keys used to group trials that are started together (see [barrier]).
+    sync_start: &'a [String],
+    sync_timeout: Duration,
+    /// The database that the results of the run are appended to (see [trends]).
+    results_db: Option<&'a std::path::Path>,
+    commit: Option<&'a str>,
+}
+
+/// Labels and notes attached to a benchmark run.
+struct RunInfo {
+    label: String,
+    notes: String,
+}
+
+fn run_bench_v2(
+    args: &Args,
+    config: &Config,
+    env: &minijinja::Environment,
+    opts: &BenchOptions,
+    run_info: &RunInfo,
+    benchmark: &std::path::Path,
+) -> anyhow::Result<()> {
+    let dry_run = opts.dry_run;
+    let mut task_list = load_benchmark(env, benchmark)?;
+    let mut names: Vec<_> = (0..task_list.len()).map(|i| format!("task-{i}")).collect();
+
+    // When resuming, the seed of the previous run is reused so that re-run tasks get the same
+    // trial seeds as they would have in the original run.
+    let journal_path = journal::Journal::path_for(&config.cache.dir, benchmark);
+    let resumed = if opts.resume {
+        let journal =
+            journal::Journal::load(journal_path.clone()).context("no previous run to resume")?;
+        if opts.seed.is_some_and(|x| x != journal.seed()) {
+            anyhow::bail!(
+                "--seed does not match the seed of the run being resumed ({})",
+                journal.seed()
+            );
+        }
+        for (name, task) in names.iter().zip(&task_list) {
+            journal.check_task(name, &task.template, task.trial)?;
+        }
+        Some(journal)
+    }
+    else {
+        None
+    };
+    let seed = match &resumed {
+        Some(journal) => journal.seed(),
+        None => opts.seed.unwrap_or_else(utils::random_seed),
+    };
+
+    if !run_info.label.is_empty() {
+        tracing::info!("run label: {}", run_info.label);
+        for task in &mut task_list {
+            add_label_tag(task, &run_info.label);
+        }
+    }
+
+    // Each trial is given a unique seed derived from the campaign seed, exposed to tasks as the
+    // `SEED` variable.
+    tracing::info!("campaign seed: {seed} (use `--seed {seed}` to reproduce)");
+    let mut seeds = vec![];
+    let mut trials = vec![];
+    let mut results = vec![];
+    for (i, task) in task_list.iter_mut().enumerate() {
+        let trial_seed = utils::trial_seed(seed, i as u64);
+        task.vars.insert(0, config::KeyValue::new("SEED", trial_seed.to_string()));
+        let mut vars = utils::Variables::default();
+        vars.insert_all(config.vars.iter().chain(&task.vars).map(|x| x.clone().into()));
+        let artifacts: Vec<_> = task.artifacts.iter().map(|x| vars.expand_vars(&x.path)).collect();
+        if opts.results_db.is_some() {
+            results.push(trends::TrialSource::new(
+                format!("task-{i}"),
+                task.trial,
+                task.tag.clone(),
+                &artifacts,
+                &task.tasks,
+                &vars,
+            ));
+        }
+        trials.push(manifest::Trial {
+            task: format!("task-{i}"),
+            template: task.template.clone(),
+            trial: task.trial,
+            tag: task.tag.clone(),
+            seed: trial_seed,
+            state: None,
+            artifacts,
+            max_clock_drift_secs: None,
+        });
+        seeds.push((
+            seed,
+            format!("task-{i}"),
+            task.template.clone(),
+            task.trial,
+            trial_seed,
+            &run_info.label,
+            task.tag.clone(),
+        ));
+    }
+
+    if let Some(journal) = resumed.as_ref() {
+        let total = task_list.len();
+        (names, task_list) = names
+            .into_iter()
+            .zip(task_list)
+            .filter(|(name, _)| journal.state(name) != Some(journal::TaskState::Completed))
+            .unzip();
+
+        tracing::info!(
+            "resuming run from {}: {} completed task(s) skipped, {} task(s) remaining",
+            journal.path().display(),
+            total - task_list.len(),
+            task_list.len(),
+        );
+        if !dry_run {
+            for (name, task) in names.iter().zip(&task_list) {
+                set_aside_partial_results(config, task)?;
+                journal.set_state(name, journal::TaskState::Pending);
+            }
+        }
+    }
+
+    let num_workers = args.workers.min(task_list.len());
+    let barriers;
+    (names, task_list, barriers) = sync_groups(names, task_list, opts, args.workers)?;
+    let estimated = estimate_total_duration(&task_list, num_workers);
+    tracing::info!(
+        "{} tasks running on {num_workers} workers. Estimated time: {}",
+        task_list.len(),
+        utils::HumanReadableDuration(estimated),
+    );
+    if let WorkerBackend::Firecracker = args.backend {
+        firecracker::check_instance_arch(config, &task_list)?;
+    }
+    if dry_run {
+        footprint::report(config, &task_list, num_workers, args.backend)?;
+    }
+
+    if !dry_run {
+        let started = std::time::Instant::now();
+        let mut manifest = manifest::Manifest {
+            benchmark: benchmark.to_owned(),
+            campaign_seed: seed,
+            label: run_info.label.clone(),
+            notes: run_info.notes.clone(),
+            started: clock::unix_secs(std::time::SystemTime::now()),
+            finished: 0.0,
+            trials,
+        };
+        events::record(events::Event::RunStarted {
+            run_id: workdirs::run_id(),
+            benchmark,
+            seed,
+            tasks: task_list.len(),
+        });
+
+        let journal = match resumed {
+            Some(journal) => journal,
+            None => {
+                // Seeds were already recorded by the original run.
+                tasks::append_csv(
+                    opts.seed_report.into(),
+                    b"campaign_seed,task,template,trial,seed,label,tag",
+                    seeds.into_iter(),
+                )?;
+                tracing::info!("trial seeds saved to: {}", opts.seed_report.display());
+
+                let tasks = names
+                    .iter()
+                    .zip(&task_list)
+                    .map(|(name, task)| (name.clone(), task.template.clone(), task.trial));
+                journal::Journal::create(journal_path, seed, tasks)?
+            }
+        };
+        let journal = Arc::new(journal);
+
+        let _dashboard = match opts.ui {
+            true => Some(dashboard::start(task_list.len(), estimated)?),
+            false => None,
+        };
+        let (worker_pool, _control) = start_workers(&config, args, Some(journal.clone()))?;
+
+        let mut current_group: Option<Arc<barrier::StartBarrier>> = None;
+        for ((name, mut task), barrier) in names.into_iter().zip(task_list).zip(barriers) {
+            // Only start queueing the tasks of a sync group once there are enough idle workers to
+            // run all of them, otherwise the group could be split across workers that become
+            // available at very different times.
+            if let Some(barrier) = barrier.as_ref() {
+                if !current_group.as_ref().is_some_and(|x| Arc::ptr_eq(x, barrier)) {
+                    worker_pool.wait_for_idle_workers(barrier.size())?;
+                    current_group = Some(barrier.clone());
+                }
+            }
+            let mut vars = config.vars.clone();
+            vars.extend(std::mem::take(&mut task.vars));
+            let estimate = task.estimate_duration();
+            worker_pool.add_task(Task {
+                name,
+                instance: task.instance.clone(),
+                vars,
+                estimate,
+                timeout: task.timeout(args.timeout_factor),
+                retry: task.retry,
+                artifacts: task.artifacts,
+                barrier,
+                runable: Box::new(tasks::DynamicTask::TaskList { tasks: task.tasks }),
+            })?;
+        }
+
+        tracing::info!("All pending tasks started");
+        // Re-queued tasks that were never run are still pending in the journal, so the manifest is
+        // saved before the error is returned.
+        let waited = worker_pool.wait_for_workers();
+        if waited.is_ok() {
+            tracing::info!("All tasks complete");
+        }
+        report_degraded_tasks();
+        worker_pool.report_health();
+        events::record(events::Event::RunFinished {
+            duration_secs: started.elapsed().as_secs_f64(),
+        });
+
+        manifest.finished = clock::unix_secs(std::time::SystemTime::now());
+        manifest.update_states(&journal);
+        manifest.write(opts.manifest)?;
+        tracing::info!("run manifest saved to: {}", opts.manifest.display());
+
+        if let Some(path) = opts.results_db {
+            let run = trends::RunSummary {
+                benchmark,
+                label: &run_info.label,
+                commit: opts.commit,
+                campaign_seed: seed,
+                started: manifest.started,
+                finished: manifest.finished,
+            };
+            if let Err(e) = trends::record_run(path, &run, &results, &journal) {
+                tracing::error!("failed to record results: {e:#}");
+            }
+        }
+
+        let summary = journal.summary();
+        let incomplete = summary
+            .iter()
+            .filter(|(state, _)| **state != journal::TaskState::Completed)
+            .map(|(state, count)| format!("{count} {state:?}"))
+            .collect::<Vec<_>>();
+        if !incomplete.is_empty() {
+            tracing::warn!(
+                "{} tasks did not complete, use `--resume` to re-run them",
+                incomplete.join(", ").to_lowercase()
+            );
+        }
+        waited?;
+    }
+
+    Ok(())
+}
+
+/// Moves the output of a task that did not complete in a previous run out of the way, so that the
+/// re-run is not rejected by `ExitIfExisting`. The partial results are kept for debugging.
+fn set_aside_partial_results(config: &Config, task: &TaskConfig) -> anyhow::Result<()> {
+    let mut vars = utils::Variables::default();
+    vars.insert_all(config.vars.iter().chain(&task.vars).map(|x| x.clone().into()));
+    let mut paths = vec![];
+    for inner in &task.tasks {
+        inner.guarded_paths(&vars, &mut paths);
+    }
+
+    let suffix = clock::unix_secs(std::time::SystemTime::now()) as u64;
+    for path in paths.into_iter().filter(|x| x.exists()) {
+        let mut dst = path.clone().into_os_string();
+        dst.push(format!(".incomplete-{suffix}"));
+        std::fs::rename(&path, &dst).with_context(|| {
+            format!("failed to move partial results of {}: {}", task.tag, path.display())
+        })?;
+        tracing::info!("partial results moved to: {}", std::path::Path::new(&dst).display());
+    }
+    Ok(())
+}
+
+/// The names and configs of tasks along with the sync group barrier of each task.
+type SyncedTasks = (Vec<String>, Vec<TaskConfig>, Vec<Option<Arc<barrier::StartBarrier>>>);
+
+/// Assigns each task to its sync group (see `--sync-start`), returning the tasks reordered so that
+/// the tasks of each group are queued consecutively, along with the barrier of each task.
+fn sync_groups(
+    names: Vec<String>,
+    task_list: Vec<TaskConfig>,
+    opts: &BenchOptions,
+    workers: usize,
+) -> anyhow::Result<SyncedTasks> {
+    if opts.sync_start.is_empty() {
+        let barriers = task_list.iter().map(|_| None).collect();
+        return Ok((names, task_list, barriers));
+    }
+
+    let groups: Vec<_> =
+        task_list.iter().map(|task| barrier::group_of(&task.tag, opts.sync_start)).collect();
+    let mut sizes: indexmap::IndexMap<&str, usize> = indexmap::IndexMap::new();
+    for group in groups.iter().flatten() {
+        *sizes.entry(group.as_str()).or_default() += 1;
+    }
+    if let Some((group, size)) = sizes.iter().find(|(_, size)| **size > workers) {
+        anyhow::bail!(
+            "sync group {group} has {size} tasks, but only {workers} workers are available \
+            (all tasks in a sync group must be able to run at the same time)"
+        );
+    }
+    let ungrouped = groups.iter().filter(|x| x.is_none()).count();
+    if ungrouped != 0 {
+        tracing::warn!(
+            "{ungrouped} task(s) are not part of any sync group (missing tag keys: {})",
+            opts.sync_start.join(",")
+        );
+    }
+    tracing::info!("{} sync groups (by {})", sizes.len(), opts.sync_start.join(","));
+
+    let barriers: HashMap<&str, Arc<barrier::StartBarrier>> = sizes
+        .iter()
+        .map(|(group, size)| {
+            let barrier = barrier::StartBarrier::new(group.to_string(), *size, opts.sync_timeout);
+            (*group, Arc::new(barrier))
+        })
+        .collect();
+
+    // Tasks are handed to workers in order, so queue the tasks of each group together to avoid
+    // holding workers for tasks that are stuck behind other groups (each group is only queued once
+    // enough workers are idle to start all of its tasks).
+    let mut tasks: Vec<_> = names
+        .into_iter()
+        .zip(task_list)
+        .zip(&groups)
+        .map(|((name, task), group)| {
+            let order = group.as_deref().and_then(|x| sizes.get_index_of(x));
+            let barrier = group.as_deref().and_then(|x| barriers.get(x).cloned());
+            (order, name, task, barrier)
+        })
+        .collect();
+    tasks.sort_by_key(|(order, ..)| order.unwrap_or(usize::MAX));
+
+    let mut output = (vec![], vec![], vec![]);
+    for (_, name, task, barrier) in tasks {
+        output.0.push(name);
+        output.1.push(task);
+        output.2.push(barrier);
+    }
+    Ok(output)
+}
+
+/// Appends `label=<label>` to the task's tag, so that the label is included in any data collected
+/// by the task, and exposes the label to the task as `LABEL`.
+fn add_label_tag(task: &mut TaskConfig, label: &str) {
+    task.tag.push_str(&format!(";label={}", utils::escape_tag_value(label)));
+    if let Some(tag) = task.vars.iter_mut().find(|x| x.key == "TAG") {
+        tag.value = task.tag.clone();
+    }
+    task.vars.push(config::KeyValue::new("LABEL", label));
+}
+
+/// Runs the task recorded in `transcript` against the transcript (see [transcript]).
+fn replay_task(
+    env: &minijinja::Environment,
+    benchmark: &std::path::Path,
+    transcript: &std::path::Path,
+    overrides: &[String],
+) -> anyhow::Result<()> {
+    let header = transcript::start_replay(transcript)?;
+    let task_list = load_benchmark(env, benchmark)?;
+    let task = header
+        .task
+        .strip_prefix("task-")
+        .and_then(|i| task_list.get(i.parse::<usize>().ok()?))
+        .ok_or_else(|| {
+            anyhow::format_err!("{} is not a task of {}", header.task, benchmark.display())
+        })?;
+
+    let mut vars = header.vars;
+    for entry in overrides {
+        let var = config::KeyValue::from_str(entry)
+            .ok_or_else(|| anyhow::format_err!("expected KEY=VALUE: {entry}"))?;
+        match vars.iter_mut().find(|x| x.key == var.key) {
+            Some(existing) => existing.value = var.value,
+            None => vars.push(var),
+        }
+    }
+
+    let mut task = Task {
+        name: header.task,
+        instance: task.instance.clone(),
+        vars,
+        estimate: task.estimate_duration(),
+        timeout: None,
+        retry: task.retry.clone(),
+        artifacts: task.artifacts.clone(),
+        barrier: None,
+        runable: Box::new(tasks::DynamicTask::TaskList { tasks: task.tasks.clone() }),
+    };
+    task.run(header.worker_id, &mut transcript::Replay)?;
+    transcript::finish_replay()?;
+    tracing::info!("{} matched the transcript", task.name);
+    Ok(())
+}
+
+/// Reads and expands all tasks in the benchmark configuration file at `benchmark`.
+fn load_benchmark(
+    env: &minijinja::Environment,
+    benchmark: &std::path::Path,
+) -> anyhow::Result<Vec<TaskConfig>> {
+    let data = std::fs::read_to_string(benchmark)
+        .with_context(|| format!("failed to read: {}", benchmark.display()))?;
+    let data = env
+        .render_str(&data, &HashMap::<(), ()>::new())
+        .with_context(|| format!("error rendering: {}", benchmark.display()))?;
+    render_tasks_template(env, &data)
+}
+
+fn start_workers(
+    config: &Config,
+    args: &Args,
+    journal: Option<Arc<journal::Journal>>,
+) -> anyhow::Result<(Arc<worker::WorkerPool>, Option<utils::DeleteOnDrop>)> {
+    let fail_fast = (!args.no_fail_fast).then(|| {
+        worker::FailFast::new(
+            args.fail_fast_rate / 100.0,
+            Duration::from_secs(args.fail_fast_window * 60),
+        )
+    });
+    let worker_pool = Arc::new(worker::WorkerPool::new(args.quarantine_after, fail_fast, journal));
+    let factory = worker::WorkerFactory::new(config, args.backend)?;
+    for _ in 0..args.workers {
+        factory.add_worker(&worker_pool)?;
+    }
+    tracing::info!("{} workers started", args.workers);
+
+    let control = match args.control.as_ref() {
+        Some(path) => Some(control::spawn_control_server(path, worker_pool.clone(), factory)?),
+        None => None,
+    };
+    Ok((worker_pool, control))
+}
+
+fn estimate_total_duration(tasks: &[TaskConfig], workers: usize) -> Duration {
+    let workers = workers.min(10000).max(1);
+
+    let mut heap = std::collections::BinaryHeap::new();
+    for id in 0..workers {
+        heap.push(std::cmp::Reverse(Duration::from_millis(id as u64 * 100)));
+    }
+
+    let mut current_time = Duration::from_secs(0);
+    for task in tasks {
+        // Determine the next time a worker is free.
+        let next_slot = heap.pop().unwrap();
+        current_time = next_slot.0;
+
+        // Determine the time when the current task will be complete at.
+        heap.push(std::cmp::Reverse(current_time + task.estimate_duration()));
+    }
+
+    // Get the finish time of the last worker.
+    while let Some(time) = heap.pop() {
+        current_time = time.0;
+    }
+
+    current_time
+}
+
+pub trait XShellExt {
+    /// Runs a command, returning stdout on success, and including stderr in the error message
+    fn read_with_err(self) -> anyhow::Result<String>;
+
+    /// Echos command to tracing
+    fn trace_cmd(self) -> Self;
+}
+
+impl<'a> XShellExt for xshell::Cmd<'a> {
+    fn read_with_err(self) -> anyhow::Result<String> {
+        let cmd = format!("{}", self);
+        let output = self.trace_cmd().ignore_status().output()?;
+        match output.status.success() {
+            true => Ok(String::from_utf8(output.stdout)?),
+            false => {
+                anyhow::bail!("`{cmd}` failed with {}", String::from_utf8_lossy(&output.stderr))
+            }
+        }
+    }
+
+    fn trace_cmd(mut self) -> Self {
+        tracing::info!("$ {}", self);
+        self.set_quiet(false);
+        self
+    }
+}
+
+/// Mutex for syncronizing host file system operations in workers.
+pub static HOST_FS_LOCK: parking_lot::Mutex<()> =
+    parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, ());
+
+/// Names of tasks that finished without producing all of their required artifacts.
+static DEGRADED_TASKS: parking_lot::Mutex<Vec<String>> =
+    parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, vec![]);
+
+pub(crate) fn mark_degraded(name: &str) {
+    DEGRADED_TASKS.lock().push(name.to_owned());
+}
+
+fn report_degraded_tasks() {
+    let degraded = DEGRADED_TASKS.lock();
+    if !degraded.is_empty() {
+        tracing::warn!(
+            "{} task(s) degraded (missing artifacts): {}",
+            degraded.len(),
+            degraded.join(", ")
+        );
+    }
+}
+
+/// Global stop flag used for supporting clean exits.
+static STOP_NOW: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Channel used for listening for cancellation events.
+static CANCELATION_RECEIVER: once_cell::sync::OnceCell<crossbeam_channel::Receiver<()>> =
+    once_cell::sync::OnceCell::new();
+
+/// Sender half of the cancellation channel, dropped to notify all listeners.
+static CANCELATION_SENDER: parking_lot::Mutex<Option<crossbeam_channel::Sender<()>>> =
+    parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, None);
+
+fn init_cancellation() {
+    let (cancel_tx, cancel_rx) = crossbeam_channel::bounded(0);
+    CANCELATION_RECEIVER.set(cancel_rx).unwrap();
+    *CANCELATION_SENDER.lock() = Some(cancel_tx);
+    ctrlc::set_handler(cancel).unwrap();
+}
+
+/// Stops all running tasks and prevents any new tasks from being started.
+pub(crate) fn cancel() {
+    STOP_NOW.store(true, std::sync::atomic::Ordering::Release);
+    CANCELATION_SENDER.lock().take();
+}
+
+pub(crate) fn should_stop() -> bool {
+    STOP_NOW.load(std::sync::atomic::Ordering::Acquire)
+}
+
+pub(crate) fn cancellation_channel() -> &'static crossbeam_channel::Receiver<()> {
+    CANCELATION_RECEIVER.get().unwrap()
+}
@@ -0,0 +1,56 @@
+//! A machine-readable summary of a benchmark run (`--manifest`), listing every trial of the
+//! benchmark along with its tag, seed, final state and artifacts, so that analysis tools can
+//! discover which trials exist without scanning the results tree.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::journal::{Journal, TaskState};
+
+#[derive(serde::Serialize)]
+pub(crate) struct Manifest {
+    pub benchmark: PathBuf,
+    pub campaign_seed: u64,
+    pub label: String,
+    pub notes: String,
+    /// The time the run was started and finished (in seconds since the Unix epoch).
+    pub started: f64,
+    pub finished: f64,
+    pub trials: Vec<Trial>,
+}
+
+#[derive(serde::Serialize)]
+pub(crate) struct Trial {
+    pub task: String,
+    pub template: String,
+    pub trial: usize,
+    pub tag: String,
+    pub seed: u64,
+    /// The state of the task at the end of the run (including trials completed by a previous run
+    /// that was resumed).
+    pub state: Option<TaskState>,
+    /// The (expanded) paths of the artifacts declared by the task.
+    pub artifacts: Vec<String>,
+    /// The largest drift of the guest clock (in seconds) measured while the trial was running, or
+    /// `None` if the clock was not checked.
+    pub max_clock_drift_secs: Option<f64>,
+}
+
+impl Manifest {
+    /// Updates the state and measured clock drift of each trial from `journal`.
+    pub fn update_states(&mut self, journal: &Journal) {
+        for trial in &mut self.trials {
+            trial.state = journal.state(&trial.task);
+            trial.max_clock_drift_secs = journal.max_clock_drift(&trial.task);
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("failed to write manifest: {}", path.display()))
+    }
+}
@@ -0,0 +1,195 @@
+//! Live export of the statsd metrics collected from running tasks, so that fuzzing throughput can
+//! be monitored while a benchmark is running (e.g. in Grafana). Metrics are served from a
+//! Prometheus `/metrics` endpoint (`--metrics-addr`) and/or appended to a file using the InfluxDB
+//! line protocol (`--influx-output`).
+//!
+//! Metrics are labeled with the entries of the task's `TAG` (e.g. `fuzzer` and `binary`) along
+//! with any tags reported with the metric itself.
+
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use parking_lot::lock_api::RawMutex;
+
+type Labels = Vec<(String, String)>;
+
+#[derive(Default)]
+struct State {
+    /// The latest value of each metric (keyed by metric name, then labels).
+    series: BTreeMap<String, BTreeMap<Labels, Series>>,
+    influx: Option<std::fs::File>,
+}
+
+struct Series {
+    kind: &'static str,
+    value: f64,
+}
+
+/// The state of the exporter, or `None` if metrics are not exported.
+static STATE: parking_lot::Mutex<Option<State>> =
+    parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, None);
+
+/// Returns whether metrics are being exported, in which case stats are collected from all timed
+/// tasks (even if they are not saved to a file).
+pub(crate) fn is_enabled() -> bool {
+    STATE.lock().is_some()
+}
+
+/// Appends the value of every metric to `path` (in InfluxDB line protocol) as it is collected.
+pub(crate) fn enable_influx(path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .with_context(|| format!("failed to open: {}", path.display()))?;
+    STATE.lock().get_or_insert_with(State::default).influx = Some(file);
+    Ok(())
+}
+
+/// Starts serving the latest value of every metric from a Prometheus `/metrics` endpoint at `addr`.
+pub(crate) fn serve_prometheus(addr: &str) -> anyhow::Result<()> {
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("failed to bind metrics server: {addr}"))?;
+    STATE.lock().get_or_insert_with(State::default);
+    tracing::info!("serving metrics on: http://{addr}/metrics");
+
+    let parent = tracing::Span::current();
+    std::thread::Builder::new().name("metrics".into()).spawn(move || {
+        let _guard = parent.enter();
+        for stream in listener.incoming() {
+            let result = stream.context("connect error").and_then(handle_connection);
+            if let Err(e) = result {
+                tracing::debug!("metrics server error: {e:#}");
+            }
+        }
+    })?;
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    // Only the request line is needed, so avoid waiting for the rest of the request.
+    let mut buf = [0; 1024];
+    let len = stream.read(&mut buf)?;
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let path = request.lines().next().and_then(|line| line.split(' ').nth(1)).unwrap_or("");
+
+    let (status, body) = match path {
+        "/metrics" => ("200 OK", render_prometheus()),
+        _ => ("404 Not Found", String::from("not found\n")),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\
+        Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+/// Records a metric reported by the task tagged with `task_tag` (if metrics are being exported).
+pub(crate) fn record(task_tag: &str, name: &str, kind: &str, value: f64, tags: &[(&str, &str)]) {
+    let mut state = STATE.lock();
+    let Some(state) = state.as_mut() else { return };
+
+    let mut labels = tag_labels(task_tag);
+    labels.extend(tags.iter().map(|(key, value)| (sanitize_name(key), value.to_string())));
+
+    let name = sanitize_name(name);
+    let entry = state.series.entry(name.clone()).or_default();
+    let series = match kind {
+        // Counters report the increment since the last time the metric was flushed.
+        "c" => {
+            let series =
+                entry.entry(labels.clone()).or_insert(Series { kind: "counter", value: 0.0 });
+            series.value += value;
+            series
+        }
+        _ => {
+            let series = entry.entry(labels.clone()).or_insert(Series { kind: "gauge", value });
+            series.value = value;
+            series
+        }
+    };
+
+    if let Some(file) = state.influx.as_mut() {
+        let line = influx_line(&name, &labels, series.value);
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            tracing::warn!("failed to write metrics: {e}");
+        }
+    }
+}
+
+/// Converts the entries of a task tag (e.g. `v2;fuzzer=afl;trial=0`) to labels. Values are kept
+/// percent-encoded, matching how they appear in the tag.
+fn tag_labels(tag: &str) -> Labels {
+    if tag.is_empty() {
+        return vec![];
+    }
+    if !crate::utils::is_tag(tag) {
+        return vec![("tag".into(), tag.into())];
+    }
+    tag.split(';')
+        .skip(1)
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(key, value)| (sanitize_name(key), value.to_owned()))
+        .collect()
+}
+
+/// Replaces characters that are not allowed in Prometheus metric and label names with `_`.
+fn sanitize_name(name: &str) -> String {
+    let mut output: String =
+        name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect();
+    if output.is_empty() || output.starts_with(|c: char| c.is_ascii_digit()) {
+        output.insert(0, '_');
+    }
+    output
+}
+
+fn render_prometheus() -> String {
+    let state = STATE.lock();
+    let Some(state) = state.as_ref() else { return String::new() };
+
+    let mut output = String::new();
+    for (name, series) in &state.series {
+        if let Some(first) = series.values().next() {
+            output.push_str(&format!("# TYPE {name} {}\n", first.kind));
+        }
+        for (labels, series) in series {
+            let labels: Vec<_> = labels
+                .iter()
+                .map(|(key, value)| format!("{key}=\"{}\"", escape_label_value(value)))
+                .collect();
+            output.push_str(&format!("{name}{{{}}} {}\n", labels.join(","), series.value));
+        }
+    }
+    output
+}
+
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Formats a metric using the InfluxDB line protocol: `<name>,<tags> value=<value> <timestamp>`.
+fn influx_line(name: &str, labels: &Labels, value: f64) -> String {
+    let escape = |x: &str| x.replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ");
+    let mut line = escape(name);
+    for (key, value) in labels.iter().filter(|(_, value)| !value.is_empty()) {
+        line.push_str(&format!(",{}={}", escape(key), escape(value)));
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |x| x.as_nanos());
+    line.push_str(&format!(" value={value} {timestamp}\n"));
+    line
+}
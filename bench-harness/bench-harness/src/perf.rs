@@ -0,0 +1,126 @@
+//! Optional collection of host hardware performance counters (using `perf stat`) while tasks are
+//! running, to help explain differences in throughput between fuzzers.
+//!
+//! Only supported for backends where the processes started by a task run directly on the host
+//! kernel (i.e. `local` and `docker`). Counters are collected from the agent process and any
+//! processes it spawns. For the local backend the agent runs inside of the harness, so counters
+//! also include the harness itself and any other local workers.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{self, Child, Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::Context;
+use parking_lot::lock_api::RawMutex;
+
+/// The events collected by `perf stat`.
+const EVENTS: &[&str] =
+    &["instructions", "cycles", "cache-references", "cache-misses", "branch-misses"];
+
+/// The file that counters are reported to, or `None` if counters are not collected.
+static REPORT: parking_lot::Mutex<Option<PathBuf>> =
+    parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, None);
+
+/// Enables collection of performance counters, appending the counters of each task to `path`.
+pub(crate) fn enable(path: PathBuf) {
+    *REPORT.lock() = Some(path);
+}
+
+/// A running `perf stat` process attached to a task.
+pub(crate) struct PerfStat {
+    task: String,
+    child: Child,
+    output: PathBuf,
+    report: PathBuf,
+}
+
+/// Used for giving the output of each `perf` process a unique name.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Starts collecting counters for `pid` (and its children) if enabled. Failures are logged rather
+/// than failing the task.
+pub(crate) fn attach(task: &str, pid: u32) -> Option<PerfStat> {
+    let report = REPORT.lock().clone()?;
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let name = format!("bench-harness-perf-{}-{id}.csv", process::id());
+    let output = std::env::temp_dir().join(name);
+    match spawn_perf(pid, &output) {
+        Ok(child) => Some(PerfStat { task: task.to_owned(), child, output, report }),
+        Err(e) => {
+            tracing::warn!("{task}: failed to start perf: {e:#}");
+            None
+        }
+    }
+}
+
+fn spawn_perf(pid: u32, output: &Path) -> anyhow::Result<Child> {
+    let mut cmd = Command::new("perf");
+    cmd.args(["stat", "-x", ",", "-e", &EVENTS.join(","), "-p", &pid.to_string(), "-o"])
+        .arg(output)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    tracing::debug!("Running: {cmd:?}");
+    cmd.spawn().context("failed to run perf")
+}
+
+impl PerfStat {
+    /// Stops collecting counters and appends them to the report.
+    pub fn finish(mut self) {
+        if let Err(e) = self.stop() {
+            tracing::warn!("{}: failed to collect perf counters: {e:#}", self.task);
+        }
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        interrupt(&mut self.child)?;
+        let status = self.child.wait()?;
+        // perf exits with the signal number when interrupted, so only an empty output is an error.
+        let output = std::fs::read_to_string(&self.output)
+            .with_context(|| format!("perf exited with {status}"))?;
+        let counters = parse_counters(&output);
+        if counters.is_empty() {
+            anyhow::bail!("no counters reported by perf (exit: {status}): {}", output.trim());
+        }
+
+        tracing::debug!("{}: perf counters: {counters:?}", self.task);
+        let _ = std::fs::remove_file(&self.output);
+
+        let rows = counters.into_iter().map(|(event, value)| (&self.task, event, value));
+        crate::tasks::append_csv(self.report.clone(), b"task,event,value", rows)
+    }
+}
+
+/// `perf stat` writes the counters when interrupted.
+#[cfg(unix)]
+fn interrupt(child: &mut Child) -> anyhow::Result<()> {
+    if unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) } != 0 {
+        anyhow::bail!("failed to signal perf: {}", std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn interrupt(child: &mut Child) -> anyhow::Result<()> {
+    child.kill().context("failed to stop perf")
+}
+
+/// Parses the CSV output of `perf stat -x ,` as `(event, value)` pairs, where `value` is `None` if
+/// the event was not supported or counted.
+fn parse_counters(output: &str) -> Vec<(String, Option<u64>)> {
+    output
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split(',');
+            let value = fields.next()?;
+            let _unit = fields.next()?;
+            let event = fields.next()?;
+            // Events may be suffixed with a modifier (e.g. `instructions:u`).
+            let event = event.split(':').next().unwrap_or(event);
+            Some((event.to_owned(), value.parse().ok()))
+        })
+        .collect()
+}
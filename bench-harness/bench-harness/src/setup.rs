@@ -141,7 +141,7 @@ pub fn hex(bytes: &[u8]) -> String {
     out
 }
 
-fn sha256_for_path(p: &Path) -> anyhow::Result<String> {
+pub(crate) fn sha256_for_path(p: &Path) -> anyhow::Result<String> {
     use sha2::Digest;
     use std::io::Read;
 
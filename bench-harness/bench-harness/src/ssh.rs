@@ -0,0 +1,238 @@
+//! Run tasks on remote machines by launching the agent over SSH.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Child, Command},
+};
+
+use anyhow::Context;
+
+use crate::config::Arch;
+
+fn default_remote_workdir() -> String {
+    "/tmp/bench-harness".into()
+}
+
+fn default_local_workdir() -> PathBuf {
+    "./ssh_worker".into()
+}
+
+fn default_base_port() -> u16 {
+    40000
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub(crate) struct SshConfig {
+    /// The machines to run tasks on (e.g. `ssh://user@host:22`). Workers are assigned to hosts in
+    /// round-robin order.
+    pub hosts: Vec<String>,
+    /// Directory on the remote machines that the agent binary is copied to. Each worker uses a
+    /// separate scratch directory inside of it as the working directory for tasks.
+    #[serde(default = "default_remote_workdir")]
+    pub workdir: String,
+    /// Directory on the host used for saving the output of `ssh` for each worker.
+    #[serde(default = "default_local_workdir")]
+    pub local_workdir: PathBuf,
+    /// The agent binary to copy to the remote machines (defaults to the agent configured for
+    /// `arch` in `[agents]`).
+    #[serde(default)]
+    pub agent: Option<PathBuf>,
+    /// The architecture of the remote machines.
+    #[serde(default)]
+    pub arch: Arch,
+    /// The port used by the agent of the first worker on each remote machine (the agent of worker
+    /// `n` listens on `base_port + n`).
+    #[serde(default = "default_base_port")]
+    pub base_port: u16,
+    /// Additional arguments passed to `ssh` and `scp` (e.g. `["-i", "~/.ssh/bench"]`).
+    #[serde(default)]
+    pub ssh_args: Vec<String>,
+}
+
+/// A remote machine parsed from an `ssh://[user@]host[:port]` URL.
+#[derive(Debug, Clone)]
+pub(crate) struct SshHost {
+    user: Option<String>,
+    host: String,
+    port: Option<u16>,
+}
+
+impl std::str::FromStr for SshHost {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s.strip_prefix("ssh://").unwrap_or(s).trim_end_matches('/');
+        let (user, rest) = match rest.split_once('@') {
+            Some((user, rest)) => (Some(user.to_owned()), rest),
+            None => (None, rest),
+        };
+        let (host, port) = match rest.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port.parse().with_context(|| format!("invalid port in: {s}"))?;
+                (host, Some(port))
+            }
+            None => (rest, None),
+        };
+        if host.is_empty() {
+            anyhow::bail!("invalid ssh host: {s}");
+        }
+        Ok(Self { user, host: host.to_owned(), port })
+    }
+}
+
+impl std::fmt::Display for SshHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.user {
+            Some(user) => write!(f, "{user}@{}", self.host),
+            None => f.write_str(&self.host),
+        }
+    }
+}
+
+impl SshHost {
+    /// Creates an `ssh` command that runs `remote_cmd` on the host.
+    fn ssh(&self, config: &SshConfig, extra_args: &[String], remote_cmd: &str) -> Command {
+        let mut cmd = Command::new("ssh");
+        // Never prompt for passwords or host keys, since there is no one to answer them.
+        cmd.args(["-o", "BatchMode=yes"]);
+        if let Some(port) = self.port {
+            cmd.arg("-p").arg(port.to_string());
+        }
+        cmd.args(&config.ssh_args).args(extra_args).arg(self.to_string()).arg(remote_cmd);
+        cmd
+    }
+
+    /// Copies `src` from the host running the harness to `dst` on the remote machine.
+    fn copy_to(&self, config: &SshConfig, src: &Path, dst: &str) -> anyhow::Result<()> {
+        let mut cmd = Command::new("scp");
+        cmd.args(["-o", "BatchMode=yes", "-q"]);
+        if let Some(port) = self.port {
+            cmd.arg("-P").arg(port.to_string());
+        }
+        cmd.args(&config.ssh_args).arg(src).arg(format!("{self}:{dst}"));
+        run(cmd)
+    }
+}
+
+/// Parses the hosts in `config` and copies the agent to each of them.
+pub(crate) fn prepare_hosts(
+    config: &crate::Config,
+    ssh_config: &SshConfig,
+) -> anyhow::Result<Vec<SshHost>> {
+    if ssh_config.hosts.is_empty() {
+        anyhow::bail!("no hosts configured for the ssh worker");
+    }
+    let agent = match ssh_config.agent.as_deref() {
+        Some(agent) => agent,
+        None => config.get_agent(ssh_config.arch)?.ok_or_else(|| {
+            anyhow::format_err!(
+                "no agent binary configured for the ssh worker (set `agent` in `[ssh_worker]` or \
+                add {} to `[agents]`)",
+                ssh_config.arch
+            )
+        })?,
+    };
+
+    let mut hosts = vec![];
+    for entry in &ssh_config.hosts {
+        let host: SshHost = entry.parse()?;
+        tracing::info!("copying {} to {host}", agent.display());
+        let workdir = &ssh_config.workdir;
+        run(host.ssh(ssh_config, &[], &format!("mkdir -p '{workdir}'")))
+            .with_context(|| format!("failed to prepare {host}"))?;
+        host.copy_to(ssh_config, agent, &format!("{workdir}/agent"))
+            .with_context(|| format!("failed to copy agent to {host}"))?;
+        run(host.ssh(ssh_config, &[], &format!("chmod +x '{workdir}/agent'")))
+            .with_context(|| format!("failed to prepare {host}"))?;
+        hosts.push(host);
+    }
+    Ok(hosts)
+}
+
+/// An agent running on a remote machine, reachable through a port forwarded by `ssh`.
+pub(crate) struct Session {
+    pub agent_addr: String,
+    process: Option<Child>,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+    }
+}
+
+impl Session {
+    pub fn wait_for_exit_timeout(mut self, timeout: std::time::Duration) -> anyhow::Result<()> {
+        let mut process = self.process.take().ok_or_else(|| anyhow::format_err!("ssh exited"))?;
+
+        match crate::utils::wait_for_process_timeout(&mut process, timeout)? {
+            None => {
+                let _ = process.kill();
+                anyhow::bail!("ssh timed out after: {} seconds", timeout.as_secs())
+            }
+            Some(status) if !status.success() => anyhow::bail!("ssh exited with error: {status:?}"),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+/// Launches the agent for worker `id` on `host`.
+///
+/// The agent only listens on the loopback interface of the remote machine, since it does not
+/// authenticate clients, and is reached through a port forwarded by `ssh`.
+pub(crate) fn spawn_ssh_worker(
+    id: usize,
+    host: &SshHost,
+    config: &SshConfig,
+) -> anyhow::Result<Session> {
+    let workdir = crate::workdirs::worker_dir(&config.local_workdir, &format!("worker-{id}"))?;
+    std::fs::create_dir_all(&workdir)
+        .with_context(|| format!("failed to create workdir {}", workdir.display()))?;
+
+    let remote_port = u16::try_from(id)
+        .ok()
+        .and_then(|offset| config.base_port.checked_add(offset))
+        .ok_or_else(|| anyhow::format_err!("no port available for worker {id}"))?;
+    let local_port = free_local_port()?;
+
+    // Remove any files left over from the previous task before starting the agent.
+    let remote = &config.workdir;
+    let scratch = format!("{remote}/worker-{id}");
+    let remote_cmd = format!(
+        "rm -rf '{scratch}' && AGENT_SCRATCH_DIR='{scratch}' exec '{remote}/agent' -t \
+        127.0.0.1:{remote_port}"
+    );
+    // Allocate a terminal for the agent so that it (and the processes it spawned) are sent SIGHUP
+    // if the connection is closed, e.g. when the session is dropped after an error.
+    let ssh_args = vec![
+        "-tt".into(),
+        "-o".into(),
+        "ExitOnForwardFailure=yes".into(),
+        "-L".into(),
+        format!("127.0.0.1:{local_port}:127.0.0.1:{remote_port}"),
+    ];
+    let mut cmd = host.ssh(config, &ssh_args, &remote_cmd);
+    crate::utils::redirect_stdio(&mut cmd, &workdir)?;
+    tracing::debug!("Running: {cmd:?}");
+    let process = cmd.spawn().context("failed to run ssh")?;
+
+    Ok(Session { agent_addr: format!("127.0.0.1:{local_port}"), process: Some(process) })
+}
+
+/// Finds an unused port on the loopback interface of the host.
+fn free_local_port() -> anyhow::Result<u16> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.port())
+}
+
+fn run(mut cmd: Command) -> anyhow::Result<()> {
+    tracing::info!("Running: {cmd:?}");
+    let output = cmd.output()?;
+    if !output.status.success() {
+        anyhow::bail!("{cmd:?} failed with {}", String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
@@ -0,0 +1,102 @@
+//! Conversion of raw statsd data collected by the agent into a normalized long-format CSV.
+
+use std::path::PathBuf;
+
+use agent_interface::client::Agent;
+
+const HEADER: &[u8] = b"timestamp,metric,type,value,tags";
+
+#[derive(serde::Serialize)]
+struct MetricRow<'a> {
+    /// Time (in milliseconds since the unix epoch) that the metric was collected from the agent.
+    timestamp: u64,
+    metric: &'a str,
+    #[serde(rename = "type")]
+    kind: &'a str,
+    value: f64,
+    tags: String,
+}
+
+/// A statsd metric, e.g. `execs:1000|c|@0.5|#cpu:0`.
+struct Metric<'a> {
+    name: &'a str,
+    kind: &'a str,
+    value: f64,
+    tags: Vec<(&'a str, &'a str)>,
+}
+
+fn parse_metric(line: &str) -> Option<Metric<'_>> {
+    let (name, rest) = line.split_once(':')?;
+    let mut parts = rest.split('|');
+    let value = parts.next()?.trim().parse().ok()?;
+    let kind = parts.next()?.trim();
+
+    let mut tags = vec![];
+    for part in parts {
+        if let Some(entries) = part.strip_prefix('#') {
+            tags.extend(entries.split(',').map(|x| x.split_once(':').unwrap_or((x, ""))));
+        }
+    }
+
+    Some(Metric { name: name.trim(), kind, value, tags })
+}
+
+/// Destination for statsd metrics collected from the agent.
+pub(crate) struct StatsSink {
+    /// The CSV file to save metrics to, or `None` if metrics are only exported (see
+    /// [crate::metrics]).
+    pub dst: Option<PathBuf>,
+    /// Tags (e.g. the task's `TAG` variable) to prepend to the tags of every metric.
+    pub tags: String,
+}
+
+impl StatsSink {
+    /// Drains the statsd data collected by the agent, appending each metric to the CSV file (and
+    /// exporting it if enabled).
+    pub fn collect(&self, agent: &mut dyn Agent) -> anyhow::Result<()> {
+        let data = agent.get_stats()?;
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |x| x.as_millis() as u64);
+
+        let mut rows = vec![];
+        for line in data.lines().map(str::trim).filter(|x| !x.is_empty()) {
+            let Some(metric) = parse_metric(line) else {
+                tracing::warn!("invalid statsd metric: {line}");
+                continue;
+            };
+            crate::metrics::record(
+                &self.tags,
+                metric.name,
+                metric.kind,
+                metric.value,
+                &metric.tags,
+            );
+
+            let mut tags = self.tags.clone();
+            for (key, value) in metric.tags {
+                if !tags.is_empty() {
+                    tags.push(';');
+                }
+                tags.push_str(key);
+                tags.push('=');
+                tags.push_str(value);
+            }
+            rows.push(MetricRow {
+                timestamp,
+                metric: metric.name,
+                kind: metric.kind,
+                value: metric.value,
+                tags,
+            });
+        }
+
+        let metrics = rows.iter().map(|x| (x.metric, x.kind, x.value));
+        crate::dashboard::record_stats(&self.tags, metrics);
+
+        if let Some(dst) = self.dst.as_ref().filter(|_| !rows.is_empty()) {
+            crate::tasks::append_csv(dst.clone(), HEADER, rows.into_iter())?;
+        }
+        Ok(())
+    }
+}
@@ -1,14 +1,18 @@
 use std::{
-    collections::{BTreeMap, HashMap},
-    io::Write,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write as _,
+    io::{BufRead, Read, Write},
     path::{Path, PathBuf},
     time::Duration,
 };
 
-use agent_interface::{client::Agent, ExitKind, RunCommand};
+use agent_interface::{
+    client::{Agent, FileReader},
+    ExitKind, RunCommand,
+};
 use anyhow::Context;
 
-use crate::{config::KeyValue, utils::Variables};
+use crate::{config::KeyValue, statsd::StatsSink, utils::Variables};
 
 pub trait Runable: Send {
     fn run(&mut self, vars: Variables, agent: &mut dyn Agent) -> anyhow::Result<()>;
@@ -18,6 +22,14 @@ pub struct Task {
     pub name: String,
     pub instance: String,
     pub vars: Vec<KeyValue>,
+    pub artifacts: Vec<Artifact>,
+    /// The expected duration of the task (see [DynamicTask::estimate_duration]).
+    pub estimate: Duration,
+    /// The time after which the VM running the task is killed (see [crate::worker::Watchdog]).
+    pub timeout: Option<Duration>,
+    pub retry: RetryPolicy,
+    /// Holds the task until the other tasks in its sync group are ready (see `--sync-start`).
+    pub barrier: Option<std::sync::Arc<crate::barrier::StartBarrier>>,
     pub runable: Box<dyn Runable>,
 }
 
@@ -27,7 +39,60 @@ impl Task {
         globals.insert("WORKER_ID".into(), worker_id.to_string());
         globals.insert_all(self.vars.iter().map(|x| x.clone().into()));
 
-        self.runable.run(globals, agent)
+        if let Some(barrier) = self.barrier.as_ref() {
+            barrier.wait(&self.name);
+            if crate::should_stop() {
+                anyhow::bail!("{} canceled while waiting for its sync group", self.name);
+            }
+        }
+
+        let mut recorder;
+        let agent = match crate::transcript::create(&self.name, worker_id, &self.vars)? {
+            Some(file) => {
+                recorder = crate::transcript::Recorder::new(file, agent);
+                &mut recorder as &mut dyn Agent
+            }
+            None => agent,
+        };
+        self.runable.run(globals.clone(), agent)?;
+        self.verify_artifacts(&globals);
+
+        Ok(())
+    }
+
+    /// Checks that all the artifacts declared by the task exist on the host and are non-empty,
+    /// marking the task as degraded if any required artifacts are missing.
+    fn verify_artifacts(&self, vars: &Variables) {
+        let mut missing = vec![];
+        for artifact in &self.artifacts {
+            let path = vars.expand_vars(&artifact.path);
+            if is_non_empty(Path::new(&path)) {
+                continue;
+            }
+            match artifact.required {
+                true => missing.push(path),
+                false => tracing::info!("optional artifact missing or empty: {path}"),
+            }
+        }
+
+        if !missing.is_empty() {
+            tracing::error!(
+                "{} degraded, missing or empty artifacts: {}",
+                self.name,
+                missing.join(", ")
+            );
+            crate::mark_degraded(&self.name);
+        }
+    }
+}
+
+fn is_non_empty(path: &Path) -> bool {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => {
+            std::fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_some())
+        }
+        Ok(metadata) => metadata.len() != 0,
+        Err(_) => false,
     }
 }
 
@@ -35,6 +100,60 @@ fn default_true() -> bool {
     true
 }
 
+/// How a task is retried after failing because of the infrastructure running it (e.g. a VM that
+/// failed to boot). Each attempt runs on a fresh VM.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// The maximum number of times the task is run (including the first attempt).
+    pub max_attempts: usize,
+    /// The delay before the first retry, doubled after each subsequent attempt.
+    #[serde(deserialize_with = "crate::utils::parse_duration")]
+    pub backoff: Duration,
+    #[serde(deserialize_with = "crate::utils::parse_duration")]
+    pub max_backoff: Duration,
+    /// Also retry tasks that timed out or lost contact with the agent. Disabled by default, since
+    /// these failures can occur after some results have been saved to the host (causing the retry
+    /// to fail if the task uses `ExitIfExisting`).
+    pub retry_timeouts: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(5 * 60),
+            retry_timeouts: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay before the attempt after `attempt` (starting from 1).
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let factor = 2_u32.saturating_pow(attempt.saturating_sub(1).min(31) as u32);
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// An output that a task is expected to produce on the host.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct Artifact {
+    pub path: String,
+    #[serde(default = "default_true")]
+    pub required: bool,
+}
+
+/// A directory inside of a disk image on the host (e.g. `{VM_WORKDIR}/var.ext4` for the `var` drive
+/// of a firecracker VM). `path` is relative to the root of the image rather than the mount point of
+/// the drive in the guest.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct ImageDir {
+    pub image: String,
+    pub path: String,
+}
+
 #[derive(serde::Deserialize, Clone, Debug)]
 // #[serde(rename_all = "snake_case", tag = "kind")]
 pub enum DynamicTask {
@@ -43,9 +162,12 @@ pub enum DynamicTask {
     ExitIfExisting {
         path: String,
     },
-    /// Saves all environment variables to the target file path.
+    /// Saves all environment variables to the target file path. If `dst` is set, the variables are
+    /// also saved to `dst` on the host (used by the `env-diff` command).
     SaveEnv {
         path: String,
+        #[serde(default)]
+        dst: Option<String>,
     },
     Run {
         command: String,
@@ -53,6 +175,10 @@ pub enum DynamicTask {
         stderr: Option<String>,
         #[serde(default, deserialize_with = "crate::utils::parse_duration_opt")]
         duration: Option<Duration>,
+        /// Path on the host to periodically save statsd metrics to while the command is running.
+        /// Metrics are collected even without a path when they are exported (`--metrics-addr`).
+        #[serde(default)]
+        stats: Option<String>,
     },
     SpawnTask {
         key: String,
@@ -82,6 +208,14 @@ pub enum DynamicTask {
         dst: String,
         #[serde(default)]
         archive: bool,
+        /// Where to recover the directory from if it cannot be copied through the agent.
+        #[serde(default)]
+        from_image: Option<ImageDir>,
+        /// A directory on the host (e.g. the results of all trials) searched for files with the
+        /// same content as the files in `src`. Matching files are copied from the host instead of
+        /// being transferred from the guest.
+        #[serde(default)]
+        dedup: Option<String>,
     },
     /// Merges the data from `src` to the file at `dst` after adding a prefix to each line.
     MergeWithPrefix {
@@ -101,11 +235,72 @@ pub enum DynamicTask {
         stdout: Option<String>,
         stderr: Option<String>,
     },
+    /// Saves any statsd metrics collected by the agent to a CSV file at `dst`.
+    SaveStats {
+        dst: String,
+    },
+    /// Copies a file from the host to the guest (e.g. a patched config), optionally setting the
+    /// permission bits of the copied file.
+    UploadFile {
+        src: String,
+        dst: String,
+        #[serde(default)]
+        mode: Option<u32>,
+    },
+    /// Recursively copies a directory from the host to the guest (e.g. a seed corpus).
+    UploadDir {
+        src: String,
+        dst: String,
+    },
+    /// Copies a deterministic random subset of the inputs in a corpus directory on the host to the
+    /// guest, so that trials started with the same `seed` are given the same seeds. All inputs are
+    /// copied if `max_inputs` is not set.
+    ProvisionCorpus {
+        src: String,
+        dst: String,
+        #[serde(default)]
+        max_inputs: Option<usize>,
+        /// The seed used to select the inputs (after variable expansion, e.g. `{SEED}` to select a
+        /// different subset for each trial).
+        #[serde(default)]
+        seed: Option<String>,
+    },
+    /// Creates a directory (and any missing parents) on the guest.
+    CreateDir {
+        path: String,
+    },
+    /// Recursively removes a file or directory on the guest (if it exists).
+    RemovePath {
+        path: String,
+    },
+    /// Moves a file or directory on the guest.
+    MovePath {
+        src: String,
+        dst: String,
+    },
+    /// Recursively copies a file or directory on the guest.
+    CopyPath {
+        src: String,
+        dst: String,
+    },
+    /// Sets the permission bits of a file or directory on the guest.
+    SetPermissions {
+        path: String,
+        mode: u32,
+    },
     InputPatternVerifier(InputPatternVerifier),
-    SaveTaggedAflPlotDataV4(SaveTaggedAflPlotDataV4),
+    /// Saves AFL plot data (from any supported AFL version) to a file after applying a tag.
+    #[serde(alias = "SaveTaggedAflPlotDataV4")]
+    SaveTaggedAflPlotData(SaveTaggedAflPlotData),
     TaskList {
         tasks: Vec<DynamicTask>,
     },
+    /// A task of a type registered by a downstream crate (see [crate::custom_tasks]).
+    Custom {
+        kind: String,
+        #[serde(default)]
+        config: BTreeMap<String, String>,
+    },
 }
 
 impl DynamicTask {
@@ -125,8 +320,27 @@ impl DynamicTask {
             | Self::MergeWithPrefix { .. }
             | Self::MergeJson { .. }
             | Self::RunHost { .. }
+            | Self::SaveStats { .. }
+            | Self::UploadFile { .. }
+            | Self::UploadDir { .. }
+            | Self::ProvisionCorpus { .. }
+            | Self::CreateDir { .. }
+            | Self::RemovePath { .. }
+            | Self::MovePath { .. }
+            | Self::CopyPath { .. }
+            | Self::SetPermissions { .. }
             | Self::InputPatternVerifier(_)
-            | Self::SaveTaggedAflPlotDataV4(_) => Duration::from_secs(0),
+            | Self::SaveTaggedAflPlotData(_)
+            | Self::Custom { .. } => Duration::from_secs(0),
+        }
+    }
+
+    /// Collects the (expanded) paths checked by any `ExitIfExisting` tasks.
+    pub fn guarded_paths(&self, vars: &Variables, paths: &mut Vec<PathBuf>) {
+        match self {
+            Self::ExitIfExisting { path } => paths.push(vars.expand_vars(path).into()),
+            Self::TaskList { tasks } => tasks.iter().for_each(|x| x.guarded_paths(vars, paths)),
+            _ => {}
         }
     }
 }
@@ -147,12 +361,20 @@ impl Runable for DynamicTask {
                     anyhow::bail!("{path} already exists (exiting)");
                 }
             }
-            DynamicTask::SaveEnv { path } => {
+            DynamicTask::SaveEnv { path, dst } => {
                 let path = vars.expand_vars(&path);
                 let string: String = vars
                     .iter()
                     .map(|(key, value)| format!("{key}={value}\n"))
                     .collect();
+                if let Some(dst) = dst {
+                    let dst: PathBuf = vars.expand_vars(dst).into();
+                    if let Some(parent) = dst.parent() {
+                        let _ = std::fs::create_dir_all(parent);
+                    }
+                    std::fs::write(&dst, &string)
+                        .with_context(|| format!("failed to write: {}", dst.display()))?;
+                }
                 let pid = agent.spawn_task(
                     RunCommand::new("echo".into())
                         .args(vec![string.into()])
@@ -165,10 +387,17 @@ impl Runable for DynamicTask {
                 stdout,
                 stderr,
                 duration,
-            } => match duration {
-                Some(t) => run_timed_task(agent, command, &vars, stdout, stderr, *t)?,
-                None => run_task(agent, command, &vars, stdout, stderr)?,
-            },
+                stats,
+            } => {
+                let stats = match stats {
+                    Some(dst) => Some(stats_sink(Some(dst), &vars)),
+                    None => crate::metrics::is_enabled().then(|| stats_sink(None, &vars)),
+                };
+                match duration {
+                    Some(t) => run_timed_task(agent, command, &vars, stdout, stderr, *t, stats)?,
+                    None => run_task(agent, command, &vars, stdout, stderr)?,
+                }
+            }
             DynamicTask::SpawnTask {
                 key,
                 command,
@@ -203,7 +432,13 @@ impl Runable for DynamicTask {
                             result.stderr.escape_ascii()
                         )
                     }
-                    ExitKind::Hang => anyhow::bail!("hang"),
+                    ExitKind::Hang => {
+                        anyhow::bail!(
+                            "timeout, stdout: {}, stderr: {}",
+                            result.stdout.escape_ascii(),
+                            result.stderr.escape_ascii()
+                        )
+                    }
                 }
             }
             DynamicTask::RunHost {
@@ -227,7 +462,7 @@ impl Runable for DynamicTask {
                     recv(crate::cancellation_channel()) -> _ => {
                         anyhow::bail!("early exit: {:?} (task canceled)", start_time.elapsed());
                     }
-                    default(Duration::from_secs_f64(*time_sec)) => {},
+                    default(crate::transcript::wait(Duration::from_secs_f64(*time_sec))) => {},
                 };
             }
             DynamicTask::Kill { signal, tasks } => {
@@ -241,27 +476,43 @@ impl Runable for DynamicTask {
             DynamicTask::CopyFile { src, dst, append } => {
                 let src: PathBuf = vars.expand_vars(&src).into();
                 let dst: PathBuf = vars.expand_vars(&dst).into();
-                try_copy(agent, src, dst, *append);
+                let result = with_reconnect(agent, &src, |agent| {
+                    try_copy(agent, src.clone(), dst.clone(), *append)
+                });
+                match result {
+                    Ok(()) => record_copy(&vars, &src, &dst),
+                    Err(e) => tracing::warn!("reading {} from agent: {e:?}", src.display()),
+                }
             }
-            DynamicTask::CopyDir { src, dst, archive } => {
+            DynamicTask::CopyDir { src, dst, archive, from_image, dedup } => {
                 let src: PathBuf = vars.expand_vars(&src).into();
                 let dst: PathBuf = vars.expand_vars(&dst).into();
+                let dedup = dedup.as_ref().map(|x| PathBuf::from(vars.expand_vars(x)));
 
                 if let Some(parent) = dst.parent() {
                     let _ = std::fs::create_dir_all(parent);
                 }
 
-                let result = match *archive {
-                    true => {
-                        let mut sink = ArchiveSink::from_path_compressed(dst)?;
-                        let result = try_copy_dir(agent, src, &mut sink);
-                        sink.archive.finish()?;
-                        result
-                    }
-                    false => try_copy_dir(agent, src, &mut HostFolderSink(dst)),
-                };
-                if let Err(e) = result {
-                    tracing::warn!("error error copying directory: {e:#}")
+                let mut result = with_reconnect(agent, &src, |agent| {
+                    copy_to_sink(&dst, *archive, |sink| {
+                        try_copy_dir(agent, src.clone(), sink, dedup.as_deref())
+                    })
+                });
+                if let (Err(e), Some(image)) = (&result, from_image) {
+                    let image_path = PathBuf::from(vars.expand_vars(&image.image));
+                    let path = vars.expand_vars(&image.path);
+                    tracing::warn!(
+                        "error copying {} from agent ({e:#}), recovering {path} from {}",
+                        src.display(),
+                        image_path.display()
+                    );
+                    result = copy_to_sink(&dst, *archive, |sink| {
+                        copy_from_image(&image_path, &path, sink)
+                    });
+                }
+                match result {
+                    Ok(()) => record_copy(&vars, &src, &dst),
+                    Err(e) => tracing::warn!("error error copying directory: {e:#}"),
                 }
             }
             DynamicTask::MergeWithPrefix {
@@ -271,6 +522,7 @@ impl Runable for DynamicTask {
                 dst,
             } => {
                 let prefix = vars.expand_vars(&prefix);
+                check_tag(&vars, &prefix)?;
                 let data = agent.read_file(vars.expand_vars(&src).into())?;
                 let dst: PathBuf = vars.expand_vars(&dst).into();
                 if let Err(e) = merge_with_prefix(dst, &header, &prefix, &data) {
@@ -279,25 +531,140 @@ impl Runable for DynamicTask {
             }
             DynamicTask::MergeJson { tag, src, dst } => {
                 let tag = vars.expand_vars(&tag);
+                check_tag(&vars, &tag)?;
                 let src: PathBuf = vars.expand_vars(&src).into();
                 let dst: PathBuf = vars.expand_vars(&dst).into();
                 if let Err(e) = merge_json(&tag, src, dst) {
                     tracing::warn!("error running task {self:?}: {e:#}")
                 }
             }
+            DynamicTask::SaveStats { dst } => stats_sink(Some(dst), &vars).collect(agent)?,
+            DynamicTask::UploadFile { src, dst, mode } => {
+                let src: PathBuf = vars.expand_vars(src).into();
+                let dst: PathBuf = vars.expand_vars(dst).into();
+                upload_file(agent, &src, dst.clone())?;
+                if let Some(mode) = mode {
+                    agent.set_permissions(dst, *mode)?;
+                }
+            }
+            DynamicTask::UploadDir { src, dst } => {
+                let src: PathBuf = vars.expand_vars(src).into();
+                let dst: PathBuf = vars.expand_vars(dst).into();
+                upload_dir(agent, &src, &dst)?;
+            }
+            DynamicTask::ProvisionCorpus { src, dst, max_inputs, seed } => {
+                let src: PathBuf = vars.expand_vars(src).into();
+                let dst: PathBuf = vars.expand_vars(dst).into();
+                let seed = match seed {
+                    Some(seed) => {
+                        let seed = vars.expand_vars(seed);
+                        seed.parse().with_context(|| format!("invalid corpus seed: {seed}"))?
+                    }
+                    None => 0,
+                };
+                provision_corpus(agent, &src, &dst, *max_inputs, seed)?;
+            }
+            DynamicTask::CreateDir { path } => agent.create_dir(vars.expand_vars(path).into())?,
+            DynamicTask::RemovePath { path } => agent.remove_path(vars.expand_vars(path).into())?,
+            DynamicTask::MovePath { src, dst } => {
+                agent.move_path(vars.expand_vars(src).into(), vars.expand_vars(dst).into())?
+            }
+            DynamicTask::CopyPath { src, dst } => {
+                agent.copy_path(vars.expand_vars(src).into(), vars.expand_vars(dst).into())?
+            }
+            DynamicTask::SetPermissions { path, mode } => {
+                agent.set_permissions(vars.expand_vars(path).into(), *mode)?
+            }
             DynamicTask::InputPatternVerifier(inner) => inner.run(agent, &vars)?,
-            DynamicTask::SaveTaggedAflPlotDataV4(inner) => inner.run(agent, &vars)?,
+            DynamicTask::SaveTaggedAflPlotData(inner) => inner.run(agent, &vars)?,
             DynamicTask::TaskList { tasks: subtasks } => {
                 for task in subtasks {
                     task.run(vars.clone(), agent)?;
                 }
             }
+            DynamicTask::Custom { kind, config } => {
+                crate::custom_tasks::create(kind, config)?.run(vars, agent)?
+            }
         }
 
         Ok(())
     }
 }
 
+/// Copies the file at `src` on the host to `dst` on the guest, creating any missing parent
+/// directories.
+fn upload_file(agent: &mut dyn Agent, src: &Path, dst: PathBuf) -> anyhow::Result<()> {
+    let data = std::fs::read(src).with_context(|| format!("failed to read: {}", src.display()))?;
+    if let Some(parent) = dst.parent().filter(|x| !x.as_os_str().is_empty()) {
+        agent.create_dir(parent.to_owned())?;
+    }
+    agent.write_file(dst, &data)
+}
+
+/// Recursively copies the directory at `src` on the host to `dst` on the guest.
+fn upload_dir(agent: &mut dyn Agent, src: &Path, dst: &Path) -> anyhow::Result<()> {
+    let mut files = 0;
+    for entry in walkdir::WalkDir::new(src).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("failed to read: {}", src.display()))?;
+        let target = dst.join(entry.path().strip_prefix(src)?);
+        if entry.file_type().is_dir() {
+            agent.create_dir(target)?;
+        }
+        else {
+            let data = std::fs::read(entry.path())
+                .with_context(|| format!("failed to read: {}", entry.path().display()))?;
+            agent.write_file(target, &data)?;
+            files += 1;
+        }
+    }
+    tracing::debug!("uploaded {files} files from {} to {}", src.display(), dst.display());
+    Ok(())
+}
+
+/// Copies up to `max_inputs` files from the corpus at `src` on the host to the `dst` directory on
+/// the guest. The inputs are chosen by shuffling the files (ordered by path) using `seed`, so the
+/// same subset is selected for the same corpus and seed.
+fn provision_corpus(
+    agent: &mut dyn Agent,
+    src: &Path,
+    dst: &Path,
+    max_inputs: Option<usize>,
+    seed: u64,
+) -> anyhow::Result<()> {
+    let mut inputs = vec![];
+    for entry in walkdir::WalkDir::new(src).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("failed to read: {}", src.display()))?;
+        if entry.file_type().is_file() {
+            inputs.push(entry.path().strip_prefix(src)?.to_owned());
+        }
+    }
+    let total = inputs.len();
+    if let Some(max_inputs) = max_inputs.filter(|x| *x < total) {
+        crate::utils::shuffle(&mut inputs, seed);
+        inputs.truncate(max_inputs);
+        inputs.sort();
+    }
+
+    agent.create_dir(dst.to_owned())?;
+    for input in &inputs {
+        let path = src.join(input);
+        let data =
+            std::fs::read(&path).with_context(|| format!("failed to read: {}", path.display()))?;
+        let target = dst.join(input);
+        if let Some(parent) = target.parent().filter(|x| *x != dst) {
+            agent.create_dir(parent.to_owned())?;
+        }
+        agent.write_file(target, &data)?;
+    }
+    tracing::info!(
+        "provisioned {} of {total} inputs from {} to {} (seed={seed})",
+        inputs.len(),
+        src.display(),
+        dst.display()
+    );
+    Ok(())
+}
+
 fn run_task(
     agent: &mut dyn Agent,
     command: &mut String,
@@ -322,25 +689,43 @@ fn run_timed_task(
     stdout: &Option<String>,
     stderr: &Option<String>,
     duration: Duration,
+    stats: Option<StatsSink>,
 ) -> Result<(), anyhow::Error> {
-    let pid = agent.spawn_task(
-        command_with_vars(&command, vars)?
-            .stdin(agent_interface::Stdio::Null)
-            .stdout(get_stdio(stdout, vars))
-            .stderr(get_stdio(stderr, vars)),
-    )?;
+    let command = command_with_vars(&command, vars)?
+        .stdin(agent_interface::Stdio::Null)
+        .stdout(get_stdio(stdout, vars))
+        .stderr(get_stdio(stderr, vars))
+        .capture_output(true);
+    let pid = agent.spawn_task(command.clone())?;
     tracing::debug!("task started with pid={pid}");
-    MonitorPidTask::new(vec![pid], duration).run(agent)?;
+    let mut monitor = MonitorPidTask::new(vec![pid], duration);
+    monitor.stats = stats;
+    monitor.restart = Some(command);
+    monitor.run(agent)?;
 
+    let pid = monitor.pids[0];
     tracing::debug!("stopping task (pid={pid})");
+    stop_process(agent, pid)?;
+
+    Ok(())
+}
+
+/// Stops `pid`, giving it a chance to exit cleanly first.
+fn stop_process(agent: &mut dyn Agent, pid: u32) -> anyhow::Result<()> {
     if let Err(e) = agent.kill_process(pid, SIGINT) {
         tracing::warn!("Error sending SIGINT: {e:#}");
         agent.kill_process(pid, SIGKILL)?;
     }
-
     Ok(())
 }
 
+fn stats_sink(dst: Option<&String>, vars: &Variables) -> StatsSink {
+    StatsSink {
+        dst: dst.map(|x| vars.expand_vars(x).into()),
+        tags: vars.get("TAG").unwrap_or_default().to_owned(),
+    }
+}
+
 fn get_stdio(value: &Option<String>, vars: &Variables) -> agent_interface::Stdio {
     value
         .as_ref()
@@ -348,10 +733,47 @@ fn get_stdio(value: &Option<String>, vars: &Variables) -> agent_interface::Stdio
         .unwrap_or(agent_interface::Stdio::Inherit)
 }
 
+/// The number of lines of recent output from a failed process to include in error reports.
+const RECENT_OUTPUT_LINES: usize = 20;
+
+/// Formats the last few lines of stderr and stdout of `pid` for including in an error report,
+/// returning an empty string if no output is available.
+fn recent_output(agent: &mut dyn Agent, pid: u32) -> String {
+    let output = match agent.get_recent_output(pid) {
+        Ok(Some(output)) => output,
+        Ok(None) => return String::new(),
+        Err(e) => {
+            tracing::debug!("failed to get recent output of pid={pid}: {e:#}");
+            return String::new();
+        }
+    };
+    let mut out = String::new();
+    for (name, data) in [("stderr", &output.stderr), ("stdout", &output.stdout)] {
+        let lines: Vec<_> = data.lines().collect();
+        if lines.is_empty() {
+            continue;
+        }
+        let tail = &lines[lines.len().saturating_sub(RECENT_OUTPUT_LINES)..];
+        let _ = write!(out, "\n--- last {} lines of {name} ---\n{}", tail.len(), tail.join("\n"));
+    }
+    out
+}
+
+/// The number of consecutive ticks a child of a monitored process must remain dead for before the
+/// task is considered to have failed.
+const DEAD_CHILD_TICKS: usize = 2;
+
+/// The maximum number of times a monitored process is restarted after one of its children died.
+const MAX_RESTARTS: usize = 3;
+
 struct MonitorPidTask {
     pids: Vec<u32>,
     duration: Duration,
     tick: Duration,
+    stats: Option<StatsSink>,
+    /// The command used to restart the (single) monitored process if one of its children dies
+    /// while the process keeps running (e.g. the target of afl-fuzz crashed).
+    restart: Option<RunCommand>,
 }
 
 impl MonitorPidTask {
@@ -360,29 +782,109 @@ impl MonitorPidTask {
             pids,
             duration,
             tick: Duration::from_secs(5),
+            stats: None,
+            restart: None,
         }
     }
 
-    fn run(&self, agent: &mut dyn Agent) -> anyhow::Result<()> {
+    fn run(&mut self, agent: &mut dyn Agent) -> anyhow::Result<()> {
+        let result = self.monitor(agent);
+        // Collect any stats that were generated since the last tick.
+        self.collect_stats(agent);
+        result
+    }
+
+    fn collect_stats(&self, agent: &mut dyn Agent) {
+        if let Some(stats) = self.stats.as_ref() {
+            if let Err(e) = stats.collect(agent) {
+                tracing::warn!("error collecting stats: {e:#}");
+            }
+        }
+    }
+
+    fn check_clock(&self, agent: &mut dyn Agent, clock: &mut crate::clock::ClockMonitor) {
+        let Some(drift) = clock.check(agent)
+        else {
+            return;
+        };
+        tracing::trace!("guest clock drift: {drift:.3}s (max: {:.3}s)", clock.max_drift());
+        if let Some(stats) = self.stats.as_ref() {
+            let metrics = [("clock_drift_ms", "g", drift * 1000.0)];
+            crate::dashboard::record_stats(&stats.tags, metrics.into_iter());
+        }
+    }
+
+    fn monitor(&mut self, agent: &mut dyn Agent) -> anyhow::Result<()> {
         let start_time = std::time::Instant::now();
         let cancel = crate::cancellation_channel();
-        let deadline = crossbeam_channel::after(self.duration);
+        let deadline = crate::transcript::after(self.duration);
+        // The number of consecutive ticks that each dead (but unreaped) descendant of each process
+        // has been observed for.
+        let mut dead_ticks: Vec<HashMap<u32, usize>> = vec![HashMap::new(); self.pids.len()];
+        let mut restarts = 0;
+        let mut clock = crate::clock::ClockMonitor::default();
         loop {
             crossbeam_channel::select! {
                 recv(deadline) -> _ => break,
                 recv(cancel) -> _ => {
                     anyhow::bail!("early exit: {:?} (task canceled)", start_time.elapsed());
                 }
-                default(self.tick) => {
-                    for pid in &self.pids {
-                        if agent.get_status(*pid)?.is_none() {
+                default(crate::transcript::wait(self.tick)) => {
+                    self.collect_stats(agent);
+                    self.check_clock(agent, &mut clock);
+                    for (pid, dead_ticks) in self.pids.iter_mut().zip(&mut dead_ticks) {
+                        let Some(status) = agent.get_status(*pid)?
+                        else {
                             if self.duration != Duration::MAX {
                                 tracing::warn!(
-                                    "early exit: {:?} (pid={pid} stopped)", start_time.elapsed()
+                                    "early exit: {:?} (pid={pid} stopped){}",
+                                    start_time.elapsed(),
+                                    recent_output(agent, *pid)
                                 );
                             }
                             return Ok(())
+                        };
+
+                        // Short-lived children (e.g. from a fork server) are constantly observed
+                        // before they are reaped, so only treat the same child remaining dead
+                        // across multiple ticks as a crash.
+                        let dead = status.dead_descendants();
+                        dead_ticks.retain(|child, _| dead.iter().any(|x| x.pid == *child));
+                        for child in &dead {
+                            *dead_ticks.entry(child.pid).or_default() += 1;
                         }
+                        if dead_ticks.values().all(|ticks| *ticks < DEAD_CHILD_TICKS) {
+                            continue;
+                        }
+
+                        let crashed = dead
+                            .iter()
+                            .filter(|x| dead_ticks[&x.pid] >= DEAD_CHILD_TICKS)
+                            .map(|x| x.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        let reason = format!(
+                            "pid={pid} is running but a child process exited: {crashed}"
+                        );
+                        let command = match self.restart.as_ref() {
+                            Some(command) if restarts < MAX_RESTARTS => command.clone(),
+                            _ => anyhow::bail!(
+                                "early exit: {:?} ({reason}){}",
+                                start_time.elapsed(),
+                                recent_output(agent, *pid)
+                            ),
+                        };
+                        restarts += 1;
+                        tracing::warn!(
+                            "restarting process ({restarts}/{MAX_RESTARTS}) after {:?}: \
+                            {reason}{}",
+                            start_time.elapsed(),
+                            recent_output(agent, *pid)
+                        );
+                        stop_process(agent, *pid)?;
+                        *pid = agent.spawn_task(command)?;
+                        dead_ticks.clear();
+                        tracing::debug!("task restarted with pid={pid}");
                     }
                 }
             }
@@ -428,14 +930,43 @@ const SIGINT: i32 = 2;
 const SIGKILL: i32 = 9;
 // const SIGTERM: i32 = 15;
 
-fn try_copy(agent: &mut dyn Agent, from: PathBuf, to: PathBuf, append: bool) {
-    let data = match agent.read_file(from.clone()) {
-        Ok(data) => data,
-        Err(e) => {
-            tracing::warn!("reading {} from agent: {e:?}", from.display());
-            return;
+/// The number of times to reconnect to an agent that stopped responding while copying results.
+const COPY_RETRIES: usize = 3;
+const COPY_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Runs `copy`, reconnecting to the agent and trying again if the copy failed because the agent
+/// stopped responding (e.g. because of a vsock error).
+fn with_reconnect(
+    agent: &mut dyn Agent,
+    src: &Path,
+    mut copy: impl FnMut(&mut dyn Agent) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut retries = 0;
+    loop {
+        let Err(e) = copy(&mut *agent) else { return Ok(()) };
+        if retries >= COPY_RETRIES || crate::should_stop() || is_responsive(agent) {
+            return Err(e);
         }
-    };
+        retries += 1;
+        tracing::warn!(
+            "agent stopped responding while copying {}, reconnecting ({retries}/{COPY_RETRIES}): \
+            {e:#}",
+            src.display()
+        );
+        std::thread::sleep(crate::transcript::wait(COPY_RETRY_DELAY));
+        agent.reconnect().with_context(|| format!("failed to copy {}: {e:#}", src.display()))?;
+    }
+}
+
+/// Checks whether the agent can still be reached (errors reported by the agent still count as a
+/// response).
+fn is_responsive(agent: &mut dyn Agent) -> bool {
+    agent.send_request(agent_interface::Request::GetTime, Some(Duration::from_secs(10))).is_ok()
+}
+
+/// Copies `from` to `to`, returning an error if the file could not be read from the agent.
+fn try_copy(agent: &mut dyn Agent, from: PathBuf, to: PathBuf, append: bool) -> anyhow::Result<()> {
+    let data = agent.read_file(from)?;
 
     let fs_guard = crate::HOST_FS_LOCK.lock();
     if let Some(parent) = to.parent() {
@@ -450,7 +981,7 @@ fn try_copy(agent: &mut dyn Agent, from: PathBuf, to: PathBuf, append: bool) {
         Ok(file) => file,
         Err(e) => {
             tracing::warn!("error opening: {}: {e:?}", to.display());
-            return;
+            return Ok(());
         }
     };
 
@@ -460,6 +991,7 @@ fn try_copy(agent: &mut dyn Agent, from: PathBuf, to: PathBuf, append: bool) {
 
     let _ = file.flush();
     drop(fs_guard);
+    Ok(())
 }
 
 fn walk_agent_dir<'a>(
@@ -505,7 +1037,16 @@ impl<'a> Iterator for AgentDirIterator<'a> {
 
 trait CopySink {
     fn add_dir(&mut self, path: &Path) -> anyhow::Result<()>;
-    fn add_file(&mut self, path: &Path, content: Vec<u8>) -> anyhow::Result<()>;
+    /// Adds a file of `len` bytes, streaming its content from `content`.
+    fn add_file(&mut self, path: &Path, len: u64, content: &mut dyn Read) -> anyhow::Result<()>;
+
+    /// Adds a file with the same content as the file at `existing` on the host.
+    fn add_existing(&mut self, path: &Path, existing: &Path) -> anyhow::Result<()> {
+        let mut file = std::fs::File::open(existing)
+            .with_context(|| format!("failed to open: {}", existing.display()))?;
+        let len = file.metadata()?.len();
+        self.add_file(path, len, &mut file)
+    }
 }
 
 struct HostFolderSink(PathBuf);
@@ -519,13 +1060,72 @@ impl CopySink for HostFolderSink {
         Ok(())
     }
 
-    fn add_file(&mut self, path: &Path, content: Vec<u8>) -> anyhow::Result<()> {
+    fn add_file(&mut self, path: &Path, _len: u64, content: &mut dyn Read) -> anyhow::Result<()> {
         let dst_path = self.0.join(path);
-        if let Err(e) = std::fs::write(&dst_path, &content) {
+        let result = std::fs::File::create(&dst_path)
+            .and_then(|mut file| std::io::copy(content, &mut file));
+        if let Err(e) = result {
             tracing::warn!("error writing data to {}: {e:?}", dst_path.display());
+            // Avoid leaving a partial file that could be mistaken for a complete one.
+            let _ = std::fs::remove_file(&dst_path);
         }
         Ok(())
     }
+
+    fn add_existing(&mut self, path: &Path, existing: &Path) -> anyhow::Result<()> {
+        let dst_path = self.0.join(path);
+        // Skip files that were already copied to the destination (e.g. by a previous run).
+        if dst_path.canonicalize().is_ok_and(|x| existing.canonicalize().is_ok_and(|y| x == y)) {
+            return Ok(());
+        }
+        std::fs::copy(existing, &dst_path).with_context(|| {
+            format!("failed to copy {} to {}", existing.display(), dst_path.display())
+        })?;
+        Ok(())
+    }
+}
+
+/// Files on the host with the same content as files on the guest, used for avoiding transferring
+/// duplicate files (see `dedup` in [DynamicTask::CopyDir]).
+struct DedupIndex {
+    /// The hash of each file on the guest.
+    guest: HashMap<PathBuf, String>,
+    /// A file on the host for each hash that matches a file on the guest.
+    host: HashMap<String, PathBuf>,
+}
+
+impl DedupIndex {
+    fn build(agent: &mut dyn Agent, src: &Path, host_dir: &Path) -> anyhow::Result<Self> {
+        let hashes = agent.hash_dir(src.into(), agent_interface::HashAlgorithm::Sha256)?;
+        let lens: HashSet<u64> = hashes.iter().map(|x| x.len).collect();
+        let wanted: HashSet<&str> = hashes.iter().map(|x| x.hash.as_str()).collect();
+
+        let mut host = HashMap::new();
+        for entry in walkdir::WalkDir::new(host_dir).into_iter().filter_map(Result::ok) {
+            // Only files with the same size as a file on the guest can match, so avoid hashing
+            // any other files.
+            let is_candidate = entry.file_type().is_file()
+                && entry.metadata().is_ok_and(|x| lens.contains(&x.len()));
+            if !is_candidate {
+                continue;
+            }
+            match crate::setup::sha256_for_path(entry.path()) {
+                Ok(hash) if wanted.contains(hash.as_str()) => {
+                    host.entry(hash).or_insert_with(|| entry.into_path());
+                }
+                Ok(_) => {}
+                Err(e) => tracing::debug!("skipping {}: {e:#}", entry.path().display()),
+            }
+        }
+
+        let guest = hashes.into_iter().map(|x| (x.path, x.hash)).collect();
+        Ok(Self { guest, host })
+    }
+
+    /// Finds a file on the host with the same content as the file at `path` on the guest.
+    fn find(&self, path: &Path) -> Option<&Path> {
+        self.host.get(self.guest.get(path)?).map(|x| x.as_path())
+    }
 }
 
 struct ArchiveSink<W: Write> {
@@ -553,38 +1153,137 @@ impl<W: Write> CopySink for ArchiveSink<W> {
         header.set_path(path)?;
         header.set_size(0);
         header.set_entry_type(tar::EntryType::dir());
-        header.set_mode(0o666);
+        header.set_mode(0o755);
         header.set_cksum();
         self.archive.append(&header, std::io::empty())?;
         Ok(())
     }
 
-    fn add_file(&mut self, path: &Path, content: Vec<u8>) -> anyhow::Result<()> {
+    fn add_file(&mut self, path: &Path, len: u64, content: &mut dyn Read) -> anyhow::Result<()> {
         let mut header = tar::Header::new_gnu();
         header.set_path(path)?;
-        header.set_size(content.len() as u64);
+        header.set_size(len);
         header.set_entry_type(tar::EntryType::file());
         header.set_mode(0o666);
         header.set_cksum();
-        self.archive
-            .append(&header, std::io::Cursor::new(&content))?;
+        // The size is written before the content, so pad (or truncate) the content to match in
+        // case the file changed after the directory was read or could not be fully read.
+        let mut reader = StopOnError { inner: content, path, read: 0, error: None };
+        self.archive.append(&header, (&mut reader).chain(std::io::repeat(0)).take(len))?;
+
+        // The entry cannot be removed once it has been written, so mark it as incomplete instead.
+        let error = match reader.error {
+            Some(e) => Some(e.to_string()),
+            None if reader.read < len => Some(format!("read {} of {len} bytes", reader.read)),
+            None => None,
+        };
+        if let Some(error) = error {
+            let mut marker = path.as_os_str().to_owned();
+            marker.push(".failed");
+            let mut header = tar::Header::new_gnu();
+            header.set_path(&marker)?;
+            header.set_size(error.len() as u64);
+            header.set_entry_type(tar::EntryType::file());
+            header.set_mode(0o666);
+            header.set_cksum();
+            self.archive.append(&header, error.as_bytes())?;
+            anyhow::bail!("{} is incomplete: {error}", path.display());
+        }
         Ok(())
     }
 }
 
-fn try_copy_dir<S: CopySink>(
+/// Treats read errors as the end of the input, to avoid leaving an archive in an inconsistent state
+/// after a partially written entry. The error is kept so that the entry can be marked as failed.
+struct StopOnError<'a> {
+    inner: &'a mut dyn Read,
+    path: &'a Path,
+    /// The number of bytes read from `inner`.
+    read: u64,
+    error: Option<std::io::Error>,
+}
+
+impl Read for StopOnError<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.error.is_some() {
+            return Ok(0);
+        }
+        match self.inner.read(buf) {
+            Ok(n) => {
+                self.read += n as u64;
+                Ok(n)
+            }
+            Err(e) => {
+                tracing::warn!("error reading {}, truncating: {e}", self.path.display());
+                self.error = Some(e);
+                Ok(0)
+            }
+        }
+    }
+}
+
+/// Records whether reading from `inner` failed, since sinks may tolerate read errors.
+struct TrackErrors<R> {
+    inner: R,
+    failed: bool,
+}
+
+impl<R: Read> Read for TrackErrors<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let result = self.inner.read(buf);
+        self.failed |= result.is_err();
+        result
+    }
+}
+
+/// Creates a sink at `dst` (a compressed archive if `archive` is set) and adds files to it with
+/// `copy`.
+fn copy_to_sink(
+    dst: &Path,
+    archive: bool,
+    copy: impl FnOnce(&mut dyn CopySink) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    match archive {
+        true => {
+            let mut sink = ArchiveSink::from_path_compressed(dst.to_owned())?;
+            let result = copy(&mut sink);
+            sink.archive.finish()?;
+            result
+        }
+        false => copy(&mut HostFolderSink(dst.to_owned())),
+    }
+}
+
+/// Copies the directory `from` on the guest to `sink`. Entries that fail to copy are skipped,
+/// returning an error after all other entries have been copied.
+fn try_copy_dir(
     agent: &mut dyn Agent,
     from: PathBuf,
-    sink: &mut S,
+    sink: &mut dyn CopySink,
+    dedup: Option<&Path>,
 ) -> anyhow::Result<()> {
+    // The index is built before taking the lock, since hashing the host directory can take a long
+    // time and would otherwise stall the copies of every other worker (files in the dedup directory
+    // are only ever added, so the index remains valid).
+    let dedup = dedup.and_then(|host_dir| match DedupIndex::build(agent, &from, host_dir) {
+        Ok(index) => Some(index),
+        Err(e) => {
+            tracing::warn!("unable to deduplicate {}: {e:#}", from.display());
+            None
+        }
+    });
+
     let fs_guard = crate::HOST_FS_LOCK.lock();
 
+    let mut failed = 0;
+    let mut deduplicated = 0;
     let mut walker = walk_agent_dir(agent, &from, true)?;
     while let Some(entry) = walker.next() {
         let entry = match entry {
             Ok(entry) => entry,
             Err(e) => {
                 tracing::warn!("{e:?}");
+                failed += 1;
                 continue;
             }
         };
@@ -598,23 +1297,93 @@ fn try_copy_dir<S: CopySink>(
             continue;
         };
 
-        if entry.is_file {
-            match walker.agent.read_file(entry.path.clone()) {
-                Ok(data) => sink.add_file(relative_path, data)?,
-                Err(e) => {
-                    tracing::warn!("Error reading {} from agent: {e:?}", entry.path.display());
+        if let Some(existing) = dedup.as_ref().and_then(|x| x.find(&entry.path)) {
+            match sink.add_existing(relative_path, existing) {
+                Ok(()) => {
+                    deduplicated += 1;
                     continue;
                 }
-            };
+                Err(e) => tracing::warn!("{e:#}"),
+            }
+        }
+
+        if entry.is_file {
+            // Files are streamed in chunks to avoid loading large files (e.g. fuzzer queues) into
+            // memory.
+            let path = entry.path.clone();
+            let reader = FileReader::new(&mut *walker.agent, path, Some(entry.len));
+            let mut reader = TrackErrors { inner: reader, failed: false };
+            if let Err(e) = sink.add_file(relative_path, entry.len, &mut reader) {
+                tracing::warn!("Error copying {} from agent: {e:?}", entry.path.display());
+                failed += 1;
+            } else if reader.failed {
+                failed += 1;
+            }
         } else {
             sink.add_dir(relative_path)?;
         }
     }
     drop(fs_guard);
 
+    if deduplicated != 0 {
+        tracing::info!("{deduplicated} files in {} copied from the host", from.display());
+    }
+    if failed != 0 {
+        anyhow::bail!("failed to copy {failed} entries from {}", from.display());
+    }
     Ok(())
 }
 
+/// Extracts `path` from the ext4 image at `image` using `debugfs` (which does not require mounting
+/// the image) and adds its content to `sink`. Used to recover results from the drives of a VM when
+/// the agent cannot be reached.
+fn copy_from_image(image: &Path, path: &str, sink: &mut dyn CopySink) -> anyhow::Result<()> {
+    let tmp = std::env::temp_dir().join(format!(
+        "bench-harness-recover-{}-{}",
+        std::process::id(),
+        image.display().to_string().replace(['/', '\\'], "_")
+    ));
+    let _ = std::fs::remove_dir_all(&tmp);
+    std::fs::create_dir_all(&tmp)
+        .with_context(|| format!("failed to create: {}", tmp.display()))?;
+
+    let result = (|| {
+        let output = std::process::Command::new("debugfs")
+            .arg("-R")
+            .arg(format!("rdump \"{path}\" \"{}\"", tmp.display()))
+            .arg(image)
+            .output()
+            .context("failed to run debugfs")?;
+        // `rdump` writes the directory to `tmp` using the name of the last component of `path`.
+        let name = Path::new(path).file_name().unwrap_or_default();
+        let root = tmp.join(name);
+        if !output.status.success() || !root.is_dir() {
+            anyhow::bail!(
+                "failed to extract {path} from {}: {}",
+                image.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let _fs_guard = crate::HOST_FS_LOCK.lock();
+        for entry in walkdir::WalkDir::new(&root).min_depth(1) {
+            let entry = entry?;
+            let relative_path = entry.path().strip_prefix(&root)?;
+            if entry.file_type().is_dir() {
+                sink.add_dir(relative_path)?;
+            } else if entry.file_type().is_file() {
+                let len = entry.metadata()?.len();
+                let mut file = std::fs::File::open(entry.path())?;
+                sink.add_file(relative_path, len, &mut file)?;
+            }
+        }
+        Ok(())
+    })();
+
+    let _ = std::fs::remove_dir_all(&tmp);
+    result
+}
+
 fn command_with_vars(command: &str, vars: &Variables) -> anyhow::Result<RunCommand> {
     let cmd_string = vars.expand_vars(&command);
     let mut cmd = RunCommand::from_cmd_string(&cmd_string)
@@ -624,6 +1393,8 @@ fn command_with_vars(command: &str, vars: &Variables) -> anyhow::Result<RunComma
     Ok(cmd)
 }
 
+/// Appends `rows` to the CSV file at `dst`, writing `header` first if the file is new. Existing
+/// files with a different header (e.g. written by an older version) are moved aside first.
 pub fn append_csv<T>(
     dst: PathBuf,
     header: &[u8],
@@ -637,6 +1408,18 @@ where
     if let Some(parent) = dst.parent() {
         let _ = std::fs::create_dir_all(parent);
     }
+    if csv_header(&dst)?.is_some_and(|x| x != header) {
+        let suffix = crate::clock::unix_secs(std::time::SystemTime::now()) as u64;
+        let mut rotated = dst.clone().into_os_string();
+        rotated.push(format!(".old-{suffix}"));
+        std::fs::rename(&dst, &rotated)
+            .with_context(|| format!("failed to move: {}", dst.display()))?;
+        tracing::warn!(
+            "{} has a different header, moved existing rows to: {}",
+            dst.display(),
+            Path::new(&rotated).display()
+        );
+    }
 
     let mut output = std::fs::OpenOptions::new()
         .append(true)
@@ -660,6 +1443,21 @@ where
     Ok(())
 }
 
+/// Reads the header of the CSV file at `path`, returning `None` if the file is missing or empty.
+fn csv_header(path: &Path) -> anyhow::Result<Option<Vec<u8>>> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("failed to open: {}", path.display())),
+    };
+    let mut header = vec![];
+    std::io::BufReader::new(file).read_until(b'\n', &mut header)?;
+    while header.last().is_some_and(|x| matches!(x, b'\n' | b'\r')) {
+        header.pop();
+    }
+    Ok((!header.is_empty()).then_some(header))
+}
+
 pub fn resolve_bug_ids<T, F>(
     agent: &mut dyn Agent,
     crash_dir: PathBuf,
@@ -742,10 +1540,30 @@ pub struct InputPatternVerifier {
 
 impl InputPatternVerifier {
     pub fn run(&self, agent: &mut dyn Agent, vars: &Variables) -> anyhow::Result<()> {
+        self.verify(agent, vars, self.crash_dir(vars))
+    }
+
+    /// The directory containing the crashes checked by the verifier.
+    pub fn crash_dir(&self, vars: &Variables) -> PathBuf {
+        vars.expand_vars(&self.crash_dir).into()
+    }
+
+    /// The file that the bugs found by the verifier are appended to.
+    pub fn dst(&self, vars: &Variables) -> PathBuf {
+        vars.expand_vars(&self.dst).into()
+    }
+
+    /// Resolves the bugs for the crashes in `crash_dir`, which may differ from the configured
+    /// directory when replaying the verifier on collected artifacts (see [crate::verify]).
+    pub fn verify(
+        &self,
+        agent: &mut dyn Agent,
+        vars: &Variables,
+        crash_dir: PathBuf,
+    ) -> anyhow::Result<()> {
         let tag = vars.get("TAG").unwrap_or("?");
 
-        let crash_dir = vars.expand_vars(&self.crash_dir);
-        let bugs = resolve_bug_ids(agent, crash_dir.into(), |agent, entry| {
+        let bugs = resolve_bug_ids(agent, crash_dir, |agent, entry| {
             let data = agent.read_file(entry.path.clone())?;
 
             for pattern in &self.patterns {
@@ -759,11 +1577,10 @@ impl InputPatternVerifier {
             Ok(vec![])
         })?;
 
-        let dst: PathBuf = vars.expand_vars(&self.dst).into();
         let rows = bugs.into_iter().map(|(bug_id, time)| (tag, bug_id, time));
         // Add a dummy bug to avoid droping trials when there are no bugs.
         append_csv(
-            dst,
+            self.dst(vars),
             b"tag,bug_id,time",
             [(tag, "none".into(), 0)].into_iter().chain(rows),
         )?;
@@ -772,22 +1589,31 @@ impl InputPatternVerifier {
     }
 }
 
-/// Saves plot data in AFL++ v4 format to a file after applying a tag.
+/// Saves plot data to a file after applying a tag. Plot data from older versions of AFL is
+/// converted to the AFL++ v4 format (see [crate::afl::PlotData::parse]).
 #[derive(Debug, Clone, serde::Deserialize)]
-pub struct SaveTaggedAflPlotDataV4 {
+pub struct SaveTaggedAflPlotData {
     workdir: String,
     dst: String,
 }
 
-impl SaveTaggedAflPlotDataV4 {
+impl SaveTaggedAflPlotData {
     pub fn run(&self, agent: &mut dyn Agent, vars: &Variables) -> anyhow::Result<()> {
         let tag = vars.get("TAG").unwrap_or("?");
 
-        let plot_data = format!("{}/default/plot_data", vars.expand_vars(&self.workdir));
+        // AFL++ saves the plot data inside of the directory of the fuzzer instance (`default`
+        // unless `-M`/`-S` is used) while AFL 2.x saves it at the root of the output directory.
+        let workdir = vars.expand_vars(&self.workdir);
+        let plot_data = [format!("{workdir}/default/plot_data"), format!("{workdir}/plot_data")]
+            .into_iter()
+            .find(|path| agent.stat(path.into()).is_ok())
+            .ok_or_else(|| anyhow::format_err!("no plot_data found in: {workdir}"))?;
         let data = agent.read_file(plot_data.into())?;
-        let rows = crate::afl::PlotDataRowV4::from_reader(std::io::Cursor::new(data))?;
+        let plot_data = crate::afl::PlotData::parse(std::io::Cursor::new(data))?;
+        tracing::debug!("{} rows of {:?} plot data", plot_data.rows.len(), plot_data.format);
+        let rows = plot_data.rows;
 
-        let header = format!("tag,{}", crate::afl::PlotDataRowV4::FIELDS.join(","));
+        let header = format!("tag,{}", crate::afl::PlotDataRow::FIELDS.join(","));
 
         let dst: PathBuf = vars.expand_vars(&self.dst).into();
         append_csv(
@@ -800,6 +1626,30 @@ impl SaveTaggedAflPlotDataV4 {
     }
 }
 
+/// Records that `src` was copied from the guest to `dst` in the event log.
+fn record_copy(vars: &Variables, src: &Path, dst: &Path) {
+    if crate::events::is_enabled() {
+        crate::events::record(crate::events::Event::DataCopied {
+            tag: vars.get("TAG"),
+            src,
+            dst,
+            bytes: crate::events::host_size(dst),
+        });
+    }
+}
+
+/// Checks that a tag written alongside collected data matches the tag the harness generated for the
+/// task, so that data is never attributed to a different fuzzer, binary or trial.
+fn check_tag(vars: &Variables, tag: &str) -> anyhow::Result<()> {
+    let Some(expected) = vars.get("TAG") else {
+        return Ok(());
+    };
+    if crate::utils::is_tag(tag) && tag != expected {
+        anyhow::bail!("collected data is tagged with {tag}, but the task's tag is {expected}");
+    }
+    Ok(())
+}
+
 pub fn merge_with_prefix(
     dst: PathBuf,
     header: &str,
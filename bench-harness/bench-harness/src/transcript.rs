@@ -0,0 +1,458 @@
+//! Recording and replaying of the interaction between a task and its agent, for testing changes to
+//! the task logic (e.g. timed runs, kill ordering and collectors) without a real guest.
+//!
+//! With `--record-transcripts <DIR>`, every request sent by a task to its agent is written to
+//! `<DIR>/<task>.jsonl` along with the response and when it was sent. The `replay` command then
+//! runs the same task against the transcript: each request must match the recorded request
+//! exactly, and is answered with the recorded response.
+//!
+//! Timers used by tasks (see [after] and [wait]) follow a virtual clock during replays, derived
+//! from the times recorded in the transcript, so a replay runs as fast as the host allows. Effects
+//! on the host (e.g. files copied from the guest) are repeated, so replays should write to a
+//! scratch directory (e.g. by overriding variables with `--var`).
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use agent_interface::{client::Agent, Request, Response};
+use anyhow::Context;
+use crossbeam_channel::{Receiver, Sender};
+use parking_lot::lock_api::RawMutex;
+
+use crate::config::KeyValue;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+enum Entry {
+    /// The first entry of every transcript.
+    Start { task: String, worker_id: usize, vars: Vec<KeyValue> },
+    Request { elapsed_ms: u64, duration_ms: u64, request: serde_json::Value, response: Outcome },
+    Reconnect { elapsed_ms: u64, error: Option<String> },
+}
+
+impl Entry {
+    fn elapsed(&self) -> Duration {
+        match self {
+            Self::Start { .. } => Duration::ZERO,
+            Self::Request { elapsed_ms, .. } | Self::Reconnect { elapsed_ms, .. } => {
+                Duration::from_millis(*elapsed_ms)
+            }
+        }
+    }
+}
+
+/// The result of a request.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    Value(serde_json::Value),
+    /// Raw bytes (hex encoded).
+    Bytes(String),
+    /// An error reported by the agent.
+    Error(String),
+    /// The request failed without a response (e.g. the connection to the agent was lost).
+    Failed(String),
+}
+
+impl Outcome {
+    fn new(result: &anyhow::Result<Response>) -> Self {
+        match result {
+            Ok(Response::Value(value)) => Self::Value(value.clone()),
+            Ok(Response::Bytes(bytes)) => Self::Bytes(to_hex(bytes)),
+            Ok(Response::Error { error }) => Self::Error(error.clone()),
+            Err(e) => Self::Failed(format!("{e:#}")),
+        }
+    }
+
+    fn into_response(self) -> anyhow::Result<Response> {
+        match self {
+            Self::Value(value) => Ok(Response::Value(value)),
+            Self::Bytes(hex) => Ok(Response::Bytes(from_hex(&hex)?)),
+            Self::Error(error) => Ok(Response::Error { error }),
+            Self::Failed(error) => Err(anyhow::format_err!("{error}")),
+        }
+    }
+}
+
+struct RecordState {
+    dir: PathBuf,
+    /// The number of transcripts recorded for each task, to avoid overwriting the transcripts of
+    /// earlier attempts (or of legacy tasks which share a name across trials).
+    counts: HashMap<String, usize>,
+}
+
+/// The directory to record transcripts to, or `None` if transcripts are not recorded.
+static RECORD: parking_lot::Mutex<Option<RecordState>> =
+    parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, None);
+
+/// Records a transcript of every task that is run to `dir`.
+pub(crate) fn enable_recording(dir: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("failed to create: {}", dir.display()))?;
+    *RECORD.lock() = Some(RecordState { dir: dir.to_owned(), counts: HashMap::new() });
+    Ok(())
+}
+
+/// Creates the transcript file for an attempt of `task` if transcripts are being recorded.
+pub(crate) fn create(
+    task: &str,
+    worker_id: usize,
+    vars: &[KeyValue],
+) -> anyhow::Result<Option<std::fs::File>> {
+    let mut state = RECORD.lock();
+    let Some(state) = state.as_mut() else { return Ok(None) };
+
+    let count = state.counts.entry(task.to_owned()).or_default();
+    let path = match *count {
+        0 => state.dir.join(format!("{task}.jsonl")),
+        n => state.dir.join(format!("{task}.{n}.jsonl")),
+    };
+    *count += 1;
+
+    let mut file = std::fs::File::create(&path)
+        .with_context(|| format!("failed to create: {}", path.display()))?;
+    let start = Entry::Start { task: task.to_owned(), worker_id, vars: vars.to_vec() };
+    writeln!(file, "{}", serde_json::to_string(&start)?)?;
+    tracing::debug!("recording transcript of {task} to: {}", path.display());
+    Ok(Some(file))
+}
+
+/// Wraps an agent, writing every request and response to a transcript.
+pub(crate) struct Recorder<'a> {
+    agent: &'a mut dyn Agent,
+    file: Option<std::fs::File>,
+    start: Instant,
+}
+
+impl<'a> Recorder<'a> {
+    pub fn new(file: std::fs::File, agent: &'a mut dyn Agent) -> Self {
+        Self { agent, file: Some(file), start: Instant::now() }
+    }
+
+    fn write(&mut self, entry: Entry) {
+        let Some(file) = self.file.as_mut() else { return };
+        let result = serde_json::to_string(&entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| Ok(writeln!(file, "{line}")?));
+        if let Err(e) = result {
+            // Stop recording, since a transcript with missing entries cannot be replayed.
+            tracing::warn!("failed to write transcript: {e:#}");
+            self.file = None;
+        }
+    }
+}
+
+impl<'a> Agent for Recorder<'a> {
+    fn send_request(
+        &mut self,
+        request: Request,
+        read_timeout: Option<Duration>,
+    ) -> anyhow::Result<Response> {
+        let elapsed = self.start.elapsed();
+        let request_value = serde_json::to_value(&request)?;
+        let result = self.agent.send_request(request, read_timeout);
+        self.write(Entry::Request {
+            elapsed_ms: elapsed.as_millis() as u64,
+            duration_ms: (self.start.elapsed() - elapsed).as_millis() as u64,
+            request: request_value,
+            response: Outcome::new(&result),
+        });
+        result
+    }
+
+    fn reconnect(&mut self) -> anyhow::Result<()> {
+        let elapsed = self.start.elapsed();
+        let result = self.agent.reconnect();
+        self.write(Entry::Reconnect {
+            elapsed_ms: elapsed.as_millis() as u64,
+            error: result.as_ref().err().map(|e| format!("{e:#}")),
+        });
+        result
+    }
+}
+
+/// The header of a transcript, describing the task that was recorded.
+pub(crate) struct Header {
+    pub task: String,
+    pub worker_id: usize,
+    pub vars: Vec<KeyValue>,
+}
+
+struct ReplayState {
+    entries: VecDeque<Entry>,
+    /// The time of the task being replayed (relative to the start of the transcript).
+    now: Duration,
+    /// Timers that have not fired yet, along with the time that they fire at.
+    timers: Vec<(Duration, Sender<Instant>)>,
+}
+
+impl ReplayState {
+    /// Fires any timers that had fired before the next request was sent in the recording.
+    fn fire_timers(&mut self) {
+        let next = self.entries.front().map(Entry::elapsed);
+        self.timers.retain(|(deadline, timer)| {
+            if next.is_some_and(|x| x < *deadline) {
+                return true;
+            }
+            let _ = timer.try_send(Instant::now());
+            false
+        });
+    }
+}
+
+/// The transcript being replayed, or `None` if tasks are running against a real agent.
+static REPLAY: parking_lot::Mutex<Option<ReplayState>> =
+    parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, None);
+
+/// Loads the transcript at `path` for replaying with [Replay].
+pub(crate) fn start_replay(path: &Path) -> anyhow::Result<Header> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open: {}", path.display()))?;
+    let mut entries = VecDeque::new();
+    for (i, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let entry: Entry = serde_json::from_str(&line)
+            .with_context(|| format!("invalid entry at {}:{}", path.display(), i + 1))?;
+        entries.push_back(entry);
+    }
+    let Some(Entry::Start { task, worker_id, vars }) = entries.pop_front()
+    else {
+        anyhow::bail!("{} is not a transcript", path.display());
+    };
+    tracing::info!("replaying {} requests of {task}", entries.len());
+
+    *REPLAY.lock() = Some(ReplayState { entries, now: Duration::ZERO, timers: vec![] });
+    Ok(Header { task, worker_id, vars })
+}
+
+/// Checks that every request in the transcript being replayed was sent by the task.
+pub(crate) fn finish_replay() -> anyhow::Result<()> {
+    let Some(state) = REPLAY.lock().take() else { return Ok(()) };
+    if let Some(Entry::Request { request, .. }) = state.entries.front() {
+        anyhow::bail!(
+            "task finished with {} requests remaining, next request: {request}",
+            state.entries.len()
+        );
+    }
+    if !state.entries.is_empty() {
+        anyhow::bail!("task finished with {} entries remaining", state.entries.len());
+    }
+    Ok(())
+}
+
+/// Returns whether a transcript is being replayed (see [start_replay]).
+pub(crate) fn is_replaying() -> bool {
+    REPLAY.lock().is_some()
+}
+
+/// An agent that answers requests using the transcript being replayed.
+pub(crate) struct Replay;
+
+impl Agent for Replay {
+    fn send_request(
+        &mut self,
+        request: Request,
+        _read_timeout: Option<Duration>,
+    ) -> anyhow::Result<Response> {
+        let mut state = REPLAY.lock();
+        let state = state.as_mut().context("no transcript is being replayed")?;
+
+        let request = serde_json::to_value(&request)?;
+        let (elapsed, duration, response) = match state.entries.pop_front() {
+            Some(Entry::Request { elapsed_ms, duration_ms, request: expected, response }) => {
+                if request != expected {
+                    anyhow::bail!(
+                        "request does not match the transcript at {elapsed_ms} ms\n  \
+                        expected: {expected}\n  got: {request}"
+                    );
+                }
+                (elapsed_ms, duration_ms, response)
+            }
+            Some(Entry::Reconnect { elapsed_ms, .. }) => {
+                anyhow::bail!("expected a reconnect at {elapsed_ms} ms, got request: {request}")
+            }
+            Some(Entry::Start { .. }) => anyhow::bail!("unexpected start of transcript"),
+            None => anyhow::bail!("unexpected request after the end of transcript: {request}"),
+        };
+        state.now = Duration::from_millis(elapsed + duration);
+        state.fire_timers();
+        response.into_response()
+    }
+
+    fn reconnect(&mut self) -> anyhow::Result<()> {
+        let mut state = REPLAY.lock();
+        let state = state.as_mut().context("no transcript is being replayed")?;
+
+        let result = match state.entries.pop_front() {
+            Some(Entry::Reconnect { elapsed_ms, error }) => {
+                state.now = Duration::from_millis(elapsed_ms);
+                error.map_or(Ok(()), |e| Err(anyhow::format_err!("{e}")))
+            }
+            Some(Entry::Request { elapsed_ms, request, .. }) => {
+                anyhow::bail!("expected request at {elapsed_ms} ms, got reconnect: {request}")
+            }
+            Some(Entry::Start { .. }) => anyhow::bail!("unexpected start of transcript"),
+            None => anyhow::bail!("unexpected reconnect after the end of transcript"),
+        };
+        state.fire_timers();
+        result
+    }
+}
+
+/// Returns a channel that receives a message once `duration` has elapsed (see
+/// [crossbeam_channel::after]). During replays, the message is received once the task reaches the
+/// point in the transcript where the timer had fired.
+pub(crate) fn after(duration: Duration) -> Receiver<Instant> {
+    let mut state = REPLAY.lock();
+    let Some(state) = state.as_mut() else { return crossbeam_channel::after(duration) };
+
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    state.timers.push((state.now.saturating_add(duration), tx));
+    state.fire_timers();
+    rx
+}
+
+/// Returns how long to wait for when waiting for `duration`. During replays, the virtual clock is
+/// advanced instead, so there is no need to wait.
+pub(crate) fn wait(duration: Duration) -> Duration {
+    let mut state = REPLAY.lock();
+    let Some(state) = state.as_mut() else { return duration };
+    state.now = state.now.saturating_add(duration);
+    Duration::ZERO
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{x:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> anyhow::Result<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        anyhow::bail!("invalid hex string in transcript");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).context("invalid hex string in transcript"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serializes tests that replay transcripts, since the replay state is global.
+    static REPLAY_LOCK: parking_lot::Mutex<()> =
+        parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, ());
+
+    /// Answers `ReadFile` requests with the path as the file content, and other requests with null.
+    struct FakeAgent;
+
+    impl Agent for FakeAgent {
+        fn send_request(
+            &mut self,
+            request: Request,
+            _read_timeout: Option<Duration>,
+        ) -> anyhow::Result<Response> {
+            match request {
+                Request::ReadFile { path, .. } => {
+                    Ok(Response::Bytes(path.to_string_lossy().into_owned().into_bytes()))
+                }
+                Request::GetStatus(_) => Ok(Response::Error { error: "no such process".into() }),
+                _ => Ok(Response::Value(serde_json::Value::Null)),
+            }
+        }
+    }
+
+    fn run_task(agent: &mut dyn Agent) -> anyhow::Result<(Vec<u8>, String)> {
+        agent.write_file("/tmp/input".into(), b"data")?;
+        let data = agent.read_file("/tmp/output".into())?;
+        let status = agent.get_status(1).unwrap_err();
+        Ok((data, format!("{status:#}")))
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("transcript-{}-{name}.jsonl", std::process::id()))
+    }
+
+    fn write_transcript(path: &Path, entries: &[Entry]) {
+        let mut file = std::fs::File::create(path).unwrap();
+        for entry in entries {
+            writeln!(file, "{}", serde_json::to_string(entry).unwrap()).unwrap();
+        }
+    }
+
+    fn request(elapsed_ms: u64, request: Request) -> Entry {
+        Entry::Request {
+            elapsed_ms,
+            duration_ms: 0,
+            request: serde_json::to_value(request).unwrap(),
+            response: Outcome::Value(serde_json::Value::Null),
+        }
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let _guard = REPLAY_LOCK.lock();
+        let path = temp_path("record");
+
+        let mut file = std::fs::File::create(&path).unwrap();
+        let start = Entry::Start { task: "task".into(), worker_id: 3, vars: vec![] };
+        writeln!(file, "{}", serde_json::to_string(&start).unwrap()).unwrap();
+        let expected = run_task(&mut Recorder::new(file, &mut FakeAgent)).unwrap();
+
+        let header = start_replay(&path).unwrap();
+        assert_eq!(header.task, "task");
+        assert_eq!(header.worker_id, 3);
+        assert_eq!(run_task(&mut Replay).unwrap(), expected);
+        finish_replay().unwrap();
+
+        // Requests that differ from the recording are rejected.
+        start_replay(&path).unwrap();
+        let err = Replay.write_file("/tmp/other".into(), b"data").unwrap_err();
+        assert!(format!("{err:#}").contains("does not match the transcript"));
+        REPLAY.lock().take();
+
+        // So are tasks that stop before sending every recorded request.
+        start_replay(&path).unwrap();
+        Replay.write_file("/tmp/input".into(), b"data").unwrap();
+        assert!(finish_replay().is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn timers_follow_transcript() {
+        let _guard = REPLAY_LOCK.lock();
+        let path = temp_path("timers");
+        write_transcript(&path, &[
+            Entry::Start { task: "task".into(), worker_id: 0, vars: vec![] },
+            request(1000, Request::GetStatus(1)),
+            request(1500, Request::GetStatus(1)),
+            request(5000, Request::GetStatus(1)),
+        ]);
+
+        // The timer fired between the second and third request in the recording.
+        start_replay(&path).unwrap();
+        let timer = after(Duration::from_secs(2));
+        Replay.send(Request::GetStatus(1)).unwrap();
+        assert!(timer.try_recv().is_err(), "timer fired before its deadline");
+        Replay.send(Request::GetStatus(1)).unwrap();
+        assert!(timer.try_recv().is_ok(), "timer did not fire after its deadline");
+        Replay.send(Request::GetStatus(1)).unwrap();
+        finish_replay().unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let bytes = [0x00, 0x7f, 0x80, 0xff];
+        assert_eq!(to_hex(&bytes), "007f80ff");
+        assert_eq!(from_hex("007f80ff").unwrap(), bytes);
+        assert!(from_hex("0").is_err());
+        assert!(from_hex("zz").is_err());
+    }
+}
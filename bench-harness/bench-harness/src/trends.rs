@@ -0,0 +1,401 @@
+//! A historical database of benchmark results (`--results-db`), used for detecting regressions
+//! across runs of the same benchmark (e.g. nightly runs against different fuzzer commits).
+//!
+//! Each run appends a row to the `runs` table and a summary row for each of its trials to the
+//! `trials` table. The `trends` command compares the median final coverage of each fuzzer on each
+//! binary with the previous run that included it.
+//!
+//! Bugs are only counted for trials with a bug verifier (e.g. `InputPatternVerifier`), using the
+//! deduplicated bug IDs it writes. The raw crashes reported by the fuzzer (e.g. in `crashes.json`)
+//! are not read, since they are neither verified nor deduplicated, so `bugs` is null for trials
+//! without a verifier.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use indexmap::IndexMap;
+
+use crate::{
+    journal::{Journal, TaskState},
+    tasks::DynamicTask,
+    utils::Variables,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    benchmark TEXT NOT NULL,
+    label TEXT NOT NULL,
+    commit_hash TEXT,
+    campaign_seed TEXT NOT NULL,
+    started REAL NOT NULL,
+    finished REAL NOT NULL
+);
+CREATE TABLE IF NOT EXISTS trials (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    task TEXT NOT NULL,
+    fuzzer TEXT,
+    binary TEXT,
+    trial INTEGER NOT NULL,
+    tag TEXT NOT NULL,
+    state TEXT,
+    final_coverage INTEGER,
+    bugs INTEGER,
+    duration_secs REAL
+);
+CREATE INDEX IF NOT EXISTS trials_by_run ON trials(run_id);
+";
+
+/// The name of the artifact that contains the coverage data for a trial.
+const COVERAGE_FILE: &str = "coverage.json";
+
+/// Where the results of a trial are found once it completes.
+pub(crate) struct TrialSource {
+    pub task: String,
+    pub trial: usize,
+    pub tag: String,
+    /// The `coverage.json` artifact of the trial (if any).
+    pub coverage: Option<PathBuf>,
+    /// The files that the bug verifiers of the trial append to.
+    pub bug_files: Vec<PathBuf>,
+}
+
+impl TrialSource {
+    pub fn new(
+        task: String,
+        trial: usize,
+        tag: String,
+        artifacts: &[String],
+        tasks: &[DynamicTask],
+        vars: &Variables,
+    ) -> Self {
+        let coverage = artifacts.iter().find(|x| x.ends_with(COVERAGE_FILE)).map(PathBuf::from);
+        let mut bug_files = vec![];
+        collect_bug_files(tasks, vars, &mut bug_files);
+        Self { task, trial, tag, coverage, bug_files }
+    }
+}
+
+fn collect_bug_files(tasks: &[DynamicTask], vars: &Variables, files: &mut Vec<PathBuf>) {
+    for task in tasks {
+        match task {
+            DynamicTask::InputPatternVerifier(inner) => files.push(inner.dst(vars)),
+            DynamicTask::TaskList { tasks } => collect_bug_files(tasks, vars, files),
+            _ => {}
+        }
+    }
+}
+
+/// Information about a benchmark run recorded in the database.
+pub(crate) struct RunSummary<'a> {
+    pub benchmark: &'a Path,
+    pub label: &'a str,
+    /// The revision of the fuzzer being benchmarked (see `--commit`).
+    pub commit: Option<&'a str>,
+    pub campaign_seed: u64,
+    pub started: f64,
+    pub finished: f64,
+}
+
+/// Appends the results of a completed run to the database at `path`, creating it if needed.
+pub(crate) fn record_run(
+    path: &Path,
+    run: &RunSummary,
+    trials: &[TrialSource],
+    journal: &Journal,
+) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let mut db = rusqlite::Connection::open(path)
+        .with_context(|| format!("failed to open results database: {}", path.display()))?;
+    db.execute_batch(SCHEMA)?;
+
+    let tx = db.transaction()?;
+    tx.execute(
+        "INSERT INTO runs (benchmark, label, commit_hash, campaign_seed, started, finished)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![
+            run.benchmark.to_string_lossy(),
+            run.label,
+            run.commit,
+            // SQLite integers are signed, so the seed is stored as text to avoid truncation.
+            run.campaign_seed.to_string(),
+            run.started,
+            run.finished,
+        ],
+    )?;
+    let run_id = tx.last_insert_rowid();
+
+    let mut bug_counts = BugCounts::default();
+    for trial in trials {
+        let state = journal.state(&trial.task);
+        let completed = state == Some(TaskState::Completed);
+        let final_coverage = match (&trial.coverage, completed) {
+            (Some(path), true) => count_blocks(path)
+                .map_err(|e| tracing::warn!("{}: {e:#}", trial.task))
+                .ok(),
+            _ => None,
+        };
+        let bugs = match trial.bug_files.is_empty() || !completed {
+            true => None,
+            false => Some(bug_counts.count(&trial.bug_files, &trial.tag)),
+        };
+        let state = state.map(|x| format!("{x:?}").to_lowercase());
+        tx.execute(
+            "INSERT INTO trials (run_id, task, fuzzer, binary, trial, tag, state, final_coverage,
+            bugs, duration_secs) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            rusqlite::params![
+                run_id,
+                trial.task,
+                tag_value(&trial.tag, "fuzzer"),
+                tag_value(&trial.tag, "binary"),
+                trial.trial as i64,
+                trial.tag,
+                state,
+                final_coverage.map(|x| x as i64),
+                bugs.map(|x| x as i64),
+                journal.duration(&trial.task).map(|x| x.as_secs_f64()),
+            ],
+        )?;
+    }
+    tx.commit()?;
+
+    tracing::info!("{} trial(s) recorded to results database: {}", trials.len(), path.display());
+    Ok(())
+}
+
+/// Gets the value of `key` from a benchmark tag (e.g. `v2;fuzzer=afl;binary=x;trial=0`).
+fn tag_value<'a>(tag: &'a str, key: &str) -> Option<&'a str> {
+    if !crate::utils::is_tag(tag) {
+        return None;
+    }
+    tag.split(';')
+        .skip(1)
+        .filter_map(|entry| entry.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, value)| value)
+}
+
+/// Counts the number of distinct blocks in a MultiFuzz `coverage.json` file. Every version of the
+/// format stores the address of a block either as the first element of each entry or in its
+/// `addr` (or `block`) field, and merged files contain a list of entries for each shard.
+///
+/// This duplicates part of the coverage loader in `plot-data` (`data_loading/json_map.rs`), which
+/// is part of the analysis workspace and depends on polars, so it is not used by the harness. Both
+/// need to be updated when the format changes.
+fn count_blocks(path: &Path) -> anyhow::Result<usize> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open: {}", path.display()))?;
+    let data: serde_json::Value = serde_json::from_reader(std::io::BufReader::new(file))
+        .with_context(|| format!("failed to parse: {}", path.display()))?;
+    let data = match data {
+        serde_json::Value::Object(mut x) if x.contains_key("version") => {
+            x.remove("coverage").unwrap_or_default()
+        }
+        data => data,
+    };
+    let shards = match data {
+        serde_json::Value::Array(entries) => vec![entries],
+        serde_json::Value::Object(shards) => shards
+            .into_iter()
+            .filter_map(|(_, entries)| match entries {
+                serde_json::Value::Array(entries) => Some(entries),
+                _ => None,
+            })
+            .collect(),
+        _ => anyhow::bail!("unknown coverage format: {}", path.display()),
+    };
+
+    let mut blocks = HashSet::new();
+    for entry in shards.iter().flatten() {
+        let addr = match entry {
+            serde_json::Value::Array(x) => x.first(),
+            serde_json::Value::Object(x) => x.get("addr").or_else(|| x.get("block")),
+            _ => None,
+        };
+        if let Some(addr) = addr.and_then(|x| x.as_u64()) {
+            blocks.insert(addr);
+        }
+    }
+    Ok(blocks.len())
+}
+
+/// The number of distinct bugs found by each trial, read from the CSV files written by bug
+/// verifiers (which are shared by all trials, with a `tag` column identifying the trial).
+#[derive(Default)]
+struct BugCounts {
+    bugs: BTreeMap<PathBuf, BTreeMap<String, HashSet<String>>>,
+}
+
+impl BugCounts {
+    fn count(&mut self, files: &[PathBuf], tag: &str) -> usize {
+        let mut bugs = HashSet::new();
+        for file in files {
+            let by_tag = self.bugs.entry(file.clone()).or_insert_with(|| {
+                read_bugs(file).unwrap_or_else(|e| {
+                    tracing::warn!("failed to read bugs from {}: {e:#}", file.display());
+                    BTreeMap::new()
+                })
+            });
+            bugs.extend(by_tag.get(tag).into_iter().flatten().cloned());
+        }
+        bugs.len()
+    }
+}
+
+fn read_bugs(path: &Path) -> anyhow::Result<BTreeMap<String, HashSet<String>>> {
+    let mut bugs: BTreeMap<String, HashSet<String>> = BTreeMap::new();
+    for row in csv::Reader::from_path(path)?.deserialize() {
+        let (tag, bug_id, _time): (String, String, u64) = row?;
+        let entry = bugs.entry(tag).or_default();
+        // Verifiers add a dummy bug so that trials without bugs are not dropped.
+        if bug_id != "none" {
+            entry.insert(bug_id);
+        }
+    }
+    Ok(bugs)
+}
+
+/// The results of a fuzzer on a binary in a single run.
+struct RunResults {
+    started: f64,
+    commit: Option<String>,
+    label: String,
+    coverage: Vec<i64>,
+    bugs: Vec<i64>,
+}
+
+#[derive(serde::Serialize)]
+struct TrendRow<'a> {
+    fuzzer: &'a str,
+    binary: &'a str,
+    run_id: i64,
+    started: f64,
+    commit: Option<&'a str>,
+    label: &'a str,
+    trials: usize,
+    median_coverage: Option<f64>,
+    median_bugs: Option<f64>,
+    /// The change in median coverage relative to the previous run (as a percentage).
+    coverage_change: Option<f64>,
+    regression: bool,
+}
+
+/// Writes a table of the median final coverage and bugs of each fuzzer on each binary in every run
+/// recorded in the database at `db` (optionally only including runs of `benchmark`) to `output`.
+/// Runs where the median coverage dropped by more than `threshold` percent compared to the
+/// previous run are flagged as regressions, and regressions in the latest run are reported.
+pub(crate) fn report_trends(
+    db: &Path,
+    benchmark: Option<&Path>,
+    output: &Path,
+    threshold: f64,
+) -> anyhow::Result<()> {
+    let db = rusqlite::Connection::open_with_flags(db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("failed to open results database: {}", db.display()))?;
+    let mut query = db.prepare(
+        "SELECT runs.id, runs.started, runs.commit_hash, runs.label, trials.fuzzer, trials.binary,
+            trials.final_coverage, trials.bugs
+        FROM trials JOIN runs ON runs.id = trials.run_id
+        WHERE trials.state = 'completed' AND (?1 IS NULL OR runs.benchmark = ?1)
+        ORDER BY runs.id",
+    )?;
+    let rows = query.query_map(
+        [benchmark.map(|x| x.to_string_lossy().into_owned())],
+        |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, f64>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+                row.get::<_, Option<i64>>(6)?,
+                row.get::<_, Option<i64>>(7)?,
+            ))
+        },
+    )?;
+
+    let mut groups: BTreeMap<(String, String), IndexMap<i64, RunResults>> = BTreeMap::new();
+    let mut latest_run = None;
+    for row in rows {
+        let (run_id, started, commit, label, fuzzer, binary, coverage, bugs) = row?;
+        latest_run = latest_run.max(Some(run_id));
+        let results = groups.entry((fuzzer, binary)).or_default().entry(run_id).or_insert_with(
+            || RunResults { started, commit, label, coverage: vec![], bugs: vec![] },
+        );
+        results.coverage.extend(coverage);
+        results.bugs.extend(bugs);
+    }
+    if groups.is_empty() {
+        anyhow::bail!("no completed trials found in the results database");
+    }
+
+    if let Some(parent) = output.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let mut writer = csv::Writer::from_path(output)
+        .with_context(|| format!("failed to create: {}", output.display()))?;
+    let mut regressions = vec![];
+    for ((fuzzer, binary), runs) in &groups {
+        let mut previous = None;
+        for (run_id, results) in runs {
+            let median_coverage = median(&results.coverage);
+            let coverage_change = match (previous, median_coverage) {
+                (Some(previous), Some(current)) if previous > 0.0 => {
+                    Some(100.0 * (current - previous) / previous)
+                }
+                _ => None,
+            };
+            let regression = coverage_change.is_some_and(|x| x < -threshold);
+            if regression && Some(*run_id) == latest_run {
+                regressions.push(format!(
+                    "{fuzzer} on {binary}: {:.1}% coverage",
+                    coverage_change.unwrap_or_default()
+                ));
+            }
+            writer.serialize(TrendRow {
+                fuzzer,
+                binary,
+                run_id: *run_id,
+                started: results.started,
+                commit: results.commit.as_deref(),
+                label: &results.label,
+                trials: results.coverage.len().max(results.bugs.len()),
+                median_coverage,
+                median_bugs: median(&results.bugs),
+                coverage_change,
+                regression,
+            })?;
+            previous = median_coverage.or(previous);
+        }
+    }
+    writer.flush()?;
+    tracing::info!("trends saved to: {}", output.display());
+
+    if !regressions.is_empty() {
+        tracing::warn!(
+            "{} regression(s) in the latest run (threshold: {threshold}%):\n{}",
+            regressions.len(),
+            regressions.join("\n")
+        );
+    }
+    Ok(())
+}
+
+fn median(values: &[i64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut values = values.to_vec();
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    Some(match values.len() % 2 {
+        0 => (values[mid - 1] + values[mid]) as f64 / 2.0,
+        _ => values[mid] as f64,
+    })
+}
@@ -81,6 +81,69 @@ impl IntoIterator for Variables {
     }
 }
 
+/// Generates a random seed for a benchmark campaign.
+pub(crate) fn random_seed() -> u64 {
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |x| x.as_nanos() as u64);
+    splitmix64(time ^ ((std::process::id() as u64) << 32))
+}
+
+/// Derives the RNG seed for the `index`-th trial of a campaign.
+///
+/// `splitmix64` is a bijection and the input is distinct for every index, so each trial in a
+/// campaign is guaranteed to get a different seed.
+pub(crate) fn trial_seed(campaign_seed: u64, index: u64) -> u64 {
+    splitmix64(campaign_seed.wrapping_add(index.wrapping_mul(0x9e3779b97f4a7c15)))
+}
+
+/// Shuffles `items` in a way that only depends on `seed` (and is stable across platforms and
+/// releases, unlike the shuffles of most RNG crates).
+pub(crate) fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    for i in (1..items.len()).rev() {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let j = (splitmix64(state) % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Escapes a key or value for use in a v2 benchmark tag (`v2;<key>=<value>;...`) by
+/// percent-encoding `%`, `;`, `=`, braces (to avoid variable expansion) and control characters.
+///
+/// Available to benchmark templates as the `tag_value` filter.
+pub(crate) fn escape_tag_value(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '%' | ';' | '=' | '{' | '}' => output.push_str(&format!("%{:02X}", c as u8)),
+            c if c.is_ascii_control() => output.push_str(&format!("%{:02X}", c as u8)),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+/// Formats a v2 benchmark tag from `entries` (see [escape_tag_value]).
+pub(crate) fn format_tag<'a>(entries: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let mut output = String::from("v2");
+    for (key, value) in entries {
+        output.push_str(&format!(";{}={}", escape_tag_value(key), escape_tag_value(value)));
+    }
+    output
+}
+
+/// Returns whether `value` looks like a benchmark tag (i.e. starts with a tag version).
+pub(crate) fn is_tag(value: &str) -> bool {
+    value.starts_with("v1;") || value.starts_with("v2;")
+}
+
 pub(crate) struct KillOnDrop {
     pub child: std::process::Child,
     done: std::sync::Arc<std::sync::atomic::AtomicBool>,
@@ -100,6 +163,20 @@ impl Drop for KillOnDrop {
     }
 }
 
+/// Forcibly kills the process with `pid`.
+#[cfg(unix)]
+pub(crate) fn kill_process(pid: u32) -> anyhow::Result<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) } != 0 {
+        anyhow::bail!("failed to kill pid={pid}: {}", std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn kill_process(pid: u32) -> anyhow::Result<()> {
+    anyhow::bail!("unable to kill pid={pid}: unsupported on the current platform")
+}
+
 pub fn wait_for_process_timeout(
     handle: &mut std::process::Child,
     timeout: std::time::Duration,
@@ -243,6 +320,25 @@ impl std::fmt::Display for HumanReadableDuration {
     }
 }
 
+pub struct HumanReadableSize(pub u64);
+
+impl std::fmt::Display for HumanReadableSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+
+        let mut size = self.0 as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit + 1 < UNITS.len() {
+            size /= 1024.0;
+            unit += 1;
+        }
+        match unit {
+            0 => f.write_fmt(format_args!("{} {}", self.0, UNITS[0])),
+            _ => f.write_fmt(format_args!("{size:.1} {}", UNITS[unit])),
+        }
+    }
+}
+
 pub fn prepare_workdir(
     api_socket: &PathBuf,
     workdir: &PathBuf,
@@ -0,0 +1,177 @@
+//! Support for re-running the verifier tasks of a completed benchmark on the host (`verify`).
+//!
+//! Verifiers normally run inside the VM after fuzzing has finished. To replay them, the directory
+//! they read from is mapped to the artifact created by a `CopyDir` task in the same task list
+//! (extracting it first if it was archived), then the verifier is run using a local agent.
+
+use std::{
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+    config::{Config, TaskConfig},
+    tasks::{DynamicTask, InputPatternVerifier},
+    utils::Variables,
+};
+
+/// A directory on the guest that was copied to the host.
+struct CopiedDir {
+    src: PathBuf,
+    dst: PathBuf,
+    archive: bool,
+}
+
+/// Re-runs all verifier tasks in `tasks` against the artifacts they collected. Archived artifacts
+/// are extracted to `scratch`. Existing verifier outputs are renamed (with a `.bak` extension)
+/// before being regenerated.
+pub(crate) fn run_verify(
+    config: &Config,
+    tasks: &[TaskConfig],
+    scratch: &Path,
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(scratch)
+        .with_context(|| format!("failed to create: {}", scratch.display()))?;
+    let (mut agent, handle) = agent::spawn_local_agent(Some(scratch.to_owned()))
+        .context("failed to spawn local agent")?;
+
+    // The local agent resolves relative paths against its working directory, so crash directories
+    // on the host are passed as absolute paths.
+    let cwd = std::env::current_dir()?;
+
+    let mut outputs = HashSet::new();
+    let mut verified = 0;
+    for (i, task) in tasks.iter().enumerate() {
+        let mut vars = Variables::default();
+        vars.insert_all(config.vars.iter().chain(&task.vars).map(|x| x.clone().into()));
+
+        let mut copied = vec![];
+        let mut verifiers = vec![];
+        collect_tasks(&task.tasks, &vars, &mut copied, &mut verifiers);
+
+        for verifier in verifiers {
+            let crash_dir = verifier.crash_dir(&vars);
+            let Some(copy) = copied.iter().find(|x| crash_dir.starts_with(&x.src)) else {
+                tracing::warn!(
+                    "task-{i}: skipping verifier, {} is not copied to the host",
+                    crash_dir.display()
+                );
+                continue;
+            };
+            let relative = crash_dir.strip_prefix(&copy.src).unwrap_or(&crash_dir);
+            let host_dir = match copy.archive {
+                true => {
+                    let name =
+                        copy.dst.to_string_lossy().trim_start_matches(['.', '/']).replace('/', "_");
+                    match extract_archive(&copy.dst, &scratch.join(name)) {
+                        Ok(dir) => dir,
+                        Err(e) => {
+                            tracing::error!("task-{i}: {e:#}");
+                            continue;
+                        }
+                    }
+                }
+                false => copy.dst.clone(),
+            };
+
+            let dst = verifier.dst(&vars);
+            if outputs.insert(dst.clone()) && dst.exists() {
+                let backup = PathBuf::from(format!("{}.bak", dst.display()));
+                std::fs::rename(&dst, &backup)
+                    .with_context(|| format!("failed to backup: {}", dst.display()))?;
+                tracing::info!("{} moved to: {}", dst.display(), backup.display());
+            }
+
+            let crash_dir = cwd.join(host_dir).join(relative);
+            if let Err(e) = verifier.verify(agent.as_mut(), &vars, crash_dir) {
+                tracing::error!("task-{i}: error running verifier: {e:#}");
+                continue;
+            }
+            verified += 1;
+        }
+    }
+
+    agent.exit()?;
+    let _ = handle.join();
+    tracing::info!("{verified} verifier(s) replayed");
+    Ok(())
+}
+
+/// Finds the directories copied to the host and the verifiers in `tasks`.
+fn collect_tasks<'a>(
+    tasks: &'a [DynamicTask],
+    vars: &Variables,
+    copied: &mut Vec<CopiedDir>,
+    verifiers: &mut Vec<&'a InputPatternVerifier>,
+) {
+    for task in tasks {
+        match task {
+            DynamicTask::CopyDir { src, dst, archive, .. } => copied.push(CopiedDir {
+                src: vars.expand_vars(src).into(),
+                dst: vars.expand_vars(dst).into(),
+                archive: *archive,
+            }),
+            DynamicTask::InputPatternVerifier(inner) => verifiers.push(inner),
+            DynamicTask::TaskList { tasks } => collect_tasks(tasks, vars, copied, verifiers),
+            _ => {}
+        }
+    }
+}
+
+/// Extracts the archive created by a `CopyDir` task to `dst` (reusing it if already extracted).
+///
+/// The archive is extracted to a temporary directory that is only moved to `dst` once extraction
+/// succeeds, so that a partially extracted archive is never reused.
+fn extract_archive(archive: &Path, dst: &Path) -> anyhow::Result<PathBuf> {
+    if dst.exists() {
+        return Ok(dst.to_owned());
+    }
+    tracing::info!("extracting {} to {}", archive.display(), dst.display());
+
+    let mut tmp = dst.as_os_str().to_owned();
+    tmp.push(".partial");
+    let tmp = PathBuf::from(tmp);
+    if tmp.exists() {
+        std::fs::remove_dir_all(&tmp)
+            .with_context(|| format!("failed to remove: {}", tmp.display()))?;
+    }
+    create_dir(&tmp).with_context(|| format!("failed to create: {}", tmp.display()))?;
+
+    let file = std::fs::File::open(archive)
+        .with_context(|| format!("failed to open: {}", archive.display()))?;
+    unpack(tar::Archive::new(flate2::read::GzDecoder::new(file)), &tmp)
+        .with_context(|| format!("failed to extract: {}", archive.display()))?;
+    std::fs::rename(&tmp, dst)
+        .with_context(|| format!("failed to move {} to {}", tmp.display(), dst.display()))?;
+    Ok(dst.to_owned())
+}
+
+/// Unpacks `archive` into `dst`. Directories are always created with mode 0o755, since older
+/// archives stored them with mode 0o666 (which would prevent the directory from being entered).
+fn unpack(mut archive: tar::Archive<impl std::io::Read>, dst: &Path) -> anyhow::Result<()> {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_dir() {
+            entry.unpack_in(dst)?;
+            continue;
+        }
+        let path = entry.path()?;
+        anyhow::ensure!(
+            path.components().all(|x| matches!(x, Component::Normal(_) | Component::CurDir)),
+            "invalid directory in archive: {}",
+            path.display()
+        );
+        create_dir(&dst.join(path))?;
+    }
+    Ok(())
+}
+
+fn create_dir(path: &Path) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o755);
+    builder.create(path)
+}
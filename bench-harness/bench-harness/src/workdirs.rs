@@ -0,0 +1,139 @@
+//! Working directories of workers (e.g. VMs and containers), namespaced by a per-process run ID so
+//! that multiple harness instances can run concurrently on the same host.
+//!
+//! For each root directory used by a process, a run directory (`<root>/<run_id>`) is created along
+//! with a lock file (`<root>/<run_id>.lock`) that is locked for the lifetime of the process. Run
+//! directories with a lock that is no longer held were left behind by a harness process that has
+//! exited, and are removed the next time the root is used.
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use parking_lot::lock_api::RawMutex;
+
+/// Identifies the current harness process, unique among harness processes running on the host.
+static RUN_ID: once_cell::sync::Lazy<String> = once_cell::sync::Lazy::new(|| {
+    let start = crate::clock::unix_secs(std::time::SystemTime::now()) as u64;
+    format!("run-{start}-{}", std::process::id())
+});
+
+/// The roots with a run directory created by the current process, along with their locks.
+static RUN_DIRS: parking_lot::Mutex<Vec<(PathBuf, File)>> =
+    parking_lot::Mutex::const_new(parking_lot::RawMutex::INIT, Vec::new());
+
+/// Returns the run ID of the current process.
+pub(crate) fn run_id() -> &'static str {
+    &RUN_ID
+}
+
+/// Returns the path of the working directory named `name` within `root` for the current process.
+/// The directory itself is not created.
+pub(crate) fn worker_dir(root: &Path, name: &str) -> anyhow::Result<PathBuf> {
+    let run_dir = root.join(run_id());
+    let mut run_dirs = RUN_DIRS.lock();
+    if !run_dirs.iter().any(|(x, _)| x == root) {
+        std::fs::create_dir_all(root)
+            .with_context(|| format!("failed to create workdir {}", root.display()))?;
+        remove_stale(root);
+
+        let lock = create_lock(root)?;
+        std::fs::create_dir_all(&run_dir)
+            .with_context(|| format!("failed to create workdir {}", run_dir.display()))?;
+        tracing::debug!("using workdir: {}", run_dir.display());
+        run_dirs.push((root.to_owned(), lock));
+    }
+    Ok(run_dir.join(name))
+}
+
+/// Creates and locks the lock file of the current process in `root`.
+///
+/// The file is locked under a temporary name and then renamed into place, so other processes never
+/// observe the lock file in an unlocked state (which would cause them to treat the run directory as
+/// stale and remove it).
+fn create_lock(root: &Path) -> anyhow::Result<File> {
+    let tmp_path = root.join(format!("{}.lock.tmp", run_id()));
+    let lock = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&tmp_path)
+        .with_context(|| format!("failed to create: {}", tmp_path.display()))?;
+    if !try_lock(&lock)? {
+        let _ = std::fs::remove_file(&tmp_path);
+        anyhow::bail!("{} is locked by another process", tmp_path.display());
+    }
+
+    let lock_path = root.join(format!("{}.lock", run_id()));
+    if let Err(e) = std::fs::rename(&tmp_path, &lock_path) {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e).with_context(|| format!("failed to create: {}", lock_path.display()));
+    }
+    Ok(lock)
+}
+
+/// Removes the run directories in `root` of harness processes that are no longer running.
+fn remove_stale(root: &Path) {
+    if !cfg!(unix) {
+        // Without file locking there is no way of knowing whether the directories are in use.
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(root) else { return };
+    for entry in entries.flatten() {
+        let lock_path = entry.path();
+        let Some(id) = lock_path.file_name().and_then(|x| x.to_str()?.strip_suffix(".lock"))
+        else {
+            continue;
+        };
+        if !id.starts_with("run-") || id == run_id() {
+            continue;
+        }
+        // Run directories without a lock file are ignored, since they may belong to a process that
+        // has not acquired its lock yet (lock files are only renamed into place once locked, see
+        // `create_lock`).
+        let Ok(lock) = File::open(&lock_path) else { continue };
+        match try_lock(&lock) {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::info!("workdirs of {id} are in use by another harness instance");
+                continue;
+            }
+            Err(e) => {
+                tracing::debug!("unable to check whether {id} is stale: {e:#}");
+                continue;
+            }
+        }
+
+        let run_dir = root.join(id);
+        tracing::info!("removing stale workdir: {}", run_dir.display());
+        if let Err(e) = std::fs::remove_dir_all(&run_dir) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!("error removing: {}, {e}", run_dir.display());
+                continue;
+            }
+        }
+        let _ = std::fs::remove_file(&lock_path);
+    }
+}
+
+/// Attempts to acquire an exclusive lock on `file` without blocking, returning whether the lock
+/// was acquired. The lock is released when the file is closed (including if the process exits).
+#[cfg(unix)]
+fn try_lock(file: &File) -> anyhow::Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() == std::io::ErrorKind::WouldBlock {
+            return Ok(false);
+        }
+        anyhow::bail!("failed to lock file: {err}");
+    }
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+fn try_lock(_file: &File) -> anyhow::Result<bool> {
+    Ok(true)
+}
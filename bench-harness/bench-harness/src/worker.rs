@@ -1,49 +1,299 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use agent_interface::client::{unix::UnixAgent, Agent};
+use agent_interface::client::Agent;
 use anyhow::Context;
 use crossbeam_channel::{Receiver, Sender};
+use parking_lot::Mutex;
 
 use crate::{
+    config::Config,
     docker::{self, DockerConfig},
+    events::Event,
     firecracker::{self, VmConfig},
+    journal::{Journal, TaskState},
+    ssh::{self, SshConfig, SshHost},
     tasks::Task,
+    WorkerBackend,
 };
 
+/// Failures caused by the infrastructure running a task rather than the task itself. Workers that
+/// repeatedly fail because of these are quarantined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InfraFailure {
+    /// The VM (or container) failed to start, or the agent inside of it could not be reached.
+    Boot,
+    /// The agent stopped responding while running a task.
+    AgentTimeout,
+    /// The task did not finish before its timeout (e.g. because the VM was wedged), so the VM was
+    /// killed.
+    TaskTimeout,
+}
+
+impl std::fmt::Display for InfraFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Boot => f.write_str("worker failed to boot"),
+            Self::AgentTimeout => f.write_str("agent timed out"),
+            Self::TaskTimeout => f.write_str("task timed out"),
+        }
+    }
+}
+
+impl InfraFailure {
+    fn classify(error: &anyhow::Error) -> Option<Self> {
+        if let Some(kind) = error.downcast_ref::<InfraFailure>() {
+            return Some(*kind);
+        }
+        let timed_out = error.chain().filter_map(|x| x.downcast_ref::<std::io::Error>()).any(|x| {
+            matches!(x.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock)
+        });
+        timed_out.then_some(Self::AgentTimeout)
+    }
+}
+
+#[derive(Default)]
+struct WorkerHealth {
+    tasks: usize,
+    failures: usize,
+    boot_failures: usize,
+    agent_timeouts: usize,
+    /// The number of infrastructure failures since the last task that completed successfully.
+    consecutive_failures: usize,
+    quarantined: bool,
+}
+
+impl WorkerHealth {
+    /// Records the result of a task, returning whether the worker should be quarantined.
+    fn record(&mut self, result: &anyhow::Result<()>, quarantine_after: usize) -> bool {
+        self.tasks += 1;
+        let Err(e) = result else {
+            self.consecutive_failures = 0;
+            return false;
+        };
+
+        self.failures += 1;
+        match InfraFailure::classify(e) {
+            Some(InfraFailure::Boot) => self.boot_failures += 1,
+            Some(InfraFailure::AgentTimeout | InfraFailure::TaskTimeout) => {
+                self.agent_timeouts += 1
+            }
+            // Failures caused by the task itself say nothing about the health of the worker.
+            None => return false,
+        }
+        self.consecutive_failures += 1;
+        self.quarantined = quarantine_after != 0 && self.consecutive_failures >= quarantine_after;
+        self.quarantined
+    }
+}
+
+/// Runs `task` using `worker`, retrying infrastructure failures according to the retry policy of
+/// the task. `record_health` is called with the result of every attempt and returns whether the
+/// worker is quarantined (stopping any further retries).
+///
+/// Returns the result of the final attempt and whether the worker was quarantined.
+fn run_with_retries<F>(
+    id: usize,
+    task: &mut Task,
+    worker: &mut F,
+    mut record_health: impl FnMut(&anyhow::Result<()>) -> bool,
+) -> (anyhow::Result<()>, bool)
+where
+    F: FnMut(&mut Task) -> anyhow::Result<()>,
+{
+    // Workers add variables to the task (e.g. `VM_WORKDIR`), so each attempt starts from a copy.
+    let vars = task.vars.clone();
+    let mut attempt = 1;
+    loop {
+        task.vars = vars.clone();
+        let result = worker(task);
+        let quarantined = record_health(&result);
+        let Err(e) = &result else { return (result, quarantined) };
+
+        let retryable = match InfraFailure::classify(e) {
+            Some(InfraFailure::Boot) => true,
+            Some(InfraFailure::AgentTimeout | InfraFailure::TaskTimeout) => {
+                task.retry.retry_timeouts
+            }
+            None => false,
+        };
+        if !retryable || quarantined || attempt >= task.retry.max_attempts || crate::should_stop()
+        {
+            return (result, quarantined);
+        }
+
+        let delay = task.retry.delay(attempt);
+        tracing::warn!(
+            "{} failed (attempt {attempt}/{}), retrying in {}: {e:#}",
+            task.name,
+            task.retry.max_attempts,
+            crate::utils::HumanReadableDuration(delay),
+        );
+        crate::events::record(Event::TaskRetried {
+            task: &task.name,
+            worker: id,
+            attempt,
+            error: format!("{e:#}"),
+            delay_secs: delay.as_secs_f64(),
+        });
+        crossbeam_channel::select! {
+            recv(crate::cancellation_channel()) -> _ => return (result, quarantined),
+            default(delay) => {}
+        }
+        attempt += 1;
+    }
+}
+
+/// Aborts the benchmark when too many tasks fail shortly after it starts, since this usually
+/// indicates a systemic problem (e.g. a wrong image or a missing binary) rather than flaky tasks.
+pub(crate) struct FailFast {
+    /// The fraction of started tasks that must fail before the benchmark is aborted.
+    max_failure_rate: f64,
+    /// Only failures that occur within this duration after the pool is created are counted.
+    window: Duration,
+    start: std::time::Instant,
+    started: usize,
+    failures: usize,
+    aborted: bool,
+}
+
+impl FailFast {
+    /// The minimum number of failures before aborting, so that a single flaky task at the start of
+    /// the benchmark is not mistaken for a systemic problem.
+    const MIN_FAILURES: usize = 3;
+
+    pub fn new(max_failure_rate: f64, window: Duration) -> Self {
+        Self {
+            max_failure_rate,
+            window,
+            start: std::time::Instant::now(),
+            started: 0,
+            failures: 0,
+            aborted: false,
+        }
+    }
+
+    /// Records a failed task, returning whether the benchmark should be aborted.
+    fn record_failure(&mut self) -> bool {
+        if self.aborted || self.start.elapsed() > self.window {
+            return false;
+        }
+        self.failures += 1;
+        let rate = self.failures as f64 / self.started.max(1) as f64;
+        self.aborted = self.failures >= Self::MIN_FAILURES && rate > self.max_failure_rate;
+        self.aborted
+    }
+}
+
 pub struct WorkerPool {
-    task_sender: Option<Sender<Task>>,
+    task_sender: Mutex<Option<Sender<Task>>>,
     task_receiver: Receiver<Task>,
-    workers: Vec<std::thread::JoinHandle<()>>,
+    retire_sender: Sender<()>,
+    retire_receiver: Receiver<()>,
+    /// Tasks that were not completed because the worker running them was quarantined.
+    requeue_sender: Sender<Task>,
+    requeue_receiver: Receiver<Task>,
+    workers: Mutex<Vec<std::thread::JoinHandle<()>>>,
+    next_id: AtomicUsize,
+    active: Arc<AtomicUsize>,
+    /// The number of workers that are waiting for a task.
+    idle: Arc<AtomicUsize>,
+    health: Arc<Mutex<BTreeMap<usize, WorkerHealth>>>,
+    /// The number of consecutive infrastructure failures before a worker is quarantined (0 to
+    /// disable quarantining).
+    quarantine_after: usize,
+    fail_fast: Option<Arc<Mutex<FailFast>>>,
+    /// Records the state of each task so that interrupted runs can be resumed.
+    journal: Option<Arc<Journal>>,
 }
 
 impl Drop for WorkerPool {
     fn drop(&mut self) {
-        if self.task_sender.is_some() {
-            self.wait_for_workers();
+        if self.task_sender.lock().is_some() {
+            if let Err(e) = self.wait_for_workers() {
+                tracing::error!("{e:#}");
+            }
         }
     }
 }
 
 impl WorkerPool {
-    pub fn new() -> Self {
+    pub(crate) fn new(
+        quarantine_after: usize,
+        fail_fast: Option<FailFast>,
+        journal: Option<Arc<Journal>>,
+    ) -> Self {
         let (task_sender, task_receiver) = crossbeam_channel::bounded(0);
-        Self { task_sender: Some(task_sender), task_receiver, workers: vec![] }
+        let (retire_sender, retire_receiver) = crossbeam_channel::unbounded();
+        let (requeue_sender, requeue_receiver) = crossbeam_channel::unbounded();
+        Self {
+            task_sender: Mutex::new(Some(task_sender)),
+            task_receiver,
+            retire_sender,
+            retire_receiver,
+            requeue_sender,
+            requeue_receiver,
+            workers: Mutex::new(vec![]),
+            next_id: AtomicUsize::new(0),
+            active: Arc::new(AtomicUsize::new(0)),
+            idle: Arc::new(AtomicUsize::new(0)),
+            health: Arc::new(Mutex::new(BTreeMap::new())),
+            quarantine_after,
+            fail_fast: fail_fast.map(|x| Arc::new(Mutex::new(x))),
+            journal,
+        }
+    }
+
+    /// Reserve an ID for a new worker.
+    pub fn next_worker_id(&self) -> usize {
+        self.next_id.fetch_add(1, Ordering::AcqRel)
+    }
+
+    /// The number of workers that are currently accepting tasks.
+    pub fn active_workers(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Blocks until at least `count` workers are waiting for a task, so that the next `count` tasks
+    /// start at the same time. Returns early if there are not enough active workers.
+    pub fn wait_for_idle_workers(&self, count: usize) -> anyhow::Result<()> {
+        while self.idle.load(Ordering::Acquire) < count.min(self.active_workers()) {
+            if crate::should_stop() {
+                anyhow::bail!("Cancellation requested");
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        Ok(())
     }
 
     /// Spawn a new worker and add it to pool.
-    pub fn add_worker<F>(&mut self, mut worker: F) -> anyhow::Result<()>
+    pub fn add_worker<F>(&self, id: usize, mut worker: F) -> anyhow::Result<()>
     where
-        F: FnMut(Task) -> anyhow::Result<()> + Send + Sync + 'static,
+        F: FnMut(&mut Task) -> anyhow::Result<()> + Send + Sync + 'static,
     {
-        let id = self.workers.len();
-
         let span = tracing::info_span!("worker", id = %id);
         let _guard = span.enter();
 
         let rx = self.task_receiver.clone();
+        let retire = self.retire_receiver.clone();
+        let requeue_tx = self.requeue_sender.clone();
+        let requeue_rx = self.requeue_receiver.clone();
+        let active = self.active.clone();
+        let idle = self.idle.clone();
+        let health = self.health.clone();
+        let quarantine_after = self.quarantine_after;
+        let fail_fast = self.fail_fast.clone();
+        let journal = self.journal.clone();
         let name = format!("[worker#{id:02}] task receiver");
 
         let parent = tracing::Span::current();
+        active.fetch_add(1, Ordering::AcqRel);
         let worker = std::thread::Builder::new().name(name).spawn(move || {
             let _guard = parent.enter();
 
@@ -53,21 +303,151 @@ impl WorkerPool {
             std::thread::sleep(Duration::from_millis(10 * id as u64));
 
             tracing::debug!("Thread started");
-            for task in rx {
-                if let Err(e) = worker(task) {
+            crate::dashboard::worker_started(id);
+            let mut stop_reason = "finished";
+            loop {
+                // Retire requests are only checked between tasks, so any task that is currently
+                // running is always allowed to finish.
+                idle.fetch_add(1, Ordering::AcqRel);
+                let task = crossbeam_channel::select! {
+                    recv(retire) -> _ => {
+                        tracing::info!("worker retired");
+                        stop_reason = "retired";
+                        None
+                    }
+                    recv(requeue_rx) -> task => task.ok(),
+                    // Once all tasks have been queued, the re-queued tasks still need to be run.
+                    recv(rx) -> task => task.or_else(|_| requeue_rx.try_recv()).ok(),
+                };
+                idle.fetch_sub(1, Ordering::AcqRel);
+                let Some(mut task) = task
+                else {
+                    break;
+                };
+                if let Some(fail_fast) = fail_fast.as_ref() {
+                    fail_fast.lock().started += 1;
+                }
+                let task_name = task.name.clone();
+                if let Some(journal) = journal.as_ref() {
+                    journal.set_state(&task_name, TaskState::Running);
+                }
+                let tag = task.vars.iter().find(|x| x.key == "TAG").map(|x| x.value.as_str());
+                crate::dashboard::task_started(id, &task_name, tag, task.estimate);
+                crate::events::record(Event::TaskStarted { task: &task_name, worker: id, tag });
+                let start = std::time::Instant::now();
+                // Discard any drift measured by a previous task.
+                crate::clock::take_max_drift();
+                let (result, quarantined) = run_with_retries(id, &mut task, &mut worker, |result| {
+                    health.lock().entry(id).or_default().record(result, quarantine_after)
+                });
+                crate::dashboard::task_finished(id, result.is_ok());
+                // The failure was caused by the worker rather than the task, so give the task to
+                // another worker.
+                if let (true, Err(e)) = (quarantined && !crate::should_stop(), &result) {
+                    tracing::error!(
+                        "worker quarantined after {quarantine_after} consecutive infrastructure \
+                        failures, re-queueing {task_name}: {e:#}"
+                    );
+                    if let Some(journal) = journal.as_ref() {
+                        journal.set_state(&task_name, TaskState::Pending);
+                    }
+                    let _ = requeue_tx.send(task);
+                    stop_reason = "quarantined";
+                    break;
+                }
+                let state = match &result {
+                    Ok(()) => TaskState::Completed,
+                    Err(_) if crate::should_stop() => TaskState::Interrupted,
+                    Err(_) => TaskState::Failed,
+                };
+                let duration = start.elapsed();
+                crate::events::record(Event::TaskFinished {
+                    task: &task_name,
+                    worker: id,
+                    status: state,
+                    duration_secs: duration.as_secs_f64(),
+                    error: result.as_ref().err().map(|e| format!("{e:#}")),
+                });
+                if let Some(journal) = journal.as_ref() {
+                    let drift = crate::clock::take_max_drift();
+                    journal.set_finished(&task_name, state, duration, drift);
+                }
+                if let Err(e) = &result {
                     tracing::error!("error running task: {:?}", e);
+                    if let Some(fail_fast) = fail_fast.as_ref() {
+                        let mut fail_fast = fail_fast.lock();
+                        if fail_fast.record_failure() {
+                            tracing::error!(
+                                "aborting benchmark: {}/{} started tasks failed within {} (use \
+                                `--no-fail-fast` to disable)",
+                                fail_fast.failures,
+                                fail_fast.started,
+                                crate::utils::HumanReadableDuration(fail_fast.window),
+                            );
+                            crate::cancel();
+                        }
+                    }
+                }
+                if quarantined {
+                    tracing::error!(
+                        "worker quarantined after {quarantine_after} consecutive infrastructure \
+                        failures"
+                    );
+                    stop_reason = "quarantined";
+                    break;
                 }
             }
-        })?;
+            crate::dashboard::worker_stopped(id, stop_reason);
 
-        self.workers.push(worker);
+            if active.fetch_sub(1, Ordering::AcqRel) == 1 {
+                tracing::debug!("No workers remaining");
+                if health.lock().get(&id).is_some_and(|x| x.quarantined) {
+                    tracing::error!(
+                        "aborting benchmark: no healthy workers remaining ({} re-queued task(s) \
+                        not run)",
+                        requeue_rx.len()
+                    );
+                    crate::cancel();
+                }
+            }
+        });
+        match worker {
+            Ok(worker) => self.workers.lock().push(worker),
+            Err(e) => {
+                self.active.fetch_sub(1, Ordering::AcqRel);
+                return Err(e.into());
+            }
+        }
+
+        Ok(())
+    }
 
+    /// Request that `count` workers stop after finishing their current task. While tasks are still
+    /// being queued, at least one worker is kept (otherwise queueing the next task would block
+    /// forever).
+    pub fn retire_workers(&self, count: usize) -> anyhow::Result<()> {
+        // Workers that have not yet picked up a pending retire request are still counted as active.
+        let remaining = self.active_workers().saturating_sub(self.retire_receiver.len());
+        let available = match self.task_sender.lock().is_some() {
+            true => remaining.saturating_sub(1),
+            false => remaining,
+        };
+        if count > 0 && available == 0 {
+            anyhow::bail!("cannot retire the last worker while tasks remain (use `add <n>` first)");
+        }
+        if count > available {
+            tracing::warn!("only retiring {available} of {count} workers");
+        }
+        for _ in 0..count.min(available) {
+            let _ = self.retire_sender.send(());
+        }
         Ok(())
     }
 
     /// Queue a task on the pool, blocking if no worker is available.
     pub fn add_task(&self, task: Task) -> anyhow::Result<()> {
-        if let Some(sender) = self.task_sender.as_ref() {
+        let sender = self.task_sender.lock().clone();
+        if let Some(sender) = sender {
             crossbeam_channel::select! {
                 send(sender, task) -> res => {
                     if res.is_err() {
@@ -80,15 +460,152 @@ impl WorkerPool {
         Ok(())
     }
 
-    /// Wait for all workers to finish execution.
-    pub fn wait_for_workers(&mut self) {
+    /// Logs the failure rate of each worker that encountered an infrastructure failure, and any
+    /// workers that were quarantined.
+    pub fn report_health(&self) {
+        let health = self.health.lock();
+        for (id, x) in health.iter().filter(|(_, x)| x.boot_failures + x.agent_timeouts != 0) {
+            tracing::warn!(
+                "worker#{id:02}: {}/{} tasks failed (boot failures: {}, agent timeouts: {}){}",
+                x.failures,
+                x.tasks,
+                x.boot_failures,
+                x.agent_timeouts,
+                if x.quarantined { " [quarantined]" } else { "" }
+            );
+        }
+
+        let quarantined: Vec<_> = health
+            .iter()
+            .filter(|(_, x)| x.quarantined)
+            .map(|(id, _)| format!("#{id:02}"))
+            .collect();
+        if !quarantined.is_empty() {
+            tracing::warn!(
+                "{} worker(s) quarantined: {}",
+                quarantined.len(),
+                quarantined.join(", ")
+            );
+        }
+    }
+
+    /// Wait for all workers to finish execution. Fails if any re-queued tasks were not run because
+    /// every remaining worker had already exited.
+    pub fn wait_for_workers(&self) -> anyhow::Result<()> {
         // Notify the workers that there is no jobs remaining by dropping the task sender.
-        drop(self.task_sender.take());
+        drop(self.task_sender.lock().take());
 
-        tracing::debug!("Waiting for {} workers to finish", self.workers.len());
-        for worker in self.workers.drain(..) {
-            if let Err(e) = worker.join() {
-                tracing::error!("Worker crashed: {:?}", e);
+        // Workers may be added while we are waiting, so keep going until there are none left.
+        loop {
+            let workers = std::mem::take(&mut *self.workers.lock());
+            if workers.is_empty() {
+                break;
+            }
+            tracing::debug!("Waiting for {} workers to finish", workers.len());
+            for worker in workers {
+                if let Err(e) = worker.join() {
+                    tracing::error!("Worker crashed: {:?}", e);
+                }
+            }
+        }
+
+        let orphaned: Vec<_> = self.requeue_receiver.try_iter().map(|task| task.name).collect();
+        if !orphaned.is_empty() {
+            anyhow::bail!(
+                "{} re-queued task(s) not run, no workers remaining: {}",
+                orphaned.len(),
+                orphaned.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Creates new workers for a particular backend.
+pub(crate) enum WorkerFactory {
+    Local(LocalWorker),
+    Firecracker(Arc<HashMap<String, VmConfig>>),
+    Docker(Arc<HashMap<String, DockerConfig>>),
+    Ssh(Arc<SshConfig>, Arc<Vec<SshHost>>, Arc<agent_interface::GuestSetup>),
+    Dummy,
+}
+
+impl WorkerFactory {
+    pub fn new(config: &Config, backend: WorkerBackend) -> anyhow::Result<Self> {
+        Ok(match backend {
+            WorkerBackend::Local => {
+                let mut worker = config
+                    .local_worker
+                    .clone()
+                    .ok_or_else(|| anyhow::format_err!("No local worker config"))?;
+                add_guest_env(&mut worker.setup, &config.guest_env);
+                Self::Local(worker)
+            }
+            WorkerBackend::Firecracker => {
+                let mut instances = firecracker::get_instance_config(config)?;
+                for instance in instances.values_mut() {
+                    add_guest_env(&mut instance.setup, &config.guest_env);
+                }
+                Self::Firecracker(Arc::new(instances))
+            }
+            WorkerBackend::Docker => {
+                let mut instances = docker::prepare_instances(config)?;
+                for instance in instances.values_mut() {
+                    add_guest_env(&mut instance.setup, &config.guest_env);
+                }
+                Self::Docker(Arc::new(instances))
+            }
+            WorkerBackend::Ssh => {
+                let ssh_config = config
+                    .ssh_worker
+                    .clone()
+                    .ok_or_else(|| anyhow::format_err!("No ssh worker config"))?;
+                let hosts = ssh::prepare_hosts(config, &ssh_config)?;
+                let mut setup = agent_interface::GuestSetup::default();
+                add_guest_env(&mut setup, &config.guest_env);
+                Self::Ssh(Arc::new(ssh_config), Arc::new(hosts), Arc::new(setup))
+            }
+            WorkerBackend::Dummy => Self::Dummy,
+        })
+    }
+
+    /// Spawn a new worker in `pool`.
+    pub fn add_worker(&self, pool: &WorkerPool) -> anyhow::Result<()> {
+        let id = pool.next_worker_id();
+        match self {
+            Self::Local(config) => {
+                let mut worker = config.clone();
+                worker.id = id;
+                pool.add_worker(id, move |task| worker.run_task(task))
+            }
+            Self::Firecracker(instances) => {
+                let mut worker = FirecrackerWorker {
+                    id: format!("vm{id}-data"),
+                    index: id,
+                    instances: instances.clone(),
+                };
+                pool.add_worker(id, move |task| worker.run_task(task))
+            }
+            Self::Docker(instances) => {
+                let mut worker =
+                    DockerWorker { id: format!("container-{id}"), instances: instances.clone() };
+                pool.add_worker(id, move |task| worker.run_task(task))
+            }
+            Self::Ssh(config, hosts, setup) => {
+                // Workers are assigned to hosts in round-robin order, the agent port is derived
+                // from the index of the worker on the host.
+                let host = hosts[id % hosts.len()].clone();
+                let mut worker = SshWorker {
+                    id: id / hosts.len(),
+                    host,
+                    config: config.clone(),
+                    setup: setup.clone(),
+                };
+                pool.add_worker(id, move |task| worker.run_task(task))
+            }
+            Self::Dummy => {
+                let mut worker = DummyWorker { id };
+                pool.add_worker(id, move |task| worker.run_task(task))
             }
         }
     }
@@ -96,11 +613,13 @@ impl WorkerPool {
 
 pub(crate) struct FirecrackerWorker {
     pub(crate) id: String,
+    /// The index of the worker, used for selecting the network device of the VM.
+    pub(crate) index: usize,
     pub(crate) instances: std::sync::Arc<HashMap<String, VmConfig>>,
 }
 
 impl FirecrackerWorker {
-    pub fn run_task(&mut self, mut task: Task) -> anyhow::Result<()> {
+    pub fn run_task(&mut self, task: &mut Task) -> anyhow::Result<()> {
         tracing::info!("running {} on firecracker: id={}", task.name, self.id);
 
         let instance = &task.instance;
@@ -109,8 +628,16 @@ impl FirecrackerWorker {
             .get(instance)
             .ok_or_else(|| anyhow::format_err!("Unknown instance {instance}"))?;
 
-        let vm = firecracker::spawn_vm(self.id.clone(), &vm_config, false)?;
-        let mut agent = firecracker::connect_to_vsock_agent(&vm)?;
+        let start = std::time::Instant::now();
+        let vm = firecracker::spawn_vm(self.id.clone(), self.index, &vm_config, false)
+            .context(InfraFailure::Boot)?;
+        let mut agent = ReconnectingAgent::connect(|| firecracker::connect_to_vsock_agent(&vm))
+            .context(InfraFailure::Boot)?;
+        crate::events::record(Event::WorkerBooted {
+            task: &task.name,
+            worker: &self.id,
+            boot_secs: start.elapsed().as_secs_f64(),
+        });
 
         // @todo: consider adding different entropy for each worker? Most cases this should not
         // matter since there is other entropy available and we are not doing anything that needs to
@@ -120,9 +647,14 @@ impl FirecrackerWorker {
                 .send(agent_interface::Request::AddEntropy(entropy))
                 .context("failed to add entropy to VM")?;
         }
+        setup_guest(&mut agent, &vm_config.setup, task)?;
+        // Allows results to be recovered from the drives of the VM (e.g. `{VM_WORKDIR}/var.ext4`).
+        let workdir = vm.workdir.display().to_string();
+        task.vars.push(crate::config::KeyValue::new("VM_WORKDIR", workdir));
 
-        task.run(0, agent.as_mut())?;
+        run_with_watchdog(task, &self.id, vm.killer(), |task| task.run(0, &mut agent))?;
         agent.shutdown_vm()?;
+        drop(agent);
 
         if let Err(e) = vm.wait_for_exit_timeout(Duration::from_secs(10)) {
             tracing::error!("Error waiting for VM to exit: {e:#}")
@@ -132,13 +664,142 @@ impl FirecrackerWorker {
     }
 }
 
+/// Kills the VM (or container) running a task if the task does not finish before its timeout, so
+/// that a wedged guest does not stall the worker forever.
+pub(crate) struct Watchdog {
+    done: Sender<()>,
+    handle: std::thread::JoinHandle<bool>,
+}
+
+impl Watchdog {
+    pub fn start(
+        task: &str,
+        timeout: Duration,
+        kill: impl FnOnce() -> anyhow::Result<()> + Send + 'static,
+    ) -> anyhow::Result<Self> {
+        let (done, rx) = crossbeam_channel::bounded::<()>(1);
+        let task = task.to_owned();
+        let parent = tracing::Span::current();
+        let handle = std::thread::Builder::new().name("watchdog".into()).spawn(move || {
+            let _guard = parent.enter();
+            crossbeam_channel::select! {
+                recv(rx) -> _ => false,
+                default(timeout) => {
+                    tracing::error!(
+                        "{task} did not finish within {}, killing worker",
+                        crate::utils::HumanReadableDuration(timeout)
+                    );
+                    if let Err(e) = kill() {
+                        tracing::error!("failed to kill worker running {task}: {e:#}");
+                    }
+                    true
+                }
+            }
+        })?;
+        Ok(Self { done, handle })
+    }
+
+    /// Stops the watchdog, returning whether the task timed out.
+    pub fn finish(self) -> bool {
+        let _ = self.done.send(());
+        self.handle.join().unwrap_or(false)
+    }
+}
+
+/// Runs `task` with a watchdog (if the task has a timeout) that calls `kill` if the task does not
+/// finish in time. Timed out tasks are reported as [InfraFailure::TaskTimeout].
+fn run_with_watchdog(
+    task: &mut Task,
+    worker: &str,
+    kill: impl FnOnce() -> anyhow::Result<()> + Send + 'static,
+    run: impl FnOnce(&mut Task) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let Some(timeout) = task.timeout else { return run(task) };
+    let watchdog = Watchdog::start(&task.name, timeout, kill)?;
+    let result = run(task);
+    if !watchdog.finish() {
+        return result;
+    }
+    crate::events::record(Event::TaskTimedOut {
+        task: &task.name,
+        worker,
+        timeout_secs: timeout.as_secs_f64(),
+    });
+    let error = result.err().unwrap_or_else(|| anyhow::format_err!("task finished after timeout"));
+    Err(error.context(InfraFailure::TaskTimeout))
+}
+
+/// Adds the variables of the global `guest_env` config to `setup`, keeping any variables that are
+/// already set (e.g. by the instance).
+fn add_guest_env(setup: &mut agent_interface::GuestSetup, env: &BTreeMap<String, String>) {
+    for (key, value) in env {
+        setup.env.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+}
+
+/// Applies the guest configuration of the instance used by `task`. If the clock is fixed, it is
+/// recorded in the `GUEST_CLOCK` variable of the task (e.g. so that it is saved by `SaveEnv`).
+fn setup_guest(
+    agent: &mut dyn Agent,
+    setup: &agent_interface::GuestSetup,
+    task: &mut Task,
+) -> anyhow::Result<()> {
+    if setup.is_empty() {
+        return Ok(());
+    }
+    agent.setup_guest(setup.clone())?;
+    if let Some(clock) = setup.clock {
+        tracing::info!("{}: guest clock set to {clock}", task.name);
+        task.vars.push(crate::config::KeyValue::new("GUEST_CLOCK", clock.to_string()));
+    }
+    Ok(())
+}
+
+/// An agent that can be reconnected to using `connect` (see [Agent::reconnect]).
+struct ReconnectingAgent<F> {
+    /// The current connection (`None` after a failed reconnection attempt).
+    agent: Option<Box<dyn Agent>>,
+    connect: F,
+}
+
+impl<F> ReconnectingAgent<F>
+where
+    F: FnMut() -> anyhow::Result<Box<dyn Agent>>,
+{
+    fn connect(mut connect: F) -> anyhow::Result<Self> {
+        Ok(Self { agent: Some(connect()?), connect })
+    }
+}
+
+impl<F> Agent for ReconnectingAgent<F>
+where
+    F: FnMut() -> anyhow::Result<Box<dyn Agent>>,
+{
+    fn send_request(
+        &mut self,
+        request: agent_interface::Request,
+        read_timeout: Option<Duration>,
+    ) -> anyhow::Result<agent_interface::Response> {
+        let agent = self.agent.as_mut().ok_or_else(|| anyhow::format_err!("agent disconnected"))?;
+        agent.send_request(request, read_timeout)
+    }
+
+    fn reconnect(&mut self) -> anyhow::Result<()> {
+        // The agent only serves a single connection at a time, so the old connection must be
+        // closed first (otherwise the agent never accepts the new one).
+        self.agent = None;
+        self.agent = Some((self.connect)().context("failed to reconnect to agent")?);
+        Ok(())
+    }
+}
+
 pub(crate) struct DockerWorker {
     pub(crate) id: String,
     pub(crate) instances: std::sync::Arc<HashMap<String, DockerConfig>>,
 }
 
 impl DockerWorker {
-    pub fn run_task(&mut self, mut task: Task) -> anyhow::Result<()> {
+    pub fn run_task(&mut self, task: &mut Task) -> anyhow::Result<()> {
         tracing::info!("running {} in docker: id={}", task.name, self.id);
 
         let instance = &task.instance;
@@ -147,11 +808,33 @@ impl DockerWorker {
             .get(instance)
             .ok_or_else(|| anyhow::format_err!("Unknown instance {instance}"))?;
 
-        let container = docker::spawn_docker_worker(self.id.clone(), docker_config)?;
+        let start = std::time::Instant::now();
+        let container = docker::spawn_docker_worker(self.id.clone(), docker_config)
+            .context(InfraFailure::Boot)?;
 
-        let mut agent = UnixAgent::connect(&container.api_socket)?;
-        task.run(0, &mut agent)?;
+        let mut agent = ReconnectingAgent::connect(|| container.connect_agent())
+            .context(InfraFailure::Boot)?;
+        crate::events::record(Event::WorkerBooted {
+            task: &task.name,
+            worker: &self.id,
+            boot_secs: start.elapsed().as_secs_f64(),
+        });
+        setup_guest(&mut agent, &docker_config.setup, task)?;
+        let perf = match container.host_pid() {
+            Ok(pid) => crate::perf::attach(&task.name, pid),
+            Err(e) => {
+                tracing::debug!("unable to get container pid: {e:#}");
+                None
+            }
+        };
+        let kill = container.killer();
+        let result = run_with_watchdog(task, &self.id, kill, |task| task.run(0, &mut agent));
+        if let Some(perf) = perf {
+            perf.finish();
+        }
+        result?;
         agent.exit()?;
+        drop(agent);
 
         if let Err(e) = container.wait_for_exit_timeout(Duration::from_secs(10)) {
             tracing::error!("Error waiting for container to exit: {e:#}")
@@ -161,23 +844,70 @@ impl DockerWorker {
     }
 }
 
+pub(crate) struct SshWorker {
+    /// The index of the worker on `host`.
+    pub(crate) id: usize,
+    pub(crate) host: SshHost,
+    pub(crate) config: Arc<SshConfig>,
+    /// Applied to the agent before running the task (see `guest_env` in the config).
+    pub(crate) setup: Arc<agent_interface::GuestSetup>,
+}
+
+impl SshWorker {
+    pub fn run_task(&mut self, task: &mut Task) -> anyhow::Result<()> {
+        tracing::info!("running {} on {}: id={}", task.name, self.host, self.id);
+
+        let session = ssh::spawn_ssh_worker(self.id, &self.host, &self.config)
+            .context(InfraFailure::Boot)?;
+        let mut agent = agent_interface::client::connect_to_tcp_agent(&session.agent_addr)
+            .context(InfraFailure::Boot)?;
+        let result = setup_guest(agent.as_mut(), &self.setup, task)
+            .and_then(|()| task.run(self.id, agent.as_mut()));
+        if let Err(e) = result {
+            // Stop the remote agent (and any processes it started), otherwise it keeps running
+            // after `ssh` is killed and the port is still in use by the next task.
+            if let Err(exit_err) = agent.exit() {
+                tracing::warn!("failed to stop agent on {}: {exit_err:#}", self.host);
+            }
+            return Err(e);
+        }
+        agent.exit()?;
+
+        if let Err(e) = session.wait_for_exit_timeout(Duration::from_secs(10)) {
+            tracing::error!("Error waiting for ssh to exit: {e:#}")
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(serde::Deserialize, Clone)]
 pub(crate) struct LocalWorker {
     pub(crate) workdir: std::path::PathBuf,
     #[serde(skip)]
     pub(crate) id: usize,
+    /// Applied to the agent before running the task (see `guest_env` in the config).
+    #[serde(skip)]
+    pub(crate) setup: agent_interface::GuestSetup,
 }
 
 impl LocalWorker {
-    pub fn run_task(&mut self, mut task: Task) -> anyhow::Result<()> {
+    pub fn run_task(&mut self, task: &mut Task) -> anyhow::Result<()> {
         if !self.workdir.exists() {
             anyhow::bail!("workdir: {} does not exist", self.workdir.display());
         }
 
         let (mut agent, handle) = agent::spawn_local_agent(Some(self.workdir.clone()))
-            .context("failed to spawn local agent")?;
+            .context("failed to spawn local agent")
+            .context(InfraFailure::Boot)?;
+        setup_guest(agent.as_mut(), &self.setup, task)?;
 
-        task.run(self.id, agent.as_mut())?;
+        let perf = crate::perf::attach(&task.name, std::process::id());
+        let result = task.run(self.id, agent.as_mut());
+        if let Some(perf) = perf {
+            perf.finish();
+        }
+        result?;
         agent.exit()?;
 
         let _ = handle.join();
@@ -190,7 +920,7 @@ pub(crate) struct DummyWorker {
 }
 
 impl DummyWorker {
-    pub fn run_task(&mut self, mut task: Task) -> anyhow::Result<()> {
+    pub fn run_task(&mut self, task: &mut Task) -> anyhow::Result<()> {
         println!("running {} on worker {}", task.name, self.id);
         task.run(self.id, &mut DummyAgent::new())?;
         Ok(())
@@ -215,7 +945,10 @@ impl DummyAgent {
         match req {
             Request::Reboot => eprintln!("reboot"),
             Request::RestartAgent => eprint!("restart agent"),
-            Request::GetStats => eprintln!("get stats"),
+            Request::GetStats => {
+                eprintln!("get stats");
+                return Ok(Response::Value(serde_json::json!("")));
+            }
             Request::SpawnProcess(process) => {
                 let pid = self.next_pid;
                 eprintln!("spawn({process}) = {pid}");
@@ -238,18 +971,49 @@ impl DummyAgent {
                 eprintln!("status(pid={pid})");
                 return Ok(agent_interface::Response::Value(serde_json::json!(null)));
             }
+            Request::GetRecentOutput(pid) => {
+                eprintln!("recent_output(pid={pid})");
+                return Ok(agent_interface::Response::Value(serde_json::json!(null)));
+            }
+            Request::GetTime => {
+                let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+                return Ok(agent_interface::Response::Value(serde_json::json!(now)));
+            }
             Request::KillProcess { pid, signal } => {
                 eprintln!("kill(pid={pid}, sig={signal})");
             }
+            Request::WriteStdin { pid, data, close } => {
+                eprintln!("write_stdin(pid={pid}, {}, close={close})", data.len())
+            }
             Request::ReadFile { path, offset, len } => {
                 eprintln!("readat({}, {offset}, {})", path.display(), len.unwrap_or(0))
             }
+            Request::WriteFile { path, offset, data } => {
+                eprintln!("writeat({}, {offset}, {})", path.display(), data.len())
+            }
             Request::StatFile(path) => {
                 eprintln!("stat({})", path.display());
                 return Ok(Response::Value(serde_json::json!(null)));
             }
             Request::ReadDir(path) => eprintln!("readdir({})", path.display()),
+            Request::HashDir { path, algorithm } => {
+                eprintln!("hash_dir({}, {algorithm:?})", path.display());
+                return Ok(Response::Value(serde_json::json!([])));
+            }
             Request::AddEntropy(bytes) => eprintln!("add_entropy({bytes:0x?})"),
+            Request::SetupGuest(setup) => eprintln!("setup_guest({setup:?})"),
+            Request::CreateDir(path) => eprintln!("create_dir({})", path.display()),
+            Request::RemovePath(path) => eprintln!("remove({})", path.display()),
+            Request::MovePath { src, dst } => {
+                eprintln!("move({}, {})", src.display(), dst.display())
+            }
+            Request::CopyPath { src, dst } => {
+                eprintln!("copy({}, {})", src.display(), dst.display())
+            }
+            Request::SetPermissions { path, mode } => {
+                eprintln!("set_permissions({}, {mode:#o})", path.display())
+            }
+            Request::SetEncoding(encoding) => eprintln!("set_encoding({encoding:?})"),
             Request::Bulk(bulk) => {
                 for req in bulk {
                     self.handle_request(req)?;
@@ -270,3 +1034,212 @@ impl agent_interface::client::Agent for DummyAgent {
         self.handle_request(request)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{config::KeyValue, tasks::RetryPolicy};
+
+    /// Tasks are never canceled in tests, but retries and queueing select on the cancellation
+    /// channel.
+    fn init_cancellation() {
+        crate::CANCELATION_RECEIVER.get_or_init(|| {
+            let (cancel_tx, cancel_rx) = crossbeam_channel::bounded(0);
+            std::mem::forget(cancel_tx);
+            cancel_rx
+        });
+    }
+
+    fn task(name: &str) -> Task {
+        Task {
+            name: name.into(),
+            instance: String::new(),
+            vars: vec![KeyValue { key: "TAG".into(), value: name.into() }],
+            artifacts: vec![],
+            estimate: Duration::ZERO,
+            timeout: None,
+            retry: RetryPolicy {
+                max_attempts: 3,
+                backoff: Duration::ZERO,
+                max_backoff: Duration::ZERO,
+                retry_timeouts: false,
+            },
+            barrier: None,
+            runable: Box::new(crate::tasks::DynamicTask::TaskList { tasks: vec![] }),
+        }
+    }
+
+    fn boot_failure() -> anyhow::Error {
+        anyhow::format_err!("failed to start VM").context(InfraFailure::Boot)
+    }
+
+    fn wait_until(mut condition: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(start.elapsed() < Duration::from_secs(10), "timed out waiting for workers");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn retire_and_add_workers() {
+        init_cancellation();
+        let pool = WorkerPool::new(0, None, None);
+        let factory = WorkerFactory::Dummy;
+        factory.add_worker(&pool).unwrap();
+        factory.add_worker(&pool).unwrap();
+        assert_eq!(pool.active_workers(), 2);
+
+        pool.retire_workers(1).unwrap();
+        wait_until(|| pool.active_workers() == 1);
+        // The last worker is needed to run the tasks that are still being queued.
+        assert!(pool.retire_workers(1).is_err());
+
+        pool.add_task(task("a")).unwrap();
+        factory.add_worker(&pool).unwrap();
+        assert_eq!(pool.active_workers(), 2);
+        pool.add_task(task("b")).unwrap();
+
+        pool.wait_for_workers().unwrap();
+        assert_eq!(pool.active_workers(), 0);
+    }
+
+    #[test]
+    fn quarantined_worker_requeues_task() {
+        init_cancellation();
+        let pool = WorkerPool::new(1, None, None);
+
+        let (fail_tx, fail_rx) = crossbeam_channel::bounded::<()>(0);
+        let broken = pool.next_worker_id();
+        pool.add_worker(broken, move |_| {
+            let _ = fail_rx.recv();
+            Err(boot_failure())
+        })
+        .unwrap();
+        // Tasks are handed over directly, so the broken worker is the only one that can receive it.
+        pool.add_task(task("a")).unwrap();
+
+        let completed = Arc::new(Mutex::new(vec![]));
+        let healthy = pool.next_worker_id();
+        let completed_by_worker = completed.clone();
+        pool.add_worker(healthy, move |task| {
+            completed_by_worker.lock().push(task.name.clone());
+            Ok(())
+        })
+        .unwrap();
+
+        drop(fail_tx);
+        wait_until(|| !completed.lock().is_empty());
+        pool.wait_for_workers().unwrap();
+
+        assert_eq!(*completed.lock(), ["a"]);
+        let health = pool.health.lock();
+        assert!(health[&broken].quarantined);
+        assert_eq!(health[&broken].boot_failures, 1);
+        assert!(!health[&healthy].quarantined);
+    }
+
+    #[test]
+    fn orphaned_requeued_tasks_are_reported() {
+        let pool = WorkerPool::new(1, None, None);
+        pool.requeue_sender.send(task("a")).unwrap();
+        pool.requeue_sender.send(task("b")).unwrap();
+
+        let error = pool.wait_for_workers().unwrap_err();
+        assert!(format!("{error:#}").ends_with("no workers remaining: a, b"), "{error:#}");
+    }
+
+    #[test]
+    fn health_quarantines_after_consecutive_infra_failures() {
+        let mut health = WorkerHealth::default();
+        assert!(!health.record(&Err(boot_failure()), 2));
+        assert!(!health.record(&Ok(()), 2));
+        assert!(!health.record(&Err(boot_failure()), 2));
+        // Failures caused by the task itself are not counted.
+        assert!(!health.record(&Err(anyhow::format_err!("task failed")), 2));
+        let timeout = anyhow::format_err!("no response").context(InfraFailure::TaskTimeout);
+        assert!(health.record(&Err(timeout), 2));
+        assert_eq!((health.tasks, health.failures, health.agent_timeouts), (5, 4, 1));
+
+        let mut health = WorkerHealth::default();
+        assert!(!health.record(&Err(boot_failure()), 0), "quarantining is disabled");
+    }
+
+    #[test]
+    fn retries_infrastructure_failures() {
+        init_cancellation();
+        let mut task = task("a");
+        let mut attempts = 0;
+        let mut worker = |task: &mut Task| {
+            // Each attempt starts with the variables of the original task.
+            assert_eq!(task.vars.len(), 1);
+            task.vars.push(KeyValue { key: "VM_WORKDIR".into(), value: "/tmp".into() });
+            attempts += 1;
+            if attempts < 3 {
+                return Err(boot_failure());
+            }
+            Ok(())
+        };
+        let (result, quarantined) = run_with_retries(0, &mut task, &mut worker, |_| false);
+        assert!(result.is_ok());
+        assert!(!quarantined);
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn retries_stop_at_max_attempts() {
+        init_cancellation();
+        let mut task = task("a");
+        let mut attempts = 0;
+        let mut worker = |_: &mut Task| {
+            attempts += 1;
+            Err(boot_failure())
+        };
+        let (result, _) = run_with_retries(0, &mut task, &mut worker, |_| false);
+        assert!(result.is_err());
+        assert_eq!(attempts, task.retry.max_attempts);
+    }
+
+    #[test]
+    fn only_retryable_failures_are_retried() {
+        init_cancellation();
+        fn task_failure() -> anyhow::Error {
+            anyhow::format_err!("task failed")
+        }
+        fn timeout() -> anyhow::Error {
+            anyhow::format_err!("no response").context(InfraFailure::TaskTimeout)
+        }
+        // (error, retry_timeouts, expected attempts)
+        let cases =
+            [(task_failure as fn() -> _, true, 1), (timeout, false, 1), (timeout, true, 3)];
+        for (error, retry_timeouts, expected) in cases {
+            let mut task = task("a");
+            task.retry.retry_timeouts = retry_timeouts;
+            let mut attempts = 0;
+            let mut worker = |_: &mut Task| {
+                attempts += 1;
+                Err(error())
+            };
+            let (result, _) = run_with_retries(0, &mut task, &mut worker, |_| false);
+            assert!(result.is_err());
+            assert_eq!(attempts, expected, "retry_timeouts: {retry_timeouts}");
+        }
+    }
+
+    #[test]
+    fn quarantine_stops_retries() {
+        init_cancellation();
+        let mut task = task("a");
+        let mut attempts = 0;
+        let mut worker = |_: &mut Task| {
+            attempts += 1;
+            Err(boot_failure())
+        };
+        let (result, quarantined) = run_with_retries(0, &mut task, &mut worker, |_| true);
+        assert!(result.is_err());
+        assert!(quarantined);
+        assert_eq!(attempts, 1);
+    }
+}